@@ -0,0 +1,61 @@
+use proc_macro::TokenStream;
+use syn::{ItemStruct, Type};
+
+/// Implementation for `#[salsa::accumulator]` decorator.
+pub(crate) fn accumulator(args: TokenStream, input: TokenStream) -> TokenStream {
+    let data_ty = syn::parse_macro_input!(args as Type);
+    let input = syn::parse_macro_input!(input as ItemStruct);
+
+    let accumulator_name = &input.ident;
+    let visibility = &input.vis;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let push_docs = format!(
+        "Pushes `data` to the `{}` accumulator on behalf of the query that \
+         is currently executing. Panics if no query is executing.",
+        accumulator_name
+    );
+    let get_docs = format!(
+        "Returns the values pushed to the `{}` accumulator while executing \
+         `root` and the queries it (transitively) read. See \
+         `salsa::plumbing::accumulated` for details.",
+        accumulator_name
+    );
+
+    let output = quote! {
+        #input
+
+        impl #impl_generics salsa::Accumulator for #accumulator_name #ty_generics #where_clause {
+            type Data = #data_ty;
+        }
+
+        impl #impl_generics #accumulator_name #ty_generics #where_clause {
+            #[doc = #push_docs]
+            #visibility fn push<DB>(db: &DB, data: #data_ty)
+            where
+                DB: ?Sized + salsa::Database,
+            {
+                salsa::plumbing::accumulate::<Self>(salsa::Database::salsa_runtime(db), data)
+            }
+
+            #[doc = #get_docs]
+            #visibility fn get<DB>(db: &DB, root: salsa::DatabaseKeyIndex) -> Vec<#data_ty>
+            where
+                DB: ?Sized + salsa::Database,
+            {
+                salsa::plumbing::accumulated::<Self>(
+                    salsa::plumbing::DatabaseOps::ops_database(db),
+                    root,
+                )
+            }
+        }
+    };
+
+    if std::env::var("SALSA_DUMP").is_ok() {
+        println!("~~~ accumulator");
+        println!("{}", output);
+        println!("~~~ accumulator");
+    }
+
+    output.into()
+}
@@ -39,6 +39,7 @@ pub(crate) fn database(args: TokenStream, input: TokenStream) -> TokenStream {
     // `foo::MyGroupGroupStorage`
     let mut storage_fields = proc_macro2::TokenStream::new();
     let mut storage_initializers = proc_macro2::TokenStream::new();
+    let mut storage_forks = proc_macro2::TokenStream::new();
     let mut has_group_impls = proc_macro2::TokenStream::new();
     for (((query_group, group_name_snake), group_storage), group_index) in query_groups
         .iter()
@@ -60,6 +61,10 @@ pub(crate) fn database(args: TokenStream, input: TokenStream) -> TokenStream {
             #group_name_snake: #group_storage::new(#group_index),
         });
 
+        storage_forks.extend(quote! {
+            #group_name_snake: storage.#group_name_snake.fork(),
+        });
+
         // ANCHOR:HasQueryGroup
         has_group_impls.extend(quote! {
             impl salsa::plumbing::HasQueryGroup<#group_path> for #database_name {
@@ -99,6 +104,12 @@ pub(crate) fn database(args: TokenStream, input: TokenStream) -> TokenStream {
     output.extend(quote! {
         impl salsa::plumbing::DatabaseStorageTypes for #database_name {
             type DatabaseStorage = __SalsaDatabaseStorage;
+
+            fn fork_database_storage(storage: &__SalsaDatabaseStorage) -> __SalsaDatabaseStorage {
+                __SalsaDatabaseStorage {
+                    #storage_forks
+                }
+            }
         }
     });
     // ANCHOR_END:DatabaseStorageTypes
@@ -106,12 +117,22 @@ pub(crate) fn database(args: TokenStream, input: TokenStream) -> TokenStream {
     // ANCHOR:DatabaseOps
     let mut fmt_ops = proc_macro2::TokenStream::new();
     let mut maybe_changed_ops = proc_macro2::TokenStream::new();
+    let mut dependencies_ops = proc_macro2::TokenStream::new();
+    let mut accumulated_ops = proc_macro2::TokenStream::new();
+    let mut validate_ops = proc_macro2::TokenStream::new();
+    let mut commit_provisional_ops = proc_macro2::TokenStream::new();
+    let mut validate_dependencies_ops = proc_macro2::TokenStream::new();
     let mut for_each_ops = proc_macro2::TokenStream::new();
-    for ((QueryGroup { group_path }, group_storage), group_index) in query_groups
+    let mut memory_usage_ops = proc_macro2::TokenStream::new();
+    let mut set_by_name_ops = proc_macro2::TokenStream::new();
+    let mut take_recorded_ops = proc_macro2::TokenStream::new();
+    let mut set_lru_capacity_ops = proc_macro2::TokenStream::new();
+    for ((query_group, group_storage), group_index) in query_groups
         .iter()
         .zip(&query_group_storage_names)
         .zip(0_u16..)
     {
+        let group_path = &query_group.group_path;
         fmt_ops.extend(quote! {
             #group_index => {
                 let storage: &#group_storage =
@@ -126,11 +147,76 @@ pub(crate) fn database(args: TokenStream, input: TokenStream) -> TokenStream {
                 storage.maybe_changed_since(self, input, revision)
             }
         });
+        dependencies_ops.extend(quote! {
+            #group_index => {
+                let storage: &#group_storage =
+                    <Self as salsa::plumbing::HasQueryGroup<#group_path>>::group_storage(self);
+                storage.dependencies(self, input)
+            }
+        });
+        accumulated_ops.extend(quote! {
+            #group_index => {
+                let storage: &#group_storage =
+                    <Self as salsa::plumbing::HasQueryGroup<#group_path>>::group_storage(self);
+                storage.accumulated(self, input)
+            }
+        });
+        validate_ops.extend(quote! {
+            #group_index => {
+                let storage: &#group_storage =
+                    <Self as salsa::plumbing::HasQueryGroup<#group_path>>::group_storage(self);
+                storage.validate(self, input)
+            }
+        });
+        commit_provisional_ops.extend(quote! {
+            #group_index => {
+                let storage: &#group_storage =
+                    <Self as salsa::plumbing::HasQueryGroup<#group_path>>::group_storage(self);
+                storage.commit_provisional(self, input, inputs, durability)
+            }
+        });
+        validate_dependencies_ops.extend(quote! {
+            {
+                let storage: &#group_storage =
+                    <Self as salsa::plumbing::HasQueryGroup<#group_path>>::group_storage(self);
+                storage.debug_validate_dependencies(self);
+            }
+        });
         for_each_ops.extend(quote! {
             let storage: &#group_storage =
                 <Self as salsa::plumbing::HasQueryGroup<#group_path>>::group_storage(self);
             storage.for_each_query(runtime, &mut op);
         });
+        set_by_name_ops.extend(quote! {
+            if <#group_storage>::has_input(name) {
+                return <#group_storage>::set_input_by_name(self, name, key, value, durability);
+            }
+        });
+        take_recorded_ops.extend(quote! {
+            {
+                let storage: &#group_storage =
+                    <Self as salsa::plumbing::HasQueryGroup<#group_path>>::group_storage(self);
+                storage.take_recorded_sets(op);
+            }
+        });
+        set_lru_capacity_ops.extend(quote! {
+            {
+                let storage: &#group_storage =
+                    <Self as salsa::plumbing::HasQueryGroup<#group_path>>::group_storage(self);
+                found |= storage.set_lru_capacity_by_name(name, capacity);
+            }
+        });
+        memory_usage_ops.extend(quote! {
+            {
+                let storage: &#group_storage =
+                    <Self as salsa::plumbing::HasQueryGroup<#group_path>>::group_storage(self);
+                let mut bytes = 0;
+                storage.for_each_query(runtime, &mut |query_storage| {
+                    bytes += query_storage.memory_usage();
+                });
+                usage.push((<#group_path as salsa::plumbing::QueryGroup>::GROUP_NAME, bytes));
+            }
+        });
     }
     output.extend(quote! {
         impl salsa::plumbing::DatabaseOps for #database_name {
@@ -168,6 +254,49 @@ pub(crate) fn database(args: TokenStream, input: TokenStream) -> TokenStream {
                 }
             }
 
+            fn dependencies(
+                &self,
+                input: salsa::DatabaseKeyIndex,
+            ) -> Option<Vec<salsa::DatabaseKeyIndex>> {
+                match input.group_index() {
+                    #dependencies_ops
+                    i => panic!("salsa: invalid group index {}", i)
+                }
+            }
+
+            fn accumulated(
+                &self,
+                input: salsa::DatabaseKeyIndex,
+            ) -> Option<salsa::plumbing::AccumulatedValues> {
+                match input.group_index() {
+                    #accumulated_ops
+                    i => panic!("salsa: invalid group index {}", i)
+                }
+            }
+
+            fn validate(&self, input: salsa::DatabaseKeyIndex) {
+                match input.group_index() {
+                    #validate_ops
+                    i => panic!("salsa: invalid group index {}", i)
+                }
+            }
+
+            fn commit_provisional(
+                &self,
+                input: salsa::DatabaseKeyIndex,
+                inputs: Option<&[salsa::DatabaseKeyIndex]>,
+                durability: salsa::Durability,
+            ) {
+                match input.group_index() {
+                    #commit_provisional_ops
+                    i => panic!("salsa: invalid group index {}", i)
+                }
+            }
+
+            fn ops_validate_dependencies(&self) {
+                #validate_dependencies_ops
+            }
+
             fn for_each_query(
                 &self,
                 mut op: &mut dyn FnMut(&dyn salsa::plumbing::QueryStorageMassOps),
@@ -175,6 +304,34 @@ pub(crate) fn database(args: TokenStream, input: TokenStream) -> TokenStream {
                 let runtime = salsa::Database::salsa_runtime(self);
                 #for_each_ops
             }
+
+            fn group_memory_usage(&self) -> Vec<(&'static str, usize)> {
+                let runtime = salsa::Database::salsa_runtime(self);
+                let mut usage = Vec::new();
+                #memory_usage_ops
+                usage
+            }
+
+            fn set_input_by_name(
+                &mut self,
+                name: &str,
+                key: Box<dyn std::any::Any>,
+                value: Box<dyn std::any::Any>,
+                durability: salsa::Durability,
+            ) -> Result<(), salsa::SetInputError> {
+                #set_by_name_ops
+                Err(salsa::SetInputError::NoSuchInput { name: name.to_string() })
+            }
+
+            fn take_recorded_sets(&self, op: &mut dyn FnMut(salsa::RecordedSet)) {
+                #take_recorded_ops
+            }
+
+            fn set_lru_capacity_by_name(&self, name: &str, capacity: usize) -> bool {
+                let mut found = false;
+                #set_lru_capacity_ops
+                found
+            }
         }
     });
     // ANCHOR_END:DatabaseOps
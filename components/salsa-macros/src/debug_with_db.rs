@@ -0,0 +1,166 @@
+use proc_macro::TokenStream;
+use proc_macro2::{Ident, TokenStream as TokenStream2};
+use syn::parse::{Parse, ParseStream};
+use syn::{parse_quote, Data, DeriveInput, Fields, Token, Type, TypeParamBound};
+
+/// Implementation for `#[derive(DebugWithDb)]`.
+pub(crate) fn debug_with_db(input: TokenStream) -> TokenStream {
+    let input = syn::parse_macro_input!(input as DeriveInput);
+
+    let type_name = &input.ident;
+
+    let mut db_ty: Option<Type> = None;
+    for attr in &input.attrs {
+        if attr.path.is_ident("debug_with_db") {
+            match attr.parse_args::<DbArg>() {
+                Ok(arg) => db_ty = Some(with_static_bound(arg.ty)),
+                Err(err) => return err.to_compile_error().into(),
+            }
+        }
+    }
+
+    // Like `#[derive(Debug)]`, we require each type parameter to
+    // implement the trait, rather than each field type (which would
+    // overflow for recursive types). Without a `db` argument, the impl
+    // is generic over the database type.
+    let mut generics = input.generics.clone();
+    let db: Type = db_ty.clone().unwrap_or_else(|| parse_quote!(__SalsaDb));
+    for param in generics.type_params_mut() {
+        param.bounds.push(parse_quote!(salsa::DebugWithDb<#db>));
+    }
+    if db_ty.is_none() {
+        generics.params.push(parse_quote!(#db: ?Sized));
+    }
+    let (impl_generics, _, where_clause) = generics.split_for_impl();
+    let (_, ty_generics, _) = input.generics.split_for_impl();
+
+    let body = match &input.data {
+        Data::Struct(data) => {
+            let (pattern, debug) = fields_debug(&type_name.to_string(), &data.fields, &db);
+            quote! {
+                let #type_name #pattern = self;
+                #debug
+            }
+        }
+        Data::Enum(data) => {
+            let arms = data.variants.iter().map(|variant| {
+                let variant_name = &variant.ident;
+                let (pattern, debug) =
+                    fields_debug(&variant_name.to_string(), &variant.fields, &db);
+                quote! {
+                    #type_name::#variant_name #pattern => { #debug }
+                }
+            });
+            if data.variants.is_empty() {
+                quote! { match *self {} }
+            } else {
+                quote! {
+                    match self {
+                        #(#arms)*
+                    }
+                }
+            }
+        }
+        Data::Union(_) => {
+            return syn::Error::new_spanned(
+                &input.ident,
+                "`DebugWithDb` cannot be derived for unions",
+            )
+            .to_compile_error()
+            .into();
+        }
+    };
+
+    let output = quote! {
+        impl #impl_generics salsa::DebugWithDb<#db> for #type_name #ty_generics #where_clause {
+            fn fmt(
+                &self,
+                f: &mut ::std::fmt::Formatter<'_>,
+                db: &(#db),
+            ) -> ::std::fmt::Result {
+                #body
+            }
+        }
+    };
+
+    if std::env::var("SALSA_DUMP").is_ok() {
+        println!("~~~ debug_with_db");
+        println!("{}", output);
+        println!("~~~ debug_with_db");
+    }
+
+    output.into()
+}
+
+/// The argument of `#[debug_with_db(db = ...)]`.
+struct DbArg {
+    ty: Type,
+}
+
+impl Parse for DbArg {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let ident: Ident = input.parse()?;
+        if ident != "db" {
+            return Err(syn::Error::new(ident.span(), "expected `db = ...`"));
+        }
+        let _: Token![=] = input.parse()?;
+        Ok(DbArg { ty: input.parse()? })
+    }
+}
+
+/// Makes a trait object type like `dyn MyQueryGroup` explicitly
+/// `'static`. That is what it means in the trait's type parameter, but
+/// in the signature `db: &dyn MyQueryGroup` it would get the lifetime
+/// of the reference instead.
+fn with_static_bound(ty: Type) -> Type {
+    match ty {
+        Type::TraitObject(mut object) => {
+            let has_lifetime = object
+                .bounds
+                .iter()
+                .any(|bound| matches!(bound, TypeParamBound::Lifetime(_)));
+            if !has_lifetime {
+                object.bounds.push(parse_quote!('static));
+            }
+            Type::TraitObject(object)
+        }
+        ty => ty,
+    }
+}
+
+/// Returns a pattern binding the `fields` of a struct or variant named
+/// `name`, and the code that formats the bound fields.
+fn fields_debug(name: &str, fields: &Fields, db: &Type) -> (TokenStream2, TokenStream2) {
+    let bindings: Vec<Ident> = (0..fields.len())
+        .map(|i| format_ident!("__field{}", i))
+        .collect();
+    let wrapped = quote! { salsa::DebugWithDb::<#db>::debug };
+
+    match fields {
+        Fields::Named(fields) => {
+            let field_names: Vec<&Ident> = fields
+                .named
+                .iter()
+                .map(|field| field.ident.as_ref().unwrap())
+                .collect();
+            let field_strings = field_names.iter().map(|name| name.to_string());
+            (
+                quote! { { #(#field_names: #bindings),* } },
+                quote! {
+                    f.debug_struct(#name)
+                        #(.field(#field_strings, &#wrapped(#bindings, db)))*
+                        .finish()
+                },
+            )
+        }
+        Fields::Unnamed(_) => (
+            quote! { ( #(#bindings),* ) },
+            quote! {
+                f.debug_tuple(#name)
+                    #(.field(&#wrapped(#bindings, db)))*
+                    .finish()
+            },
+        ),
+        Fields::Unit => (quote! {}, quote! { f.write_str(#name) }),
+    }
+}
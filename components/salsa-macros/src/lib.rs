@@ -9,7 +9,9 @@ extern crate quote;
 
 use proc_macro::TokenStream;
 
+mod accumulator;
 mod database_storage;
+mod debug_with_db;
 mod parenthesized;
 mod query_group;
 
@@ -44,22 +46,108 @@ mod query_group;
 /// }
 /// ```
 ///
+/// Besides `my_query`, each query gets a `my_query_fn` method, which
+/// returns the query as a boxed closure (`Box<dyn Fn(u32) -> u64 + '_>`
+/// for `my_query`) that borrows the database. It can be passed to code
+/// that takes an `Fn`, such as `iter.map(db.my_query_fn())`, and records
+/// dependencies like calling `my_query` does.
+///
+/// Derived queries also get a `my_query_probed` method, which returns
+/// the value together with a `salsa::CacheOutcome`: `Hit` if the
+/// memoized value was already verified in the current revision,
+/// `Validated` if it was verified by checking its inputs, and
+/// `Computed` if the query had to be executed. Schedulers can use this
+/// to tell cheap results from expensive ones.
+///
 /// Here is a list of legal `salsa::XXX` attributes:
 ///
 /// - Storage attributes: control how the query data is stored and set. These
 ///   are described in detail in the section below.
-///   - `#[salsa::input]`
+///   - `#[salsa::input]` (or `#[salsa::input(eq)]`,
+///     `#[salsa::input(immortal)]`)
 ///   - `#[salsa::memoized]`
 ///   - `#[salsa::dependencies]`
+///   - `#[salsa::transparent]`
+///   - `#[salsa::interned]` (or `#[salsa::interned(generational)]`, whose
+///     ids detect lookups of collected values; see `salsa::InternKey`)
 /// - Query execution:
 ///   - `#[salsa::invoke(path::to::my_fn)]` -- for a non-input, this
 ///     indicates the function to call when a query must be
 ///     recomputed. The default is to call a function in the same
-///     module with the same name as the query.
+///     module with the same name as the query. The path is called as
+///     `path::to::my_fn(db, key1, ...)`, so it can also name an
+///     associated function (`#[salsa::invoke(Parser::parse)]`) or a
+///     trait method whose receiver is the database
+///     (`#[salsa::invoke(ParseExt::parse)]`, with `ParseExt`
+///     implemented for `dyn MyQueryGroup`).
+///   - `#[salsa::volatile]` -- for a derived query, indicates that it
+///     reads state salsa does not know about (like the system clock),
+///     so it is re-executed in every revision, as if it had called
+///     `report_untracked_read`. Queries that depend on it are then
+///     re-executed too, should its value change.
+///   - `#[salsa::retry_err]` -- for a derived query returning a
+///     `Result`, marks an `Err` result as volatile: it is still
+///     memoized for the current revision, but the query is re-executed
+///     in every later revision until it succeeds. `Ok` results are
+///     re-validated as usual.
+///   - `#[salsa::no_eq]` -- for a memoized query, never compares a
+///     recomputed value with the old one: every re-execution counts as
+///     a change, and the queries that read the value are re-executed
+///     too. This suits values for which comparing is expensive (or
+///     meaningless), and lifts the `PartialEq` requirement. The values
+///     of such a query are not compared by strict mode or by
+///     `debug_validate_dependencies` either, and it cannot be a
+///     fixpoint query.
+///   - `#[salsa::history(depth = N)]` -- for a memoized query, keeps
+///     the last `N` distinct values computed for each key, with the
+///     revisions in which they became the value, for debugging. They
+///     are returned (oldest first) by a generated `foo_at_history`
+///     method, or `QueryTable::history`. Each kept value is a clone, so
+///     this costs up to `N` values per key on top of the memoized one.
+///   - `#[salsa::requires(expr)]` -- for a derived query, a
+///     precondition: a `bool` expression over the database, `db`
+///     (like `db.config().is_loaded()`), that must hold whenever the
+///     query is executed. In debug builds, it is evaluated before the
+///     query function runs, and the query panics (naming itself, its
+///     key and the expression) if it is false. What it reads is a
+///     dependency of the query, like anything the query function
+///     reads. The attribute can be given more than once.
+///   - `#[salsa::cycle(path::to::recover_fn)]` -- for a non-input,
+///     the function to call if the query turns out to depend on
+///     itself, instead of panicking. It is invoked as
+///     `recover_fn(db, cycle, key1, ...)`, where `cycle` is a
+///     `&salsa::Cycle` describing the participants, and returns the
+///     value to use for the query. This can be overridden at runtime
+///     with `Runtime::set_cycle_handling`.
+///   - `#[salsa::cycle(fixpoint, initial = expr)]` -- for a memoized
+///     query, resolves cycles by fixed-point iteration instead: a call
+///     that would form a cycle returns `expr` (which may refer to the
+///     query's keys), and the query is then re-executed, with that
+///     call returning the previous result, until the result no longer
+///     changes. The queries that read those provisional results are
+///     not memoized until the iteration converges; then the values of
+///     the last iteration are memoized, each depending on the inputs of
+///     the whole cycle, so the cycle is only re-run once one of those
+///     changes. Iteration panics if it does not converge after a fixed
+///     number of steps.
 ///   - `#[query_type(MyQueryTypeName)]` specifies the name of the
 ///     dummy struct created for the query. Default is the name of the
 ///     query, in camel case, plus the word "Query" (e.g.,
 ///     `MyQueryQuery` and `OtherQueryQuery` in the examples above).
+///   - `#[salsa::default = expr]` -- for an input, gives the value
+///     returned when the input is read before being set (see below).
+///   - `#[salsa::or_default]` -- for a query returning `Option<T>`,
+///     where `T: Default`, also generates `my_query_or_default`, which
+///     returns `T`, giving `T::default()` in place of `None`. It reads
+///     (and depends on) the query just like `my_query` does.
+///   - `#[salsa::iter]` -- for a query returning `Arc<Vec<T>>`, also
+///     generates `my_query_iter`, which returns a `salsa::ArcIter<T>`
+///     over the elements. The iterator holds on to the `Arc`, so
+///     reading the query does not clone the `Vec` (only the elements
+///     that are iterated over, one at a time).
+///   - `#[salsa::instantiate(name1 = Type1, name2 = Type2)]` -- for a
+///     query with one type parameter, declares the types it is used
+///     with (see "Generic queries" below).
 ///
 /// # Storage attributes
 ///
@@ -71,11 +159,38 @@ mod query_group;
 /// Specifying `storage input` will give you an **input
 /// query**. Unlike derived queries, whose value is given by a
 /// function, input queries are explicitly set by doing
-/// `db.query(QueryType).set(key, value)` (where `QueryType` is the
+/// `db.set_my_query(key).to(value)`, optionally adding
+/// `.with_durability(durability)` before the `to` (or, equivalently,
+/// `QueryType.in_db_mut(db).set(key, value)`, where `QueryType` is the
 /// `type` specified for the query). Accessing a value that has not
-/// yet been set will panic. Each time you invoke `set`, we assume the
-/// value has changed, and so we will potentially re-execute derived
-/// queries that read (transitively) from this input.
+/// yet been set will panic, unless the input has a
+/// `#[salsa::default = expr]` attribute: in that case, `expr` is
+/// evaluated the first time an unset key is read, and used as its value
+/// (with `Durability::LOW`) until it is `set`. Each time you invoke `set`,
+/// we assume the value has changed, and so we will potentially re-execute
+/// derived queries that read (transitively) from this input. With
+/// `#[salsa::input(eq)]`, the new value is instead compared (using
+/// `PartialEq`) to the current one, and setting an equal value with the
+/// same durability does nothing. The generated `try_my_query` method
+/// reads an input without panicking, giving `None` if it has not been
+/// set; the caller still depends on the input, and is re-executed when
+/// it is set.
+///
+/// For data that never changes while the program runs (like built-in
+/// definitions compiled into it), `#[salsa::input(immortal)]` skips
+/// the dependency tracking: reading a key that has a value records no
+/// dependency at all, so a query that only reads immortal inputs never
+/// needs to be re-validated. In exchange, a key can only be set once;
+/// setting (or removing) it again panics. Reading a key that has not
+/// been set (with `try_my_query`) still records a dependency, so that
+/// setting it afterwards is seen. The options can be combined, as in
+/// `#[salsa::input(eq, immortal)]`, but not with `#[salsa::default]`.
+///
+/// Like other queries, inputs may take any number of keys. An input
+/// `fn cell(&self, sheet: SheetId, cell: CellId) -> Value` is stored in
+/// a table keyed by `(SheetId, CellId)`, and set with
+/// `db.set_cell(sheet, cell).to(value)`; each entry has its own value
+/// and durability, as with a single key.
 ///
 /// ## Derived queries
 ///
@@ -83,14 +198,96 @@ mod query_group;
 ///
 /// - `#[salsa::memoized]` (the default) -- The result is memoized
 ///   between calls.  If the inputs have changed, we will recompute
-///   the value, but then compare against the old memoized value,
-///   which can significantly reduce the amount of recomputation
-///   required in new revisions. This does require that the value
-///   implements `Eq`.
+///   the value, but then compare against the old memoized value (with
+///   `PartialEq`): if they are equal, the value counts as unchanged
+///   ("early cutoff"), so queries that read it are validated without
+///   being re-executed. This can significantly reduce the amount of
+///   recomputation required in new revisions. This does require that
+///   the value implements `PartialEq`, unless `#[salsa::no_eq]` is set.
 /// - `#[salsa::dependencies]` -- does not cache the value, so it will
 ///   be recomputed every time it is needed. We do track the inputs, however,
 ///   so if they have not changed, then things that rely on this query
 ///   may be known not to have changed.
+/// - `#[salsa::transparent]` -- the query is not stored at all: the
+///   generated trait method simply calls the query function. Any
+///   queries that it reads are recorded as dependencies of the calling
+///   query instead. This suits thin wrappers around other queries (like
+///   `self.parse(file).items`), which would waste memory if memoized.
+///   Transparent queries have no storage, so they are not affected by
+///   (or visible to) sweeping and LRU.
+///
+/// ## Interned queries
+///
+/// `#[salsa::interned]` queries map their keys to small ids: interning
+/// an equal key again gives the same id, and the generated
+/// `lookup_my_query` method maps an id back to its key. A query with
+/// several keys interns the tuple of them, compared by structural
+/// equality, so no wrapper type is needed: for
+/// `fn intern_path(&self, module: ModuleId, name: Name) -> PathId`,
+/// `lookup_intern_path(id)` returns `(ModuleId, Name)`. The id type
+/// must implement `salsa::InternKey`; `salsa::intern_id!(pub struct
+/// PathId);` declares such a newtype of `InternId`.
+///
+/// The number of values an interned query holds can be capped with
+/// `QueryTableMut::set_intern_capacity`. The generated `try_my_query`
+/// method then returns a `salsa::InternCapacityExceeded` error for a
+/// new key once the table is full, where `my_query` would panic.
+///
+/// ## Cloning values
+///
+/// Every read of a query returns a clone of the stored value, so large
+/// values should be cheap to clone. The usual idiom is to return an
+/// `Arc<T>`: salsa never clones the `T` itself, each read only bumps the
+/// reference count (as long as the memoized value is reused, all reads
+/// return handles to the same allocation). Salsa does not wrap values
+/// in an `Arc` of its own, so there is no double wrapping, and no
+/// attribute is needed to opt in.
+///
+/// ## Hashing keys
+///
+/// Every query stores its values in a hash map from the key, using
+/// `FxHash` (from the `rustc-hash` crate) rather than the standard
+/// library's SipHash. It is much faster for small keys, such as integers
+/// and interned ids, but is not resistant to collisions chosen by an
+/// attacker. The hasher cannot be configured.
+///
+/// ## Group versions
+///
+/// `#[salsa::query_group(MyStorage, version = 3)]` gives the group a
+/// version (0 if none is given). Memoized values saved in one session
+/// (with `QueryTable::verified_entries`) and loaded back in another
+/// (with `QueryTableMut::prime_versioned`) are only used if the version
+/// is still the same, so bump it whenever the queries of the group
+/// change how they compute their values.
+///
+/// ## Generic queries
+///
+/// Query methods cannot be generic, as the query group trait must be
+/// usable as a trait object. A query with a single type parameter can
+/// instead be *monomorphized* over a fixed set of types, listed with
+/// `#[salsa::instantiate]`:
+///
+/// ```ignore
+/// #[salsa::query_group(ParseStorage)]
+/// trait ParseDatabase: SourceDatabase {
+///     #[salsa::instantiate(parse_json = Json, parse_toml = Toml)]
+///     fn parse<T: Grammar>(&self, id: FileId) -> Ast<T>;
+/// }
+///
+/// fn parse<T: Grammar>(db: &dyn ParseDatabase, id: FileId) -> Ast<T> {
+///     ...
+/// }
+/// ```
+///
+/// This declares two ordinary queries, as if written by hand:
+/// `parse_json(&self, id: FileId) -> Ast<Json>` (with query type
+/// `ParseJsonQuery`), which invokes `parse::<Json>`, and likewise
+/// `parse_toml`. Any other attributes apply to each of them; an
+/// `#[salsa::invoke(path)]` function is instantiated the same way.
+///
+/// Each instance is a separate query, with its own storage and its own
+/// copy of the generated code, so the cost (in compile time, and in the
+/// size of the database) is the same as writing out every instance.
 ///
 /// ## Attribute combinations
 ///
@@ -118,6 +315,46 @@ mod query_group;
 ///     fn my_query(&self, input: u32) -> u64;
 /// }
 /// ```
+///
+/// Likewise, a `default` may only be given for an `input` query:
+///
+/// ```compile_fail
+/// # use salsa_macros as salsa;
+/// #[salsa::query_group]
+/// trait CodegenDatabase {
+///     #[salsa::default = 0]
+///     fn my_query(&self, input: u32) -> u64;
+/// }
+/// ```
+///
+/// A fixpoint query cannot be `no_eq`, since fixed-point iteration
+/// compares values to tell when it has converged:
+///
+/// ```compile_fail
+/// # use salsa_macros as salsa;
+/// #[salsa::query_group]
+/// trait CodegenDatabase {
+///     #[salsa::no_eq]
+///     #[salsa::cycle(fixpoint, initial = 0)]
+///     fn my_query(&self, input: u32) -> u64;
+/// }
+/// ```
+///
+/// Queries cannot have a body in the trait: a derived query is computed
+/// by the function of the same name (or the one given with `invoke`),
+/// so the body would go unused. The macro reports this, naming the
+/// query; if the function is missing instead, the compiler reports
+/// that it cannot find it, pointing at the query.
+///
+/// ```compile_fail
+/// # use salsa_macros as salsa;
+/// #[salsa::query_group]
+/// trait CodegenDatabase {
+///     fn my_query(&self, input: u32) -> u64 {
+///         input as u64
+///     }
+/// }
+/// ```
 #[proc_macro_attribute]
 pub fn query_group(args: TokenStream, input: TokenStream) -> TokenStream {
     query_group::query_group(args, input)
@@ -146,3 +383,55 @@ pub fn query_group(args: TokenStream, input: TokenStream) -> TokenStream {
 pub fn database(args: TokenStream, input: TokenStream) -> TokenStream {
     database_storage::database(args, input)
 }
+
+/// This attribute declares an accumulator, which collects values of the
+/// given type (typically diagnostics) that queries push as they execute.
+///
+/// ```rust,ignore
+/// #[salsa::accumulator(Diagnostic)]
+/// pub struct Diagnostics;
+/// ```
+///
+/// This implements [`salsa::Accumulator`] for `Diagnostics` and adds
+/// two associated functions:
+///
+/// - `Diagnostics::push(db, diagnostic)`, to be called by a query while
+///   it executes;
+/// - `Diagnostics::get(db, root)`, which returns the diagnostics pushed
+///   while executing the query `root` (a `salsa::DatabaseKeyIndex`) and
+///   all the queries it read. It is usually easier to call
+///   `MyQuery.in_db(db).accumulated::<Diagnostics>(key)`, which also
+///   executes the query if needed.
+///
+/// The values are stored with the memoized value of the query that pushed
+/// them, so they are reported again when that value is reused.
+///
+/// [`salsa::Accumulator`]: trait.Accumulator.html
+#[proc_macro_attribute]
+pub fn accumulator(args: TokenStream, input: TokenStream) -> TokenStream {
+    accumulator::accumulator(args, input)
+}
+
+/// Derives `salsa::DebugWithDb` for a struct or enum, formatting it like
+/// `#[derive(Debug)]` would, except that each field is formatted with
+/// `DebugWithDb`. Every field type must therefore implement
+/// `DebugWithDb`, and so must each type parameter.
+///
+/// By default, the impl is generic over the database type, which only
+/// works if the fields' impls are, too. If some field can only be
+/// formatted with a particular database (typically an interned key),
+/// name that database type with an attribute:
+///
+/// ```rust,ignore
+/// #[derive(DebugWithDb)]
+/// #[debug_with_db(db = dyn MyQueryGroup)]
+/// struct Path {
+///     segments: Vec<NameId>,
+/// }
+/// ```
+///
+/// [`salsa::DebugWithDb`]: trait.DebugWithDb.html
+#[proc_macro_derive(DebugWithDb, attributes(debug_with_db))]
+pub fn debug_with_db(input: TokenStream) -> TokenStream {
+    debug_with_db::debug_with_db(input)
+}
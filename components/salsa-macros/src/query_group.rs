@@ -12,7 +12,10 @@ use syn::{
 
 /// Implementation for `[salsa::query_group]` decorator.
 pub(crate) fn query_group(args: TokenStream, input: TokenStream) -> TokenStream {
-    let group_struct = parse_macro_input!(args as Ident);
+    let QueryGroupArgs {
+        group_struct,
+        version,
+    } = parse_macro_input!(args as QueryGroupArgs);
     let input: ItemTrait = parse_macro_input!(input as ItemTrait);
     // println!("args: {:#?}", args);
     // println!("input: {:#?}", input);
@@ -33,14 +36,31 @@ pub(crate) fn query_group(args: TokenStream, input: TokenStream) -> TokenStream
     let _generics = input.generics.clone();
     let dyn_db = quote! { dyn #trait_name };
 
+    // Expand generic queries into one query per instantiation.
+    let items = match instantiate_generic_queries(input.items) {
+        Ok(items) => items,
+        Err(err) => return err.to_compile_error().into(),
+    };
+
     // Decompose the trait into the corresponding queries.
     let mut queries = vec![];
-    for item in input.items {
+    for item in items {
         match item {
             TraitItem::Method(method) => {
                 let mut storage = QueryStorage::Memoized;
                 let mut cycle = None;
                 let mut invoke = None;
+                let mut default = None;
+                let mut volatile = false;
+                let mut retry_err = false;
+                let mut no_eq = false;
+                let mut history = None;
+                let mut or_default = false;
+                let mut iter_elements = false;
+                let mut requires = vec![];
+                let mut input_eq = false;
+                let mut input_immortal = false;
+                let mut interned_generational = false;
                 let query_name = method.sig.ident.to_string();
                 let mut query_type = Ident::new(
                     &format!("{}Query", method.sig.ident.to_string().to_camel_case()),
@@ -63,17 +83,82 @@ pub(crate) fn query_group(args: TokenStream, input: TokenStream) -> TokenStream
                         "input" => {
                             storage = QueryStorage::Input;
                             num_storages += 1;
+                            if !tts.is_empty() {
+                                let options =
+                                    parse_macro_input!(tts as Parenthesized<InputOptions>).0;
+                                for option in options.0 {
+                                    if option == "eq" {
+                                        input_eq = true;
+                                    } else if option == "immortal" {
+                                        input_immortal = true;
+                                    } else {
+                                        return Error::new(
+                                            option.span(),
+                                            format!("unknown #[salsa::input] option `{}`", option),
+                                        )
+                                        .to_compile_error()
+                                        .into();
+                                    }
+                                }
+                            }
                         }
                         "interned" => {
                             storage = QueryStorage::Interned;
                             num_storages += 1;
+                            if !tts.is_empty() {
+                                let options =
+                                    parse_macro_input!(tts as Parenthesized<InputOptions>).0;
+                                for option in options.0 {
+                                    if option == "generational" {
+                                        interned_generational = true;
+                                    } else {
+                                        return Error::new(
+                                            option.span(),
+                                            format!(
+                                                "unknown #[salsa::interned] option `{}`",
+                                                option
+                                            ),
+                                        )
+                                        .to_compile_error()
+                                        .into();
+                                    }
+                                }
+                            }
                         }
                         "cycle" => {
-                            cycle = Some(parse_macro_input!(tts as Parenthesized<syn::Path>).0);
+                            cycle = Some(parse_macro_input!(tts as Parenthesized<Cycle>).0);
                         }
                         "invoke" => {
                             invoke = Some(parse_macro_input!(tts as Parenthesized<syn::Path>).0);
                         }
+                        "default" => {
+                            default = Some(parse_macro_input!(tts as DefaultValue).0);
+                        }
+                        "volatile" => {
+                            volatile = true;
+                        }
+                        "retry_err" => {
+                            retry_err = true;
+                        }
+                        "no_eq" => {
+                            no_eq = true;
+                        }
+                        "history" => {
+                            history = Some(
+                                parse_macro_input!(tts as Parenthesized<HistoryOptions>)
+                                    .0
+                                    .depth,
+                            );
+                        }
+                        "or_default" => {
+                            or_default = true;
+                        }
+                        "iter" => {
+                            iter_elements = true;
+                        }
+                        "requires" => {
+                            requires.push(parse_macro_input!(tts as Parenthesized<syn::Expr>).0);
+                        }
                         "query_type" => {
                             query_type = parse_macro_input!(tts as Parenthesized<Ident>).0;
                         }
@@ -89,6 +174,29 @@ pub(crate) fn query_group(args: TokenStream, input: TokenStream) -> TokenStream
                     }
                 }
 
+                // The query calls a function of its own (or the one given
+                // with `#[salsa::invoke]`), so a body here would go unused.
+                if let Some(body) = &method.default {
+                    let message = match (&invoke, &storage) {
+                        (_, QueryStorage::Input) => format!(
+                            "query `{}` cannot have a body: the values of inputs are set, not computed",
+                            method.sig.ident,
+                        ),
+                        (Some(_), _) => format!(
+                            "query `{}` cannot have a body: it is computed by the function \
+                             given with `#[salsa::invoke]`",
+                            method.sig.ident,
+                        ),
+                        (None, _) => format!(
+                            "query `{0}` cannot have a body: it is computed by the function `{0}`, \
+                             which takes the database (as `&dyn {1}`) and the keys; \
+                             move the body there, or name another function with `#[salsa::invoke]`",
+                            method.sig.ident, trait_name,
+                        ),
+                    };
+                    return Error::new(body.span(), message).to_compile_error().into();
+                }
+
                 // Check attribute combinations.
                 if num_storages > 1 {
                     return Error::new(method.sig.span(), "multiple storage attributes specified")
@@ -106,6 +214,88 @@ pub(crate) fn query_group(args: TokenStream, input: TokenStream) -> TokenStream
                     }
                     _ => {}
                 }
+                match &default {
+                    Some(default) if storage != QueryStorage::Input => {
+                        return Error::new(
+                            default.span(),
+                            "#[salsa::default] can only be set on #[salsa::input] queries",
+                        )
+                        .to_compile_error()
+                        .into();
+                    }
+                    Some(default) if input_immortal => {
+                        return Error::new(
+                            default.span(),
+                            "#[salsa::default] cannot be set on immortal inputs",
+                        )
+                        .to_compile_error()
+                        .into();
+                    }
+                    _ => {}
+                }
+                if let Some(Cycle::Fixpoint { initial }) = &cycle {
+                    if storage != QueryStorage::Memoized {
+                        return Error::new(
+                            initial.span(),
+                            "#[salsa::cycle(fixpoint, ..)] can only be set on memoized queries",
+                        )
+                        .to_compile_error()
+                        .into();
+                    }
+                }
+                if volatile && !storage.needs_query_function() {
+                    return Error::new(
+                        method.sig.span(),
+                        "#[salsa::volatile] can only be set on derived queries",
+                    )
+                    .to_compile_error()
+                    .into();
+                }
+                if no_eq && storage != QueryStorage::Memoized {
+                    return Error::new(
+                        method.sig.span(),
+                        "#[salsa::no_eq] can only be set on memoized queries",
+                    )
+                    .to_compile_error()
+                    .into();
+                }
+                if let (true, Some(Cycle::Fixpoint { initial })) = (no_eq, &cycle) {
+                    return Error::new(
+                        initial.span(),
+                        "#[salsa::no_eq] cannot be combined with #[salsa::cycle(fixpoint, ..)], \
+                         as fixed-point iteration compares values to detect convergence",
+                    )
+                    .to_compile_error()
+                    .into();
+                }
+                if retry_err && !storage.needs_query_function() {
+                    return Error::new(
+                        method.sig.span(),
+                        "#[salsa::retry_err] can only be set on derived queries",
+                    )
+                    .to_compile_error()
+                    .into();
+                }
+                if let Some(depth) = &history {
+                    if storage != QueryStorage::Memoized {
+                        return Error::new(
+                            depth.span(),
+                            "#[salsa::history] can only be set on memoized queries",
+                        )
+                        .to_compile_error()
+                        .into();
+                    }
+                }
+                if let Some(expr) = requires.first() {
+                    if !storage.needs_query_function() {
+                        return Error::new(
+                            expr.span(),
+                            "#[salsa::requires] can only be set on derived queries",
+                        )
+                        .to_compile_error()
+                        .into();
+                    }
+                }
 
                 // Extract keys.
                 let mut iter = method.sig.inputs.iter();
@@ -159,6 +349,43 @@ pub(crate) fn query_group(args: TokenStream, input: TokenStream) -> TokenStream
                     }
                 };
 
+                // For `#[salsa::or_default]`, the value must be an
+                // `Option`; the `foo_or_default` method returns its
+                // contents.
+                let or_default = if or_default {
+                    match inner_type(&value, "Option") {
+                        Some(inner) => Some(inner.clone()),
+                        None => {
+                            return Error::new(
+                                value.span(),
+                                "#[salsa::or_default] can only be set on queries returning `Option<_>`",
+                            )
+                            .to_compile_error()
+                            .into();
+                        }
+                    }
+                } else {
+                    None
+                };
+
+                // For `#[salsa::iter]`, the value must be an `Arc<Vec<_>>`;
+                // the `foo_iter` method iterates over its elements.
+                let iter_item = if iter_elements {
+                    match inner_type(&value, "Arc").and_then(|vec| inner_type(vec, "Vec")) {
+                        Some(item) => Some(item.clone()),
+                        None => {
+                            return Error::new(
+                                value.span(),
+                                "#[salsa::iter] can only be set on queries returning `Arc<Vec<_>>`",
+                            )
+                            .to_compile_error()
+                            .into();
+                        }
+                    }
+                } else {
+                    None
+                };
+
                 // For `#[salsa::interned]` keys, we create a "lookup key" automatically.
                 //
                 // For a query like:
@@ -195,6 +422,17 @@ pub(crate) fn query_group(args: TokenStream, input: TokenStream) -> TokenStream
                         value: lookup_value,
                         invoke: None,
                         cycle: cycle.clone(),
+                        default: None,
+                        volatile: false,
+                        retry_err: false,
+                        no_eq: false,
+                        history: None,
+                        or_default: None,
+                        iter: None,
+                        requires: vec![],
+                        input_eq: false,
+                        input_immortal: false,
+                        interned_generational: false,
                     })
                 } else {
                     None
@@ -210,6 +448,17 @@ pub(crate) fn query_group(args: TokenStream, input: TokenStream) -> TokenStream
                     value,
                     invoke,
                     cycle,
+                    default,
+                    volatile,
+                    retry_err,
+                    no_eq,
+                    history,
+                    or_default,
+                    iter: iter_item,
+                    requires,
+                    input_eq,
+                    input_immortal,
+                    interned_generational,
                 });
 
                 queries.extend(lookup_query);
@@ -242,6 +491,80 @@ pub(crate) fn query_group(args: TokenStream, input: TokenStream) -> TokenStream
             fn #fn_name(&self, #(#key_names: #keys),*) -> #value;
         });
 
+        // For every query, we have `foo_fn`, which returns the query as a closure
+        let fn_fn_name = Ident::new(&format!("{}_fn", fn_name), fn_name.span());
+
+        let fn_fn_docs = format!(
+            "
+            Returns a closure that invokes `{fn_name}`, for passing the
+            query to code that takes an `Fn`. The closure borrows the
+            database, and records dependencies like `{fn_name}` does.
+        ",
+            fn_name = fn_name
+        );
+
+        query_fn_declarations.extend(quote! {
+            # [doc = #fn_fn_docs]
+            fn #fn_fn_name(&self) -> Box<dyn Fn(#(#keys),*) -> #value + '_>;
+        });
+
+        query_fn_definitions.extend(quote! {
+            fn #fn_fn_name(&self) -> Box<dyn Fn(#(#keys),*) -> #value + '_> {
+                Box::new(move |#(#key_names),*| self.#fn_name(#(#key_names),*))
+            }
+        });
+
+        // For `#[salsa::iter]` queries, we also need `foo_iter`
+        if let Some(item) = &query.iter {
+            let iter_fn_name = Ident::new(&format!("{}_iter", fn_name), fn_name.span());
+
+            let iter_fn_docs = format!(
+                "
+                Iterates over the elements of the `Vec` returned by
+                `{fn_name}`, which is shared with the memoized value
+                rather than cloned. The same dependency on `{fn_name}`
+                is recorded.
+            ",
+                fn_name = fn_name
+            );
+
+            query_fn_declarations.extend(quote! {
+                # [doc = #iter_fn_docs]
+                fn #iter_fn_name(&self, #(#key_names: #keys),*) -> salsa::ArcIter<#item>;
+            });
+
+            query_fn_definitions.extend(quote! {
+                fn #iter_fn_name(&self, #(#key_names: #keys),*) -> salsa::ArcIter<#item> {
+                    salsa::ArcIter::new(self.#fn_name(#(#key_names),*))
+                }
+            });
+        }
+
+        // For `#[salsa::or_default]` queries, we also need `foo_or_default`
+        if let Some(inner) = &query.or_default {
+            let or_default_fn_name = Ident::new(&format!("{}_or_default", fn_name), fn_name.span());
+
+            let or_default_fn_docs = format!(
+                "
+                Like `{fn_name}`, but returns the default value instead of
+                `None` (see `QueryTable::get_or_default`). The same
+                dependency on `{fn_name}` is recorded.
+            ",
+                fn_name = fn_name
+            );
+
+            query_fn_declarations.extend(quote! {
+                # [doc = #or_default_fn_docs]
+                fn #or_default_fn_name(&self, #(#key_names: #keys),*) -> #inner;
+            });
+
+            query_fn_definitions.extend(quote! {
+                fn #or_default_fn_name(&self, #(#key_names: #keys),*) -> #inner {
+                    self.#fn_name(#(#key_names),*).unwrap_or_default()
+                }
+            });
+        }
+
         // Special case: transparent queries don't create actual storage,
         // just inline the definition
         if let QueryStorage::Transparent = query.storage {
@@ -273,14 +596,15 @@ pub(crate) fn query_group(args: TokenStream, input: TokenStream) -> TokenStream
         // For input queries, we need `set_foo` etc
         if let QueryStorage::Input = query.storage {
             let set_fn_name = Ident::new(&format!("set_{}", fn_name), fn_name.span());
-            let set_with_durability_fn_name =
-                Ident::new(&format!("set_{}_with_durability", fn_name), fn_name.span());
 
             let set_fn_docs = format!(
                 "
                 Set the value of the `{fn_name}` input.
 
-                See `{fn_name}` for details.
+                See `{fn_name}` for details. The returned setter
+                writes nothing until its `to` method is called with
+                the new value; `with_durability` may be used first to
+                promise that the value will rarely (or never) change.
 
                 *Note:* Setting values will trigger cancellation
                 of any ongoing queries; this method blocks until
@@ -289,42 +613,184 @@ pub(crate) fn query_group(args: TokenStream, input: TokenStream) -> TokenStream
                 fn_name = fn_name
             );
 
-            let set_constant_fn_docs = format!(
+            query_fn_declarations.extend(quote! {
+                # [doc = #set_fn_docs]
+                fn #set_fn_name(&mut self, #(#key_names: #keys),*) -> salsa::InputSetter<'_, #qt>;
+            });
+
+            query_fn_definitions.extend(quote! {
+                fn #set_fn_name(&mut self, #(#key_names: #keys),*) -> salsa::InputSetter<'_, #qt> {
+                    fn __shim(db: &mut dyn #trait_name, #(#key_names: #keys),*) -> salsa::InputSetter<'_, #qt> {
+                        salsa::plumbing::get_query_table_mut::<#qt>(db).setter((#(#key_names),*))
+                    }
+                    __shim(self, #(#key_names),*)
+                }
+            });
+
+            let try_fn_name = Ident::new(&format!("try_{}", fn_name), fn_name.span());
+
+            let try_fn_docs = format!(
                 "
-                Set the value of the `{fn_name}` input and promise
-                that its value will never change again.
+                Get the value of the `{fn_name}` input, or `None` if it
+                has not been set.
 
-                See `{fn_name}` for details.
+                Unlike `{fn_name}`, this does not panic when the value is
+                missing. Either way, a dependency on the input is
+                recorded, so setting it later re-executes the caller.
+            ",
+                fn_name = fn_name
+            );
 
-                *Note:* Setting values will trigger cancellation
-                of any ongoing queries; this method blocks until
-                those queries have been cancelled.
+            query_fn_declarations.extend(quote! {
+                # [doc = #try_fn_docs]
+                fn #try_fn_name(&self, #(#key_names: #keys),*) -> Option<#value>;
+            });
+
+            query_fn_definitions.extend(quote! {
+                fn #try_fn_name(&self, #(#key_names: #keys),*) -> Option<#value> {
+                    fn __shim(db: &(dyn #trait_name + '_), #(#key_names: #keys),*) -> Option<#value> {
+                        salsa::plumbing::get_query_table::<#qt>(db).get_if_set((#(#key_names),*))
+                    }
+                    __shim(self, #(#key_names),*)
+                }
+            });
+        }
+
+        // For derived queries, `foo_probed` also tells how the value
+        // was obtained.
+        if query.storage.needs_query_function() {
+            let probed_fn_name = Ident::new(&format!("{}_probed", fn_name), fn_name.span());
+
+            let probed_fn_docs = format!(
+                "
+                Like `{fn_name}`, but also returns whether the memoized
+                value was reused as is, reused after validating it, or
+                computed anew (see `salsa::CacheOutcome`).
             ",
                 fn_name = fn_name
             );
 
             query_fn_declarations.extend(quote! {
-                # [doc = #set_fn_docs]
-                fn #set_fn_name(&mut self, #(#key_names: #keys,)* value__: #value);
+                # [doc = #probed_fn_docs]
+                fn #probed_fn_name(&self, #(#key_names: #keys),*) -> (#value, salsa::CacheOutcome);
+            });
+
+            query_fn_definitions.extend(quote! {
+                fn #probed_fn_name(&self, #(#key_names: #keys),*) -> (#value, salsa::CacheOutcome) {
+                    fn __shim(db: &(dyn #trait_name + '_), #(#key_names: #keys),*) -> (#value, salsa::CacheOutcome) {
+                        salsa::plumbing::get_query_table::<#qt>(db).get_probed((#(#key_names),*))
+                    }
+                    __shim(self, #(#key_names),*)
+                }
+            });
+        }
 
+        // Queries with a history can also return it.
+        if query.history.is_some() {
+            let history_fn_name = Ident::new(&format!("{}_at_history", fn_name), fn_name.span());
 
-                # [doc = #set_constant_fn_docs]
-                fn #set_with_durability_fn_name(&mut self, #(#key_names: #keys,)* value__: #value, durability__: salsa::Durability);
+            let history_fn_docs = format!(
+                "
+                Returns the last few distinct values computed by `{fn_name}`
+                for the given keys, oldest first, each with the revision in
+                which it became the value (see `#[salsa::history]`).
+            ",
+                fn_name = fn_name
+            );
+
+            query_fn_declarations.extend(quote! {
+                # [doc = #history_fn_docs]
+                fn #history_fn_name(&self, #(#key_names: #keys),*) -> Vec<(salsa::Revision, #value)>;
             });
 
             query_fn_definitions.extend(quote! {
-                fn #set_fn_name(&mut self, #(#key_names: #keys,)* value__: #value) {
-                    fn __shim(db: &mut dyn #trait_name, #(#key_names: #keys,)* value__: #value) {
-                        salsa::plumbing::get_query_table_mut::<#qt>(db).set((#(#key_names),*), value__)
+                fn #history_fn_name(&self, #(#key_names: #keys),*) -> Vec<(salsa::Revision, #value)> {
+                    fn __shim(db: &(dyn #trait_name + '_), #(#key_names: #keys),*) -> Vec<(salsa::Revision, #value)> {
+                        salsa::plumbing::get_query_table::<#qt>(db).history(&(#(#key_names),*))
                     }
-                    __shim(self, #(#key_names,)* value__)
+                    __shim(self, #(#key_names),*)
                 }
+            });
+        }
+
+        // For lookup queries, we also need `lookup_foo_ref`
+        if let QueryStorage::InternedLookup { .. } = query.storage {
+            let ref_fn_name = Ident::new(&format!("{}_ref", fn_name), fn_name.span());
+
+            let ref_fn_docs = format!(
+                "
+                Like `{fn_name}`, but borrows the interned value rather
+                than cloning it.
+            ",
+                fn_name = fn_name
+            );
 
-                fn #set_with_durability_fn_name(&mut self, #(#key_names: #keys,)* value__: #value, durability__: salsa::Durability) {
-                    fn __shim(db: &mut dyn #trait_name, #(#key_names: #keys,)* value__: #value, durability__: salsa::Durability) {
-                        salsa::plumbing::get_query_table_mut::<#qt>(db).set_with_durability((#(#key_names),*), value__, durability__)
+            query_fn_declarations.extend(quote! {
+                # [doc = #ref_fn_docs]
+                fn #ref_fn_name(&self, #(#key_names: #keys),*) -> salsa::InternedRef<#value>;
+            });
+
+            query_fn_definitions.extend(quote! {
+                fn #ref_fn_name(&self, #(#key_names: #keys),*) -> salsa::InternedRef<#value> {
+                    fn __shim(db: &(dyn #trait_name + '_), #(#key_names: #keys),*) -> salsa::InternedRef<#value> {
+                        salsa::plumbing::get_query_table::<#qt>(db).lookup_ref((#(#key_names),*))
                     }
-                    __shim(self, #(#key_names,)* value__ ,durability__)
+                    __shim(self, #(#key_names),*)
+                }
+            });
+
+            let try_fn_name = Ident::new(&format!("try_{}", fn_name), fn_name.span());
+
+            let try_fn_docs = format!(
+                "
+                Like `{fn_name}`, but returns `None` rather than panicking
+                if nothing is interned as the given id (for example, an
+                id that was freed or that belongs to another table).
+            ",
+                fn_name = fn_name
+            );
+
+            query_fn_declarations.extend(quote! {
+                # [doc = #try_fn_docs]
+                fn #try_fn_name(&self, #(#key_names: #keys),*) -> Option<#value>;
+            });
+
+            query_fn_definitions.extend(quote! {
+                fn #try_fn_name(&self, #(#key_names: #keys),*) -> Option<#value> {
+                    fn __shim(db: &(dyn #trait_name + '_), #(#key_names: #keys),*) -> Option<#value> {
+                        salsa::plumbing::get_query_table::<#qt>(db).try_lookup((#(#key_names),*))
+                    }
+                    __shim(self, #(#key_names),*)
+                }
+            });
+        }
+
+        // For interned queries, `try_foo` fails rather than panicking
+        // once the table is full.
+        if let QueryStorage::Interned = query.storage {
+            let try_fn_name = Ident::new(&format!("try_{}", fn_name), fn_name.span());
+
+            let try_fn_docs = format!(
+                "
+                Like `{fn_name}`, but returns an error rather than panicking
+                if the value is new and the table already holds as many
+                values as its capacity allows (see
+                `QueryTableMut::set_intern_capacity`).
+            ",
+                fn_name = fn_name
+            );
+
+            query_fn_declarations.extend(quote! {
+                # [doc = #try_fn_docs]
+                fn #try_fn_name(&self, #(#key_names: #keys),*) -> Result<#value, salsa::InternCapacityExceeded>;
+            });
+
+            query_fn_definitions.extend(quote! {
+                fn #try_fn_name(&self, #(#key_names: #keys),*) -> Result<#value, salsa::InternCapacityExceeded> {
+                    fn __shim(db: &(dyn #trait_name + '_), #(#key_names: #keys),*) -> Result<#value, salsa::InternCapacityExceeded> {
+                        salsa::plumbing::get_query_table::<#qt>(db).try_intern((#(#key_names),*))
+                    }
+                    __shim(self, #(#key_names),*)
                 }
             });
         }
@@ -353,6 +819,12 @@ pub(crate) fn query_group(args: TokenStream, input: TokenStream) -> TokenStream
     };
 
     // Emit the query group struct and impl of `QueryGroup`.
+    let trait_name_string = trait_name.to_string();
+    let version = version.map(|version| {
+        quote! {
+            const VERSION: u32 = #version;
+        }
+    });
     output.extend(quote! {
         /// Representative struct for the query group.
         #trait_vis struct #group_struct { }
@@ -361,6 +833,8 @@ pub(crate) fn query_group(args: TokenStream, input: TokenStream) -> TokenStream
         {
             type DynDb = #dyn_db;
             type GroupStorage = #group_storage;
+            const GROUP_NAME: &'static str = #trait_name_string;
+            #version
         }
     });
 
@@ -392,6 +866,9 @@ pub(crate) fn query_group(args: TokenStream, input: TokenStream) -> TokenStream
         let qt = &query.query_type;
 
         let storage = match &query.storage {
+            QueryStorage::Memoized if query.no_eq => {
+                quote!(salsa::plumbing::MemoizedNoEqStorage<Self>)
+            }
             QueryStorage::Memoized => quote!(salsa::plumbing::MemoizedStorage<Self>),
             QueryStorage::Dependencies => quote!(salsa::plumbing::DependencyStorage<Self>),
             QueryStorage::Input => quote!(salsa::plumbing::InputStorage<Self>),
@@ -501,35 +978,160 @@ pub(crate) fn query_group(args: TokenStream, input: TokenStream) -> TokenStream
             };
             let invoke = query.invoke_tt();
 
-            let recover = if let Some(cycle_recovery_fn) = &query.cycle {
+            // Volatile queries are re-executed in every revision.
+            let report_untracked_read = if query.volatile {
+                quote! {
+                    salsa::Database::salsa_runtime(db).report_untracked_read();
+                }
+            } else {
+                quote! {}
+            };
+
+            // Memoized errors are re-attempted in the next revision, as
+            // if the query had been volatile when it failed.
+            let invoke_call = if query.retry_err {
+                quote! {
+                    let value = #invoke(db, #(#key_names),*);
+                    if value.is_err() {
+                        salsa::Database::salsa_runtime(db).report_untracked_read();
+                    }
+                    value
+                }
+            } else {
+                quote! { #invoke(db, #(#key_names),*) }
+            };
+
+            // Preconditions are checked in debug builds, before the
+            // query function runs (so what they read is tracked).
+            let key_debug = if query.keys.len() == 1 {
+                quote! { #(#key_names),* }
+            } else {
+                quote! { (#(&#key_names),*) }
+            };
+            let check_requires = query.requires.iter().map(|expr| {
+                let text = quote!(#expr).to_string();
                 quote! {
-                    fn recover(db: &<Self as salsa::QueryDb<'_>>::DynDb, cycle: &[salsa::DatabaseKeyIndex], #key_pattern: &<Self as salsa::Query>::Key)
+                    if cfg!(debug_assertions) && !(#expr) {
+                        panic!(
+                            "precondition of {}({:?}) does not hold: `{}`",
+                            <Self as salsa::Query>::QUERY_NAME,
+                            #key_debug,
+                            #text,
+                        );
+                    }
+                }
+            });
+
+            let recover = match &query.cycle {
+                Some(Cycle::Recover(cycle_recovery_fn)) => quote! {
+                    fn recover(db: &<Self as salsa::QueryDb<'_>>::DynDb, cycle: &salsa::Cycle, #key_pattern: &<Self as salsa::Query>::Key)
                         -> Option<<Self as salsa::Query>::Value> {
                         Some(#cycle_recovery_fn(
                                 db,
-                                &cycle.iter().map(|k| format!("{:?}", k.debug(db))).collect::<Vec<String>>(),
+                                cycle,
                                 #(#key_names),*
                         ))
                     }
-                }
-            } else {
-                quote! {}
+                },
+                Some(Cycle::Fixpoint { initial }) => quote! {
+                    #[allow(unused_variables)]
+                    fn cycle_initial(#key_pattern: &<Self as salsa::Query>::Key)
+                        -> Option<<Self as salsa::Query>::Value> {
+                        Some(#initial)
+                    }
+                },
+                None => quote! {},
             };
 
+            let history_depth = query.history.as_ref().map(|depth| {
+                quote! {
+                    const HISTORY_DEPTH: usize = #depth;
+                }
+            });
+
             output.extend(quote_spanned! {span=>
                 // ANCHOR:QueryFunction_impl
                 impl salsa::plumbing::QueryFunction for #qt
                 {
                     fn execute(db: &<Self as salsa::QueryDb<'_>>::DynDb, #key_pattern: <Self as salsa::Query>::Key)
                         -> <Self as salsa::Query>::Value {
-                        #invoke(db, #(#key_names),*)
+                        #report_untracked_read
+                        #(#check_requires)*
+                        #invoke_call
                     }
 
                     #recover
+                    #history_depth
+
+                    fn downcast_recovered(value: Box<dyn std::any::Any>) -> Option<<Self as salsa::Query>::Value> {
+                        value.downcast().ok().map(|value| *value)
+                    }
                 }
                 // ANCHOR_END:QueryFunction_impl
             });
         }
+
+        // Implement the InputQuery trait for input queries.
+        if let QueryStorage::Input = query.storage {
+            let default_value = if let Some(default) = &query.default {
+                quote! {
+                    fn default_value(_key: &<Self as salsa::Query>::Key)
+                        -> Option<<Self as salsa::Query>::Value> {
+                        Some(#default)
+                    }
+                }
+            } else {
+                quote! {}
+            };
+
+            let values_equal = if query.input_eq {
+                quote! {
+                    fn values_equal(
+                        old_value: &<Self as salsa::Query>::Value,
+                        new_value: &<Self as salsa::Query>::Value,
+                    ) -> bool {
+                        old_value == new_value
+                    }
+                }
+            } else {
+                quote! {}
+            };
+
+            let immortal = if query.input_immortal {
+                quote! {
+                    const IMMORTAL: bool = true;
+                }
+            } else {
+                quote! {}
+            };
+
+            output.extend(quote! {
+                impl salsa::plumbing::InputQuery for #qt
+                {
+                    #default_value
+                    #values_equal
+                    #immortal
+                }
+            });
+        }
+
+        // Implement the InternedQuery trait for interned queries.
+        if let QueryStorage::Interned = query.storage {
+            let generational = if query.interned_generational {
+                quote! {
+                    const GENERATIONAL: bool = true;
+                }
+            } else {
+                quote! {}
+            };
+
+            output.extend(quote! {
+                impl salsa::plumbing::InternedQuery for #qt
+                {
+                    #generational
+                }
+            });
+        }
     }
 
     let mut fmt_ops = proc_macro2::TokenStream::new();
@@ -554,6 +1156,89 @@ pub(crate) fn query_group(args: TokenStream, input: TokenStream) -> TokenStream
         });
     }
 
+    let mut dependencies_ops = proc_macro2::TokenStream::new();
+    for (Query { fn_name, .. }, query_index) in non_transparent_queries().zip(0_u16..) {
+        dependencies_ops.extend(quote! {
+            #query_index => {
+                salsa::plumbing::QueryStorageOps::dependencies(
+                    &*self.#fn_name, db, input
+                )
+            }
+        });
+    }
+
+    let mut accumulated_ops = proc_macro2::TokenStream::new();
+    for (Query { fn_name, .. }, query_index) in non_transparent_queries().zip(0_u16..) {
+        accumulated_ops.extend(quote! {
+            #query_index => {
+                salsa::plumbing::QueryStorageOps::accumulated(
+                    &*self.#fn_name, db, input
+                )
+            }
+        });
+    }
+
+    let mut validate_ops = proc_macro2::TokenStream::new();
+    for (Query { fn_name, .. }, query_index) in non_transparent_queries().zip(0_u16..) {
+        validate_ops.extend(quote! {
+            #query_index => {
+                salsa::plumbing::QueryStorageOps::validate(
+                    &*self.#fn_name, db, input
+                )
+            }
+        });
+    }
+
+    let mut commit_provisional_ops = proc_macro2::TokenStream::new();
+    for (Query { fn_name, .. }, query_index) in non_transparent_queries().zip(0_u16..) {
+        commit_provisional_ops.extend(quote! {
+            #query_index => {
+                salsa::plumbing::QueryStorageOps::commit_provisional(
+                    &*self.#fn_name, db, input, inputs, durability
+                )
+            }
+        });
+    }
+
+    let mut validate_dependencies_ops = proc_macro2::TokenStream::new();
+    for Query { fn_name, .. } in non_transparent_queries() {
+        validate_dependencies_ops.extend(quote! {
+            salsa::plumbing::QueryStorageOps::debug_validate_dependencies(&*self.#fn_name, db);
+        });
+    }
+
+    let mut set_by_name_ops = proc_macro2::TokenStream::new();
+    let mut take_recorded_ops = proc_macro2::TokenStream::new();
+    let mut input_names = vec![];
+    for query in non_transparent_queries() {
+        if let QueryStorage::Input = query.storage {
+            let qt = &query.query_type;
+            let query_name = &query.query_name;
+            let fn_name = &query.fn_name;
+            set_by_name_ops.extend(quote! {
+                #query_name => salsa::plumbing::set_input_from_any::<#qt>(db, key, value, durability),
+            });
+            take_recorded_ops.extend(quote! {
+                salsa::plumbing::take_recorded_sets::<#qt>(&*self.#fn_name, op);
+            });
+            input_names.push(query_name);
+        }
+    }
+
+    let mut set_lru_capacity_ops = proc_macro2::TokenStream::new();
+    for query in non_transparent_queries() {
+        if let QueryStorage::Memoized | QueryStorage::Dependencies = query.storage {
+            let query_name = &query.query_name;
+            let fn_name = &query.fn_name;
+            set_lru_capacity_ops.extend(quote! {
+                #query_name => {
+                    salsa::plumbing::LruQueryStorageOps::set_lru_capacity(&*self.#fn_name, capacity);
+                    true
+                }
+            });
+        }
+    }
+
     let mut for_each_ops = proc_macro2::TokenStream::new();
     for Query { fn_name, .. } in non_transparent_queries() {
         for_each_ops.extend(quote! {
@@ -580,6 +1265,19 @@ pub(crate) fn query_group(args: TokenStream, input: TokenStream) -> TokenStream
         }
         // ANCHOR_END:group_storage_new
 
+        impl #group_storage {
+            #trait_vis fn fork(&self) -> Self {
+                #group_storage {
+                    #(
+                        #queries_with_storage:
+                        std::sync::Arc::new(salsa::plumbing::QueryStorageOps::fork(
+                            &*self.#queries_with_storage
+                        )),
+                    )*
+                }
+            }
+        }
+
         // ANCHOR:group_storage_methods
         impl #group_storage {
             #trait_vis fn fmt_index(
@@ -606,6 +1304,88 @@ pub(crate) fn query_group(args: TokenStream, input: TokenStream) -> TokenStream
                 }
             }
 
+            #trait_vis fn dependencies(
+                &self,
+                db: &(#dyn_db + '_),
+                input: salsa::DatabaseKeyIndex,
+            ) -> Option<Vec<salsa::DatabaseKeyIndex>> {
+                match input.query_index() {
+                    #dependencies_ops
+                    i => panic!("salsa: impossible query index {}", i),
+                }
+            }
+
+            #trait_vis fn accumulated(
+                &self,
+                db: &(#dyn_db + '_),
+                input: salsa::DatabaseKeyIndex,
+            ) -> Option<salsa::plumbing::AccumulatedValues> {
+                match input.query_index() {
+                    #accumulated_ops
+                    i => panic!("salsa: impossible query index {}", i),
+                }
+            }
+
+            #trait_vis fn validate(
+                &self,
+                db: &(#dyn_db + '_),
+                input: salsa::DatabaseKeyIndex,
+            ) {
+                match input.query_index() {
+                    #validate_ops
+                    i => panic!("salsa: impossible query index {}", i),
+                }
+            }
+
+            #trait_vis fn commit_provisional(
+                &self,
+                db: &(#dyn_db + '_),
+                input: salsa::DatabaseKeyIndex,
+                inputs: Option<&[salsa::DatabaseKeyIndex]>,
+                durability: salsa::Durability,
+            ) {
+                match input.query_index() {
+                    #commit_provisional_ops
+                    i => panic!("salsa: impossible query index {}", i),
+                }
+            }
+
+            #trait_vis fn debug_validate_dependencies(&self, db: &(#dyn_db + '_)) {
+                #validate_dependencies_ops
+            }
+
+            #trait_vis fn has_input(name: &str) -> bool {
+                let input_names: &[&str] = &[#(#input_names),*];
+                input_names.contains(&name)
+            }
+
+            #[allow(unused_variables)]
+            #trait_vis fn set_input_by_name(
+                db: &mut (#dyn_db + '_),
+                name: &str,
+                key: Box<dyn std::any::Any>,
+                value: Box<dyn std::any::Any>,
+                durability: salsa::Durability,
+            ) -> Result<(), salsa::SetInputError> {
+                match name {
+                    #set_by_name_ops
+                    _ => Err(salsa::SetInputError::NoSuchInput { name: name.to_string() }),
+                }
+            }
+
+            #[allow(unused_variables)]
+            #trait_vis fn set_lru_capacity_by_name(&self, name: &str, capacity: usize) -> bool {
+                match name {
+                    #set_lru_capacity_ops
+                    _ => false,
+                }
+            }
+
+            #[allow(unused_variables)]
+            #trait_vis fn take_recorded_sets(&self, op: &mut dyn FnMut(salsa::RecordedSet)) {
+                #take_recorded_ops
+            }
+
             #trait_vis fn for_each_query(
                 &self,
                 _runtime: &salsa::Runtime,
@@ -654,6 +1434,258 @@ impl TryFrom<syn::Attribute> for SalsaAttr {
     }
 }
 
+/// One `name = Type` entry of a `#[salsa::instantiate(...)]` attribute.
+struct Instance {
+    name: Ident,
+    ty: Type,
+}
+
+impl syn::parse::Parse for Instance {
+    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+        let name = input.parse()?;
+        input.parse::<syn::Token![=]>()?;
+        let ty = input.parse()?;
+        Ok(Instance { name, ty })
+    }
+}
+
+/// Replaces each generic query, that is, each method with a single type
+/// parameter and a `#[salsa::instantiate(name1 = Type1, ...)]`
+/// attribute, with one non-generic method per instance, each of which
+/// invokes the query function with the given type.
+fn instantiate_generic_queries(items: Vec<TraitItem>) -> syn::Result<Vec<TraitItem>> {
+    let mut result = vec![];
+    for item in items {
+        let mut method = match item {
+            TraitItem::Method(method) => method,
+            item => {
+                result.push(item);
+                continue;
+            }
+        };
+
+        let position = method.attrs.iter().position(|attr| {
+            !is_not_salsa_attr_path(&attr.path) && attr.path.segments[1].ident == "instantiate"
+        });
+        let position = match position {
+            Some(position) => position,
+            None => {
+                result.push(TraitItem::Method(method));
+                continue;
+            }
+        };
+        let attr = method.attrs.remove(position);
+        let instances = attr.parse_args_with(
+            syn::punctuated::Punctuated::<Instance, syn::Token![,]>::parse_terminated,
+        )?;
+
+        let generics = &method.sig.generics;
+        let type_param = match (generics.type_params().count(), generics.params.len()) {
+            (1, 1) => generics.type_params().next().unwrap().ident.clone(),
+            _ => {
+                return Err(Error::new(
+                    generics.span(),
+                    "#[salsa::instantiate] requires exactly one type parameter",
+                ))
+            }
+        };
+
+        // The function to invoke, which is instantiated with each type.
+        let mut invoke: syn::Path = method.sig.ident.clone().into();
+        if let Some(position) = method.attrs.iter().position(|attr| {
+            !is_not_salsa_attr_path(&attr.path) && attr.path.segments[1].ident == "invoke"
+        }) {
+            let attr = method.attrs.remove(position);
+            invoke = syn::parse2::<Parenthesized<syn::Path>>(attr.tokens)?.0;
+        }
+
+        for Instance { name, ty } in instances {
+            let mut instance = method.clone();
+            instance.sig.ident = name;
+            instance.sig.generics = Default::default();
+            for input in instance.sig.inputs.iter_mut() {
+                if let FnArg::Typed(pat_type) = input {
+                    *pat_type.ty = substitute_type(&pat_type.ty, &type_param, &ty)?;
+                }
+            }
+            if let ReturnType::Type(_, output) = &mut instance.sig.output {
+                **output = substitute_type(output, &type_param, &ty)?;
+            }
+            instance
+                .attrs
+                .push(parse_quote! { #[salsa::invoke(#invoke::<#ty>)] });
+            result.push(TraitItem::Method(instance));
+        }
+    }
+    Ok(result)
+}
+
+/// Replaces each occurrence of the type parameter `param` in `ty` with
+/// `replacement`.
+fn substitute_type(ty: &Type, param: &Ident, replacement: &Type) -> syn::Result<Type> {
+    fn substitute(
+        tokens: proc_macro2::TokenStream,
+        param: &Ident,
+        replacement: &Type,
+    ) -> proc_macro2::TokenStream {
+        tokens
+            .into_iter()
+            .map(|tree| match tree {
+                proc_macro2::TokenTree::Ident(ident) if ident == *param => {
+                    let group = proc_macro2::Group::new(
+                        proc_macro2::Delimiter::None,
+                        replacement.to_token_stream(),
+                    );
+                    proc_macro2::TokenTree::Group(group)
+                }
+                proc_macro2::TokenTree::Group(group) => {
+                    let mut new_group = proc_macro2::Group::new(
+                        group.delimiter(),
+                        substitute(group.stream(), param, replacement),
+                    );
+                    new_group.set_span(group.span());
+                    proc_macro2::TokenTree::Group(new_group)
+                }
+                tree => tree,
+            })
+            .collect()
+    }
+
+    syn::parse2(substitute(ty.to_token_stream(), param, replacement))
+}
+
+/// The contents of a `#[salsa::cycle(..)]` attribute.
+#[derive(Clone, Debug)]
+enum Cycle {
+    /// `#[salsa::cycle(path::to::recover_fn)]`
+    Recover(syn::Path),
+
+    /// `#[salsa::cycle(fixpoint, initial = expr)]`
+    Fixpoint { initial: syn::Expr },
+}
+
+impl syn::parse::Parse for Cycle {
+    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+        if input.peek(Ident) && input.peek2(syn::Token![,]) {
+            let ident: Ident = input.parse()?;
+            if ident != "fixpoint" {
+                return Err(Error::new(
+                    ident.span(),
+                    format!("unknown #[salsa::cycle] option `{}`", ident),
+                ));
+            }
+            input.parse::<syn::Token![,]>()?;
+            let initial: Ident = input.parse()?;
+            if initial != "initial" {
+                return Err(Error::new(initial.span(), "expected `initial = expr`"));
+            }
+            input.parse::<syn::Token![=]>()?;
+            let initial = input.parse()?;
+            Ok(Cycle::Fixpoint { initial })
+        } else {
+            input.parse().map(Cycle::Recover)
+        }
+    }
+}
+
+/// The `= expr` part of a `#[salsa::default = expr]` attribute.
+struct DefaultValue(syn::Expr);
+
+/// The arguments of `#[salsa::query_group(MyStorage, version = 3)]`.
+struct QueryGroupArgs {
+    group_struct: Ident,
+    version: Option<syn::LitInt>,
+}
+
+impl syn::parse::Parse for QueryGroupArgs {
+    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+        let group_struct = input.parse()?;
+        let mut version = None;
+        if input.parse::<Option<syn::Token![,]>>()?.is_some() && !input.is_empty() {
+            let ident: Ident = input.parse()?;
+            if ident != "version" {
+                return Err(Error::new(
+                    ident.span(),
+                    format!("unknown #[salsa::query_group] option `{}`", ident),
+                ));
+            }
+            input.parse::<syn::Token![=]>()?;
+            let lit: syn::LitInt = input.parse()?;
+            lit.base10_parse::<u32>()?;
+            version = Some(lit);
+        }
+        Ok(QueryGroupArgs {
+            group_struct,
+            version,
+        })
+    }
+}
+
+/// The arguments of `#[salsa::history(depth = N)]`.
+struct HistoryOptions {
+    depth: syn::LitInt,
+}
+
+impl syn::parse::Parse for HistoryOptions {
+    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+        let ident: Ident = input.parse()?;
+        if ident != "depth" {
+            return Err(Error::new(
+                ident.span(),
+                format!("unknown #[salsa::history] option `{}`", ident),
+            ));
+        }
+        input.parse::<syn::Token![=]>()?;
+        let depth: syn::LitInt = input.parse()?;
+        if depth.base10_parse::<usize>()? == 0 {
+            return Err(Error::new(
+                depth.span(),
+                "the history depth must be at least 1",
+            ));
+        }
+        Ok(HistoryOptions { depth })
+    }
+}
+
+/// The options of `#[salsa::input(...)]`, like `eq`.
+struct InputOptions(Vec<Ident>);
+
+impl syn::parse::Parse for InputOptions {
+    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+        let options =
+            syn::punctuated::Punctuated::<Ident, syn::Token![,]>::parse_terminated(input)?;
+        Ok(InputOptions(options.into_iter().collect()))
+    }
+}
+
+impl syn::parse::Parse for DefaultValue {
+    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+        input.parse::<syn::Token![=]>()?;
+        input.parse().map(DefaultValue)
+    }
+}
+
+/// Returns the `T` of a type `wrapper<T>`, such as `Option<T>`.
+fn inner_type<'a>(ty: &'a Type, wrapper: &str) -> Option<&'a Type> {
+    let path = match ty {
+        Type::Path(ty) if ty.qself.is_none() => &ty.path,
+        _ => return None,
+    };
+    let segment = path.segments.last()?;
+    if segment.ident != wrapper {
+        return None;
+    }
+    match &segment.arguments {
+        syn::PathArguments::AngleBracketed(args) if args.args.len() == 1 => {
+            match args.args.first()? {
+                syn::GenericArgument::Type(inner) => Some(inner),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
 fn is_not_salsa_attr_path(path: &syn::Path) -> bool {
     path.segments
         .first()
@@ -688,7 +1720,25 @@ struct Query {
     keys: Vec<syn::Type>,
     value: syn::Type,
     invoke: Option<syn::Path>,
-    cycle: Option<syn::Path>,
+    cycle: Option<Cycle>,
+    default: Option<syn::Expr>,
+    volatile: bool,
+    /// Set by `#[salsa::retry_err]`.
+    retry_err: bool,
+    /// Set by `#[salsa::no_eq]`.
+    no_eq: bool,
+    /// The depth given with `#[salsa::history(depth = N)]`.
+    history: Option<syn::LitInt>,
+    /// Set by `#[salsa::or_default]`: the `T` of the `Option<T>` value.
+    or_default: Option<syn::Type>,
+    /// Set by `#[salsa::iter]`: the `T` of the `Arc<Vec<T>>` value.
+    iter: Option<syn::Type>,
+    /// The preconditions given with `#[salsa::requires(expr)]`.
+    requires: Vec<syn::Expr>,
+    input_eq: bool,
+    input_immortal: bool,
+    /// Set by `#[salsa::interned(generational)]`.
+    interned_generational: bool,
 }
 
 impl Query {
@@ -19,7 +19,7 @@ consectetur,adipiscing,elit
 fn test() {
     let mut db = DatabaseImpl::default();
 
-    db.set_input_string(Arc::new(INPUT_STR.to_owned()));
+    db.set_input_string().to(Arc::new(INPUT_STR.to_owned()));
 
     let all_fields = db.all_fields();
     assert_eq!(
@@ -31,7 +31,7 @@ fn test() {
 fn main() {
     let mut db = DatabaseImpl::default();
 
-    db.set_input_string(Arc::new(INPUT_STR.to_owned()));
+    db.set_input_string().to(Arc::new(INPUT_STR.to_owned()));
 
     for field in db.all_fields().iter() {
         let field_data = db.lookup_intern_field(*field);
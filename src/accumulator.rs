@@ -0,0 +1,96 @@
+use crate::runtime::Runtime;
+use crate::walk::{walk_dependencies, DependencyVisitor};
+use crate::{Database, DatabaseKeyIndex};
+use std::any::{Any, TypeId};
+use std::fmt;
+use std::sync::Arc;
+
+/// An accumulator collects values (typically diagnostics) that queries
+/// push as a side channel, without them being part of the query's
+/// result. Accumulators are usually declared with the
+/// `#[salsa::accumulator(Data)]` attribute, which also generates
+/// `push` and `get` methods for them.
+///
+/// The values pushed by a query are stored alongside its memoized
+/// value, so when the value is reused in a later revision, so are the
+/// values it pushed.
+pub trait Accumulator: 'static {
+    /// The type of value collected by this accumulator.
+    type Data: Clone + fmt::Debug + Send + Sync + 'static;
+}
+
+/// A value pushed to an accumulator, tagged with the `TypeId` of the
+/// accumulator (as several accumulators may have the same `Data`).
+pub(crate) type AccumulatedValue = (TypeId, Box<dyn Any + Send + Sync>);
+
+/// The values pushed, to any accumulator, while a query executed.
+#[derive(Clone, Default)]
+pub struct AccumulatedValues {
+    values: Arc<Vec<AccumulatedValue>>,
+}
+
+impl AccumulatedValues {
+    pub(crate) fn new(values: Vec<AccumulatedValue>) -> Self {
+        AccumulatedValues {
+            values: Arc::new(values),
+        }
+    }
+
+    fn extend_with<A: Accumulator>(&self, output: &mut Vec<A::Data>) {
+        output.extend(
+            self.values
+                .iter()
+                .filter(|(accumulator, _)| *accumulator == TypeId::of::<A>())
+                .filter_map(|(_, value)| value.downcast_ref::<A::Data>())
+                .cloned(),
+        );
+    }
+}
+
+impl fmt::Debug for AccumulatedValues {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.debug_struct("AccumulatedValues")
+            .field("len", &self.values.len())
+            .finish()
+    }
+}
+
+/// Pushes `data` to the accumulator `A` on behalf of the currently
+/// executing query.
+///
+/// # Panics
+///
+/// If no query is executing.
+pub fn accumulate<A: Accumulator>(runtime: &Runtime, data: A::Data) {
+    runtime.accumulate((TypeId::of::<A>(), Box::new(data)));
+}
+
+/// Returns the values pushed to the accumulator `A` while executing
+/// `root` and all of the queries it (transitively) read, as recorded
+/// by their memoized values. A query's own values come first, in the
+/// order they were pushed, followed by those of its dependencies in
+/// the order they were read; each query is visited only once.
+///
+/// Call this after executing `root` in the current revision, or the
+/// results may be out of date. Values pushed by the dependencies of a
+/// query that reported an untracked read are not included, as those
+/// dependencies are not recorded.
+pub fn accumulated<A: Accumulator>(db: &dyn Database, root: DatabaseKeyIndex) -> Vec<A::Data> {
+    struct Collect<'db, A: Accumulator> {
+        db: &'db dyn Database,
+        output: Vec<A::Data>,
+    }
+
+    impl<A: Accumulator> DependencyVisitor for Collect<'_, A> {
+        fn enter(&mut self, node: DatabaseKeyIndex) -> bool {
+            if let Some(values) = self.db.accumulated(node) {
+                values.extend_with::<A>(&mut self.output);
+            }
+            true
+        }
+    }
+
+    let mut collect = Collect::<A> { db, output: vec![] };
+    walk_dependencies(db, Some(root), &mut collect);
+    collect.output
+}
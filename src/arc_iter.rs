@@ -0,0 +1,71 @@
+use std::fmt;
+use std::iter::FusedIterator;
+use std::ops::Range;
+use std::sync::Arc;
+
+/// An iterator over the elements of an `Arc<Vec<T>>`, as returned by
+/// the `foo_iter` methods of `#[salsa::iter]` queries. It holds on to
+/// the `Arc`, so the `Vec` is shared with the memoized value; each
+/// element is cloned only when it is yielded. Use `as_slice` to borrow
+/// the remaining elements instead.
+pub struct ArcIter<T> {
+    vec: Arc<Vec<T>>,
+    range: Range<usize>,
+}
+
+impl<T> ArcIter<T> {
+    /// Returns an iterator over all the elements of `vec`.
+    pub fn new(vec: Arc<Vec<T>>) -> Self {
+        let range = 0..vec.len();
+        ArcIter { vec, range }
+    }
+
+    /// Returns the elements that have not been yielded yet.
+    pub fn as_slice(&self) -> &[T] {
+        &self.vec[self.range.clone()]
+    }
+}
+
+impl<T: Clone> Iterator for ArcIter<T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        let index = self.range.next()?;
+        Some(self.vec[index].clone())
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.range.size_hint()
+    }
+
+    fn nth(&mut self, n: usize) -> Option<T> {
+        let index = self.range.nth(n)?;
+        Some(self.vec[index].clone())
+    }
+}
+
+impl<T: Clone> DoubleEndedIterator for ArcIter<T> {
+    fn next_back(&mut self) -> Option<T> {
+        let index = self.range.next_back()?;
+        Some(self.vec[index].clone())
+    }
+}
+
+impl<T: Clone> ExactSizeIterator for ArcIter<T> {}
+
+impl<T: Clone> FusedIterator for ArcIter<T> {}
+
+impl<T> Clone for ArcIter<T> {
+    fn clone(&self) -> Self {
+        ArcIter {
+            vec: self.vec.clone(),
+            range: self.range.clone(),
+        }
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for ArcIter<T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.debug_tuple("ArcIter").field(&self.as_slice()).finish()
+    }
+}
@@ -3,10 +3,19 @@
 
 use crate::durability::Durability;
 use crate::plumbing::QueryStorageOps;
+use crate::DatabaseKeyIndex;
 use crate::Query;
 use crate::QueryTable;
+use std::fmt::Debug;
 use std::iter::FromIterator;
 
+/// Estimates the size of one stored entry of `Q`, for
+/// `Database::memory_usage`. This is the shallow size of a key and a
+/// value, so any heap data they own is not counted.
+pub(crate) fn entry_size<Q: Query>() -> usize {
+    std::mem::size_of::<Q::Key>() + std::mem::size_of::<Q::Value>()
+}
+
 /// Additional methods on queries that can be used to "peek into"
 /// their current state. These methods are meant for debugging and
 /// observing the effects of garbage collection etc.
@@ -49,6 +58,55 @@ impl<K, V> TableEntry<K, V> {
     }
 }
 
+/// Execution statistics for a single query key, as returned by
+/// `Runtime::query_stats`.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct QueryStat {
+    /// Number of times the query function was executed.
+    pub executions: usize,
+    /// Number of times a memoized value was returned without
+    /// executing the query function (either because it was already
+    /// verified in the current revision, or because its inputs were
+    /// found to be unchanged).
+    pub cache_hits: usize,
+}
+
+/// The size of the table of an interned query, as returned by
+/// `QueryTable::intern_stats`.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct InternStats {
+    /// Number of values currently interned.
+    pub entries: usize,
+    /// Number of entries that the hash map from values to ids can hold
+    /// without reallocating.
+    pub capacity: usize,
+    /// Number of ids that were freed by a sweep and not yet reused.
+    pub free_ids: usize,
+}
+
+impl InternStats {
+    pub(crate) fn new(entries: usize, capacity: usize, free_ids: usize) -> Self {
+        InternStats {
+            entries,
+            capacity,
+            free_ids,
+        }
+    }
+
+    /// The fraction of the hash map's capacity that is in use (between
+    /// 0 and 1, or 0 for an empty map). The map grows (rehashing every
+    /// value) when this approaches 1.
+    pub fn load_factor(&self) -> f64 {
+        if self.capacity == 0 {
+            0.0
+        } else {
+            self.entries as f64 / self.capacity as f64
+        }
+    }
+}
+
 impl<'d, Q> DebugQueryTable for QueryTable<'_, Q>
 where
     Q: Query,
@@ -68,3 +126,79 @@ where
         self.storage.entries(self.db)
     }
 }
+
+/// A memoized value (or the value of an input), as given to
+/// `QueryStorageMassOps::for_each_memo`.
+#[non_exhaustive]
+pub struct MemoEntry<'me> {
+    /// Identifies the query and key.
+    pub database_key_index: DatabaseKeyIndex,
+    /// The key of the query.
+    pub key: &'me dyn Debug,
+    /// True if the value has been verified in the current revision.
+    pub verified: bool,
+    /// The value, if it is stored.
+    pub value: Option<&'me dyn Debug>,
+    /// The queries that the value was computed from (none, for the
+    /// value of an input), or `None` if they are not known because the
+    /// query reported an untracked read.
+    pub inputs: Option<&'me [DatabaseKeyIndex]>,
+}
+
+impl<'me> MemoEntry<'me> {
+    pub(crate) fn new(
+        database_key_index: DatabaseKeyIndex,
+        key: &'me dyn Debug,
+        verified: bool,
+        value: Option<&'me dyn Debug>,
+        inputs: Option<&'me [DatabaseKeyIndex]>,
+    ) -> Self {
+        MemoEntry {
+            database_key_index,
+            key,
+            verified,
+            value,
+            inputs,
+        }
+    }
+}
+
+/// Return value of `Database::debug_all_memoized`. The memo tables are
+/// only walked when this is formatted (and again each time).
+#[cfg(feature = "debug-dump")]
+pub struct MemoizedDump<'me> {
+    pub(crate) db: &'me dyn crate::Database,
+}
+
+#[cfg(feature = "debug-dump")]
+impl Debug for MemoizedDump<'_> {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let db = self.db;
+        let runtime = db.salsa_runtime();
+        let mut list = fmt.debug_list();
+        db.for_each_query(&mut |storage| {
+            storage.for_each_memo(runtime, &mut |entry| {
+                list.entry(&MemoEntryDump { db, entry });
+            })
+        });
+        list.finish()
+    }
+}
+
+#[cfg(feature = "debug-dump")]
+struct MemoEntryDump<'me> {
+    db: &'me dyn crate::Database,
+    entry: MemoEntry<'me>,
+}
+
+#[cfg(feature = "debug-dump")]
+impl Debug for MemoEntryDump<'_> {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        fmt.debug_struct("Memo")
+            .field("index", &self.entry.database_key_index.debug(self.db))
+            .field("key", self.entry.key)
+            .field("verified", &self.entry.verified)
+            .field("value", &self.entry.value)
+            .finish()
+    }
+}
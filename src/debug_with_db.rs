@@ -0,0 +1,209 @@
+use crate::intern_id::InternId;
+use crate::plumbing::DatabaseOps;
+use crate::DatabaseKeyIndex;
+use std::fmt;
+use std::sync::Arc;
+
+/// Like `std::fmt::Debug`, but with access to a database `Db`, so that
+/// values can print the data behind the interned ids they contain
+/// rather than bare numbers.
+///
+/// Implement it by hand for your interned keys, looking the value up:
+///
+/// ```
+/// use salsa::DebugWithDb;
+/// use std::fmt;
+///
+/// #[salsa::query_group(NamesStorage)]
+/// trait Names: salsa::Database {
+///     #[salsa::interned]
+///     fn intern_name(&self, name: String) -> NameId;
+/// }
+///
+/// salsa::intern_id!(pub struct NameId);
+///
+/// impl DebugWithDb<dyn Names> for NameId {
+///     fn fmt(&self, f: &mut fmt::Formatter<'_>, db: &dyn Names) -> fmt::Result {
+///         fmt::Debug::fmt(&db.lookup_intern_name(*self), f)
+///     }
+/// }
+///
+/// #[derive(DebugWithDb)]
+/// #[debug_with_db(db = dyn Names)]
+/// struct Path {
+///     segments: Vec<NameId>,
+///     absolute: bool,
+/// }
+///
+/// #[salsa::database(NamesStorage)]
+/// #[derive(Default)]
+/// struct Database {
+///     storage: salsa::Storage<Self>,
+/// }
+///
+/// impl salsa::Database for Database {}
+///
+/// let db = Database::default();
+/// let path = Path {
+///     segments: vec![db.intern_name("std".into()), db.intern_name("mem".into())],
+///     absolute: true,
+/// };
+/// let db: &dyn Names = &db;
+/// assert_eq!(
+///     format!("{:?}", path.debug(db)),
+///     r#"Path { segments: ["std", "mem"], absolute: true }"#,
+/// );
+/// ```
+///
+/// `#[derive(DebugWithDb)]` implements the trait for structs and enums
+/// whose fields all implement it, printing them like
+/// `#[derive(Debug)]` would. The derived impl is generic over the
+/// database type unless one is given with `#[debug_with_db(db = ...)]`,
+/// as above, which is needed when some field (like `NameId`) can only be
+/// formatted with that database. The trait is implemented for the common
+/// primitive and standard library types, for containers and tuples of
+/// values that implement it, and for `DatabaseKeyIndex`. A bare
+/// `InternId` does not know which query interned it, so it prints just
+/// the integer.
+pub trait DebugWithDb<Db: ?Sized> {
+    /// Formats `self`, looking up any interned ids in `db`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>, db: &Db) -> fmt::Result;
+
+    /// Returns a wrapper that implements `Debug` by calling `fmt` with
+    /// `db`, for use with `format!`, `dbg!` and friends.
+    fn debug<'me>(&'me self, db: &'me Db) -> DebugWith<'me, Self, Db> {
+        DebugWith { value: self, db }
+    }
+}
+
+/// Implements `Debug` for a value and a database; see
+/// `DebugWithDb::debug`.
+pub struct DebugWith<'me, T: ?Sized, Db: ?Sized> {
+    value: &'me T,
+    db: &'me Db,
+}
+
+impl<T, Db> fmt::Debug for DebugWith<'_, T, Db>
+where
+    T: ?Sized + DebugWithDb<Db>,
+    Db: ?Sized,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        DebugWithDb::fmt(self.value, f, self.db)
+    }
+}
+
+macro_rules! debug_with_db_via_debug {
+    ($($ty:ty),* $(,)?) => {
+        $(
+            impl<Db: ?Sized> DebugWithDb<Db> for $ty {
+                fn fmt(&self, f: &mut fmt::Formatter<'_>, _db: &Db) -> fmt::Result {
+                    fmt::Debug::fmt(self, f)
+                }
+            }
+        )*
+    };
+}
+
+debug_with_db_via_debug! {
+    (), bool, char, str, String,
+    u8, u16, u32, u64, u128, usize,
+    i8, i16, i32, i64, i128, isize,
+    f32, f64,
+    InternId,
+}
+
+impl<Db> DebugWithDb<Db> for DatabaseKeyIndex
+where
+    Db: ?Sized + DatabaseOps,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>, db: &Db) -> fmt::Result {
+        fmt::Debug::fmt(&DatabaseKeyIndex::debug(*self, db), f)
+    }
+}
+
+impl<T, Db> DebugWithDb<Db> for &T
+where
+    T: ?Sized + DebugWithDb<Db>,
+    Db: ?Sized,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>, db: &Db) -> fmt::Result {
+        T::fmt(self, f, db)
+    }
+}
+
+impl<T, Db> DebugWithDb<Db> for Box<T>
+where
+    T: ?Sized + DebugWithDb<Db>,
+    Db: ?Sized,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>, db: &Db) -> fmt::Result {
+        T::fmt(self, f, db)
+    }
+}
+
+impl<T, Db> DebugWithDb<Db> for Arc<T>
+where
+    T: ?Sized + DebugWithDb<Db>,
+    Db: ?Sized,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>, db: &Db) -> fmt::Result {
+        T::fmt(self, f, db)
+    }
+}
+
+impl<T, Db> DebugWithDb<Db> for [T]
+where
+    T: DebugWithDb<Db>,
+    Db: ?Sized,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>, db: &Db) -> fmt::Result {
+        f.debug_list()
+            .entries(self.iter().map(|element| element.debug(db)))
+            .finish()
+    }
+}
+
+impl<T, Db> DebugWithDb<Db> for Vec<T>
+where
+    T: DebugWithDb<Db>,
+    Db: ?Sized,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>, db: &Db) -> fmt::Result {
+        <[T]>::fmt(self, f, db)
+    }
+}
+
+impl<T, Db> DebugWithDb<Db> for Option<T>
+where
+    T: DebugWithDb<Db>,
+    Db: ?Sized,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>, db: &Db) -> fmt::Result {
+        match self {
+            Some(value) => f.debug_tuple("Some").field(&value.debug(db)).finish(),
+            None => f.write_str("None"),
+        }
+    }
+}
+
+macro_rules! debug_with_db_tuple {
+    ($($name:ident),*) => {
+        impl<Db: ?Sized, $($name: DebugWithDb<Db>),*> DebugWithDb<Db> for ($($name,)*) {
+            #[allow(non_snake_case)]
+            fn fmt(&self, f: &mut fmt::Formatter<'_>, db: &Db) -> fmt::Result {
+                let ($($name,)*) = self;
+                f.debug_tuple("")
+                    $(.field(&$name.debug(db)))*
+                    .finish()
+            }
+        }
+    };
+}
+
+debug_with_db_tuple!(A);
+debug_with_db_tuple!(A, B);
+debug_with_db_tuple!(A, B, C);
+debug_with_db_tuple!(A, B, C, D);
+debug_with_db_tuple!(A, B, C, D, E);
+debug_with_db_tuple!(A, B, C, D, E, F);
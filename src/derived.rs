@@ -1,18 +1,28 @@
+use crate::debug::entry_size;
+use crate::debug::MemoEntry;
 use crate::debug::TableEntry;
 use crate::durability::Durability;
 use crate::lru::Lru;
+use crate::lru::LruNode;
+use crate::plumbing::AccumulatedValues;
+use crate::plumbing::DatabaseOps;
 use crate::plumbing::DerivedQueryStorageOps;
 use crate::plumbing::LruQueryStorageOps;
 use crate::plumbing::QueryFunction;
 use crate::plumbing::QueryStorageMassOps;
 use crate::plumbing::QueryStorageOps;
+use crate::plumbing::SubscriptionCallback;
 use crate::runtime::{FxIndexMap, StampedValue};
-use crate::{CycleError, Database, DatabaseKeyIndex, QueryDb, Revision, Runtime, SweepStrategy};
-use parking_lot::RwLock;
+use crate::{
+    CacheOutcome, CycleError, Database, DatabaseKeyIndex, QueryDb, Revision, Runtime, SweepStrategy,
+};
+use parking_lot::{Mutex, RwLock};
+use rustc_hash::FxHashMap;
 use std::borrow::Borrow;
 use std::convert::TryFrom;
 use std::hash::Hash;
 use std::marker::PhantomData;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 
 mod slot;
@@ -23,6 +33,11 @@ use slot::Slot;
 /// none of those inputs have changed.
 pub type MemoizedStorage<Q> = DerivedStorage<Q, AlwaysMemoizeValue>;
 
+/// Like `MemoizedStorage`, but never compares a recomputed value with
+/// the old one, so the value need not implement `PartialEq` (see
+/// `#[salsa::no_eq]`).
+pub type MemoizedNoEqStorage<Q> = DerivedStorage<Q, MemoizeWithoutEq>;
+
 /// "Dependency" queries just track their dependencies and not the
 /// actual value (which they produce on demand). This lessens the
 /// storage requirements.
@@ -39,6 +54,22 @@ where
     lru_list: Lru<Slot<Q, MP>>,
     slot_map: RwLock<FxIndexMap<Q::Key, Arc<Slot<Q, MP>>>>,
     policy: PhantomData<MP>,
+
+    /// Callbacks registered with `QueryTable::subscribe`, by key index.
+    subscriptions: Mutex<FxHashMap<u32, Vec<Subscription<Q::Value>>>>,
+
+    /// True once anything subscribed, so that reads of queries without
+    /// subscriptions do not need to take the lock.
+    has_subscriptions: AtomicBool,
+}
+
+/// A callback registered with `QueryTable::subscribe`.
+struct Subscription<V> {
+    callback: SubscriptionCallback<V>,
+
+    /// When the last value that the callback saw (or that was memoized
+    /// when it subscribed) had changed, if there was one.
+    changed_at: Option<Revision>,
 }
 
 impl<Q, MP> std::panic::RefUnwindSafe for DerivedStorage<Q, MP>
@@ -54,6 +85,10 @@ pub trait MemoizationPolicy<Q>: Send + Sync
 where
     Q: QueryFunction,
 {
+    /// False if `memoized_value_eq` does not actually compare values
+    /// (see `#[salsa::no_eq]`), so values are never known to be equal.
+    const HAS_EQ: bool = true;
+
     fn should_memoize_value(key: &Q::Key) -> bool;
 
     fn memoized_value_eq(old_value: &Q::Value, new_value: &Q::Value) -> bool;
@@ -63,7 +98,7 @@ pub enum AlwaysMemoizeValue {}
 impl<Q> MemoizationPolicy<Q> for AlwaysMemoizeValue
 where
     Q: QueryFunction,
-    Q::Value: Eq,
+    Q::Value: PartialEq,
 {
     fn should_memoize_value(_key: &Q::Key) -> bool {
         true
@@ -74,6 +109,22 @@ where
     }
 }
 
+pub enum MemoizeWithoutEq {}
+impl<Q> MemoizationPolicy<Q> for MemoizeWithoutEq
+where
+    Q: QueryFunction,
+{
+    const HAS_EQ: bool = false;
+
+    fn should_memoize_value(_key: &Q::Key) -> bool {
+        true
+    }
+
+    fn memoized_value_eq(_old_value: &Q::Value, _new_value: &Q::Value) -> bool {
+        false
+    }
+}
+
 pub enum NeverMemoizeValue {}
 impl<Q> MemoizationPolicy<Q> for NeverMemoizeValue
 where
@@ -110,6 +161,33 @@ where
             .or_insert_with(|| Arc::new(Slot::new(key.clone(), database_key_index)))
             .clone()
     }
+
+    /// Records that `slot` was used in the LRU list, evicting the value
+    /// that it displaces (if any). Pinned slots are left out of the list.
+    fn record_use(&self, db: &dyn Database, slot: &Arc<Slot<Q, MP>>) {
+        if slot.is_pinned() {
+            return;
+        }
+        if let Some(evicted) = self.lru_list.record_use(slot) {
+            evicted.evict(db);
+        }
+    }
+
+    /// Invokes the callbacks subscribed to `slot` if `value` changed
+    /// since they last saw a value.
+    fn notify_subscribers(&self, slot: &Slot<Q, MP>, value: &StampedValue<Q::Value>) {
+        if !self.has_subscriptions.load(Ordering::Acquire) {
+            return;
+        }
+        let mut subscriptions = self.subscriptions.lock();
+        let key_index = slot.database_key_index().key_index;
+        for subscription in subscriptions.get_mut(&key_index).into_iter().flatten() {
+            if subscription.changed_at != Some(value.changed_at) {
+                subscription.changed_at = Some(value.changed_at);
+                (subscription.callback)(&value.value);
+            }
+        }
+    }
 }
 
 impl<Q, MP> QueryStorageOps<Q> for DerivedStorage<Q, MP>
@@ -123,6 +201,27 @@ where
             slot_map: RwLock::new(FxIndexMap::default()),
             lru_list: Default::default(),
             policy: PhantomData,
+            subscriptions: Default::default(),
+            has_subscriptions: AtomicBool::new(false),
+        }
+    }
+
+    fn fork(&self) -> Self {
+        let slot_map = self
+            .slot_map
+            .read()
+            .iter()
+            .map(|(key, slot)| (key.clone(), Arc::new(slot.fork())))
+            .collect();
+        let lru_list = Lru::default();
+        lru_list.set_lru_capacity(self.lru_list.lru_capacity());
+        DerivedStorage {
+            group_index: self.group_index,
+            slot_map: RwLock::new(slot_map),
+            lru_list,
+            policy: PhantomData,
+            subscriptions: Default::default(),
+            has_subscriptions: AtomicBool::new(false),
         }
     }
 
@@ -157,26 +256,94 @@ where
         slot.maybe_changed_since(db, revision)
     }
 
+    fn dependencies(
+        &self,
+        _db: &<Q as QueryDb<'_>>::DynDb,
+        index: DatabaseKeyIndex,
+    ) -> Option<Vec<DatabaseKeyIndex>> {
+        assert_eq!(index.group_index, self.group_index);
+        assert_eq!(index.query_index, Q::QUERY_INDEX);
+        let slot = self
+            .slot_map
+            .read()
+            .get_index(index.key_index as usize)?
+            .1
+            .clone();
+        slot.dependencies()
+    }
+
+    fn accumulated(
+        &self,
+        _db: &<Q as QueryDb<'_>>::DynDb,
+        index: DatabaseKeyIndex,
+    ) -> Option<AccumulatedValues> {
+        assert_eq!(index.group_index, self.group_index);
+        assert_eq!(index.query_index, Q::QUERY_INDEX);
+        let slot = self
+            .slot_map
+            .read()
+            .get_index(index.key_index as usize)?
+            .1
+            .clone();
+        slot.accumulated()
+    }
+
+    fn validate(&self, db: &<Q as QueryDb<'_>>::DynDb, index: DatabaseKeyIndex) {
+        assert_eq!(index.group_index, self.group_index);
+        assert_eq!(index.query_index, Q::QUERY_INDEX);
+        let slot = match self.slot_map.read().get_index(index.key_index as usize) {
+            Some((_, slot)) => slot.clone(),
+            None => return,
+        };
+
+        // A cycle is reported when the query is read.
+        if let Ok(value) = slot.read(db) {
+            self.record_use(db.ops_database(), &slot);
+            self.notify_subscribers(&slot, &value);
+        }
+    }
+
+    fn commit_provisional(
+        &self,
+        db: &<Q as QueryDb<'_>>::DynDb,
+        index: DatabaseKeyIndex,
+        inputs: Option<&[DatabaseKeyIndex]>,
+        durability: Durability,
+    ) {
+        assert_eq!(index.group_index, self.group_index);
+        assert_eq!(index.query_index, Q::QUERY_INDEX);
+        let slot = match self.slot_map.read().get_index(index.key_index as usize) {
+            Some((_, slot)) => slot.clone(),
+            None => return,
+        };
+        if slot.commit_provisional(inputs, durability) {
+            self.record_use(db.ops_database(), &slot);
+        }
+    }
+
+    fn debug_validate_dependencies(&self, db: &<Q as QueryDb<'_>>::DynDb) {
+        let slots: Vec<_> = self.slot_map.read().values().cloned().collect();
+        for slot in slots {
+            slot.debug_validate_dependencies(db);
+        }
+    }
+
     fn try_fetch(
         &self,
         db: &<Q as QueryDb<'_>>::DynDb,
         key: &Q::Key,
     ) -> Result<Q::Value, CycleError<DatabaseKeyIndex>> {
-        let slot = self.slot(key);
-        let StampedValue {
-            value,
-            durability,
-            changed_at,
-        } = slot.read(db)?;
-
-        if let Some(evicted) = self.lru_list.record_use(&slot) {
-            evicted.evict();
-        }
+        self.fetch_probed(db, key).map(|(value, _)| value)
+    }
 
-        db.salsa_runtime()
-            .report_query_read(slot.database_key_index(), durability, changed_at);
+    fn peek(&self, db: &<Q as QueryDb<'_>>::DynDb, key: &Q::Key) -> Option<Q::Value> {
+        let slot = self.slot_map.read().get(key)?.clone();
+        slot.peek(db)
+    }
 
-        Ok(value)
+    fn changed_at(&self, db: &<Q as QueryDb<'_>>::DynDb, key: &Q::Key) -> Option<Revision> {
+        let slot = self.slot_map.read().get(key)?.clone();
+        slot.changed_at(db)
     }
 
     fn durability(&self, db: &<Q as QueryDb<'_>>::DynDb, key: &Q::Key) -> Durability {
@@ -204,13 +371,55 @@ where
         let map_read = self.slot_map.read();
         let revision_now = runtime.current_revision();
         for slot in map_read.values() {
-            slot.sweep(revision_now, strategy);
+            slot.sweep(revision_now, &strategy);
         }
     }
     fn purge(&self) {
         self.lru_list.purge();
         *self.slot_map.write() = Default::default();
     }
+    fn clear_derived(&self) {
+        for slot in self.slot_map.read().values() {
+            slot.clear();
+        }
+    }
+    fn for_each_memo(&self, runtime: &Runtime, op: &mut dyn FnMut(MemoEntry<'_>)) {
+        let map_read = self.slot_map.read();
+        let revision_now = runtime.current_revision();
+        for slot in map_read.values() {
+            slot.for_each_memo(revision_now, op);
+        }
+    }
+    fn memory_usage(&self) -> usize {
+        self.memoized_len() * entry_size::<Q>()
+    }
+    fn enforce_lru_capacity(&self, db: &dyn Database) {
+        let capacity = self.lru_list.lru_capacity();
+        if capacity == 0 {
+            return;
+        }
+
+        // Every value in the LRU list is within the capacity, so the
+        // excess values are ones that are not in the list (e.g. because
+        // they were computed before the capacity was last set). Evict
+        // the oldest of those first.
+        let slots: Vec<_> = self
+            .slot_map
+            .read()
+            .values()
+            .filter(|slot| slot.has_value() && !slot.is_pinned())
+            .cloned()
+            .collect();
+        let mut excess = slots.len().saturating_sub(capacity);
+        for slot in slots {
+            if excess == 0 {
+                break;
+            }
+            if !slot.lru_index().is_in_lru() && slot.evict(db) {
+                excess -= 1;
+            }
+        }
+    }
 }
 
 impl<Q, MP> LruQueryStorageOps for DerivedStorage<Q, MP>
@@ -221,6 +430,18 @@ where
     fn set_lru_capacity(&self, new_capacity: usize) {
         self.lru_list.set_lru_capacity(new_capacity);
     }
+
+    fn lru_capacity(&self) -> Option<usize> {
+        match self.lru_list.lru_capacity() {
+            0 => None,
+            capacity => Some(capacity),
+        }
+    }
+
+    fn memoized_len(&self) -> usize {
+        let slot_map = self.slot_map.read();
+        slot_map.values().filter(|slot| slot.has_value()).count()
+    }
 }
 
 impl<Q, MP> DerivedQueryStorageOps<Q> for DerivedStorage<Q, MP>
@@ -228,6 +449,30 @@ where
     Q: QueryFunction,
     MP: MemoizationPolicy<Q>,
 {
+    fn fetch_probed(
+        &self,
+        db: &<Q as QueryDb<'_>>::DynDb,
+        key: &Q::Key,
+    ) -> Result<(Q::Value, CacheOutcome), CycleError<DatabaseKeyIndex>> {
+        let slot = self.slot(key);
+        let (stamped_value, outcome) = slot.read_probed(db)?;
+
+        self.record_use(db.ops_database(), &slot);
+        self.notify_subscribers(&slot, &stamped_value);
+
+        let StampedValue {
+            value,
+            durability,
+            changed_at,
+        } = stamped_value;
+
+        let runtime = db.salsa_runtime();
+        runtime.check_snapshot_read(db, slot.database_key_index(), changed_at);
+        runtime.report_query_read(slot.database_key_index(), durability, changed_at);
+
+        Ok((value, outcome))
+    }
+
     fn invalidate<S>(&self, db: &mut <Q as QueryDb<'_>>::DynDb, key: &S)
     where
         S: Eq + Hash,
@@ -246,4 +491,142 @@ where
                 None
             })
     }
+
+    fn clear(&self, db: &mut <Q as QueryDb<'_>>::DynDb) {
+        db.salsa_runtime_mut()
+            .with_incremented_revision(&mut |_new_revision| {
+                self.slot_map
+                    .read()
+                    .values()
+                    .filter_map(|slot| slot.clear())
+                    .max()
+            })
+    }
+
+    fn dependencies<S>(&self, key: &S) -> Option<Vec<DatabaseKeyIndex>>
+    where
+        S: Eq + Hash,
+        Q::Key: Borrow<S>,
+    {
+        let slot = self.slot_map.read().get(key)?.clone();
+        slot.dependencies()
+    }
+
+    fn history<S>(&self, key: &S) -> Vec<(Revision, Q::Value)>
+    where
+        S: Eq + Hash,
+        Q::Key: Borrow<S>,
+    {
+        match self.slot_map.read().get(key) {
+            Some(slot) => slot.history(),
+            None => vec![],
+        }
+    }
+
+    fn first_computed_at<S>(&self, key: &S) -> Option<Revision>
+    where
+        S: Eq + Hash,
+        Q::Key: Borrow<S>,
+    {
+        let slot = self.slot_map.read().get(key)?.clone();
+        slot.first_computed_at()
+    }
+
+    fn memoized_durability<S>(&self, key: &S) -> Option<Durability>
+    where
+        S: Eq + Hash,
+        Q::Key: Borrow<S>,
+    {
+        let slot = self.slot_map.read().get(key)?.clone();
+        slot.memoized_durability()
+    }
+
+    fn database_key_index<S>(&self, key: &S) -> Option<DatabaseKeyIndex>
+    where
+        S: Eq + Hash,
+        Q::Key: Borrow<S>,
+    {
+        let slot = self.slot_map.read().get(key)?.clone();
+        Some(slot.database_key_index())
+    }
+
+    fn prime(
+        &self,
+        db: &mut <Q as QueryDb<'_>>::DynDb,
+        key: Q::Key,
+        value: Q::Value,
+        inputs: Vec<DatabaseKeyIndex>,
+    ) {
+        let slot = self.slot(&key);
+        db.salsa_runtime_mut()
+            .with_incremented_revision(&mut |new_revision| {
+                slot.prime(value.clone(), inputs.clone(), new_revision)
+            });
+        // The primed memo is verified in the new revision, so (in a
+        // batch) a later set cannot reuse that revision.
+        db.salsa_runtime().report_verified_memo();
+        self.record_use(db.ops_database(), &slot);
+    }
+
+    fn set_override(&self, db: &mut <Q as QueryDb<'_>>::DynDb, key: Q::Key, value: Q::Value) {
+        let slot = self.slot(&key);
+        db.salsa_runtime_mut()
+            .with_incremented_revision(&mut |new_revision| {
+                slot.set_override(value.clone(), new_revision)
+            });
+        self.record_use(db.ops_database(), &slot);
+    }
+
+    fn clear_override(&self, db: &mut <Q as QueryDb<'_>>::DynDb, key: &Q::Key) {
+        let slot = match self.slot_map.read().get(key) {
+            Some(slot) if slot.is_overridden() => slot.clone(),
+            _ => return,
+        };
+        db.salsa_runtime_mut()
+            .with_incremented_revision(&mut |_new_revision| slot.clear_override());
+    }
+
+    fn is_overridden(&self, key: &Q::Key) -> bool {
+        match self.slot_map.read().get(key) {
+            Some(slot) => slot.is_overridden(),
+            None => false,
+        }
+    }
+
+    fn set_pinned(&self, key: &Q::Key, pinned: bool) {
+        self.slot(key).set_pinned(pinned);
+    }
+
+    fn subscribe(&self, key: &Q::Key, callback: SubscriptionCallback<Q::Value>) {
+        let slot = self.slot(key);
+        let subscription = Subscription {
+            callback,
+            changed_at: slot.memoized_changed_at(),
+        };
+        self.subscriptions
+            .lock()
+            .entry(slot.database_key_index().key_index)
+            .or_default()
+            .push(subscription);
+        self.has_subscriptions.store(true, Ordering::Release);
+    }
+
+    fn verified_entries(&self, runtime: &Runtime) -> Vec<(Q::Key, Q::Value)> {
+        let revision_now = runtime.current_revision();
+        self.slot_map
+            .read()
+            .values()
+            .filter_map(|slot| slot.verified_entry(revision_now))
+            .collect()
+    }
+
+    fn memoized_keys(&self, runtime: &Runtime) -> Vec<Q::Key> {
+        let revision_now = runtime.current_revision();
+        self.slot_map
+            .read()
+            .iter()
+            .filter(|(_, slot)| slot.has_verified_value(revision_now))
+            .map(|(key, _)| key.clone())
+            .collect()
+    }
 }
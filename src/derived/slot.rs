@@ -1,4 +1,6 @@
+use crate::accumulator::AccumulatedValues;
 use crate::blocking_future::{BlockingFuture, Promise};
+use crate::debug::MemoEntry;
 use crate::debug::TableEntry;
 use crate::derived::MemoizationPolicy;
 use crate::durability::Durability;
@@ -7,21 +9,30 @@ use crate::lru::LruNode;
 use crate::plumbing::CycleDetected;
 use crate::plumbing::{DatabaseOps, QueryFunction};
 use crate::revision::Revision;
+use crate::runtime::FxIndexSet;
+use crate::runtime::ProvisionalMember;
 use crate::runtime::Runtime;
 use crate::runtime::RuntimeId;
 use crate::runtime::StampedValue;
 use crate::{
-    CycleError, Database, DatabaseKeyIndex, DiscardIf, DiscardWhat, Event, EventKind, QueryDb,
-    SweepStrategy,
+    CacheOutcome, Cycle, CycleError, CycleHandling, Database, DatabaseKeyIndex, DiscardIf,
+    DiscardWhat, Event, EventKind, OverflowBehavior, QueryDb, SweepStrategy,
 };
 use log::{debug, info};
 use parking_lot::Mutex;
 use parking_lot::{RawRwLock, RwLock};
 use smallvec::SmallVec;
+use std::collections::VecDeque;
+use std::fmt::Debug;
 use std::marker::PhantomData;
 use std::ops::Deref;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 
+/// The number of times a fixpoint query is re-executed before we give
+/// up on its value converging.
+const MAX_FIXPOINT_ITERATIONS: usize = 256;
+
 pub(super) struct Slot<Q, MP>
 where
     Q: QueryFunction,
@@ -32,12 +43,33 @@ where
     state: RwLock<QueryState<Q>>,
     policy: PhantomData<MP>,
     lru_index: LruIndex,
+
+    /// If set, the value is never evicted by the LRU list (see
+    /// `QueryTableMut::pin`).
+    pinned: AtomicBool,
+
+    /// While fixed-point iteration is in progress for this query, the
+    /// value that it provisionally has (see `QueryFunction::cycle_initial`).
+    provisional: Mutex<Option<Q::Value>>,
+
+    /// The memo of the last value computed from the provisional values
+    /// of fixpoint queries, which is memoized (by `commit_provisional`)
+    /// if the iteration it was computed in turns out to be the last.
+    provisional_memo: Mutex<Option<Memo<Q>>>,
+
+    /// The last `QueryFunction::HISTORY_DEPTH` distinct values computed
+    /// for this key, oldest first, each with its `changed_at` revision.
+    history: Mutex<VecDeque<(Revision, Q::Value)>>,
 }
 
 #[derive(Clone)]
 struct WaitResult<V, K> {
     value: StampedValue<V>,
     cycle: Vec<K>,
+
+    /// True if `value` was computed from provisional values, and so
+    /// must not be used by the waiting thread.
+    provisional: bool,
 }
 
 /// Defines the "current state" of query's memoized results.
@@ -66,10 +98,30 @@ where
     /// The result of the query, if we decide to memoize it.
     value: Option<Q::Value>,
 
+    /// The values pushed to accumulators while executing the query.
+    accumulated: AccumulatedValues,
+
     /// Revision information
     revisions: MemoRevisions,
+
+    /// The revision in which the query was first executed (or primed),
+    /// kept when it is re-executed.
+    first_computed_at: Revision,
+
+    /// True if the value was computed by fixed-point iteration, as a
+    /// head or a member of a cycle (see `commit_provisional_members`).
+    /// Its inputs then differ from those read when the query is
+    /// executed from scratch.
+    in_fixpoint: bool,
+
+    /// True if the value was set with `QueryTableMut::set_override`,
+    /// rather than computed. Such a memo has no inputs, and is kept
+    /// (not discarded, evicted or invalidated) until the override is
+    /// cleared.
+    overridden: bool,
 }
 
+#[derive(Clone)]
 struct MemoRevisions {
     /// Last revision when this memo was verified (if there are
     /// untracked inputs, this will also be when the memo was
@@ -84,10 +136,15 @@ struct MemoRevisions {
 
     /// The inputs that went into our query, if we are tracking them.
     inputs: MemoInputs,
+
+    /// If set, this memo also depends on all inputs of this durability
+    /// (or higher); see `Runtime::depend_on_durability`.
+    durability_dependency: Option<Durability>,
 }
 
 /// An insertion-order-preserving set of queries. Used to track the
 /// inputs accessed during query execution.
+#[derive(Clone)]
 pub(super) enum MemoInputs {
     /// Non-empty set of inputs, fully known
     Tracked { inputs: Arc<[DatabaseKeyIndex]> },
@@ -99,6 +156,17 @@ pub(super) enum MemoInputs {
     Untracked,
 }
 
+impl MemoInputs {
+    /// Returns the inputs, or `None` if they are untracked.
+    fn to_vec(&self) -> Option<Vec<DatabaseKeyIndex>> {
+        match self {
+            MemoInputs::Tracked { inputs } => Some(inputs.to_vec()),
+            MemoInputs::NoInputs => Some(vec![]),
+            MemoInputs::Untracked => None,
+        }
+    }
+}
+
 /// Return value of `probe` helper.
 enum ProbeState<V, K, G> {
     UpToDate(Result<V, CycleError<K>>),
@@ -116,7 +184,38 @@ where
             database_key_index,
             state: RwLock::new(QueryState::NotComputed),
             lru_index: LruIndex::default(),
+            pinned: AtomicBool::new(false),
             policy: PhantomData,
+            provisional: Mutex::new(None),
+            provisional_memo: Mutex::new(None),
+            history: Default::default(),
+        }
+    }
+
+    /// Returns a copy of this slot, with the same memoized value (if
+    /// any), for a forked storage.
+    pub(super) fn fork(&self) -> Self {
+        let state = match &*self.state.read() {
+            QueryState::Memoized(memo) => QueryState::Memoized(Memo {
+                value: memo.value.clone(),
+                accumulated: memo.accumulated.clone(),
+                revisions: memo.revisions.clone(),
+                first_computed_at: memo.first_computed_at,
+                in_fixpoint: memo.in_fixpoint,
+                overridden: memo.overridden,
+            }),
+            QueryState::NotComputed | QueryState::InProgress { .. } => QueryState::NotComputed,
+        };
+        Self {
+            key: self.key.clone(),
+            database_key_index: self.database_key_index,
+            state: RwLock::new(state),
+            lru_index: LruIndex::default(),
+            pinned: AtomicBool::new(self.is_pinned()),
+            policy: PhantomData,
+            provisional: Mutex::new(None),
+            provisional_memo: Mutex::new(None),
+            history: Mutex::new(self.history.lock().clone()),
         }
     }
 
@@ -128,7 +227,16 @@ where
         &self,
         db: &<Q as QueryDb<'_>>::DynDb,
     ) -> Result<StampedValue<Q::Value>, CycleError<DatabaseKeyIndex>> {
+        self.read_probed(db).map(|(value, _)| value)
+    }
+
+    /// Like `read`, but also returns how the value was obtained.
+    pub(super) fn read_probed(
+        &self,
+        db: &<Q as QueryDb<'_>>::DynDb,
+    ) -> Result<(StampedValue<Q::Value>, CacheOutcome), CycleError<DatabaseKeyIndex>> {
         let runtime = db.salsa_runtime();
+        runtime.report_verified_memo();
 
         // NB: We don't need to worry about people modifying the
         // revision out from under our feet. Either `db` is a frozen
@@ -141,13 +249,43 @@ where
 
         // First, do a check with a read-lock.
         match self.probe(db, self.state.read(), runtime, revision_now) {
-            ProbeState::UpToDate(v) => return v,
+            ProbeState::UpToDate(v) => return v.map(|v| (v, CacheOutcome::Hit)),
             ProbeState::StaleOrAbsent(_guard) => (),
         }
 
         self.read_upgrade(db, revision_now)
     }
 
+    /// Computes the value to use for this query when it is part of
+    /// `cycle`, as directed by the runtime's `CycleHandling`, or returns
+    /// `None` if it should not recover.
+    fn recover(
+        &self,
+        db: &<Q as QueryDb<'_>>::DynDb,
+        runtime: &Runtime,
+        cycle: &[DatabaseKeyIndex],
+    ) -> Option<Q::Value> {
+        let cycle = Cycle::new(cycle.to_vec());
+        match runtime.cycle_handling() {
+            CycleHandling::Static => Q::recover(db, &cycle, &self.key),
+            CycleHandling::Panic => None,
+            CycleHandling::RecoverWith(recover_fn) => recover_fn(&cycle, self.database_key_index)
+                .and_then(Q::downcast_recovered)
+                .or_else(|| Q::recover(db, &cycle, &self.key)),
+        }
+    }
+
+    /// Returns the provisional value of this query, if it is a fixpoint
+    /// query, first setting it to the initial value if iteration has
+    /// not yet started.
+    fn provisional_value(&self) -> Option<Q::Value> {
+        let mut provisional = self.provisional.lock();
+        if provisional.is_none() {
+            *provisional = Q::cycle_initial(&self.key);
+        }
+        provisional.clone()
+    }
+
     /// Second phase of a read operation: acquires an upgradable-read
     /// and -- if needed -- validates whether inputs have changed,
     /// recomputes value, etc. This is invoked after our initial probe
@@ -156,7 +294,7 @@ where
         &self,
         db: &<Q as QueryDb<'_>>::DynDb,
         revision_now: Revision,
-    ) -> Result<StampedValue<Q::Value>, CycleError<DatabaseKeyIndex>> {
+    ) -> Result<(StampedValue<Q::Value>, CacheOutcome), CycleError<DatabaseKeyIndex>> {
         let runtime = db.salsa_runtime();
 
         debug!("{:?}: read_upgrade(revision_now={:?})", self, revision_now,);
@@ -165,7 +303,7 @@ where
         // already. (This permits other readers but prevents anyone
         // else from running `read_upgrade` at the same time.)
         let old_memo = match self.probe(db, self.state.upgradable_read(), runtime, revision_now) {
-            ProbeState::UpToDate(v) => return v,
+            ProbeState::UpToDate(v) => return v.map(|v| (v, CacheOutcome::Hit)),
             ProbeState::StaleOrAbsent(state) => {
                 type RwLockUpgradableReadGuard<'a, T> =
                     lock_api::RwLockUpgradableReadGuard<'a, RawRwLock, T>;
@@ -189,12 +327,18 @@ where
             if let Some(value) = memo.validate_memoized_value(db, revision_now) {
                 info!("{:?}: validated old memoized value", self,);
 
-                db.salsa_event(Event {
-                    runtime_id: runtime.id(),
-                    kind: EventKind::DidValidateMemoizedValue {
-                        database_key: self.database_key_index,
+                runtime.report_cache_hit(self.database_key_index);
+
+                runtime.report_event(
+                    db,
+                    Event {
+                        runtime_id: runtime.id(),
+                        kind: EventKind::DidValidateMemoizedValue {
+                            database_key: self.database_key_index,
+                            revision: revision_now,
+                        },
                     },
-                });
+                );
 
                 panic_guard.proceed(
                     &value,
@@ -206,20 +350,95 @@ where
                     Vec::new(),
                 );
 
-                return Ok(value);
+                return Ok((value, CacheOutcome::Validated));
+            }
+        }
+
+        // Executing the query would exceed the maximum query depth; if
+        // we are to recover, do so like for a cycle made of the stack.
+        if runtime.overflow_behavior() == OverflowBehavior::Recover && runtime.query_stack_is_full()
+        {
+            let mut participants = runtime.active_query_stack();
+            participants.push(self.database_key_index);
+            let recovered = self
+                .recover(db, runtime, &participants)
+                .or_else(|| Q::cycle_initial(&self.key));
+            if let Some(value) = recovered {
+                debug!("{:?}: recovered from query stack overflow", self);
+                // Like a provisional value that never converges, the
+                // recovered value (and what is computed from it) is not
+                // memoized.
+                runtime.report_provisional_read(&[self.database_key_index]);
+                let new_value = StampedValue {
+                    value,
+                    durability: Durability::LOW,
+                    changed_at: revision_now,
+                };
+                panic_guard.memo = None;
+                panic_guard.provisional = true;
+                panic_guard.proceed(&new_value, Vec::new());
+                return Ok((new_value, CacheOutcome::Computed));
             }
         }
 
         // Query was not previously executed, or value is potentially
         // stale, or value is absent. Let's execute!
-        let mut result = runtime.execute_query_implementation(db, self.database_key_index, || {
-            info!("{:?}: executing query", self);
-
-            Q::execute(db, self.key.clone())
-        });
+        let execute = || {
+            runtime.execute_query_implementation(db, self.database_key_index, || {
+                info!("{:?}: executing query", self);
+
+                // Spans of the queries this one executes are nested in
+                // it, as they are entered on the same thread.
+                #[cfg(feature = "tracing")]
+                let _span = tracing::info_span!(
+                    "query",
+                    name = Q::QUERY_NAME,
+                    key = ?self.key,
+                    revision = ?revision_now,
+                )
+                .entered();
+
+                Q::execute(db, self.key.clone())
+            })
+        };
+        *self.provisional.lock() = None;
+        let mut result = execute();
+
+        // If we read our own provisional value (see `probe`), iterate
+        // until the value we compute is the one we assumed.
+        let mut iterations = 0;
+        while let Some(position) = result
+            .provisional_heads
+            .iter()
+            .position(|&head| head == self.database_key_index)
+        {
+            result.provisional_heads.remove(position);
+            let mut provisional = self.provisional.lock();
+            if MP::memoized_value_eq(provisional.as_ref().unwrap(), &result.value) {
+                break;
+            }
+            iterations += 1;
+            assert!(
+                iterations < MAX_FIXPOINT_ITERATIONS,
+                "{:?}: fixpoint iteration did not converge after {} iterations",
+                self,
+                iterations,
+            );
+            debug!("{:?}: fixpoint iteration {}", self, iterations);
+            *provisional = Some(result.value.clone());
+            std::mem::drop(provisional);
+            result = execute();
+        }
+        let in_fixpoint = self.provisional.lock().take().is_some();
+        if in_fixpoint {
+            // We are not an input of our own converged value.
+            if let Some(dependencies) = &mut result.dependencies {
+                dependencies.retain(|&input| input != self.database_key_index);
+            }
+        }
 
         if !result.cycle.is_empty() {
-            result.value = match Q::recover(db, &result.cycle, &self.key) {
+            result.value = match self.recover(db, runtime, &result.cycle) {
                 Some(v) => v,
                 None => {
                     let err = CycleError {
@@ -233,6 +452,25 @@ where
             };
         }
 
+        // In strict mode, check that executing the query again with the
+        // same inputs produces the same value; if not, it must have read
+        // something that salsa did not track.
+        if runtime.is_strict()
+            && result.cycle.is_empty()
+            && result.provisional_heads.is_empty()
+            && result.dependencies.is_some()
+            && self.should_memoize_value(&self.key)
+            && MP::HAS_EQ
+        {
+            let rerun = runtime.muted(execute);
+            assert!(
+                MP::memoized_value_eq(&result.value, &rerun.value),
+                "{:?}: query produced a different value when executed again with \
+                 the same inputs; it may read state that is not tracked by salsa",
+                self,
+            );
+        }
+
         // We assume that query is side-effect free -- that is, does
         // not mutate the "inputs" to the query system. Sanity check
         // that assumption here, at least to the best of our ability.
@@ -299,19 +537,78 @@ where
         };
         debug!("read_upgrade({:?}): inputs={:?}", self, inputs);
 
+        let first_computed_at = match &panic_guard.memo {
+            Some(old_memo) => old_memo.first_computed_at,
+            None => revision_now,
+        };
+
+        // A value computed from the provisional values of a fixpoint
+        // query (still iterating further up the stack) may change in the
+        // next iteration, so it is not memoized yet, and neither is the
+        // value of whoever reads it. Once the iteration converges, the
+        // values computed in its last iteration are memoized (see
+        // `commit_provisional_members`).
+        if !result.provisional_heads.is_empty() {
+            debug!(
+                "read_upgrade({:?}): provisional, depends on {:?}",
+                self, result.provisional_heads
+            );
+            runtime.report_provisional_read(&result.provisional_heads);
+            let mut members = result.provisional_members;
+            members.push(ProvisionalMember {
+                database_key_index: self.database_key_index,
+                inputs: inputs.to_vec(),
+            });
+            runtime.report_provisional_members(members);
+            *self.provisional_memo.lock() = Some(Memo {
+                value,
+                first_computed_at,
+                in_fixpoint: true,
+                overridden: false,
+                accumulated: result.accumulated,
+                revisions: MemoRevisions {
+                    changed_at: result.changed_at,
+                    verified_at: revision_now,
+                    inputs,
+                    durability: result.durability,
+                    durability_dependency: result.durability_dependency,
+                },
+            });
+            panic_guard.memo = None;
+            panic_guard.provisional = true;
+            panic_guard.proceed(&new_value, result.cycle);
+            return Ok((new_value, CacheOutcome::Computed));
+        }
+
+        if !result.provisional_members.is_empty() {
+            self.commit_provisional_members(
+                db,
+                &result.provisional_members,
+                &inputs,
+                result.durability,
+            );
+        }
+
+        self.record_history(&new_value);
+
         panic_guard.memo = Some(Memo {
             value,
+            first_computed_at,
+            in_fixpoint,
+            overridden: false,
+            accumulated: result.accumulated,
             revisions: MemoRevisions {
                 changed_at: result.changed_at,
                 verified_at: revision_now,
                 inputs,
                 durability: result.durability,
+                durability_dependency: result.durability_dependency,
             },
         });
 
         panic_guard.proceed(&new_value, result.cycle);
 
-        Ok(new_value)
+        Ok((new_value, CacheOutcome::Computed))
     }
 
     /// Helper for `read` that does a shallow check (not recursive) if we have an up-to-date value.
@@ -340,20 +637,49 @@ where
 
             QueryState::InProgress { id, waiting } => {
                 let other_id = *id;
+
+                // If we are executing this query ourselves, and it is a
+                // fixpoint query, return its provisional value (see
+                // `read_upgrade`) rather than reporting a cycle.
+                let fixpoint_allowed = match runtime.cycle_handling() {
+                    CycleHandling::Panic => false,
+                    CycleHandling::Static | CycleHandling::RecoverWith(_) => true,
+                };
+                if other_id == runtime.id() && fixpoint_allowed {
+                    if let Some(value) = self.provisional_value() {
+                        std::mem::drop(state);
+                        debug!("{:?}: returning provisional value {:?}", self, value);
+                        runtime.report_provisional_read(&[self.database_key_index]);
+                        return ProbeState::UpToDate(Ok(StampedValue {
+                            value,
+                            durability: Durability::HIGH,
+                            changed_at: revision_now,
+                        }));
+                    }
+                }
+
                 return match self.register_with_in_progress_thread(db, runtime, other_id, waiting) {
                     Ok(future) => {
                         // Release our lock on `self.state`, so other thread can complete.
                         std::mem::drop(state);
 
-                        db.salsa_event(Event {
-                            runtime_id: runtime.id(),
-                            kind: EventKind::WillBlockOn {
-                                other_runtime_id: other_id,
-                                database_key: self.database_key_index,
+                        runtime.report_event(
+                            db,
+                            Event {
+                                runtime_id: runtime.id(),
+                                kind: EventKind::WillBlockOn {
+                                    other_runtime_id: other_id,
+                                    database_key: self.database_key_index,
+                                },
                             },
-                        });
+                        );
 
                         let result = future.wait().unwrap_or_else(|| db.on_propagated_panic());
+                        if result.provisional {
+                            // The other thread's value cannot be used
+                            // here; compute it ourselves.
+                            return ProbeState::UpToDate(self.read(db));
+                        }
                         ProbeState::UpToDate(if result.cycle.is_empty() {
                             Ok(result.value)
                         } else {
@@ -363,7 +689,7 @@ where
                                 durability: result.value.durability,
                             };
                             runtime.mark_cycle_participants(&err);
-                            Q::recover(db, &err.cycle, &self.key)
+                            self.recover(db, runtime, &err.cycle)
                                 .map(|value| StampedValue {
                                     value,
                                     durability: err.durability,
@@ -380,7 +706,7 @@ where
                             revision_now,
                         );
                         ProbeState::UpToDate(
-                            Q::recover(db, &err.cycle, &self.key)
+                            self.recover(db, runtime, &err.cycle)
                                 .map(|value| StampedValue {
                                     value,
                                     changed_at: err.changed_at,
@@ -411,6 +737,8 @@ where
                             self, value.changed_at
                         );
 
+                        runtime.report_cache_hit(self.database_key_index);
+
                         return ProbeState::UpToDate(Ok(value));
                     }
                 }
@@ -420,6 +748,34 @@ where
         ProbeState::StaleOrAbsent(state)
     }
 
+    /// Returns the memoized value if it has already been verified in
+    /// the current revision.
+    pub(super) fn peek(&self, db: &<Q as QueryDb<'_>>::DynDb) -> Option<Q::Value> {
+        let revision_now = db.salsa_runtime().current_revision();
+        match &*self.state.read() {
+            QueryState::Memoized(memo) if memo.revisions.verified_at == revision_now => {
+                memo.value.clone()
+            }
+            QueryState::NotComputed | QueryState::InProgress { .. } | QueryState::Memoized(_) => {
+                None
+            }
+        }
+    }
+
+    /// Returns the revision in which the memoized value last changed,
+    /// if it has already been verified in the current revision.
+    pub(super) fn changed_at(&self, db: &<Q as QueryDb<'_>>::DynDb) -> Option<Revision> {
+        let revision_now = db.salsa_runtime().current_revision();
+        match &*self.state.read() {
+            QueryState::Memoized(memo) if memo.revisions.verified_at == revision_now => {
+                Some(memo.revisions.changed_at)
+            }
+            QueryState::NotComputed | QueryState::InProgress { .. } | QueryState::Memoized(_) => {
+                None
+            }
+        }
+    }
+
     pub(super) fn durability(&self, db: &<Q as QueryDb<'_>>::DynDb) -> Durability {
         match &*self.state.read() {
             QueryState::NotComputed => Durability::LOW,
@@ -444,21 +800,410 @@ where
         }
     }
 
-    pub(super) fn evict(&self) {
+    /// Returns the key and memoized value, if the value was verified in
+    /// `revision_now`.
+    pub(super) fn verified_entry(&self, revision_now: Revision) -> Option<(Q::Key, Q::Value)> {
+        match &*self.state.read() {
+            QueryState::Memoized(memo) if memo.revisions.verified_at == revision_now => {
+                let value = memo.value.clone()?;
+                Some((self.key.clone(), value))
+            }
+            _ => None,
+        }
+    }
+
+    /// True if there is a memoized value, verified in `revision_now`.
+    pub(super) fn has_verified_value(&self, revision_now: Revision) -> bool {
+        match &*self.state.read() {
+            QueryState::Memoized(memo) => {
+                memo.revisions.verified_at == revision_now && memo.value.is_some()
+            }
+            QueryState::NotComputed | QueryState::InProgress { .. } => false,
+        }
+    }
+
+    pub(super) fn dependencies(&self) -> Option<Vec<DatabaseKeyIndex>> {
+        match &*self.state.read() {
+            QueryState::Memoized(memo) => memo.revisions.inputs.to_vec(),
+            QueryState::NotComputed | QueryState::InProgress { .. } => None,
+        }
+    }
+
+    /// Memoizes the values of `members`, which were computed from our
+    /// provisional value in the last iteration, now that it converged
+    /// (with the given `inputs` and `durability`).
+    ///
+    /// Each member is given the inputs of all members (and ours), other
+    /// than the members themselves: its value depends on the whole
+    /// cycle, and leaving the members out keeps the dependencies of
+    /// memoized values acyclic. Our own memo keeps the inputs we read,
+    /// so it is validated through the members.
+    fn commit_provisional_members(
+        &self,
+        db: &<Q as QueryDb<'_>>::DynDb,
+        members: &[ProvisionalMember],
+        inputs: &MemoInputs,
+        durability: Durability,
+    ) {
+        let is_member = |input: &DatabaseKeyIndex| {
+            *input == self.database_key_index
+                || members
+                    .iter()
+                    .any(|member| member.database_key_index == *input)
+        };
+        let mut cycle_inputs: Option<FxIndexSet<_>> =
+            inputs.to_vec().map(|inputs| inputs.into_iter().collect());
+        for member in members {
+            match (&mut cycle_inputs, &member.inputs) {
+                (Some(cycle_inputs), Some(inputs)) => cycle_inputs.extend(inputs.iter().copied()),
+                _ => cycle_inputs = None,
+            }
+        }
+        let cycle_inputs: Option<Vec<_>> = cycle_inputs.map(|cycle_inputs| {
+            cycle_inputs
+                .into_iter()
+                .filter(|input| !is_member(input))
+                .collect()
+        });
+        debug!(
+            "{:?}: memoizing provisional values of {} members, with inputs {:?}",
+            self,
+            members.len(),
+            cycle_inputs,
+        );
+        for member in members {
+            db.commit_provisional(
+                member.database_key_index,
+                cycle_inputs.as_deref(),
+                durability,
+            );
+        }
+    }
+
+    /// Memoizes the value last computed from provisional values (see
+    /// `commit_provisional_members`), if there is one and no memo has
+    /// been stored since; returns true if it did.
+    pub(super) fn commit_provisional(
+        &self,
+        inputs: Option<&[DatabaseKeyIndex]>,
+        durability: Durability,
+    ) -> bool {
+        let mut memo = match self.provisional_memo.lock().take() {
+            Some(memo) => memo,
+            None => return false,
+        };
         let mut state = self.state.write();
-        if let QueryState::Memoized(memo) = &mut *state {
-            // Similar to GC, evicting a value with an untracked input could
-            // lead to inconsistencies. Note that we can't check
-            // `has_untracked_input` when we add the value to the cache,
-            // because inputs can become untracked in the next revision.
-            if memo.revisions.has_untracked_input() {
+        match &*state {
+            QueryState::NotComputed => {}
+            QueryState::Memoized(_) | QueryState::InProgress { .. } => return false,
+        }
+        memo.revisions.inputs = match inputs {
+            None => MemoInputs::Untracked,
+            Some([]) => MemoInputs::NoInputs,
+            Some(inputs) => MemoInputs::Tracked {
+                inputs: inputs.into(),
+            },
+        };
+        memo.revisions.durability = durability;
+        *state = QueryState::Memoized(memo);
+        true
+    }
+
+    pub(super) fn memoized_changed_at(&self) -> Option<Revision> {
+        match &*self.state.read() {
+            QueryState::Memoized(memo) => Some(memo.revisions.changed_at),
+            QueryState::NotComputed | QueryState::InProgress { .. } => None,
+        }
+    }
+
+    /// Adds `value` to the history of this key (see
+    /// `QueryFunction::HISTORY_DEPTH`), unless it equals the newest
+    /// value there (as when the query is re-executed after its value
+    /// was evicted, or computes the same value again).
+    fn record_history(&self, value: &StampedValue<Q::Value>) {
+        if Q::HISTORY_DEPTH == 0 || !MP::should_memoize_value(&self.key) {
+            return;
+        }
+        let mut history = self.history.lock();
+        if let Some((_, newest)) = history.back() {
+            if MP::memoized_value_eq(newest, &value.value) {
                 return;
             }
-            memo.value = None;
         }
+        if history.len() == Q::HISTORY_DEPTH {
+            history.pop_front();
+        }
+        history.push_back((value.changed_at, value.value.clone()));
+    }
+
+    pub(super) fn history(&self) -> Vec<(Revision, Q::Value)> {
+        self.history.lock().iter().cloned().collect()
+    }
+
+    pub(super) fn first_computed_at(&self) -> Option<Revision> {
+        match &*self.state.read() {
+            QueryState::Memoized(memo) => Some(memo.first_computed_at),
+            QueryState::NotComputed | QueryState::InProgress { .. } => None,
+        }
+    }
+
+    pub(super) fn memoized_durability(&self) -> Option<Durability> {
+        match &*self.state.read() {
+            QueryState::Memoized(memo) => Some(memo.revisions.durability),
+            QueryState::NotComputed | QueryState::InProgress { .. } => None,
+        }
+    }
+
+    pub(super) fn accumulated(&self) -> Option<AccumulatedValues> {
+        match &*self.state.read() {
+            QueryState::Memoized(memo) => Some(memo.accumulated.clone()),
+            QueryState::NotComputed | QueryState::InProgress { .. } => None,
+        }
+    }
+
+    pub(super) fn for_each_memo(&self, revision_now: Revision, op: &mut dyn FnMut(MemoEntry<'_>)) {
+        if let QueryState::Memoized(memo) = &*self.state.read() {
+            op(MemoEntry::new(
+                self.database_key_index,
+                &self.key,
+                memo.revisions.verified_at == revision_now,
+                memo.value.as_ref().map(|value| value as &dyn Debug),
+                match &memo.revisions.inputs {
+                    MemoInputs::Tracked { inputs } => Some(inputs),
+                    MemoInputs::NoInputs => Some(&[]),
+                    MemoInputs::Untracked => None,
+                },
+            ));
+        }
+    }
+
+    /// Brings the memoized value up to date (as reading it would), then
+    /// executes the query again and panics if the result differs from
+    /// the memoized value or its recorded inputs. Values that are not
+    /// memoized, were read in a cycle, or have untracked inputs are not
+    /// checked, and neither are overrides.
+    pub(super) fn debug_validate_dependencies(&self, db: &<Q as QueryDb<'_>>::DynDb) {
+        let runtime = db.salsa_runtime();
+        if !matches!(
+            &*self.state.read(),
+            QueryState::Memoized(memo) if !memo.overridden && !memo.in_fixpoint
+        ) || runtime.muted(|| self.read(db)).is_err()
+        {
+            return;
+        }
+
+        let (value, mut inputs) = match &*self.state.read() {
+            QueryState::Memoized(memo) => match (&memo.value, &memo.revisions.inputs) {
+                (Some(value), MemoInputs::Tracked { inputs }) => (value.clone(), inputs.to_vec()),
+                (Some(value), MemoInputs::NoInputs) => (value.clone(), vec![]),
+                _ => return,
+            },
+            QueryState::NotComputed | QueryState::InProgress { .. } => return,
+        };
+
+        let result = runtime.muted(|| {
+            runtime.execute_query_implementation(db, self.database_key_index, || {
+                info!("{:?}: executing query to validate dependencies", self);
+
+                Q::execute(db, self.key.clone())
+            })
+        });
+        if !result.cycle.is_empty() || !result.provisional_heads.is_empty() {
+            return;
+        }
+        let mut dependencies = match result.dependencies {
+            Some(dependencies) => dependencies.into_iter().collect::<Vec<_>>(),
+            None => return,
+        };
+
+        assert!(
+            !MP::HAS_EQ || MP::memoized_value_eq(&value, &result.value),
+            "{:?}: memoized value differs from the value computed from scratch",
+            self,
+        );
+
+        inputs.sort();
+        dependencies.sort();
+        if inputs != dependencies {
+            let debug = |indices: &[DatabaseKeyIndex]| {
+                indices
+                    .iter()
+                    .map(|index| format!("{:?}", index.debug(db)))
+                    .collect::<Vec<_>>()
+            };
+            panic!(
+                "{:?}: recorded inputs {:?} differ from the inputs read when computed from scratch {:?}",
+                self,
+                debug(&inputs),
+                debug(&dependencies),
+            );
+        }
+    }
+
+    pub(super) fn has_value(&self) -> bool {
+        match &*self.state.read() {
+            QueryState::Memoized(memo) => memo.value.is_some(),
+            QueryState::NotComputed | QueryState::InProgress { .. } => false,
+        }
+    }
+
+    /// Replaces the memo with `value`, as though the query had just been
+    /// executed in the new revision `revision_now` and had read `inputs`
+    /// (see `QueryTableMut::prime`). Returns the durability of the memo
+    /// it replaced, if any.
+    pub(super) fn prime(
+        &self,
+        value: Q::Value,
+        inputs: Vec<DatabaseKeyIndex>,
+        revision_now: Revision,
+    ) -> Option<Durability> {
+        let value = if self.should_memoize_value(&self.key) {
+            Some(value)
+        } else {
+            None
+        };
+        let inputs = if inputs.is_empty() {
+            MemoInputs::NoInputs
+        } else {
+            MemoInputs::Tracked {
+                inputs: inputs.into_iter().collect(),
+            }
+        };
+
+        let mut state = self.state.write();
+        let (first_computed_at, old_durability) = match &*state {
+            QueryState::InProgress { .. } => {
+                panic!("cannot prime {:?} while it is being computed", self)
+            }
+            QueryState::Memoized(memo) => (memo.first_computed_at, Some(memo.revisions.durability)),
+            QueryState::NotComputed => (revision_now, None),
+        };
+        *state = QueryState::Memoized(Memo {
+            value,
+            first_computed_at,
+            in_fixpoint: false,
+            accumulated: AccumulatedValues::default(),
+            revisions: MemoRevisions {
+                verified_at: revision_now,
+                changed_at: revision_now,
+                durability: Durability::LOW,
+                inputs,
+                durability_dependency: None,
+            },
+            overridden: false,
+        });
+        old_durability
+    }
+
+    /// Replaces the memo with an override of the given value, in the new
+    /// revision `revision_now` (see `QueryTableMut::set_override`).
+    /// Returns the durability of the memo it replaced, if any.
+    pub(super) fn set_override(
+        &self,
+        value: Q::Value,
+        revision_now: Revision,
+    ) -> Option<Durability> {
+        let mut state = self.state.write();
+        let (first_computed_at, old_durability) = match &*state {
+            QueryState::InProgress { .. } => {
+                panic!("cannot override {:?} while it is being computed", self)
+            }
+            QueryState::Memoized(memo) => (memo.first_computed_at, Some(memo.revisions.durability)),
+            QueryState::NotComputed => (revision_now, None),
+        };
+        *state = QueryState::Memoized(Memo {
+            value: Some(value),
+            first_computed_at,
+            in_fixpoint: false,
+            accumulated: AccumulatedValues::default(),
+            revisions: MemoRevisions {
+                verified_at: revision_now,
+                changed_at: revision_now,
+                durability: Durability::LOW,
+                inputs: MemoInputs::NoInputs,
+                durability_dependency: None,
+            },
+            overridden: true,
+        });
+        old_durability
+    }
+
+    /// Discards the memo if it is an override, so that the query is
+    /// executed when next needed, returning its durability.
+    pub(super) fn clear_override(&self) -> Option<Durability> {
+        let mut state = self.state.write();
+        match &*state {
+            QueryState::Memoized(memo) if memo.overridden => {
+                let durability = memo.revisions.durability;
+                *state = QueryState::NotComputed;
+                Some(durability)
+            }
+            QueryState::Memoized(_) | QueryState::NotComputed | QueryState::InProgress { .. } => {
+                None
+            }
+        }
+    }
+
+    pub(super) fn is_overridden(&self) -> bool {
+        match &*self.state.read() {
+            QueryState::Memoized(memo) => memo.overridden,
+            QueryState::NotComputed | QueryState::InProgress { .. } => false,
+        }
+    }
+
+    pub(super) fn set_pinned(&self, pinned: bool) {
+        self.pinned.store(pinned, Ordering::Relaxed);
+    }
+
+    pub(super) fn is_pinned(&self) -> bool {
+        self.pinned.load(Ordering::Relaxed)
+    }
+
+    /// Discards the memoized value, if there is one that can be
+    /// discarded, and returns whether it did.
+    pub(super) fn evict(&self, db: &dyn Database) -> bool {
+        if self.is_pinned() {
+            return false;
+        }
+
+        // Similar to GC, evicting a value with an untracked input could
+        // lead to inconsistencies. Note that we can't check
+        // `has_untracked_input` when we add the value to the cache,
+        // because inputs can become untracked in the next revision.
+        let mut state = self.state.write();
+        let evicted = match &mut *state {
+            QueryState::Memoized(memo)
+                if memo.value.is_some()
+                    && !memo.revisions.has_untracked_input()
+                    && !memo.overridden =>
+            {
+                memo.value = None;
+                true
+            }
+            QueryState::Memoized(_) | QueryState::NotComputed | QueryState::InProgress { .. } => {
+                false
+            }
+        };
+
+        // Report the event without holding the lock, as `salsa_event`
+        // may read from the database.
+        std::mem::drop(state);
+        if evicted {
+            let runtime = db.salsa_runtime();
+            runtime.report_event(
+                db,
+                Event {
+                    runtime_id: runtime.id(),
+                    kind: EventKind::WillDiscardStaleValue {
+                        database_key: self.database_key_index,
+                    },
+                },
+            );
+        }
+        evicted
     }
 
-    pub(super) fn sweep(&self, revision_now: Revision, strategy: SweepStrategy) {
+    pub(super) fn sweep(&self, revision_now: Revision, strategy: &SweepStrategy) {
         let mut state = self.state.write();
         match &mut *state {
             QueryState::NotComputed => (),
@@ -496,6 +1241,13 @@ where
                 // revision, since we are holding the write lock
                 // when we read `revision_now`.
                 assert!(memo.revisions.verified_at <= revision_now);
+
+                // Values reachable from the roots of a `keep_roots`
+                // strategy are always kept, as are overrides.
+                if strategy.keeps(self.database_key_index) || memo.overridden {
+                    return;
+                }
+
                 match strategy.discard_if {
                     DiscardIf::Never => unreachable!(),
 
@@ -525,11 +1277,28 @@ where
     }
 
     pub(super) fn invalidate(&self) -> Option<Durability> {
-        if let QueryState::Memoized(memo) = &mut *self.state.write() {
-            memo.revisions.inputs = MemoInputs::Untracked;
-            Some(memo.revisions.durability)
-        } else {
-            None
+        match &mut *self.state.write() {
+            QueryState::Memoized(memo) if !memo.overridden => {
+                memo.revisions.inputs = MemoInputs::Untracked;
+                Some(memo.revisions.durability)
+            }
+            _ => None,
+        }
+    }
+
+    /// Discards the memoized value and inputs, if any (and if they are
+    /// not an override), returning their durability.
+    pub(super) fn clear(&self) -> Option<Durability> {
+        let mut state = self.state.write();
+        match &*state {
+            QueryState::Memoized(memo) if !memo.overridden => {
+                let durability = memo.revisions.durability;
+                *state = QueryState::NotComputed;
+                Some(durability)
+            }
+            QueryState::Memoized(_) | QueryState::NotComputed | QueryState::InProgress { .. } => {
+                None
+            }
         }
     }
 
@@ -574,8 +1343,26 @@ where
                         // Release our lock on `self.state`, so other thread can complete.
                         std::mem::drop(state);
 
+                        runtime.report_event(
+                            db,
+                            Event {
+                                runtime_id: runtime.id(),
+                                kind: EventKind::WillBlockOn {
+                                    other_runtime_id: other_id,
+                                    database_key: self.database_key_index,
+                                },
+                            },
+                        );
+
                         let result = future.wait().unwrap_or_else(|| db.on_propagated_panic());
-                        return !result.cycle.is_empty() || result.value.changed_at > revision;
+
+                        // A value computed from provisional values (see
+                        // `WaitResult::provisional`) is not memoized, and
+                        // may differ from the one the iteration converges
+                        // to, so consider it changed.
+                        return result.provisional
+                            || !result.cycle.is_empty()
+                            || result.value.changed_at > revision;
                     }
 
                     // Consider a cycle to have changed.
@@ -604,6 +1391,12 @@ where
         if memo.revisions.check_durability(runtime) {
             std::mem::drop(state);
             maybe_changed = false;
+        } else if memo.revisions.durability_dependency_changed(runtime) {
+            debug!(
+                "maybe_changed_since({:?}: true since an input of its durability changed",
+                self,
+            );
+            return true;
         } else {
             match &memo.revisions.inputs {
                 MemoInputs::Untracked => {
@@ -634,7 +1427,7 @@ where
                     if memo.value.is_some() {
                         std::mem::drop(state);
                         return match self.read_upgrade(db, revision_now) {
-                            Ok(v) => {
+                            Ok((v, _)) => {
                                 debug!(
                                 "maybe_changed_since({:?}: {:?} since (recomputed) value changed at {:?}",
                                 self,
@@ -778,6 +1571,10 @@ where
     slot: &'me Slot<Q, MP>,
     memo: Option<Memo<Q>>,
     runtime: &'me Runtime,
+
+    /// True if the value we proceed with was computed from provisional
+    /// values (see `WaitResult::provisional`).
+    provisional: bool,
 }
 
 impl<'me, Q, MP> PanicGuard<'me, Q, MP>
@@ -796,6 +1593,7 @@ where
             slot,
             memo,
             runtime,
+            provisional: false,
         }
     }
 
@@ -847,6 +1645,7 @@ where
                             promise.fulfil(WaitResult {
                                 value: new_value.clone(),
                                 cycle: cycle.clone(),
+                                provisional: self.provisional,
                             });
                         }
                     }
@@ -924,6 +1723,11 @@ impl MemoRevisions {
             return self.mark_value_as_verified(revision_now);
         }
 
+        if self.durability_dependency_changed(db.salsa_runtime()) {
+            debug!("validate_memoized_value: an input of its durability changed");
+            return false;
+        }
+
         match &self.inputs {
             // We can't validate values that had untracked inputs; just have to
             // re-execute.
@@ -971,6 +1775,15 @@ impl MemoRevisions {
         last_changed <= self.verified_at
     }
 
+    /// True if an input of the durability this memo depends on (see
+    /// `Runtime::depend_on_durability`) changed since it was verified.
+    fn durability_dependency_changed(&self, runtime: &Runtime) -> bool {
+        match self.durability_dependency {
+            Some(durability) => runtime.last_changed_revision(durability) > self.verified_at,
+            None => false,
+        }
+    }
+
     fn mark_value_as_verified(&mut self, revision_now: Revision) -> bool {
         self.verified_at = revision_now;
         true
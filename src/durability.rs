@@ -15,6 +15,28 @@
 /// frequently editing. Medium or high durabilities are used for
 /// configuration, the source from library crates, or other things
 /// that are unlikely to be edited.
+///
+/// # Custom durability levels
+///
+/// For finer-grained control, there are `Durability::LEN` levels in
+/// total, ordered from `0` (`Durability::LOW`) to `Durability::LEN - 1`
+/// (`Durability::HIGH`). `Durability::MEDIUM` is level `1`, and the
+/// remaining levels, created with `Durability::new`, lie between
+/// `MEDIUM` and `HIGH`. As with the named levels, setting an input of
+/// some durability only invalidates queries whose inputs all have that
+/// durability or lower.
+///
+/// ```
+/// use salsa::Durability;
+///
+/// let compiler_flags = Durability::MEDIUM;
+/// let vendored_deps = Durability::new(2);
+/// let sysroot = Durability::HIGH;
+/// assert!(Durability::LOW < compiler_flags);
+/// assert!(compiler_flags < vendored_deps);
+/// assert!(vendored_deps < sysroot);
+/// assert_eq!(Durability::new(Durability::LEN as u8 - 1), Durability::HIGH);
+/// ```
 #[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
 pub struct Durability(u8);
 
@@ -33,7 +55,7 @@ impl Durability {
     /// common usage.
     ///
     /// Example: the standard library or something from crates.io
-    pub const HIGH: Durability = Durability(2);
+    pub const HIGH: Durability = Durability(Self::LEN as u8 - 1);
 
     /// The maximum possible durability; equivalent to HIGH but
     /// "conceptually" distinct (i.e., if we add more durability
@@ -41,7 +63,28 @@ impl Durability {
     pub(crate) const MAX: Durability = Self::HIGH;
 
     /// Number of durability levels.
-    pub(crate) const LEN: usize = 3;
+    pub const LEN: usize = 8;
+
+    /// Creates the durability with the given level, where `0` is
+    /// `Durability::LOW` and `Durability::LEN - 1` is `Durability::HIGH`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `level` is not less than `Durability::LEN`.
+    pub fn new(level: u8) -> Durability {
+        assert!(
+            (level as usize) < Self::LEN,
+            "durability level {} is out of range (must be less than {})",
+            level,
+            Self::LEN
+        );
+        Durability(level)
+    }
+
+    /// Returns the level of this durability (see `Durability::new`).
+    pub fn level(self) -> u8 {
+        self.0
+    }
 
     pub(crate) fn index(self) -> usize {
         self.0 as usize
@@ -1,5 +1,9 @@
+use crate::debug::entry_size;
+use crate::debug::MemoEntry;
 use crate::debug::TableEntry;
 use crate::durability::Durability;
+use crate::plumbing::AccumulatedValues;
+use crate::plumbing::InputQuery;
 use crate::plumbing::InputQueryStorageOps;
 use crate::plumbing::QueryStorageMassOps;
 use crate::plumbing::QueryStorageOps;
@@ -9,9 +13,10 @@ use crate::CycleError;
 use crate::Database;
 use crate::Query;
 use crate::{DatabaseKeyIndex, QueryDb, Runtime, SweepStrategy};
+use crate::{Event, EventKind};
 use indexmap::map::Entry;
 use log::debug;
-use parking_lot::RwLock;
+use parking_lot::{Mutex, RwLock};
 use std::convert::TryFrom;
 use std::sync::Arc;
 
@@ -24,15 +29,26 @@ where
 {
     group_index: u16,
     slots: RwLock<FxIndexMap<Q::Key, Arc<Slot<Q>>>>,
+
+    /// The values set while the runtime was recording (see
+    /// `Runtime::start_recording`), in the order they were set.
+    recorded: Mutex<Vec<RecordedValue<Q>>>,
 }
 
+/// A key and value that were set, with their durability and the
+/// revision they were set in.
+type RecordedValue<Q> = (<Q as Query>::Key, <Q as Query>::Value, Durability, Revision);
+
 struct Slot<Q>
 where
     Q: Query,
 {
     key: Q::Key,
     database_key_index: DatabaseKeyIndex,
-    stamped_value: RwLock<StampedValue<Q::Value>>,
+
+    /// The current value, or `None` if it was removed (the slot itself
+    /// is kept, as queries may refer to `database_key_index`).
+    stamped_value: RwLock<StampedValue<Option<Q::Value>>>,
 }
 
 impl<Q> std::panic::RefUnwindSafe for InputStorage<Q>
@@ -50,16 +66,127 @@ where
     fn slot(&self, key: &Q::Key) -> Option<Arc<Slot<Q>>> {
         self.slots.read().get(key).cloned()
     }
+
+    /// Returns the slot for `key`, if it has a value.
+    fn slot_with_value(&self, key: &Q::Key) -> Option<Arc<Slot<Q>>> {
+        self.slot(key)
+            .filter(|slot| slot.stamped_value.read().value.is_some())
+    }
+}
+
+impl<Q> InputStorage<Q>
+where
+    Q: InputQuery,
+{
+    /// True if `key` already has the value `value` (as judged by
+    /// `InputQuery::values_equal`) with the same durability, so that
+    /// setting it again would not change anything.
+    fn is_unchanged(&self, key: &Q::Key, value: &Q::Value, durability: Durability) -> bool {
+        self.has_value(key, durability, &mut |old_value| {
+            Q::values_equal(old_value, value)
+        })
+    }
+
+    /// Panics if this input is immortal and `key` already has a value
+    /// (see `InputQuery::IMMORTAL`): queries that read it did not
+    /// record a dependency on it, so they would not see the change.
+    fn assert_mortal(&self, key: &Q::Key) {
+        if Q::IMMORTAL && self.slot_with_value(key).is_some() {
+            panic!(
+                "cannot change {:?}({:?}): the input is immortal and already has a value",
+                Q::default(),
+                key,
+            );
+        }
+    }
+
+    /// Returns the slot for `key`, first giving it the query's default
+    /// value if it has not been set (or was removed). Returns `None` if
+    /// there is no value and the query has no default.
+    fn slot_or_default(
+        &self,
+        db: &<Q as QueryDb<'_>>::DynDb,
+        key: &Q::Key,
+    ) -> Option<Arc<Slot<Q>>> {
+        if let Some(slot) = self.slot_with_value(key) {
+            return Some(slot);
+        }
+
+        // No query can have read this key yet (or since it was removed),
+        // as doing so would have panicked, so the default may be
+        // inserted without starting a new revision.
+        let default_stamped_value = |value| StampedValue {
+            value: Some(value),
+            durability: Durability::LOW,
+            changed_at: db.salsa_runtime().current_revision(),
+        };
+        let mut slots = self.slots.write();
+        let entry = slots.entry(key.clone());
+        let key_index = u32::try_from(entry.index()).unwrap();
+        match entry {
+            Entry::Occupied(entry) => {
+                let slot = entry.get();
+                let mut stamped_value = slot.stamped_value.write();
+                if stamped_value.value.is_none() {
+                    let value = Q::default_value(key)?;
+                    log::debug!("{:?}({:?}) = {:?} (default)", Q::default(), key, value);
+                    *stamped_value = default_stamped_value(value);
+                }
+                Some(slot.clone())
+            }
+            Entry::Vacant(entry) => {
+                let value = Q::default_value(key)?;
+                log::debug!("{:?}({:?}) = {:?} (default)", Q::default(), key, value);
+                let database_key_index = DatabaseKeyIndex {
+                    group_index: self.group_index,
+                    query_index: Q::QUERY_INDEX,
+                    key_index,
+                };
+                let stamped_value = default_stamped_value(value);
+                Some(
+                    entry
+                        .insert(Arc::new(Slot {
+                            key: key.clone(),
+                            database_key_index,
+                            stamped_value: RwLock::new(stamped_value),
+                        }))
+                        .clone(),
+                )
+            }
+        }
+    }
 }
 
 impl<Q> QueryStorageOps<Q> for InputStorage<Q>
 where
-    Q: Query,
+    Q: InputQuery,
 {
     fn new(group_index: u16) -> Self {
         InputStorage {
             group_index,
             slots: Default::default(),
+            recorded: Default::default(),
+        }
+    }
+
+    fn fork(&self) -> Self {
+        let slots = self
+            .slots
+            .read()
+            .iter()
+            .map(|(key, slot)| {
+                let slot = Slot {
+                    key: slot.key.clone(),
+                    database_key_index: slot.database_key_index,
+                    stamped_value: RwLock::new(slot.stamped_value.read().clone()),
+                };
+                (key.clone(), Arc::new(slot))
+            })
+            .collect();
+        InputStorage {
+            group_index: self.group_index,
+            slots: RwLock::new(slots),
+            recorded: Default::default(),
         }
     }
 
@@ -94,15 +221,45 @@ where
         slot.maybe_changed_since(db, revision)
     }
 
+    fn dependencies(
+        &self,
+        _db: &<Q as QueryDb<'_>>::DynDb,
+        _index: DatabaseKeyIndex,
+    ) -> Option<Vec<DatabaseKeyIndex>> {
+        None
+    }
+
+    fn accumulated(
+        &self,
+        _db: &<Q as QueryDb<'_>>::DynDb,
+        _index: DatabaseKeyIndex,
+    ) -> Option<AccumulatedValues> {
+        None
+    }
+
+    fn validate(&self, _db: &<Q as QueryDb<'_>>::DynDb, _index: DatabaseKeyIndex) {}
+
+    fn commit_provisional(
+        &self,
+        _db: &<Q as QueryDb<'_>>::DynDb,
+        _index: DatabaseKeyIndex,
+        _inputs: Option<&[DatabaseKeyIndex]>,
+        _durability: Durability,
+    ) {
+    }
+
+    fn debug_validate_dependencies(&self, _db: &<Q as QueryDb<'_>>::DynDb) {}
+
     fn try_fetch(
         &self,
         db: &<Q as QueryDb<'_>>::DynDb,
         key: &Q::Key,
     ) -> Result<Q::Value, CycleError<DatabaseKeyIndex>> {
         let slot = self
-            .slot(key)
+            .slot_or_default(db, key)
             .unwrap_or_else(|| panic!("no value set for {:?}({:?})", Q::default(), key));
 
+        // `slot_or_default` only returns slots that have a value.
         let StampedValue {
             value,
             durability,
@@ -110,13 +267,30 @@ where
         } = slot.stamped_value.read().clone();
 
         db.salsa_runtime()
-            .report_query_read(slot.database_key_index, durability, changed_at);
+            .check_snapshot_read(db, slot.database_key_index, changed_at);
+
+        // Immortal values never change, so there is nothing to depend on.
+        if !Q::IMMORTAL {
+            db.salsa_runtime()
+                .report_query_read(slot.database_key_index, durability, changed_at);
+        }
 
-        Ok(value)
+        Ok(value.unwrap())
+    }
+
+    fn peek(&self, _db: &<Q as QueryDb<'_>>::DynDb, key: &Q::Key) -> Option<Q::Value> {
+        self.slot(key)
+            .and_then(|slot| slot.stamped_value.read().value.clone())
+    }
+
+    fn changed_at(&self, _db: &<Q as QueryDb<'_>>::DynDb, key: &Q::Key) -> Option<Revision> {
+        let slot = self.slot_with_value(key)?;
+        let changed_at = slot.stamped_value.read().changed_at;
+        Some(changed_at)
     }
 
     fn durability(&self, _db: &<Q as QueryDb<'_>>::DynDb, key: &Q::Key) -> Durability {
-        match self.slot(key) {
+        match self.slot_with_value(key) {
             Some(slot) => slot.stamped_value.read().durability,
             None => panic!("no value set for {:?}({:?})", Q::default(), key),
         }
@@ -129,11 +303,9 @@ where
         let slots = self.slots.read();
         slots
             .values()
-            .map(|slot| {
-                TableEntry::new(
-                    slot.key.clone(),
-                    Some(slot.stamped_value.read().value.clone()),
-                )
+            .filter_map(|slot| {
+                let value = slot.stamped_value.read().value.clone()?;
+                Some(TableEntry::new(slot.key.clone(), Some(value)))
             })
             .collect()
     }
@@ -165,11 +337,36 @@ where
     fn purge(&self) {
         *self.slots.write() = Default::default();
     }
+    fn for_each_memo(&self, _runtime: &Runtime, op: &mut dyn FnMut(MemoEntry<'_>)) {
+        for slot in self.slots.read().values() {
+            let stamped_value = slot.stamped_value.read();
+            if let Some(value) = &stamped_value.value {
+                op(MemoEntry::new(
+                    slot.database_key_index,
+                    &slot.key,
+                    true,
+                    Some(value),
+                    Some(&[]),
+                ));
+            }
+        }
+    }
+    fn memory_usage(&self) -> usize {
+        let slots = self.slots.read();
+        let len = slots
+            .values()
+            .filter(|slot| slot.stamped_value.read().value.is_some())
+            .count();
+        len * entry_size::<Q>()
+    }
+    fn enforce_lru_capacity(&self, _db: &dyn Database) {}
+
+    fn clear_derived(&self) {}
 }
 
 impl<Q> InputQueryStorageOps<Q> for InputStorage<Q>
 where
-    Q: Query,
+    Q: InputQuery,
 {
     fn set(
         &self,
@@ -201,7 +398,14 @@ where
         // keys, we only need a new revision if the key used to
         // exist. But we may add such methods in the future and this
         // case doesn't generally seem worth optimizing for.
+        if self.is_unchanged(key, &value, durability) {
+            debug!("set: value unchanged, not starting a new revision");
+            return;
+        }
+        self.assert_mortal(key);
+
         let mut value = Some(value);
+        let mut database_key_index = None;
         db.salsa_runtime_mut()
             .with_incremented_revision(&mut |next_revision| {
                 let mut slots = self.slots.write();
@@ -216,30 +420,232 @@ where
                     changed_at: next_revision,
                 };
 
-                match slots.entry(key.clone()) {
-                    Entry::Occupied(entry) => {
-                        let mut slot_stamped_value = entry.get().stamped_value.write();
-                        let old_durability = slot_stamped_value.durability;
-                        *slot_stamped_value = stamped_value;
-                        Some(old_durability)
-                    }
-
-                    Entry::Vacant(entry) => {
-                        let key_index = u32::try_from(entry.index()).unwrap();
-                        let database_key_index = DatabaseKeyIndex {
-                            group_index: self.group_index,
-                            query_index: Q::QUERY_INDEX,
-                            key_index,
-                        };
-                        entry.insert(Arc::new(Slot {
-                            key: key.clone(),
-                            database_key_index,
-                            stamped_value: RwLock::new(stamped_value),
-                        }));
-                        None
-                    }
+                let old_durability = self.insert_slot(&mut slots, key, stamped_value);
+                database_key_index = Some(slots[key].database_key_index);
+                old_durability
+            });
+        self.report_set(db, database_key_index.unwrap(), durability);
+    }
+
+    fn set_many<I>(&self, db: &mut <Q as QueryDb<'_>>::DynDb, values: I, durability: Durability)
+    where
+        I: IntoIterator<Item = (Q::Key, Q::Value)>,
+    {
+        // Filter out unchanged values eagerly: `is_unchanged` needs to
+        // read `self.slots`, which is locked while the values are stored.
+        let values: Vec<_> = values
+            .into_iter()
+            .filter(|(key, value)| !self.is_unchanged(key, value, durability))
+            .collect();
+        if values.is_empty() {
+            return;
+        }
+        for (key, _) in &values {
+            self.assert_mortal(key);
+        }
+
+        // As in `set`, but all the values share one new revision. The
+        // durabilities that must be marked as changed are those of
+        // every replaced value, so we report the highest of them.
+        let mut values = Some(values);
+        let mut database_key_indices = vec![];
+        db.salsa_runtime_mut()
+            .with_incremented_revision(&mut |next_revision| {
+                let mut slots = self.slots.write();
+
+                let mut changed_durability = None;
+                for (key, value) in values.take().unwrap() {
+                    log::debug!(
+                        "{:?}({:?}) = {:?} ({:?})",
+                        Q::default(),
+                        key,
+                        value,
+                        durability
+                    );
+                    let stamped_value = StampedValue {
+                        value,
+                        durability,
+                        changed_at: next_revision,
+                    };
+                    let old_durability = self.insert_slot(&mut slots, &key, stamped_value);
+                    changed_durability = std::cmp::max(changed_durability, old_durability);
+                    database_key_indices.push(slots[&key].database_key_index);
                 }
+                changed_durability
+            });
+        for database_key_index in database_key_indices {
+            self.report_set(db, database_key_index, durability);
+        }
+    }
+
+    fn has_value(
+        &self,
+        key: &Q::Key,
+        durability: Durability,
+        matches: &mut dyn FnMut(&Q::Value) -> bool,
+    ) -> bool {
+        match self.slot_with_value(key) {
+            Some(slot) => {
+                let stamped_value = slot.stamped_value.read();
+                stamped_value.durability == durability
+                    && matches(stamped_value.value.as_ref().unwrap())
+            }
+            None => false,
+        }
+    }
+
+    fn remove(&self, db: &mut <Q as QueryDb<'_>>::DynDb, key: &Q::Key) -> Option<Q::Value> {
+        let slot = self.slot_with_value(key)?;
+        self.assert_mortal(key);
+        log::debug!("{:?}({:?}) removed", Q::default(), key);
+
+        // As in `set`, queries that read the value must be re-executed,
+        // so the removal needs a new revision.
+        let mut old_value = None;
+        db.salsa_runtime_mut()
+            .with_incremented_revision(&mut |next_revision| {
+                let mut stamped_value = slot.stamped_value.write();
+                stamped_value.changed_at = next_revision;
+                old_value = stamped_value.value.take();
+                old_value.as_ref().map(|_| stamped_value.durability)
             });
+        old_value
+    }
+
+    fn fetch_if_set(&self, db: &<Q as QueryDb<'_>>::DynDb, key: &Q::Key) -> Option<Q::Value> {
+        if let Some(slot) = self.slot_or_default(db, key) {
+            let StampedValue {
+                value,
+                durability,
+                changed_at,
+            } = slot.stamped_value.read().clone();
+            if !Q::IMMORTAL {
+                db.salsa_runtime().report_query_read(
+                    slot.database_key_index,
+                    durability,
+                    changed_at,
+                );
+            }
+            return value;
+        }
+
+        // There is no value, but the reader must still depend on `key`,
+        // so that it is re-executed when `key` is set; that needs a
+        // slot. As in `slot_or_default`, creating the slot does not
+        // require a new revision. Setting a value always starts a new
+        // revision, which invalidates `LOW` durability reads.
+        let mut slots = self.slots.write();
+        let entry = slots.entry(key.clone());
+        let key_index = u32::try_from(entry.index()).unwrap();
+        let slot = entry.or_insert_with(|| {
+            Arc::new(Slot {
+                key: key.clone(),
+                database_key_index: DatabaseKeyIndex {
+                    group_index: self.group_index,
+                    query_index: Q::QUERY_INDEX,
+                    key_index,
+                },
+                stamped_value: RwLock::new(StampedValue {
+                    value: None,
+                    durability: Durability::LOW,
+                    changed_at: db.salsa_runtime().current_revision(),
+                }),
+            })
+        });
+        let stamped_value = slot.stamped_value.read();
+        db.salsa_runtime().report_query_read(
+            slot.database_key_index,
+            Durability::LOW,
+            stamped_value.changed_at,
+        );
+        stamped_value.value.clone()
+    }
+
+    fn take_recorded(&self) -> Vec<RecordedValue<Q>> {
+        std::mem::take(&mut *self.recorded.lock())
+    }
+}
+
+impl<Q> InputStorage<Q>
+where
+    Q: Query,
+{
+    /// Reports the `DidSetInput` event, once the new revision started,
+    /// and records the value if the runtime is recording.
+    fn report_set(
+        &self,
+        db: &<Q as QueryDb<'_>>::DynDb,
+        database_key_index: DatabaseKeyIndex,
+        durability: Durability,
+    ) {
+        let runtime = db.salsa_runtime();
+        if runtime.is_recording() {
+            let slots = self.slots.read();
+            let (key, slot) = slots
+                .get_index(database_key_index.key_index as usize)
+                .unwrap();
+            let value = slot.stamped_value.read().value.clone().unwrap();
+            self.recorded
+                .lock()
+                .push((key.clone(), value, durability, runtime.current_revision()));
+        }
+        runtime.report_event(
+            db,
+            Event {
+                runtime_id: runtime.id(),
+                kind: EventKind::DidSetInput {
+                    database_key: database_key_index,
+                    durability,
+                    revision: runtime.current_revision(),
+                },
+            },
+        );
+    }
+
+    /// Stores `stamped_value` for `key`, returning the durability of
+    /// the value it replaced (if any).
+    fn insert_slot(
+        &self,
+        slots: &mut FxIndexMap<Q::Key, Arc<Slot<Q>>>,
+        key: &Q::Key,
+        stamped_value: StampedValue<Q::Value>,
+    ) -> Option<Durability> {
+        let StampedValue {
+            value,
+            durability,
+            changed_at,
+        } = stamped_value;
+        let stamped_value = StampedValue {
+            value: Some(value),
+            durability,
+            changed_at,
+        };
+        match slots.entry(key.clone()) {
+            Entry::Occupied(entry) => {
+                let mut slot_stamped_value = entry.get().stamped_value.write();
+                let old_durability = slot_stamped_value
+                    .value
+                    .as_ref()
+                    .map(|_| slot_stamped_value.durability);
+                *slot_stamped_value = stamped_value;
+                old_durability
+            }
+
+            Entry::Vacant(entry) => {
+                let key_index = u32::try_from(entry.index()).unwrap();
+                let database_key_index = DatabaseKeyIndex {
+                    group_index: self.group_index,
+                    query_index: Q::QUERY_INDEX,
+                    key_index,
+                };
+                entry.insert(Arc::new(Slot {
+                    key: key.clone(),
+                    database_key_index,
+                    stamped_value: RwLock::new(stamped_value),
+                }));
+                None
+            }
+        }
     }
 }
 
@@ -279,3 +685,42 @@ where
         write!(fmt, "{:?}({:?})", Q::default(), self.key)
     }
 }
+
+/// The error returned by `Database::set_input_by_name`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SetInputError {
+    /// The database has no input query with the given name.
+    NoSuchInput {
+        /// The name that was given.
+        name: String,
+    },
+
+    /// The key was not of the input's key type (for inputs with several
+    /// keys, the tuple of them).
+    WrongKeyType {
+        /// The name of the input.
+        input: &'static str,
+    },
+
+    /// The value was not of the input's value type.
+    WrongValueType {
+        /// The name of the input.
+        input: &'static str,
+    },
+}
+
+impl std::fmt::Display for SetInputError {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SetInputError::NoSuchInput { name } => write!(fmt, "no input named `{}`", name),
+            SetInputError::WrongKeyType { input } => {
+                write!(fmt, "wrong key type for input `{}`", input)
+            }
+            SetInputError::WrongValueType { input } => {
+                write!(fmt, "wrong value type for input `{}`", input)
+            }
+        }
+    }
+}
+
+impl std::error::Error for SetInputError {}
@@ -1,5 +1,5 @@
-use std::fmt;
-use std::num::NonZeroU32;
+use core::fmt;
+use core::num::NonZeroU32;
 
 /// The "raw-id" is used for interned keys in salsa -- it is basically
 /// a newtype'd u32. Typically, it is wrapped in a type of your own
@@ -45,6 +45,55 @@ use std::num::NonZeroU32;
 /// InternId::from(InternId::MAX);
 /// ```
 ///
+/// If the integer comes from an untrusted source (e.g., a serialized
+/// cache), use `try_from_u32` or `try_from_usize` instead, which
+/// return an error rather than panicking:
+///
+/// ```
+/// # use salsa::InternId;
+/// assert!(InternId::try_from_u32(22).is_ok());
+/// assert!(InternId::try_from_u32(InternId::MAX).is_err());
+/// ```
+///
+/// # Packing ids
+///
+/// The value of an `InternId` always fits in a `u32`, so two of them can
+/// be packed into a `u64` without loss. Values from `InternId::MAX` up
+/// to `u32::MAX` are never used by an id, so they are free for use as
+/// sentinels in such a packed representation (for example, to mean "no
+/// id"):
+///
+/// ```
+/// # use salsa::InternId;
+/// fn pack(a: InternId, b: Option<InternId>) -> u64 {
+///     let b = b.map_or(InternId::MAX, InternId::as_u32);
+///     (u64::from(a.as_u32()) << 32) | u64::from(b)
+/// }
+///
+/// fn unpack(packed: u64) -> (InternId, Option<InternId>) {
+///     let a = InternId::from((packed >> 32) as u32);
+///     let b = InternId::try_from_u32(packed as u32).ok();
+///     (a, b)
+/// }
+///
+/// let a = InternId::from(22_u32);
+/// let b = InternId::max_value();
+/// assert_eq!(unpack(pack(a, Some(b))), (a, Some(b)));
+/// assert_eq!(unpack(pack(a, None)), (a, None));
+/// ```
+///
+/// The layout itself is fixed: reserving the values above `MAX` (rather
+/// than, say, a number of high bits chosen by the user) is what lets
+/// `Option<InternId>` fit in a single `u32`, and only costs a few
+/// hundred of the four billion possible ids.
+///
+/// # Serialization
+///
+/// With the `serde` feature enabled, `InternId` implements `Serialize`
+/// and `Deserialize`, using the same `u32` value as `as_u32`.
+/// Deserializing a value that is not less than `InternId::MAX` yields
+/// an error.
+///
 /// [rfc]: https://github.com/salsa-rs/salsa-rfcs/pull/2
 #[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct InternId {
@@ -65,6 +114,194 @@ impl InternId {
         }
     }
 
+    /// Creates a new InternId from `value`, returning an error if
+    /// `value` is not less than `MAX`. (This is not a `TryFrom` impl, as
+    /// that would conflict with the blanket one implied by `From<u32>`.)
+    ///
+    /// ```
+    /// # use salsa::InternId;
+    /// let intern_id = InternId::try_from_u32(22).unwrap();
+    /// assert_eq!(intern_id.as_u32(), 22);
+    /// ```
+    pub fn try_from_u32(value: u32) -> Result<InternId, InternIdOutOfRange> {
+        if value < InternId::MAX {
+            Ok(unsafe { InternId::new_unchecked(value) })
+        } else {
+            Err(InternIdOutOfRange {
+                value: value as usize,
+            })
+        }
+    }
+
+    /// Creates a new InternId from `value`, returning an error if
+    /// `value` is not less than `MAX`.
+    ///
+    /// ```
+    /// # use salsa::InternId;
+    /// let intern_id = InternId::try_from_usize(22).unwrap();
+    /// assert_eq!(intern_id.as_usize(), 22);
+    /// ```
+    pub fn try_from_usize(value: usize) -> Result<InternId, InternIdOutOfRange> {
+        if value < (InternId::MAX as usize) {
+            Ok(unsafe { InternId::new_unchecked(value as u32) })
+        } else {
+            Err(InternIdOutOfRange { value })
+        }
+    }
+
+    /// Creates an `InternId` from the representation returned by
+    /// `as_non_zero`, returning an error if that is not the
+    /// representation of a legal id (that is, if `value - 1` is not
+    /// less than `MAX`).
+    ///
+    /// ```
+    /// # use salsa::InternId;
+    /// # use std::num::NonZeroU32;
+    /// let intern_id = InternId::from(22_u32);
+    /// assert_eq!(InternId::try_from_non_zero(intern_id.as_non_zero()), Ok(intern_id));
+    ///
+    /// let too_large = NonZeroU32::new(u32::MAX).unwrap();
+    /// assert!(InternId::try_from_non_zero(too_large).is_err());
+    /// ```
+    pub fn try_from_non_zero(value: NonZeroU32) -> Result<InternId, InternIdOutOfRange> {
+        if value.get() <= InternId::MAX {
+            Ok(InternId { value })
+        } else {
+            Err(InternIdOutOfRange {
+                value: value.get() as usize - 1,
+            })
+        }
+    }
+
+    /// Creates an `InternId` from the representation returned by
+    /// `as_non_zero`, like `try_from_non_zero`.
+    ///
+    /// # Panics
+    ///
+    /// If `value - 1` is not less than `MAX`.
+    ///
+    /// ```
+    /// # use salsa::InternId;
+    /// let intern_id = InternId::from(22_u32);
+    /// assert_eq!(InternId::from_non_zero(intern_id.as_non_zero()), intern_id);
+    /// ```
+    pub fn from_non_zero(value: NonZeroU32) -> InternId {
+        InternId::try_from_non_zero(value).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Returns the largest legal `InternId`, whose value is `MAX - 1`.
+    ///
+    /// ```
+    /// # use salsa::InternId;
+    /// assert_eq!(InternId::max_value().as_u32(), InternId::MAX - 1);
+    /// ```
+    pub fn max_value() -> InternId {
+        unsafe { InternId::new_unchecked(InternId::MAX - 1) }
+    }
+
+    /// Returns the `InternId` following this one, or `None` if this is
+    /// already `InternId::max_value()`.
+    ///
+    /// ```
+    /// # use salsa::InternId;
+    /// let intern_id = InternId::from(22_u32);
+    /// assert_eq!(intern_id.next(), Some(InternId::from(23_u32)));
+    /// assert_eq!(InternId::max_value().next(), None);
+    /// ```
+    pub fn next(self) -> Option<InternId> {
+        InternId::try_from_u32(self.as_u32() + 1).ok()
+    }
+
+    /// Returns the id `n` places after this one, or `None` if that is
+    /// not a legal id (that is, not less than `MAX`).
+    ///
+    /// ```
+    /// # use salsa::InternId;
+    /// let intern_id = InternId::from(22_u32);
+    /// assert_eq!(intern_id.checked_add(3), Some(InternId::from(25_u32)));
+    /// assert_eq!(intern_id.checked_add(u32::MAX), None);
+    /// assert_eq!(InternId::max_value().checked_add(1), None);
+    /// ```
+    pub fn checked_add(self, n: u32) -> Option<InternId> {
+        let value = self.as_u32().checked_add(n)?;
+        InternId::try_from_u32(value).ok()
+    }
+
+    /// Returns the id `delta` places after (or, if `delta` is negative,
+    /// before) this one, or `None` if that would be below zero or not
+    /// less than `MAX`.
+    ///
+    /// ```
+    /// # use salsa::InternId;
+    /// let intern_id = InternId::from(22_u32);
+    /// assert_eq!(intern_id.offset(-2), Some(InternId::from(20_u32)));
+    /// assert_eq!(intern_id.offset(2), Some(InternId::from(24_u32)));
+    /// assert_eq!(intern_id.offset(-23), None);
+    /// assert_eq!(intern_id.offset(i64::from(InternId::MAX)), None);
+    /// ```
+    pub fn offset(self, delta: i64) -> Option<InternId> {
+        let value = i64::from(self.as_u32()) + delta;
+        if value < 0 || value >= i64::from(InternId::MAX) {
+            None
+        } else {
+            Some(unsafe { InternId::new_unchecked(value as u32) })
+        }
+    }
+
+    /// Returns the number of ids between this one and `other`, in
+    /// either order.
+    ///
+    /// ```
+    /// # use salsa::InternId;
+    /// let a = InternId::from(22_u32);
+    /// let b = InternId::from(25_u32);
+    /// assert_eq!(a.distance(b), 3);
+    /// assert_eq!(b.distance(a), 3);
+    /// assert_eq!(a.distance(a), 0);
+    /// ```
+    pub fn distance(self, other: InternId) -> u32 {
+        let (low, high) = if self <= other {
+            (self, other)
+        } else {
+            (other, self)
+        };
+        high.as_u32() - low.as_u32()
+    }
+
+    /// Iterates over the ids in `start..end`. Values not less than
+    /// `MAX` are not legal ids, so iteration stops there even if `end`
+    /// is larger.
+    ///
+    /// ```
+    /// # use salsa::InternId;
+    /// let ids: Vec<u32> = InternId::range(3, 6).map(InternId::as_u32).collect();
+    /// assert_eq!(ids, vec![3, 4, 5]);
+    ///
+    /// let ids: Vec<u32> = InternId::range(InternId::MAX - 2, u32::MAX)
+    ///     .map(InternId::as_u32)
+    ///     .collect();
+    /// assert_eq!(ids, vec![InternId::MAX - 2, InternId::MAX - 1]);
+    /// ```
+    pub fn range(start: u32, end: u32) -> impl Iterator<Item = InternId> {
+        let end = core::cmp::min(end, InternId::MAX);
+        (start..end).map(|value| unsafe { InternId::new_unchecked(value) })
+    }
+
+    /// Returns the `NonZeroU32` that this id is stored as, which is its
+    /// value plus one. Storing that (rather than the `u32` value) keeps
+    /// the niche that lets `Option<InternId>` fit in a `u32`, for types
+    /// (or other APIs) that work with `NonZeroU32`s rather than with
+    /// `InternId`s. Convert it back with `from_non_zero` (or
+    /// `try_from_non_zero`).
+    ///
+    /// ```
+    /// # use salsa::InternId;
+    /// assert_eq!(InternId::from(22_u32).as_non_zero().get(), 23);
+    /// ```
+    pub fn as_non_zero(self) -> NonZeroU32 {
+        self.value
+    }
+
     /// Convert this raw-id into a u32 value.
     ///
     /// ```
@@ -104,15 +341,34 @@ impl From<InternId> for usize {
 
 impl From<u32> for InternId {
     fn from(id: u32) -> InternId {
-        assert!(id < InternId::MAX);
-        unsafe { InternId::new_unchecked(id) }
+        InternId::try_from_u32(id).unwrap_or_else(|err| panic!("{}", err))
     }
 }
 
 impl From<usize> for InternId {
     fn from(id: usize) -> InternId {
-        assert!(id < (InternId::MAX as usize));
-        unsafe { InternId::new_unchecked(id as u32) }
+        InternId::try_from_usize(id).unwrap_or_else(|err| panic!("{}", err))
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for InternId {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_u32(self.as_u32())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for InternId {
+    fn deserialize<D>(deserializer: D) -> Result<InternId, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let value = u32::deserialize(deserializer)?;
+        InternId::try_from_u32(value).map_err(serde::de::Error::custom)
     }
 }
 
@@ -127,3 +383,113 @@ impl fmt::Display for InternId {
         self.as_usize().fmt(f)
     }
 }
+
+/// The error returned by `InternId::try_from_u32` and
+/// `InternId::try_from_usize` when the value is not less than
+/// `InternId::MAX`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct InternIdOutOfRange {
+    value: usize,
+}
+
+impl InternIdOutOfRange {
+    /// The value that could not be converted into an `InternId`.
+    pub fn value(self) -> usize {
+        self.value
+    }
+}
+
+impl fmt::Display for InternIdOutOfRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "intern id {} is out of range (must be less than {})",
+            self.value,
+            InternId::MAX
+        )
+    }
+}
+
+impl std::error::Error for InternIdOutOfRange {}
+
+/// Declares a newtype around `InternId` suitable for use as the key
+/// of a `#[salsa::interned]` query. This generates the struct, an
+/// `InternKey` impl, `From` conversions to and from `InternId`, and
+/// `Debug`/`Display` impls.
+///
+/// ```
+/// salsa::intern_id!(
+///     /// An interned file name.
+///     pub struct FileId;
+/// );
+///
+/// let file_id = FileId::from(salsa::InternId::from(22_u32));
+/// assert_eq!(format!("{:?}", file_id), "22");
+/// assert_eq!(format!("{}", file_id), "22");
+/// ```
+///
+/// By default, `Debug` prints just the underlying integer. A prefix can
+/// be given with `debug = "..."`:
+///
+/// ```
+/// salsa::intern_id!(pub struct FileId, debug = "File");
+///
+/// let file_id = FileId::from(salsa::InternId::from(22_u32));
+/// assert_eq!(format!("{:?}", file_id), "File(22)");
+/// ```
+#[macro_export]
+macro_rules! intern_id {
+    ($(#[$attr:meta])* $vis:vis struct $name:ident $(;)?) => {
+        $crate::intern_id!(@struct $(#[$attr])* $vis $name);
+
+        impl ::core::fmt::Debug for $name {
+            fn fmt(&self, f: &mut ::core::fmt::Formatter<'_>) -> ::core::fmt::Result {
+                ::core::fmt::Debug::fmt(&self.0, f)
+            }
+        }
+    };
+
+    ($(#[$attr:meta])* $vis:vis struct $name:ident, debug = $prefix:expr $(;)?) => {
+        $crate::intern_id!(@struct $(#[$attr])* $vis $name);
+
+        impl ::core::fmt::Debug for $name {
+            fn fmt(&self, f: &mut ::core::fmt::Formatter<'_>) -> ::core::fmt::Result {
+                write!(f, "{}({:?})", $prefix, self.0)
+            }
+        }
+    };
+
+    (@struct $(#[$attr:meta])* $vis:vis $name:ident) => {
+        $(#[$attr])*
+        #[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+        $vis struct $name($crate::InternId);
+
+        impl $crate::InternKey for $name {
+            fn from_intern_id(v: $crate::InternId) -> Self {
+                $name(v)
+            }
+
+            fn as_intern_id(&self) -> $crate::InternId {
+                self.0
+            }
+        }
+
+        impl ::core::convert::From<$crate::InternId> for $name {
+            fn from(v: $crate::InternId) -> Self {
+                $name(v)
+            }
+        }
+
+        impl ::core::convert::From<$name> for $crate::InternId {
+            fn from(v: $name) -> $crate::InternId {
+                v.0
+            }
+        }
+
+        impl ::core::fmt::Display for $name {
+            fn fmt(&self, f: &mut ::core::fmt::Formatter<'_>) -> ::core::fmt::Result {
+                ::core::fmt::Display::fmt(&self.0, f)
+            }
+        }
+    };
+}
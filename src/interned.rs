@@ -1,7 +1,14 @@
+use crate::debug::entry_size;
+use crate::debug::InternStats;
+use crate::debug::MemoEntry;
 use crate::debug::TableEntry;
 use crate::durability::Durability;
 use crate::intern_id::InternId;
+use crate::plumbing::AccumulatedValues;
 use crate::plumbing::HasQueryGroup;
+use crate::plumbing::InternedQuery;
+use crate::plumbing::InternedQueryStorageOps;
+use crate::plumbing::LookupInternedQueryStorageOps;
 use crate::plumbing::QueryStorageMassOps;
 use crate::plumbing::QueryStorageOps;
 use crate::revision::Revision;
@@ -10,14 +17,85 @@ use crate::{CycleError, Database, DatabaseKeyIndex, DiscardIf, QueryDb, Runtime,
 use crossbeam_utils::atomic::AtomicCell;
 use parking_lot::RwLock;
 use rustc_hash::FxHashMap;
+use std::borrow::Borrow;
 use std::collections::hash_map::Entry;
+use std::collections::HashMap;
 use std::convert::From;
 use std::fmt::Debug;
 use std::hash::Hash;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 
 const INTERN_DURABILITY: Durability = Durability::HIGH;
 
+/// In the ids of generational tables (see `InternedQuery::GENERATIONAL`),
+/// the bits below `GENERATION_SHIFT` hold the index of the value, and
+/// the bits above it the generation of that index.
+const GENERATION_SHIFT: u32 = 24;
+const INDEX_MASK: u32 = (1 << GENERATION_SHIFT) - 1;
+
+/// Generations wrap around at this count, which keeps every id below
+/// `InternId::MAX`.
+const GENERATIONS: u32 = 0xFF;
+
+/// The error returned by `QueryTable::intern_with_id`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum InternWithIdError {
+    /// Another value is interned with the requested id.
+    IdTaken {
+        /// The requested id.
+        id: InternId,
+    },
+
+    /// The value is already interned, with a different id.
+    AlreadyInterned {
+        /// The id of the value.
+        id: InternId,
+    },
+
+    /// The table is full (see `QueryTableMut::set_intern_capacity`).
+    CapacityExceeded(InternCapacityExceeded),
+}
+
+impl std::fmt::Display for InternWithIdError {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            InternWithIdError::IdTaken { id } => {
+                write!(fmt, "id {:?} is taken by another value", id)
+            }
+            InternWithIdError::AlreadyInterned { id } => {
+                write!(fmt, "value is already interned with id {:?}", id)
+            }
+            InternWithIdError::CapacityExceeded(err) => write!(fmt, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for InternWithIdError {}
+
+/// The error returned when interning a new value would exceed the
+/// capacity of an interned query (see `QueryTableMut::set_intern_capacity`).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InternCapacityExceeded {
+    /// The name of the interned query.
+    pub query: &'static str,
+
+    /// Its capacity.
+    pub capacity: usize,
+}
+
+impl std::fmt::Display for InternCapacityExceeded {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            fmt,
+            "`{}` already holds its capacity of {} interned values",
+            self.query, self.capacity
+        )
+    }
+}
+
+impl std::error::Error for InternCapacityExceeded {}
+
 /// Handles storage where the value is 'derived' by executing a
 /// function (in contrast to "inputs").
 pub struct InternedStorage<Q>
@@ -26,7 +104,16 @@ where
     Q::Value: InternKey,
 {
     group_index: u16,
+
+    /// Looking up a value that is already interned (in either
+    /// direction) only takes the read lock, so concurrent readers do
+    /// not block one another; the write lock is only taken to insert
+    /// new values (and to collect them).
     tables: RwLock<InternTables<Q::Key>>,
+
+    /// Set with `QueryTableMut::set_intern_capacity`; `usize::MAX` if
+    /// there is no limit.
+    capacity: AtomicUsize,
 }
 
 /// Storage for the looking up interned things.
@@ -50,11 +137,47 @@ struct InternTables<K> {
 
     /// Index of the first free intern-index, if any.
     first_free: Option<InternId>,
+
+    /// Sequence number to give the next interned value.
+    next_sequence: u64,
+
+    /// Whether ids carry a generation (see `InternedQuery::GENERATIONAL`).
+    generational: bool,
 }
 
 /// Trait implemented for the "key" that results from a
 /// `#[salsa::intern]` query.  This is basically meant to be a
 /// "newtype"'d `u32`.
+///
+/// # Garbage collection
+///
+/// Interned values take part in sweeping (see `Database::sweep_all`).
+/// A sweep can collect any interned value that has not been accessed
+/// since the last change to an input of `Durability::HIGH` (for
+/// example, after `Runtime::synthetic_write(Durability::HIGH)`).
+/// Values accessed in the current revision are never collected. The
+/// index of a collected value is placed on a free list and will be
+/// handed out again, so re-interning an evicted value may give it a
+/// fresh key.
+///
+/// **Warning:** keys for collected values become dangling. Salsa
+/// cannot tell when such a key is stored outside the database (e.g.,
+/// in a side table of your own). Looking one up may panic, or -- if its
+/// index has since been reused -- silently return a different value.
+/// Only sweep interned queries when you know that every key you hold
+/// was accessed again since the last `HIGH` durability change.
+///
+/// Queries declared with `#[salsa::interned(generational)]` close the
+/// second gap: their ids pack a generation (in the top 8 bits) that
+/// changes whenever an index is reused, so looking up a dangling key
+/// always panics (or gives `None`, with `try_lookup_foo`). Such a
+/// query can intern at most 2^24 values at a time, and since the
+/// generation wraps around after 255 reuses of an index, a key that
+/// is held through that many reuses is not detected.
+///
+/// The two methods must be inverses: `from_intern_id(v).as_intern_id()`
+/// must return `v`. Debug builds check this whenever a value is
+/// interned.
 pub trait InternKey {
     /// Create an instance of the intern-key from a `u32` value.
     fn from_intern_id(v: InternId) -> Self;
@@ -93,6 +216,10 @@ struct Slot<K> {
     /// Value that was interned.
     value: K,
 
+    /// Counts the values interned before this one (in the same
+    /// table), including ones that have since been collected.
+    sequence: u64,
+
     /// When was this intern'd?
     ///
     /// (This informs the "changed-at" result)
@@ -111,6 +238,28 @@ struct Slot<K> {
     accessed_at: AtomicCell<Option<Revision>>,
 }
 
+/// A value that was interned, borrowed from its interning table, as
+/// returned by the `lookup_foo_ref` methods. It dereferences to the
+/// value, and keeps it alive even if it is collected (by a sweep)
+/// while the `InternedRef` is held.
+pub struct InternedRef<K> {
+    slot: Arc<Slot<K>>,
+}
+
+impl<K> std::ops::Deref for InternedRef<K> {
+    type Target = K;
+
+    fn deref(&self) -> &K {
+        &self.slot.value
+    }
+}
+
+impl<K: Debug> Debug for InternedRef<K> {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        Debug::fmt(&self.slot.value, fmt)
+    }
+}
+
 impl<Q> std::panic::RefUnwindSafe for InternedStorage<Q>
 where
     Q: Query,
@@ -125,7 +274,11 @@ impl<K: Debug + Hash + Eq> InternTables<K> {
     ///
     /// The slot will have its "accessed at" field updated to its current revision,
     /// ensuring that it cannot be GC'd until the current queries complete.
-    fn slot_for_key(&self, key: &K, revision_now: Revision) -> Option<Arc<Slot<K>>> {
+    fn slot_for_key<B>(&self, key: &B, revision_now: Revision) -> Option<Arc<Slot<K>>>
+    where
+        K: Borrow<B>,
+        B: ?Sized + Hash + Eq,
+    {
         let index = self.map.get(key)?;
         Some(self.slot_for_index(*index, revision_now))
     }
@@ -135,8 +288,17 @@ impl<K: Debug + Hash + Eq> InternTables<K> {
     /// The slot will have its "accessed at" field updated to its current revision,
     /// ensuring that it cannot be GC'd until the current queries complete.
     fn slot_for_index(&self, index: InternId, revision_now: Revision) -> Arc<Slot<K>> {
-        match &self.values[index.as_usize()] {
-            InternValue::Present { slot } => {
+        match self.try_slot_for_index(index, revision_now) {
+            Some(slot) => slot,
+            None => panic!("index {:?} is free but should not be", index),
+        }
+    }
+
+    /// Like `slot_for_index`, but returns `None` if `index` is free or
+    /// was never handed out.
+    fn try_slot_for_index(&self, index: InternId, revision_now: Revision) -> Option<Arc<Slot<K>>> {
+        match self.values.get(self.position(index))? {
+            InternValue::Present { slot } if slot.index == index => {
                 // Subtle: we must update the "accessed at" to the
                 // current revision *while the lock is held* to
                 // prevent this slot from being GC'd.
@@ -146,28 +308,105 @@ impl<K: Debug + Hash + Eq> InternTables<K> {
                     "failed to update slot {:?} while holding read lock",
                     slot
                 );
-                slot.clone()
-            }
-            InternValue::Free { .. } => {
-                panic!("index {:?} is free but should not be", index);
+                Some(slot.clone())
             }
+
+            // Either the value was freed, or the index now holds a
+            // value of a later generation.
+            InternValue::Present { .. } | InternValue::Free { .. } => None,
         }
     }
 }
 
-impl<K> Default for InternTables<K>
+impl<K> InternTables<K>
 where
     K: Eq + Hash,
 {
-    fn default() -> Self {
+    fn new(generational: bool) -> Self {
         Self {
             map: Default::default(),
             values: Default::default(),
             first_free: Default::default(),
+            next_sequence: 0,
+            generational,
+        }
+    }
+
+    /// Returns the position of the value with id `index` in `values`.
+    fn position(&self, index: InternId) -> usize {
+        position_of(index, self.generational)
+    }
+
+    /// Takes the free `position` out of the free list, first adding
+    /// free positions up to it if `values` is not that long yet.
+    fn take_free(&mut self, position: usize) {
+        while self.values.len() <= position {
+            let index = fresh_id(self.values.len(), self.generational);
+            self.values.push(InternValue::Free {
+                next: self.first_free,
+            });
+            self.first_free = Some(index);
+        }
+
+        let after = match &self.values[position] {
+            InternValue::Free { next } => *next,
+            InternValue::Present { slot } => {
+                panic!("index {:?} was supposed to be free", slot.index)
+            }
+        };
+        let generational = self.generational;
+        let mut link = &mut self.first_free;
+        loop {
+            match *link {
+                Some(index) if position_of(index, generational) == position => {
+                    *link = after;
+                    return;
+                }
+                Some(index) => match &mut self.values[position_of(index, generational)] {
+                    InternValue::Free { next } => link = next,
+                    InternValue::Present { slot } => {
+                        panic!("index {:?} was supposed to be free", slot.index)
+                    }
+                },
+                None => panic!("position {} is free but not in the free list", position),
+            }
         }
     }
 }
 
+/// Like `InternTables::position`, for when the tables are borrowed
+/// field by field.
+fn position_of(index: InternId, generational: bool) -> usize {
+    if generational {
+        (index.as_u32() & INDEX_MASK) as usize
+    } else {
+        index.as_usize()
+    }
+}
+
+/// Returns the id to give a value stored at `position`, which was
+/// never used before.
+fn fresh_id(position: usize, generational: bool) -> InternId {
+    if generational {
+        assert!(
+            position <= INDEX_MASK as usize,
+            "too many values interned at once for a generational query"
+        );
+    }
+    InternId::from(position)
+}
+
+/// Returns the id to give a value that reuses the position of `freed`,
+/// the id of a value that was collected.
+fn reused_id(freed: InternId, generational: bool) -> InternId {
+    if generational {
+        let generation = ((freed.as_u32() >> GENERATION_SHIFT) + 1) % GENERATIONS;
+        InternId::from((generation << GENERATION_SHIFT) | (freed.as_u32() & INDEX_MASK))
+    } else {
+        freed
+    }
+}
+
 impl<Q> InternedStorage<Q>
 where
     Q: Query,
@@ -179,17 +418,52 @@ where
     /// In either case, the `accessed_at` field of the slot is updated
     /// to the current revision, ensuring that the slot cannot be GC'd
     /// while the current queries execute.
-    fn intern_index(&self, db: &<Q as QueryDb<'_>>::DynDb, key: &Q::Key) -> Arc<Slot<Q::Key>> {
+    ///
+    /// The key is only converted into an owned key
+    /// (`key.to_owned().into()`) if it is not interned yet.
+    fn intern_index<B>(
+        &self,
+        db: &<Q as QueryDb<'_>>::DynDb,
+        key: &B,
+    ) -> Result<Arc<Slot<Q::Key>>, InternCapacityExceeded>
+    where
+        Q::Key: Borrow<B>,
+        B: ?Sized + Hash + Eq + ToOwned,
+        B::Owned: Into<Q::Key>,
+    {
         if let Some(i) = self.intern_check(db, key) {
-            return i;
+            return Ok(i);
         }
 
-        let owned_key1 = key.to_owned();
-        let owned_key2 = owned_key1.clone();
+        let owned_key: Q::Key = key.to_owned().into();
         let revision_now = db.salsa_runtime().current_revision();
+        self.insert(&mut self.tables.write(), owned_key, revision_now)
+    }
 
-        let mut tables = self.tables.write();
-        let tables = &mut *tables;
+    /// Returns an error if the table holds as many values as its
+    /// capacity allows.
+    fn check_capacity(&self, tables: &InternTables<Q::Key>) -> Result<(), InternCapacityExceeded> {
+        let capacity = self.capacity.load(Ordering::Relaxed);
+        if tables.map.len() >= capacity {
+            return Err(InternCapacityExceeded {
+                query: Q::QUERY_NAME,
+                capacity,
+            });
+        }
+        Ok(())
+    }
+
+    /// Interns `owned_key` in `tables`, which the caller has locked for
+    /// writing, returning the existing slot if somebody interned it
+    /// while the caller was waiting for the lock.
+    fn insert(
+        &self,
+        tables: &mut InternTables<Q::Key>,
+        owned_key1: Q::Key,
+        revision_now: Revision,
+    ) -> Result<Arc<Slot<Q::Key>>, InternCapacityExceeded> {
+        let owned_key2 = owned_key1.clone();
+        let full = self.check_capacity(tables);
         let entry = match tables.map.entry(owned_key1) {
             Entry::Vacant(entry) => entry,
             Entry::Occupied(entry) => {
@@ -198,20 +472,26 @@ where
                 // update the `accessed_at` field because they should
                 // have already done so!
                 let index = *entry.get();
-                match &tables.values[index.as_usize()] {
+                match &tables.values[tables.position(index)] {
                     InternValue::Present { slot } => {
                         debug_assert_eq!(owned_key2, slot.value);
                         debug_assert_eq!(slot.accessed_at.load(), Some(revision_now));
-                        return slot.clone();
+                        return Ok(slot.clone());
                     }
 
                     InternValue::Free { .. } => {
-                        panic!("key {:?} should be present but is not", key,);
+                        panic!("key {:?} should be present but is not", owned_key2);
                     }
                 }
             }
         };
 
+        full?;
+
+        let sequence = tables.next_sequence;
+        tables.next_sequence += 1;
+        let generational = tables.generational;
+
         let create_slot = |index: InternId| {
             let database_key_index = DatabaseKeyIndex {
                 group_index: self.group_index,
@@ -222,6 +502,7 @@ where
                 index,
                 database_key_index,
                 value: owned_key2,
+                sequence,
                 interned_at: revision_now,
                 accessed_at: AtomicCell::new(Some(revision_now)),
             })
@@ -230,7 +511,7 @@ where
         let (slot, index);
         match tables.first_free {
             None => {
-                index = InternId::from(tables.values.len());
+                index = fresh_id(tables.values.len(), generational);
                 slot = create_slot(index);
                 tables
                     .values
@@ -238,10 +519,11 @@ where
             }
 
             Some(i) => {
-                index = i;
+                index = reused_id(i, generational);
                 slot = create_slot(index);
 
-                let next_free = match &tables.values[i.as_usize()] {
+                let position = position_of(i, generational);
+                let next_free = match &tables.values[position] {
                     InternValue::Free { next } => *next,
                     InternValue::Present { slot } => {
                         panic!(
@@ -251,21 +533,41 @@ where
                     }
                 };
 
-                tables.values[index.as_usize()] = InternValue::Present { slot: slot.clone() };
+                tables.values[position] = InternValue::Present { slot: slot.clone() };
                 tables.first_free = next_free;
             }
         }
 
         entry.insert(index);
 
-        slot
+        Ok(slot)
     }
 
-    fn intern_check(
-        &self,
-        db: &<Q as QueryDb<'_>>::DynDb,
-        key: &Q::Key,
-    ) -> Option<Arc<Slot<Q::Key>>> {
+    /// Reports a read of the interned value in `slot` and returns its
+    /// key (the value of the query).
+    fn read_slot(&self, db: &<Q as QueryDb<'_>>::DynDb, slot: &Slot<Q::Key>) -> Q::Value {
+        let changed_at = slot.interned_at;
+        let index = slot.index;
+        db.salsa_runtime().report_query_read(
+            slot.database_key_index,
+            INTERN_DURABILITY,
+            changed_at,
+        );
+        let value = <Q::Value>::from_intern_id(index);
+        debug_assert_eq!(
+            value.as_intern_id(),
+            index,
+            "inconsistent `InternKey` impl for `{}`: `as_intern_id` does not undo `from_intern_id`",
+            std::any::type_name::<Q::Value>(),
+        );
+        value
+    }
+
+    fn intern_check<B>(&self, db: &<Q as QueryDb<'_>>::DynDb, key: &B) -> Option<Arc<Slot<Q::Key>>>
+    where
+        Q::Key: Borrow<B>,
+        B: ?Sized + Hash + Eq,
+    {
         let revision_now = db.salsa_runtime().current_revision();
         let slot = self.tables.read().slot_for_key(key, revision_now)?;
         Some(slot)
@@ -277,17 +579,61 @@ where
         let revision_now = db.salsa_runtime().current_revision();
         self.tables.read().slot_for_index(index, revision_now)
     }
+
+    /// Like `lookup_value`, but returns `None` if no value is interned
+    /// at `index`.
+    fn try_lookup_value(
+        &self,
+        db: &<Q as QueryDb<'_>>::DynDb,
+        index: InternId,
+    ) -> Option<Arc<Slot<Q::Key>>> {
+        let revision_now = db.salsa_runtime().current_revision();
+        self.tables.read().try_slot_for_index(index, revision_now)
+    }
 }
 
 impl<Q> QueryStorageOps<Q> for InternedStorage<Q>
 where
-    Q: Query,
+    Q: InternedQuery,
     Q::Value: InternKey,
 {
     fn new(group_index: u16) -> Self {
         InternedStorage {
             group_index,
-            tables: RwLock::new(InternTables::default()),
+            tables: RwLock::new(InternTables::new(Q::GENERATIONAL)),
+            capacity: AtomicUsize::new(usize::MAX),
+        }
+    }
+
+    fn fork(&self) -> Self {
+        let tables = self.tables.read();
+        let values = tables
+            .values
+            .iter()
+            .map(|value| match value {
+                InternValue::Present { slot } => InternValue::Present {
+                    slot: Arc::new(Slot {
+                        index: slot.index,
+                        database_key_index: slot.database_key_index,
+                        value: slot.value.clone(),
+                        sequence: slot.sequence,
+                        interned_at: slot.interned_at,
+                        accessed_at: AtomicCell::new(slot.accessed_at.load()),
+                    }),
+                },
+                InternValue::Free { next } => InternValue::Free { next: *next },
+            })
+            .collect();
+        InternedStorage {
+            group_index: self.group_index,
+            tables: RwLock::new(InternTables {
+                map: tables.map.clone(),
+                values,
+                first_free: tables.first_free,
+                next_sequence: tables.next_sequence,
+                generational: tables.generational,
+            }),
+            capacity: AtomicUsize::new(self.capacity.load(Ordering::Relaxed)),
         }
     }
 
@@ -313,24 +659,71 @@ where
         assert_eq!(input.group_index, self.group_index);
         assert_eq!(input.query_index, Q::QUERY_INDEX);
         let intern_id = InternId::from(input.key_index);
-        let slot = self.lookup_value(db, intern_id);
-        slot.maybe_changed_since(db, revision)
+        let tables = self.tables.read();
+        match tables.values.get(tables.position(intern_id)) {
+            Some(InternValue::Present { slot }) if slot.index == intern_id => {
+                slot.maybe_changed_since(db, revision)
+            }
+
+            // The value was freed (see `InternedQueryStorageOps::retain`);
+            // if it is re-interned, it gets a new slot. Sealing the table
+            // can also drop the position altogether.
+            Some(InternValue::Present { .. }) | Some(InternValue::Free { .. }) | None => true,
+        }
+    }
+
+    fn dependencies(
+        &self,
+        _db: &<Q as QueryDb<'_>>::DynDb,
+        _index: DatabaseKeyIndex,
+    ) -> Option<Vec<DatabaseKeyIndex>> {
+        None
     }
 
+    fn accumulated(
+        &self,
+        _db: &<Q as QueryDb<'_>>::DynDb,
+        _index: DatabaseKeyIndex,
+    ) -> Option<AccumulatedValues> {
+        None
+    }
+
+    fn validate(&self, _db: &<Q as QueryDb<'_>>::DynDb, _index: DatabaseKeyIndex) {}
+
+    fn commit_provisional(
+        &self,
+        _db: &<Q as QueryDb<'_>>::DynDb,
+        _index: DatabaseKeyIndex,
+        _inputs: Option<&[DatabaseKeyIndex]>,
+        _durability: Durability,
+    ) {
+    }
+
+    fn debug_validate_dependencies(&self, _db: &<Q as QueryDb<'_>>::DynDb) {}
+
     fn try_fetch(
         &self,
         db: &<Q as QueryDb<'_>>::DynDb,
         key: &Q::Key,
     ) -> Result<Q::Value, CycleError<DatabaseKeyIndex>> {
-        let slot = self.intern_index(db, key);
-        let changed_at = slot.interned_at;
-        let index = slot.index;
-        db.salsa_runtime().report_query_read(
-            slot.database_key_index,
-            INTERN_DURABILITY,
-            changed_at,
-        );
-        Ok(<Q::Value>::from_intern_id(index))
+        Ok(self.intern_borrowed(db, key))
+    }
+
+    fn peek(&self, _db: &<Q as QueryDb<'_>>::DynDb, key: &Q::Key) -> Option<Q::Value> {
+        let tables = self.tables.read();
+        tables
+            .map
+            .get(key)
+            .map(|&index| <Q::Value>::from_intern_id(index))
+    }
+
+    fn changed_at(&self, _db: &<Q as QueryDb<'_>>::DynDb, key: &Q::Key) -> Option<Revision> {
+        let tables = self.tables.read();
+        let &index = tables.map.get(key)?;
+        match &tables.values[tables.position(index)] {
+            InternValue::Present { slot } => Some(slot.interned_at),
+            InternValue::Free { .. } => None,
+        }
     }
 
     fn durability(&self, _db: &<Q as QueryDb<'_>>::DynDb, _key: &Q::Key) -> Durability {
@@ -352,6 +745,251 @@ where
     }
 }
 
+impl<Q> InternedQueryStorageOps<Q> for InternedStorage<Q>
+where
+    Q: Query,
+    Q::Value: InternKey,
+{
+    fn interned_ids(&self) -> Vec<InternId> {
+        let tables = self.tables.read();
+        let mut slots: Vec<_> = tables
+            .values
+            .iter()
+            .filter_map(|value| match value {
+                InternValue::Present { slot } => Some((slot.sequence, slot.index)),
+                InternValue::Free { .. } => None,
+            })
+            .collect();
+        slots.sort_unstable();
+        slots.into_iter().map(|(_, index)| index).collect()
+    }
+
+    fn intern_borrowed<B>(&self, db: &<Q as QueryDb<'_>>::DynDb, key: &B) -> Q::Value
+    where
+        Q::Key: Borrow<B>,
+        B: ?Sized + Hash + Eq + ToOwned,
+        B::Owned: Into<Q::Key>,
+    {
+        match self.intern_index(db, key) {
+            Ok(slot) => self.read_slot(db, &slot),
+            Err(err) => panic!("{}", err),
+        }
+    }
+
+    fn try_intern(
+        &self,
+        db: &<Q as QueryDb<'_>>::DynDb,
+        key: &Q::Key,
+    ) -> Result<Q::Value, InternCapacityExceeded> {
+        let slot = self.intern_index(db, key)?;
+        Ok(self.read_slot(db, &slot))
+    }
+
+    fn set_intern_capacity(&self, capacity: Option<usize>) {
+        self.capacity
+            .store(capacity.unwrap_or(usize::MAX), Ordering::Relaxed);
+    }
+
+    fn intern_many<I>(&self, db: &<Q as QueryDb<'_>>::DynDb, keys: I) -> Vec<Q::Value>
+    where
+        I: IntoIterator<Item = Q::Key>,
+    {
+        // Collect the keys first, so that the iterator does not run
+        // (and maybe intern something itself) while the lock is held.
+        let keys: Vec<Q::Key> = keys.into_iter().collect();
+        let revision_now = db.salsa_runtime().current_revision();
+        let slots: Vec<_> = {
+            let mut tables = self.tables.write();
+            keys.into_iter()
+                .map(|key| match tables.slot_for_key(&key, revision_now) {
+                    Some(slot) => slot,
+                    None => match self.insert(&mut tables, key, revision_now) {
+                        Ok(slot) => slot,
+                        Err(err) => panic!("{}", err),
+                    },
+                })
+                .collect()
+        };
+        slots.iter().map(|slot| self.read_slot(db, slot)).collect()
+    }
+
+    fn intern_stats(&self) -> InternStats {
+        let tables = self.tables.read();
+        let free_ids = tables
+            .values
+            .iter()
+            .filter(|value| match value {
+                InternValue::Present { .. } => false,
+                InternValue::Free { .. } => true,
+            })
+            .count();
+        InternStats::new(tables.map.len(), tables.map.capacity(), free_ids)
+    }
+
+    fn retain(
+        &self,
+        db: &mut <Q as QueryDb<'_>>::DynDb,
+        keep: &mut dyn FnMut(&Q::Key) -> bool,
+    ) -> usize {
+        let discard: Vec<Q::Key> = self
+            .tables
+            .read()
+            .map
+            .keys()
+            .filter(|key| !keep(key))
+            .cloned()
+            .collect();
+        if discard.is_empty() {
+            return 0;
+        }
+
+        // Queries may have read the discarded values in the current
+        // revision, so a new revision is needed for them to notice.
+        // Intern slots have `INTERN_DURABILITY`, so that is what changed.
+        let mut freed = 0;
+        db.salsa_runtime_mut()
+            .with_incremented_revision(&mut |_next_revision| {
+                let mut tables = self.tables.write();
+                let InternTables {
+                    map,
+                    values,
+                    first_free,
+                    next_sequence: _,
+                    generational,
+                } = &mut *tables;
+                for key in &discard {
+                    if let Some(index) = map.remove(key) {
+                        let position = position_of(index, *generational);
+                        if let InternValue::Present { slot } = &values[position] {
+                            slot.accessed_at.store(None);
+                        }
+                        values[position] = InternValue::Free { next: *first_free };
+                        *first_free = Some(index);
+                        freed += 1;
+                    }
+                }
+                Some(INTERN_DURABILITY)
+            });
+        freed
+    }
+
+    fn intern_with_id(
+        &self,
+        db: &<Q as QueryDb<'_>>::DynDb,
+        key: Q::Key,
+        id: InternId,
+    ) -> Result<Q::Value, InternWithIdError> {
+        let revision_now = db.salsa_runtime().current_revision();
+        let slot = {
+            let mut tables = self.tables.write();
+            if let Some(&index) = tables.map.get(&key) {
+                if index != id {
+                    return Err(InternWithIdError::AlreadyInterned { id: index });
+                }
+                tables.slot_for_index(index, revision_now)
+            } else {
+                let position = tables.position(id);
+                if let Some(InternValue::Present { .. }) = tables.values.get(position) {
+                    return Err(InternWithIdError::IdTaken { id });
+                }
+                self.check_capacity(&tables)
+                    .map_err(InternWithIdError::CapacityExceeded)?;
+                tables.take_free(position);
+
+                let sequence = tables.next_sequence;
+                tables.next_sequence += 1;
+                let slot = Arc::new(Slot {
+                    index: id,
+                    database_key_index: DatabaseKeyIndex {
+                        group_index: self.group_index,
+                        query_index: Q::QUERY_INDEX,
+                        key_index: id.as_u32(),
+                    },
+                    value: key.clone(),
+                    sequence,
+                    interned_at: revision_now,
+                    accessed_at: AtomicCell::new(Some(revision_now)),
+                });
+                tables.values[position] = InternValue::Present { slot: slot.clone() };
+                tables.map.insert(key, id);
+                slot
+            }
+        };
+        Ok(self.read_slot(db, &slot))
+    }
+
+    fn seal(&self, db: &mut <Q as QueryDb<'_>>::DynDb) -> HashMap<InternId, InternId>
+    where
+        Q::Key: Ord,
+    {
+        let (slots, remap) = {
+            let tables = self.tables.read();
+            let mut slots: Vec<Arc<Slot<Q::Key>>> = tables
+                .values
+                .iter()
+                .filter_map(|value| match value {
+                    InternValue::Present { slot } => Some(slot.clone()),
+                    InternValue::Free { .. } => None,
+                })
+                .collect();
+            slots.sort_unstable_by(|a, b| a.value.cmp(&b.value));
+            let remap: HashMap<InternId, InternId> = slots
+                .iter()
+                .enumerate()
+                .map(|(position, slot)| (slot.index, fresh_id(position, tables.generational)))
+                .collect();
+            (slots, remap)
+        };
+
+        // If no id changes, only the free positions (which all come after
+        // the values) have to go, so that later values get the ids they
+        // would get in an empty table. No query can tell the difference.
+        if remap.iter().all(|(old, new)| old == new) {
+            let mut tables = self.tables.write();
+            tables.values.truncate(slots.len());
+            tables.first_free = None;
+            return remap;
+        }
+
+        // Otherwise, the values that move are interned anew, and their old
+        // slots are collected, so the queries that read either id are
+        // re-executed (see `maybe_changed_since`).
+        db.salsa_runtime_mut()
+            .with_incremented_revision(&mut |next_revision| {
+                let mut tables = self.tables.write();
+                let mut sealed = InternTables::new(tables.generational);
+                for (position, slot) in slots.iter().enumerate() {
+                    let index = remap[&slot.index];
+                    let interned_at = if slot.index == index {
+                        slot.interned_at
+                    } else {
+                        slot.accessed_at.store(None);
+                        next_revision
+                    };
+                    sealed.map.insert(slot.value.clone(), index);
+                    sealed.values.push(InternValue::Present {
+                        slot: Arc::new(Slot {
+                            index,
+                            database_key_index: DatabaseKeyIndex {
+                                group_index: self.group_index,
+                                query_index: Q::QUERY_INDEX,
+                                key_index: index.as_u32(),
+                            },
+                            value: slot.value.clone(),
+                            sequence: position as u64,
+                            interned_at,
+                            accessed_at: AtomicCell::new(Some(next_revision)),
+                        }),
+                    });
+                }
+                sealed.next_sequence = sealed.values.len() as u64;
+                *tables = sealed;
+                Some(INTERN_DURABILITY)
+            });
+        remap
+    }
+}
+
 impl<Q> QueryStorageMassOps for InternedStorage<Q>
 where
     Q: Query,
@@ -365,8 +1003,11 @@ where
             map,
             values,
             first_free,
+            next_sequence: _,
+            generational,
         } = &mut *tables;
         map.retain(|key, intern_index| {
+            let position = position_of(*intern_index, *generational);
             match strategy.discard_if {
                 DiscardIf::Never => true,
 
@@ -382,11 +1023,12 @@ where
                 // revision don't have this problem. Anything
                 // dependent on them would regard itself as dirty if
                 // they are removed and also be forced to re-execute.
-                DiscardIf::Always | DiscardIf::Outdated => match &values[intern_index.as_usize()] {
+                DiscardIf::Always | DiscardIf::Outdated => match &values[position] {
                     InternValue::Present { slot, .. } => {
-                        if slot.try_collect(last_changed, revision_now) {
-                            values[intern_index.as_usize()] =
-                                InternValue::Free { next: *first_free };
+                        if strategy.keeps(slot.database_key_index) {
+                            true
+                        } else if slot.try_collect(last_changed, revision_now) {
+                            values[position] = InternValue::Free { next: *first_free };
                             *first_free = Some(*intern_index);
                             false
                         } else {
@@ -405,8 +1047,16 @@ where
         });
     }
     fn purge(&self) {
-        *self.tables.write() = Default::default();
+        let mut tables = self.tables.write();
+        *tables = InternTables::new(tables.generational);
+    }
+    fn for_each_memo(&self, _: &Runtime, _op: &mut dyn FnMut(MemoEntry<'_>)) {}
+    fn memory_usage(&self) -> usize {
+        self.tables.read().map.len() * entry_size::<Q>()
     }
+    fn enforce_lru_capacity(&self, _db: &dyn Database) {}
+
+    fn clear_derived(&self) {}
 }
 
 // Workaround for
@@ -447,7 +1097,7 @@ where
     Q: Query,
     Q::Key: InternKey,
     Q::Value: Eq + Hash,
-    IQ: Query<Key = Q::Value, Value = Q::Key, Storage = InternedStorage<IQ>>,
+    IQ: InternedQuery<Key = Q::Value, Value = Q::Key, Storage = InternedStorage<IQ>>,
     for<'d> Q: EqualDynDb<'d, IQ>,
 {
     fn new(_group_index: u16) -> Self {
@@ -456,6 +1106,12 @@ where
         }
     }
 
+    fn fork(&self) -> Self {
+        LookupInternedStorage {
+            phantom: std::marker::PhantomData,
+        }
+    }
+
     fn fmt_index(
         &self,
         db: &<Q as QueryDb<'_>>::DynDb,
@@ -480,24 +1136,65 @@ where
         interned_storage.maybe_changed_since(Q::convert_db(db), input, revision)
     }
 
+    fn dependencies(
+        &self,
+        _db: &<Q as QueryDb<'_>>::DynDb,
+        _index: DatabaseKeyIndex,
+    ) -> Option<Vec<DatabaseKeyIndex>> {
+        None
+    }
+
+    fn accumulated(
+        &self,
+        _db: &<Q as QueryDb<'_>>::DynDb,
+        _index: DatabaseKeyIndex,
+    ) -> Option<AccumulatedValues> {
+        None
+    }
+
+    fn validate(&self, _db: &<Q as QueryDb<'_>>::DynDb, _index: DatabaseKeyIndex) {}
+
+    fn commit_provisional(
+        &self,
+        _db: &<Q as QueryDb<'_>>::DynDb,
+        _index: DatabaseKeyIndex,
+        _inputs: Option<&[DatabaseKeyIndex]>,
+        _durability: Durability,
+    ) {
+    }
+
+    fn debug_validate_dependencies(&self, _db: &<Q as QueryDb<'_>>::DynDb) {}
+
     fn try_fetch(
         &self,
         db: &<Q as QueryDb<'_>>::DynDb,
         key: &Q::Key,
     ) -> Result<Q::Value, CycleError<DatabaseKeyIndex>> {
+        Ok(self.lookup_ref(db, key).clone())
+    }
+
+    fn peek(&self, db: &<Q as QueryDb<'_>>::DynDb, key: &Q::Key) -> Option<Q::Value> {
         let index = key.as_intern_id();
         let group_storage =
             <<Q as QueryDb<'_>>::DynDb as HasQueryGroup<Q::Group>>::group_storage(db);
         let interned_storage = IQ::query_storage(Q::convert_group_storage(group_storage));
-        let slot = interned_storage.lookup_value(Q::convert_db(db), index);
-        let value = slot.value.clone();
-        let interned_at = slot.interned_at;
-        db.salsa_runtime().report_query_read(
-            slot.database_key_index,
-            INTERN_DURABILITY,
-            interned_at,
-        );
-        Ok(value)
+        let tables = interned_storage.tables.read();
+        match tables.values.get(tables.position(index)) {
+            Some(InternValue::Present { slot }) if slot.index == index => Some(slot.value.clone()),
+            Some(InternValue::Present { .. }) | Some(InternValue::Free { .. }) | None => None,
+        }
+    }
+
+    fn changed_at(&self, db: &<Q as QueryDb<'_>>::DynDb, key: &Q::Key) -> Option<Revision> {
+        let index = key.as_intern_id();
+        let group_storage =
+            <<Q as QueryDb<'_>>::DynDb as HasQueryGroup<Q::Group>>::group_storage(db);
+        let interned_storage = IQ::query_storage(Q::convert_group_storage(group_storage));
+        let tables = interned_storage.tables.read();
+        match tables.values.get(tables.position(index)) {
+            Some(InternValue::Present { slot }) if slot.index == index => Some(slot.interned_at),
+            Some(InternValue::Present { .. }) | Some(InternValue::Free { .. }) | None => None,
+        }
     }
 
     fn durability(&self, _db: &<Q as QueryDb<'_>>::DynDb, _key: &Q::Key) -> Durability {
@@ -522,6 +1219,52 @@ where
     }
 }
 
+impl<Q, IQ> LookupInternedQueryStorageOps<Q> for LookupInternedStorage<Q, IQ>
+where
+    Q: Query,
+    Q::Key: InternKey,
+    Q::Value: Eq + Hash,
+    IQ: InternedQuery<Key = Q::Value, Value = Q::Key, Storage = InternedStorage<IQ>>,
+    for<'d> Q: EqualDynDb<'d, IQ>,
+{
+    fn lookup_ref(&self, db: &<Q as QueryDb<'_>>::DynDb, key: &Q::Key) -> InternedRef<Q::Value> {
+        let index = key.as_intern_id();
+        let group_storage =
+            <<Q as QueryDb<'_>>::DynDb as HasQueryGroup<Q::Group>>::group_storage(db);
+        let interned_storage = IQ::query_storage(Q::convert_group_storage(group_storage));
+        let slot = interned_storage.lookup_value(Q::convert_db(db), index);
+        db.salsa_runtime().report_query_read(
+            slot.database_key_index,
+            INTERN_DURABILITY,
+            slot.interned_at,
+        );
+        InternedRef { slot }
+    }
+
+    fn try_lookup(&self, db: &<Q as QueryDb<'_>>::DynDb, key: &Q::Key) -> Option<Q::Value> {
+        let index = key.as_intern_id();
+        let group_storage =
+            <<Q as QueryDb<'_>>::DynDb as HasQueryGroup<Q::Group>>::group_storage(db);
+        let interned_storage = IQ::query_storage(Q::convert_group_storage(group_storage));
+        match interned_storage.try_lookup_value(Q::convert_db(db), index) {
+            Some(slot) => {
+                db.salsa_runtime().report_query_read(
+                    slot.database_key_index,
+                    INTERN_DURABILITY,
+                    slot.interned_at,
+                );
+                Some(slot.value.clone())
+            }
+            None => {
+                // A value may be interned at this index later on, so
+                // the caller must not reuse the `None`.
+                db.salsa_runtime().report_untracked_read();
+                None
+            }
+        }
+    }
+}
+
 impl<Q, IQ> QueryStorageMassOps for LookupInternedStorage<Q, IQ>
 where
     Q: Query,
@@ -531,6 +1274,15 @@ where
 {
     fn sweep(&self, _: &Runtime, _strategy: SweepStrategy) {}
     fn purge(&self) {}
+    fn for_each_memo(&self, _: &Runtime, _op: &mut dyn FnMut(MemoEntry<'_>)) {}
+    fn memory_usage(&self) -> usize {
+        // The interned values are stored (and counted) by the
+        // interning query.
+        0
+    }
+    fn enforce_lru_capacity(&self, _db: &dyn Database) {}
+
+    fn clear_derived(&self) {}
 }
 
 impl<K> Slot<K> {
@@ -7,7 +7,10 @@
 //! re-execute the derived queries and it will try to re-use results
 //! from previous invocations as appropriate.
 
+mod accumulator;
+mod arc_iter;
 mod blocking_future;
+mod debug_with_db;
 mod derived;
 mod doctest;
 mod durability;
@@ -15,9 +18,11 @@ mod input;
 mod intern_id;
 mod interned;
 mod lru;
+mod replay;
 mod revision;
 mod runtime;
 mod storage;
+mod walk;
 
 pub mod debug;
 /// Items in this module are public for implementation reasons,
@@ -27,20 +32,39 @@ pub mod plumbing;
 
 use crate::plumbing::DerivedQueryStorageOps;
 use crate::plumbing::InputQueryStorageOps;
+use crate::plumbing::InternedQueryStorageOps;
+use crate::plumbing::LookupInternedQueryStorageOps;
 use crate::plumbing::LruQueryStorageOps;
 use crate::plumbing::QueryStorageMassOps;
 use crate::plumbing::QueryStorageOps;
 pub use crate::revision::Revision;
+use rustc_hash::{FxHashMap, FxHashSet};
 use std::fmt::{self, Debug};
 use std::hash::Hash;
 use std::sync::Arc;
 
+pub use crate::accumulator::Accumulator;
+pub use crate::arc_iter::ArcIter;
+pub use crate::debug_with_db::DebugWith;
+pub use crate::debug_with_db::DebugWithDb;
 pub use crate::durability::Durability;
+pub use crate::input::SetInputError;
 pub use crate::intern_id::InternId;
+pub use crate::intern_id::InternIdOutOfRange;
+pub use crate::interned::InternCapacityExceeded;
 pub use crate::interned::InternKey;
+pub use crate::interned::InternWithIdError;
+pub use crate::interned::InternedRef;
+pub use crate::lru::LruConfig;
+pub use crate::replay::InputLog;
+pub use crate::replay::RecordedSet;
+pub use crate::runtime::Canceled;
+pub use crate::runtime::NoCyclesGuard;
+pub use crate::runtime::QueryStackOverflow;
 pub use crate::runtime::Runtime;
 pub use crate::runtime::RuntimeId;
 pub use crate::storage::Storage;
+pub use crate::walk::DependencyVisitor;
 
 /// The base trait which your "query context" must implement. Gives
 /// access to the salsa runtime, which you must embed into your query
@@ -59,8 +83,159 @@ pub trait Database: plumbing::DatabaseOps {
         // and there is no need to bring things to a halt. That said,
         // users may wish to guarantee atomicity.
 
+        let strategy = strategy.resolve_roots(self);
         let runtime = self.salsa_runtime();
-        self.for_each_query(&mut |query_storage| query_storage.sweep(runtime, strategy));
+        self.for_each_query(&mut |query_storage| query_storage.sweep(runtime, strategy.clone()));
+    }
+
+    /// Discards the memoized values (and dependencies) of every derived
+    /// query, so that reading a query next executes it from scratch, as
+    /// in a fresh database. Inputs, interned values and the settings of
+    /// the runtime are kept, which makes this useful for measuring the
+    /// time to compute queries "cold", repeatedly, without setting the
+    /// inputs again. Unlike sweeping (with
+    /// `SweepStrategy::discard_everything`), interned values are not
+    /// affected, and values in use in the current revision are
+    /// discarded too.
+    ///
+    /// Like setting an input, this starts a new revision, and so waits
+    /// for all snapshots to be dropped.
+    fn reset_derived(&mut self) {
+        // While the new revision is started, no snapshot exists; none
+        // can be taken while we hold `&mut self`, so no query runs.
+        self.salsa_runtime_mut().synthetic_write(Durability::LOW);
+        self.for_each_query(&mut |query_storage| query_storage.clear_derived());
+    }
+
+    /// Immediately enforces the LRU capacity of every query that has one
+    /// (see `QueryTable::set_lru_capacity`), evicting memoized values
+    /// until no more than that many remain. Normally, values are only
+    /// evicted as other values are stored, so more can be retained than
+    /// the capacity allows (e.g., after the capacity is lowered); this
+    /// lets memory be released promptly, e.g. when going idle. Each
+    /// eviction is reported with a `WillDiscardStaleValue` event.
+    fn trigger_lru_eviction(&mut self) {
+        let db = self.ops_database();
+        self.for_each_query(&mut |query_storage| query_storage.enforce_lru_capacity(db));
+    }
+
+    /// Brings the values of the given (derived) queries up to date, as if
+    /// they were read, but without returning them: each is re-validated
+    /// and, if needed, re-executed, as are the queries it depends on.
+    /// This can warm the cache, typically on a snapshot in a background
+    /// thread, for the queries that are likely to be read next (like
+    /// those of the open files) after inputs changed.
+    ///
+    /// Keys of inputs and interned queries are ignored, and so are
+    /// queries that were never executed. Cycles are not reported here,
+    /// but when the query is next read.
+    fn validate(&self, keys: &[DatabaseKeyIndex]) {
+        for &key in keys {
+            plumbing::DatabaseOps::validate(self, key);
+        }
+    }
+
+    /// Returns the memoized queries that changing `key` (say, an input
+    /// that is about to be set) could cause to be re-executed: those
+    /// that read it, those that read one of them, and so on, in that
+    /// order (followed by the queries with untracked inputs, see
+    /// below). This can be used to preview the effect of a change before
+    /// making it, e.g. to tell how much will be re-analyzed when a file
+    /// is edited.
+    ///
+    /// The result is an upper bound. A query that is re-executed may
+    /// still compute the same value, in which case the queries that
+    /// read it are only re-validated. Queries with untracked inputs are
+    /// re-executed after any change, so they are always included, along
+    /// with the queries that read them.
+    ///
+    /// The edges from each query to those that read it are not kept,
+    /// so this walks over all memoized values.
+    fn affected_by(&self, key: DatabaseKeyIndex) -> Vec<DatabaseKeyIndex> {
+        let runtime = self.salsa_runtime();
+        let mut readers: FxHashMap<DatabaseKeyIndex, Vec<DatabaseKeyIndex>> = FxHashMap::default();
+        let mut untracked = vec![];
+        self.for_each_query(&mut |query_storage| {
+            query_storage.for_each_memo(runtime, &mut |memo| match memo.inputs {
+                Some(inputs) => {
+                    for &input in inputs {
+                        readers
+                            .entry(input)
+                            .or_default()
+                            .push(memo.database_key_index);
+                    }
+                }
+                None => untracked.push(memo.database_key_index),
+            })
+        });
+
+        // Walk breadth-first from `key`, then from each query with
+        // untracked inputs that was not reached yet.
+        let mut affected = vec![key];
+        let mut seen: FxHashSet<_> = affected.iter().cloned().collect();
+        let mut untracked = untracked.into_iter();
+        let mut next = 0;
+        loop {
+            if next == affected.len() {
+                match untracked.find(|&query| seen.insert(query)) {
+                    Some(query) => affected.push(query),
+                    None => break,
+                }
+            }
+            let changed = affected[next];
+            next += 1;
+            for &reader in readers.get(&changed).into_iter().flatten() {
+                if seen.insert(reader) {
+                    affected.push(reader);
+                }
+            }
+        }
+        affected.remove(0);
+        affected
+    }
+
+    /// Sets the input query named `name` (e.g. `"file_text"`) for `key`
+    /// to `value`, like calling `set` on its query table. The key (the
+    /// tuple of the keys, for inputs with several) and the value must
+    /// have the input's types, or an error is returned and nothing is
+    /// set. This allows inputs to be set generically, e.g. by a loader
+    /// that reads their names and values from a data file, without a
+    /// match over every input.
+    ///
+    /// If several query groups have an input named `name`, the first
+    /// one (in the order given to `#[salsa::database]`) is set.
+    fn set_input_by_name(
+        &mut self,
+        name: &str,
+        key: Box<dyn std::any::Any>,
+        value: Box<dyn std::any::Any>,
+    ) -> Result<(), SetInputError> {
+        plumbing::DatabaseOps::set_input_by_name(self, name, key, value, Durability::LOW)
+    }
+
+    /// Returns (and forgets) the inputs that were set while the runtime
+    /// was recording (see `Runtime::start_recording`), in the order they
+    /// were set.
+    fn take_recording(&self) -> InputLog {
+        let mut sets = Vec::new();
+        self.take_recorded_sets(&mut |set| sets.push(set));
+        InputLog::new(sets)
+    }
+
+    /// Sets the LRU capacity of each query named in `config`, like
+    /// calling `set_lru_capacity` on its query table would. A name
+    /// matches the derived queries of that name in all query groups.
+    /// Names that match no derived query are logged as a warning and
+    /// returned, so a misspelled name is not silently ignored.
+    fn apply_lru_config(&self, config: &LruConfig) -> Vec<String> {
+        let mut unknown = Vec::new();
+        for (name, capacity) in config.caps() {
+            if !plumbing::DatabaseOps::set_lru_capacity_by_name(self, name, capacity) {
+                log::warn!("apply_lru_config: no derived query named `{}`", name);
+                unknown.push(name.to_string());
+            }
+        }
+        unknown
     }
 
     /// This function is invoked at key points in the salsa
@@ -70,6 +245,82 @@ pub trait Database: plumbing::DatabaseOps {
         #![allow(unused_variables)]
     }
 
+    /// Unwinds with a `Canceled` payload if the current revision is
+    /// canceled (see `Runtime::is_current_revision_canceled`, which this
+    /// calls); this suits queries that use the unwinding approach to
+    /// cancellation. Like `resume_unwind`, it does not invoke the panic
+    /// hook.
+    ///
+    /// Each call reports an `EventKind::WillCheckCancellation` event,
+    /// and a call that unwinds reports `EventKind::WasCanceled` first,
+    /// so `salsa_event` can measure how often each query checks for
+    /// cancellation.
+    fn unwind_if_canceled(&self) {
+        let runtime = self.salsa_runtime();
+        let database_key = runtime.active_query();
+        runtime.report_event(
+            self,
+            Event {
+                runtime_id: runtime.id(),
+                kind: EventKind::WillCheckCancellation { database_key },
+            },
+        );
+        if runtime.is_current_revision_canceled() {
+            runtime.report_event(
+                self,
+                Event {
+                    runtime_id: runtime.id(),
+                    kind: EventKind::WasCanceled { database_key },
+                },
+            );
+            std::panic::resume_unwind(Box::new(Canceled));
+        }
+    }
+
+    /// A cheap cancellation checkpoint, for loops in queries that run
+    /// for a long time without calling into salsa (and so would
+    /// otherwise never notice that they were canceled). Call it
+    /// periodically, say once per iteration: unless the current
+    /// revision is canceled (or the active query timed out), it only
+    /// reads a few counters, and reports no event and no read. If it
+    /// is canceled, it unwinds with a `Canceled` payload, just like
+    /// `unwind_if_canceled` (which it then calls).
+    #[inline]
+    fn yield_now(&self) {
+        if self.salsa_runtime().is_cancellation_pending() {
+            self.unwind_if_canceled();
+        }
+    }
+
+    /// Runs `op`, sending every event that salsa reports on this
+    /// database's runtime to `handler` instead of `salsa_event`, and
+    /// then restores the handler that was installed before (if any),
+    /// even if `op` unwinds. This makes it easy for a test to capture
+    /// the events of one operation:
+    ///
+    /// ```ignore
+    /// let events = Arc::new(Mutex::new(Vec::new()));
+    /// let sink = events.clone();
+    /// db.with_event_handler(
+    ///     move |db, event| sink.lock().unwrap().push(event.resolve(db)),
+    ///     || db.length(()),
+    /// );
+    /// ```
+    ///
+    /// Only the events of this runtime are captured, not those of
+    /// snapshots (which have runtimes of their own). Events reported
+    /// while `handler` itself runs still go to `salsa_event`.
+    fn with_event_handler<R>(
+        &self,
+        handler: impl FnMut(&dyn Database, Event) + Send + 'static,
+        op: impl FnOnce() -> R,
+    ) -> R
+    where
+        Self: Sized,
+    {
+        self.salsa_runtime().with_event_handler(handler, op)
+    }
+
     /// This function is invoked when a dependent query is being computed by the
     /// other thread, and that thread panics.
     fn on_propagated_panic(&self) -> ! {
@@ -81,6 +332,104 @@ pub trait Database: plumbing::DatabaseOps {
         self.ops_salsa_runtime()
     }
 
+    /// Returns a value whose `Debug` output lists every memoized value
+    /// and input in the database: its query and key, whether it has
+    /// been verified in the current revision, and its value (if one is
+    /// stored). The tables are walked only when the result is
+    /// formatted, which can take a while for a large database.
+    ///
+    /// Requires the `debug-dump` feature.
+    #[cfg(feature = "debug-dump")]
+    fn debug_all_memoized(&self) -> debug::MemoizedDump<'_> {
+        debug::MemoizedDump {
+            db: self.ops_database(),
+        }
+    }
+
+    /// Checks that incremental results match results computed from
+    /// scratch: brings every memoized value of a derived query up to date
+    /// (re-executing or re-validating it as reading it would), executes
+    /// the query once more, and panics, naming the query, if that
+    /// produces a different value (according to its `values_equal`) or
+    /// reads a different set of inputs than were recorded. Values that
+    /// were discarded, have untracked inputs, or were computed by
+    /// fixed-point iteration (whose recorded inputs are those of the
+    /// whole cycle) are skipped.
+    ///
+    /// This executes every memoized query at least once, so it is only
+    /// meant for tests and debugging. None of these reads or executions
+    /// are reported to `salsa_event` or recorded in the query stats.
+    ///
+    /// Requires the `debug-dump` feature.
+    #[cfg(feature = "debug-dump")]
+    fn debug_validate_dependencies(&self) {
+        self.ops_validate_dependencies()
+    }
+
+    /// Walks the dependencies recorded for `root`, depth first: calls
+    /// `visitor.enter` on each query reachable from `root` (including
+    /// `root` itself), and `visitor.exit` once the dependencies of that
+    /// query have been walked. Each query is visited once, so shared
+    /// dependencies and cycles are fine.
+    ///
+    /// The dependencies are those recorded by the memoized values of
+    /// derived queries: inputs and interned values have none, and
+    /// neither do queries that are not memoized (or whose dependencies
+    /// were discarded, or that reported an untracked read). As with
+    /// `QueryTable::dependencies`, they are as of the last time each
+    /// query was executed or validated, so walk them after reading
+    /// `root` in the current revision to see its current dependencies.
+    fn walk_dependencies(&self, root: DatabaseKeyIndex, visitor: &mut dyn DependencyVisitor) {
+        walk::walk_dependencies(self, Some(root), visitor)
+    }
+
+    /// Returns a rough estimate of the memory used by each query group
+    /// of this database, in bytes, identified by the name of its trait.
+    /// This counts the memoized values (and input values) of each query,
+    /// taking the size of each to be that of its key and value types
+    /// (as given by `std::mem::size_of`); heap data owned by keys and
+    /// values, as well as bookkeeping such as dependency lists, is not
+    /// counted. It is meant for spotting groups that grow unexpectedly
+    /// (and might benefit from an LRU capacity), not for exact figures.
+    fn memory_usage(&self) -> Vec<(&'static str, usize)> {
+        self.group_memory_usage()
+    }
+
+    /// Makes every cycle panic until the returned guard is dropped, even
+    /// for queries that would otherwise recover from it (with
+    /// `#[salsa::cycle]`). This is the same as setting the cycle handling
+    /// to `CycleHandling::Panic`, but restores the previous handling
+    /// afterwards. As always, the panic message lists the queries that
+    /// form the cycle, in order.
+    fn assert_no_cycles(&self) -> NoCyclesGuard<'_> {
+        NoCyclesGuard::new(self.salsa_runtime())
+    }
+
+    /// Runs `op`, letting all the inputs it sets share one new revision,
+    /// as if they were set with one `set_many` call, even if they belong
+    /// to different queries or have different durabilities. This gives
+    /// control over when revisions advance, e.g. to apply all the
+    /// changes of a batch job at once.
+    ///
+    /// `op` can also read queries, and sees the values set so far. But
+    /// a value read in the batch's revision is only correct for the
+    /// inputs set before it was read, so a set that follows reads
+    /// starts another revision (which later sets share, until the next
+    /// read). To advance the revision exactly once, set all the inputs
+    /// before reading any query.
+    fn batch<R>(&mut self, op: impl FnOnce(&mut Self) -> R) -> R
+    where
+        Self: Sized,
+    {
+        self.salsa_runtime_mut().start_batch();
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| op(self)));
+        self.salsa_runtime_mut().end_batch();
+        match result {
+            Ok(result) => result,
+            Err(payload) => std::panic::resume_unwind(payload),
+        }
+    }
+
     /// Gives access to the underlying salsa runtime.
     fn salsa_runtime_mut(&mut self) -> &mut Runtime {
         self.ops_salsa_runtime_mut()
@@ -99,6 +448,69 @@ pub struct Event {
     pub kind: EventKind,
 }
 
+impl Event {
+    /// Renders the database keys in this event (using
+    /// `DatabaseKeyIndex::debug`), giving an owned value that can be
+    /// logged or stored without access to the database.
+    pub fn resolve<D>(&self, db: &D) -> ResolvedEvent
+    where
+        D: ?Sized + plumbing::DatabaseOps,
+    {
+        let kind = match self.kind {
+            EventKind::DidValidateMemoizedValue {
+                database_key,
+                revision,
+            } => ResolvedEventKind::DidValidateMemoizedValue {
+                database_key: ResolvedKey::new(database_key, db),
+                revision,
+            },
+            EventKind::WillBlockOn {
+                other_runtime_id,
+                database_key,
+            } => ResolvedEventKind::WillBlockOn {
+                other_runtime_id,
+                database_key: ResolvedKey::new(database_key, db),
+            },
+            EventKind::WillExecute { database_key } => ResolvedEventKind::WillExecute {
+                database_key: ResolvedKey::new(database_key, db),
+            },
+            EventKind::WillDiscardStaleValue { database_key } => {
+                ResolvedEventKind::WillDiscardStaleValue {
+                    database_key: ResolvedKey::new(database_key, db),
+                }
+            }
+            EventKind::DidSetInput {
+                database_key,
+                durability,
+                revision,
+            } => ResolvedEventKind::DidSetInput {
+                database_key: ResolvedKey::new(database_key, db),
+                durability,
+                revision,
+            },
+            EventKind::DeepRecursion {
+                database_key,
+                depth,
+            } => ResolvedEventKind::DeepRecursion {
+                database_key: ResolvedKey::new(database_key, db),
+                depth,
+            },
+            EventKind::WillCheckCancellation { database_key } => {
+                ResolvedEventKind::WillCheckCancellation {
+                    database_key: database_key.map(|key| ResolvedKey::new(key, db)),
+                }
+            }
+            EventKind::WasCanceled { database_key } => ResolvedEventKind::WasCanceled {
+                database_key: database_key.map(|key| ResolvedKey::new(key, db)),
+            },
+        };
+        ResolvedEvent {
+            runtime_id: self.runtime_id,
+            kind,
+        }
+    }
+}
+
 impl fmt::Debug for Event {
     fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
         fmt.debug_struct("Event")
@@ -118,6 +530,10 @@ pub enum EventKind {
     DidValidateMemoizedValue {
         /// The database-key for the affected value. Implements `Debug`.
         database_key: DatabaseKeyIndex,
+
+        /// The revision in which the value was validated (i.e., the
+        /// current revision).
+        revision: Revision,
     },
 
     /// Indicates that another thread (with id `other_runtime_id`) is processing the
@@ -140,18 +556,91 @@ pub enum EventKind {
     /// Indicates that the function for this query will be executed.
     /// This is either because it has never executed before or because
     /// its inputs may be out of date.
+    ///
+    /// With the `tracing` feature, the function also runs in a `tracing`
+    /// span named `query`, with the query name, key and revision as its
+    /// `name`, `key` and `revision` fields. Spans of the queries it
+    /// executes in turn are nested in it.
     WillExecute {
         /// The database-key for the affected value. Implements `Debug`.
         database_key: DatabaseKeyIndex,
     },
+
+    /// Indicates that the memoized value for this query was discarded
+    /// because it was evicted from the query's LRU cache (see
+    /// `set_lru_capacity`). The query will be re-executed if its value
+    /// is needed again.
+    WillDiscardStaleValue {
+        /// The database-key for the affected value. Implements `Debug`.
+        database_key: DatabaseKeyIndex,
+    },
+
+    /// Indicates that an input was set (with `QueryTableMut::set` or
+    /// one of its variants), starting a new revision. Setting an
+    /// `#[salsa::input(eq)]` query to the value it already has does not
+    /// start a new revision, so it is not reported.
+    ///
+    /// Executes after the value was stored.
+    DidSetInput {
+        /// The database-key for the input. Implements `Debug`.
+        database_key: DatabaseKeyIndex,
+
+        /// The durability the input was set with.
+        durability: Durability,
+
+        /// The revision started by setting the input (i.e., the new
+        /// current revision).
+        revision: Revision,
+    },
+
+    /// Indicates that the query will be executed while `depth - 1`
+    /// executions of the same query (with other keys) are already on
+    /// the stack, where `depth` is the threshold set with
+    /// `Runtime::set_recursion_warning_depth`. Such deep recursion is
+    /// not a cycle, but it may be unintended, and it can overflow the
+    /// stack.
+    ///
+    /// Reported once each time the threshold is reached, before the
+    /// `WillExecute` event.
+    DeepRecursion {
+        /// The database-key of the query that will be executed.
+        /// Implements `Debug`.
+        database_key: DatabaseKeyIndex,
+
+        /// The number of executions of the query on the stack,
+        /// including this one.
+        depth: usize,
+    },
+
+    /// Indicates that `Database::unwind_if_canceled` will check whether
+    /// the current revision is canceled. Counting these events per query
+    /// (and the time between them) shows which queries check for
+    /// cancellation too rarely.
+    WillCheckCancellation {
+        /// The database-key of the query that is checking, or `None`
+        /// if no query is executing. Implements `Debug`.
+        database_key: Option<DatabaseKeyIndex>,
+    },
+
+    /// Indicates that `Database::unwind_if_canceled` found the current
+    /// revision canceled, and will unwind.
+    WasCanceled {
+        /// The database-key of the query that will unwind, or `None`
+        /// if no query is executing. Implements `Debug`.
+        database_key: Option<DatabaseKeyIndex>,
+    },
 }
 
 impl fmt::Debug for EventKind {
     fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            EventKind::DidValidateMemoizedValue { database_key } => fmt
+            EventKind::DidValidateMemoizedValue {
+                database_key,
+                revision,
+            } => fmt
                 .debug_struct("DidValidateMemoizedValue")
                 .field("database_key", database_key)
+                .field("revision", revision)
                 .finish(),
             EventKind::WillBlockOn {
                 other_runtime_id,
@@ -165,10 +654,175 @@ impl fmt::Debug for EventKind {
                 .debug_struct("WillExecute")
                 .field("database_key", database_key)
                 .finish(),
+            EventKind::WillDiscardStaleValue { database_key } => fmt
+                .debug_struct("WillDiscardStaleValue")
+                .field("database_key", database_key)
+                .finish(),
+            EventKind::DidSetInput {
+                database_key,
+                durability,
+                revision,
+            } => fmt
+                .debug_struct("DidSetInput")
+                .field("database_key", database_key)
+                .field("durability", durability)
+                .field("revision", revision)
+                .finish(),
+            EventKind::DeepRecursion {
+                database_key,
+                depth,
+            } => fmt
+                .debug_struct("DeepRecursion")
+                .field("database_key", database_key)
+                .field("depth", depth)
+                .finish(),
+            EventKind::WillCheckCancellation { database_key } => fmt
+                .debug_struct("WillCheckCancellation")
+                .field("database_key", database_key)
+                .finish(),
+            EventKind::WasCanceled { database_key } => fmt
+                .debug_struct("WasCanceled")
+                .field("database_key", database_key)
+                .finish(),
+        }
+    }
+}
+
+/// An `Event` whose database keys have been rendered as strings; see
+/// `Event::resolve`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ResolvedEvent {
+    /// The id of the snapshot that triggered the event.
+    pub runtime_id: RuntimeId,
+
+    /// What sort of event was it.
+    pub kind: ResolvedEventKind,
+}
+
+/// The kinds of `ResolvedEvent`; each corresponds to the `EventKind`
+/// variant of the same name.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ResolvedEventKind {
+    /// See `EventKind::DidValidateMemoizedValue`.
+    DidValidateMemoizedValue {
+        /// The affected query and key.
+        database_key: ResolvedKey,
+
+        /// The revision in which the value was validated.
+        revision: Revision,
+    },
+
+    /// See `EventKind::WillBlockOn`.
+    WillBlockOn {
+        /// The id of the runtime we will block on.
+        other_runtime_id: RuntimeId,
+
+        /// The affected query and key.
+        database_key: ResolvedKey,
+    },
+
+    /// See `EventKind::WillExecute`.
+    WillExecute {
+        /// The affected query and key.
+        database_key: ResolvedKey,
+    },
+
+    /// See `EventKind::WillDiscardStaleValue`.
+    WillDiscardStaleValue {
+        /// The affected query and key.
+        database_key: ResolvedKey,
+    },
+
+    /// See `EventKind::DidSetInput`.
+    DidSetInput {
+        /// The input query and key that was set.
+        database_key: ResolvedKey,
+
+        /// The durability the input was set with.
+        durability: Durability,
+
+        /// The revision started by setting the input.
+        revision: Revision,
+    },
+
+    /// See `EventKind::DeepRecursion`.
+    DeepRecursion {
+        /// The query and key that will be executed.
+        database_key: ResolvedKey,
+
+        /// The number of executions of the query on the stack.
+        depth: usize,
+    },
+
+    /// See `EventKind::WillCheckCancellation`.
+    WillCheckCancellation {
+        /// The query and key that is checking, if any.
+        database_key: Option<ResolvedKey>,
+    },
+
+    /// See `EventKind::WasCanceled`.
+    WasCanceled {
+        /// The query and key that will unwind, if any.
+        database_key: Option<ResolvedKey>,
+    },
+}
+
+impl ResolvedEventKind {
+    /// The query and key that the event concerns, if any (cancellation
+    /// checks outside of a query concern none).
+    pub fn database_key(&self) -> Option<&ResolvedKey> {
+        match self {
+            ResolvedEventKind::DidValidateMemoizedValue { database_key, .. }
+            | ResolvedEventKind::WillBlockOn { database_key, .. }
+            | ResolvedEventKind::WillExecute { database_key }
+            | ResolvedEventKind::WillDiscardStaleValue { database_key }
+            | ResolvedEventKind::DidSetInput { database_key, .. }
+            | ResolvedEventKind::DeepRecursion { database_key, .. } => Some(database_key),
+            ResolvedEventKind::WillCheckCancellation { database_key }
+            | ResolvedEventKind::WasCanceled { database_key } => database_key.as_ref(),
         }
     }
 }
 
+/// A `DatabaseKeyIndex`, rendered as the name of its query and the
+/// `Debug` output of its key. It displays as `query_name(key)`, like
+/// `DatabaseKeyIndex::debug`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ResolvedKey {
+    /// The name of the query (e.g. `parse_file`).
+    pub query_name: String,
+
+    /// The `Debug` output of the key (e.g. `FileId(3)`).
+    pub key: String,
+}
+
+impl ResolvedKey {
+    fn new<D>(index: DatabaseKeyIndex, db: &D) -> Self
+    where
+        D: ?Sized + plumbing::DatabaseOps,
+    {
+        // The storages all format an index as `query_name(key)`, and
+        // query names are identifiers, so the first `(` ends the name.
+        let rendered = format!("{:?}", index.debug(db));
+        match rendered.find('(') {
+            Some(paren) if rendered.ends_with(')') => ResolvedKey {
+                query_name: rendered[..paren].to_string(),
+                key: rendered[paren + 1..rendered.len() - 1].to_string(),
+            },
+            _ => ResolvedKey {
+                query_name: rendered,
+                key: String::new(),
+            },
+        }
+    }
+}
+
+impl fmt::Display for ResolvedKey {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(fmt, "{}({})", self.query_name, self.key)
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 enum DiscardIf {
     Never,
@@ -199,11 +853,16 @@ impl Default for DiscardWhat {
 /// do a GC-sweep. The default (`SweepStrategy::default`) is a no-op,
 /// use `SweepStrategy::discard_outdated` constructor or `discard_*`
 /// and `sweep_*` builder functions to construct useful strategies.
-#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
 pub struct SweepStrategy {
     discard_if: DiscardIf,
     discard_what: DiscardWhat,
     shrink_to_fit: bool,
+
+    /// Queries that are never discarded (see `keep_roots`). This
+    /// initially holds the roots; when the sweep starts, it is
+    /// replaced by everything reachable from them.
+    keep: Option<Arc<FxHashSet<DatabaseKeyIndex>>>,
 }
 
 impl SweepStrategy {
@@ -217,11 +876,79 @@ impl SweepStrategy {
             .sweep_outdated()
     }
 
+    /// Discards all derived values, regardless of when they were last
+    /// used, except those of the given "root" queries and everything
+    /// they (transitively) depend on. Unlike `discard_outdated`, which
+    /// keeps whatever was used in the current revision, this follows
+    /// the dependency edges recorded when the roots were last executed,
+    /// so the roots need not have been (re-)validated since the last
+    /// change.
+    ///
+    /// As with other strategies, derived values that read untracked
+    /// state in the current revision, and interned values accessed in
+    /// the current revision, are always kept.
+    ///
+    /// Equivalent to
+    /// `SweepStrategy::default().discard_everything().sweep_all_revisions()`,
+    /// plus the set of roots to keep.
+    pub fn keep_roots(roots: impl IntoIterator<Item = DatabaseKeyIndex>) -> SweepStrategy {
+        SweepStrategy {
+            keep: Some(Arc::new(roots.into_iter().collect())),
+            ..SweepStrategy::default()
+                .discard_everything()
+                .sweep_all_revisions()
+        }
+    }
+
+    /// Returns true if `index` must be kept by this sweep (see
+    /// `keep_roots`).
+    pub(crate) fn keeps(&self, index: DatabaseKeyIndex) -> bool {
+        match &self.keep {
+            Some(keep) => keep.contains(&index),
+            None => false,
+        }
+    }
+
+    /// Expands the roots given to `keep_roots`, if any, into the set of
+    /// all queries reachable from them.
+    pub(crate) fn resolve_roots<DB>(self, db: &DB) -> SweepStrategy
+    where
+        DB: ?Sized + plumbing::DatabaseOps,
+    {
+        let roots = match &self.keep {
+            Some(roots) => roots,
+            None => return self,
+        };
+
+        #[derive(Default)]
+        struct Reachable(FxHashSet<DatabaseKeyIndex>);
+
+        impl DependencyVisitor for Reachable {
+            fn enter(&mut self, node: DatabaseKeyIndex) -> bool {
+                self.0.insert(node);
+                true
+            }
+        }
+
+        let mut reachable = Reachable::default();
+        walk::walk_dependencies(db, roots.iter().copied(), &mut reachable);
+
+        SweepStrategy {
+            keep: Some(Arc::new(reachable.0)),
+            ..self
+        }
+    }
+
     /// Collects query values.
     ///
     /// Query dependencies are left in the database, which allows to quickly
     /// determine if the query is up to date, and avoid recomputing
     /// dependencies.
+    ///
+    /// That is, the value's memory is reclaimed but its dependency list
+    /// and verified revision are kept. Queries that read it can still be
+    /// validated (by checking those dependencies) without recomputing
+    /// it; only reading the query itself recomputes the value.
     pub fn discard_values(self) -> SweepStrategy {
         SweepStrategy {
             discard_what: self.discard_what.max(DiscardWhat::Values),
@@ -487,19 +1214,395 @@ where
     /// queries (those with no inputs, or those with more than one
     /// input) the key will be a tuple.
     pub fn get(&self, key: Q::Key) -> Q::Value {
-        self.try_get(key).unwrap_or_else(|err| panic!("{}", err))
+        self.try_get(key)
+            .unwrap_or_else(|err| panic!("{}", err.display(self.db)))
+    }
+
+    /// Like `get`, for a query whose value is an `Option`, but returns
+    /// the default value in place of `None`. The dependency recorded is
+    /// the same as for `get`.
+    pub fn get_or_default<T>(&self, key: Q::Key) -> T
+    where
+        Q: Query<Value = Option<T>>,
+        T: Default,
+    {
+        self.get(key).unwrap_or_default()
     }
 
     fn try_get(&self, key: Q::Key) -> Result<Q::Value, CycleError<DatabaseKeyIndex>> {
         self.storage.try_fetch(self.db, &key)
     }
 
+    /// Returns the value of the query for the given key, but only if
+    /// it has already been computed and verified in the current
+    /// revision; otherwise returns `None`. Unlike `get`, this never
+    /// executes (or revalidates) the query and does not record a
+    /// dependency on it, so a value left over from an earlier revision
+    /// yields `None` even if it would still be valid.
+    pub fn peek(&self, key: &Q::Key) -> Option<Q::Value> {
+        self.storage.peek(self.db, key)
+    }
+
+    /// Returns the revision in which the value of the query for `key`
+    /// last changed, but (as with `peek`) only if it has already been
+    /// computed and verified in the current revision; otherwise returns
+    /// `None`. This never executes the query or records a dependency.
+    /// A value that was re-executed but came out equal to the old one
+    /// keeps the old revision, so this can tell external systems that
+    /// work derived from the value need not be redone.
+    pub fn changed_at(&self, key: &Q::Key) -> Option<Revision> {
+        self.storage.changed_at(self.db, key)
+    }
+
+    /// True unless the value of the query for `key` is known not to
+    /// have changed since `revision` (see `changed_at`). If it was not
+    /// verified in the current revision, this conservatively returns
+    /// true.
+    pub fn changed_since(&self, key: &Q::Key, revision: Revision) -> bool {
+        match self.changed_at(key) {
+            Some(changed_at) => changed_at > revision,
+            None => true,
+        }
+    }
+
+    /// Returns the value of an "input query" for `key`, or `None` if
+    /// it has not been set (and the query has no default). Unlike `get`,
+    /// this does not panic for unset keys; either way, the active query
+    /// records a dependency on `key`, so it is re-executed once `key` is
+    /// set.
+    ///
+    /// This is what the generated `try_foo` methods of input queries
+    /// use.
+    pub fn get_if_set(&self, key: Q::Key) -> Option<Q::Value>
+    where
+        Q::Storage: plumbing::InputQueryStorageOps<Q>,
+    {
+        self.storage.fetch_if_set(self.db, &key)
+    }
+
+    /// Executes the query for `key` (if needed) and returns the values
+    /// pushed to the accumulator `A` while executing it and all of the
+    /// queries it (transitively) read. See `plumbing::accumulated` for
+    /// the order of the values.
+    pub fn accumulated<A>(&self, key: Q::Key) -> Vec<A::Data>
+    where
+        A: Accumulator,
+        Q::Storage: plumbing::DerivedQueryStorageOps<Q>,
+    {
+        self.get(key.clone());
+        match self.storage.database_key_index(&key) {
+            Some(index) => {
+                accumulator::accumulated::<A>(plumbing::DatabaseOps::ops_database(self.db), index)
+            }
+            None => vec![],
+        }
+    }
+
+    /// Returns the ids of the values currently interned by this
+    /// (interned) query, in the order in which they were first
+    /// interned. Values that were collected by a sweep are skipped;
+    /// if re-interned, they count as new. Unlike iterating over the
+    /// table entries, the order is deterministic, even though ids may
+    /// be reused after a sweep.
+    pub fn intern_keys_in_insertion_order(&self) -> impl Iterator<Item = InternId>
+    where
+        Q::Storage: plumbing::InternedQueryStorageOps<Q>,
+    {
+        self.storage.interned_ids().into_iter()
+    }
+
+    /// Interns the key borrowed as `key` with this (interned) query,
+    /// just like calling the query would, except that the owned key is
+    /// only created (with `key.to_owned().into()`) if it is not interned
+    /// yet. For a query interning `String`s, this looks up a `&str`
+    /// without allocating:
+    ///
+    /// ```rust,ignore
+    /// let name = InternNameQuery.in_db(db).intern_borrowed("main");
+    /// ```
+    ///
+    /// Unsized keys can be interned by giving the query a boxed key,
+    /// like `Box<[u8]>` or `Arc<[Token]>`, and interning slices:
+    ///
+    /// ```rust,ignore
+    /// let bytes = InternBytesQuery.in_db(db).intern_borrowed(&b"main"[..]);
+    /// ```
+    pub fn intern_borrowed<B>(&self, key: &B) -> Q::Value
+    where
+        Q::Storage: plumbing::InternedQueryStorageOps<Q>,
+        Q::Key: std::borrow::Borrow<B>,
+        B: ?Sized + Hash + Eq + ToOwned,
+        B::Owned: Into<Q::Key>,
+    {
+        self.storage.intern_borrowed(self.db, key)
+    }
+
+    /// Interns each of `keys` with this (interned) query, returning
+    /// their interned values in order, just like calling the query for
+    /// each would (keys that are interned already get their existing
+    /// ids). The interning table is locked only once for the whole
+    /// batch, which is cheaper than locking it for each key when
+    /// interning many keys at once, for instance all the names in a
+    /// newly parsed file:
+    ///
+    /// ```rust,ignore
+    /// let ids = InternNameQuery.in_db(db).intern_many(names);
+    /// ```
+    pub fn intern_many<I>(&self, keys: I) -> Vec<Q::Value>
+    where
+        Q::Storage: plumbing::InternedQueryStorageOps<Q>,
+        I: IntoIterator<Item = Q::Key>,
+    {
+        self.storage.intern_many(self.db, keys)
+    }
+
+    /// Interns `key` with this (interned) query, just like calling the
+    /// query would, except that if `key` is not interned yet and the
+    /// table already holds as many values as its capacity allows (see
+    /// `QueryTableMut::set_intern_capacity`), it returns an error rather
+    /// than panicking. Looking up a value that is already interned always
+    /// succeeds. This is what the generated `try_intern_foo` methods call.
+    pub fn try_intern(&self, key: Q::Key) -> Result<Q::Value, InternCapacityExceeded>
+    where
+        Q::Storage: plumbing::InternedQueryStorageOps<Q>,
+    {
+        self.storage.try_intern(self.db, &key)
+    }
+
+    /// Interns `key` with this (interned) query, giving it the id `id`
+    /// rather than the next free one, so that ids can match those of an
+    /// external system. Returns an error if another value already has
+    /// that id, or if `key` is already interned with a different id;
+    /// interning `key` with the id it already has just returns it.
+    ///
+    /// Values interned later get ids that are still free, so they skip
+    /// over the ids assigned this way. The table grows to hold the
+    /// largest id given (the ids below it that are not taken stay free
+    /// for later values), so the ids should not be too sparse.
+    pub fn intern_with_id(&self, key: Q::Key, id: InternId) -> Result<Q::Value, InternWithIdError>
+    where
+        Q::Storage: plumbing::InternedQueryStorageOps<Q>,
+    {
+        self.storage.intern_with_id(self.db, key, id)
+    }
+
+    /// Looks up the value interned as `key`, for the lookup query of an
+    /// interned query, just like calling the query would, except that
+    /// the value is not cloned. This is what the generated
+    /// `lookup_foo_ref` methods call.
+    pub fn lookup_ref(&self, key: Q::Key) -> InternedRef<Q::Value>
+    where
+        Q::Storage: plumbing::LookupInternedQueryStorageOps<Q>,
+    {
+        self.storage.lookup_ref(self.db, &key)
+    }
+
+    /// Looks up the value interned as `key`, for the lookup query of an
+    /// interned query, or returns `None` if nothing is interned as
+    /// `key`: calling the query panics instead. This is what the
+    /// generated `try_lookup_foo` methods call, and suits ids that come
+    /// from outside of the database (e.g. deserialized ones), which
+    /// may have been freed or belong to another table.
+    ///
+    /// If it returns `None`, the active query is treated as having an
+    /// untracked read, since a value may be interned as `key` later.
+    pub fn try_lookup(&self, key: Q::Key) -> Option<Q::Value>
+    where
+        Q::Storage: plumbing::LookupInternedQueryStorageOps<Q>,
+    {
+        self.storage.try_lookup(self.db, &key)
+    }
+
+    /// Returns the keys and values of this (derived) query that are
+    /// memoized and were verified in the current revision, in no
+    /// particular order. Values that might be stale are left out (as
+    /// are those of `#[salsa::dependencies]` queries, which are not
+    /// memoized), so this suits saving the valid results of a query,
+    /// for instance to a cache on disk.
+    pub fn verified_entries(&self) -> impl Iterator<Item = (Q::Key, Q::Value)>
+    where
+        Q::Storage: plumbing::DerivedQueryStorageOps<Q>,
+    {
+        self.storage
+            .verified_entries(self.db.salsa_runtime())
+            .into_iter()
+    }
+
+    /// Returns the version of the query group of this query, as set with
+    /// `#[salsa::query_group(MyStorage, version = 3)]` (or 0). Save it
+    /// along with the `verified_entries` of the query, and pass it to
+    /// `QueryTableMut::prime_versioned` when loading them back in.
+    pub fn group_version(&self) -> u32 {
+        <<Q as QueryDb<'me>>::Group as plumbing::QueryGroup>::VERSION
+    }
+
+    /// Returns the keys of this (derived) query whose values are
+    /// memoized and were verified in the current revision, in no
+    /// particular order: the keys of `verified_entries`, without
+    /// cloning the values. Like `peek`, this does not record a
+    /// dependency, so it suits diagnostics (like counting the cached
+    /// results of a query) rather than queries.
+    pub fn memoized_keys(&self) -> impl Iterator<Item = Q::Key>
+    where
+        Q::Storage: plumbing::DerivedQueryStorageOps<Q>,
+    {
+        self.storage
+            .memoized_keys(self.db.salsa_runtime())
+            .into_iter()
+    }
+
+    /// Describes the size of the table of this (interned) query: the
+    /// number of values, the capacity of the underlying hash map, and
+    /// its load factor. The map does not report how long its probe
+    /// sequences are, so collisions cannot be measured directly; a
+    /// slow interner with a low load factor suggests a poor hash.
+    pub fn intern_stats(&self) -> debug::InternStats
+    where
+        Q::Storage: plumbing::InternedQueryStorageOps<Q>,
+    {
+        self.storage.intern_stats()
+    }
+
+    /// Returns the capacity set with `set_lru_capacity`, or `None`
+    /// if LRU caching is disabled for this query.
+    pub fn lru_capacity(&self) -> Option<usize>
+    where
+        Q::Storage: plumbing::LruQueryStorageOps,
+    {
+        self.storage.lru_capacity()
+    }
+
+    /// Returns the number of keys for which this query currently has
+    /// a memoized value. Keys whose value was discarded (e.g., by LRU
+    /// eviction or a sweep) are not counted.
+    pub fn len(&self) -> usize
+    where
+        Q::Storage: plumbing::LruQueryStorageOps,
+    {
+        self.storage.memoized_len()
+    }
+
+    /// Returns true if this query has no memoized values (see `len`).
+    pub fn is_empty(&self) -> bool
+    where
+        Q::Storage: plumbing::LruQueryStorageOps,
+    {
+        self.len() == 0
+    }
+
+    /// Returns the queries that were read (directly) by the most
+    /// recent execution of this query for the given key, in the order
+    /// they were read. Returns `None` if the query has no memoized value
+    /// for `key`, or if its inputs are not known (e.g., because it
+    /// reported an untracked read).
+    ///
+    /// Use `DatabaseKeyIndex::debug` to get readable names for the
+    /// results.
+    pub fn dependencies(&self, key: &Q::Key) -> Option<Vec<DatabaseKeyIndex>>
+    where
+        Q::Storage: plumbing::DerivedQueryStorageOps<Q>,
+    {
+        DerivedQueryStorageOps::dependencies(self.storage, key)
+    }
+
+    /// Returns the durability recorded for the memoized value of `key`:
+    /// the lowest durability of the inputs it read, transitively. Returns
+    /// `None` if the query has no memoized value for `key`.
+    ///
+    /// A query that is expected to have high durability but reports
+    /// `Durability::LOW` depends on some low durability input; follow
+    /// `dependencies` to find out which. Unlike
+    /// `DebugQueryTable::durability`, this reports the recorded
+    /// durability even if the value has not been verified since an input
+    /// of that durability changed.
+    pub fn memoized_durability(&self, key: &Q::Key) -> Option<Durability>
+    where
+        Q::Storage: plumbing::DerivedQueryStorageOps<Q>,
+    {
+        DerivedQueryStorageOps::memoized_durability(self.storage, key)
+    }
+
+    /// Returns the last few distinct values that this (derived) query
+    /// computed for `key`, oldest first, each with the revision in which
+    /// it became the value (its `changed_at`). How many are kept is set
+    /// with `#[salsa::history(depth = N)]`; queries without it keep
+    /// none. Only values that were actually computed are recorded (not
+    /// ones that were merely validated, or computed from provisional
+    /// values), and the history survives sweeping and eviction, so it
+    /// can tell what the query returned a few edits ago.
+    pub fn history(&self, key: &Q::Key) -> Vec<(Revision, Q::Value)>
+    where
+        Q::Storage: plumbing::DerivedQueryStorageOps<Q>,
+    {
+        DerivedQueryStorageOps::history(self.storage, key)
+    }
+
+    /// Returns the revision in which this (derived) query was first
+    /// executed for `key`, or `None` if it has no memoized value. Unlike
+    /// the revision in which the value last changed, this stays the
+    /// same when the query is re-executed, so comparing the two tells
+    /// long-lived values apart from ones that change often. It starts
+    /// over if the memo is discarded altogether (by
+    /// `QueryTableMut::clear`, or sweeping with `discard_everything`),
+    /// but not when only the value is (as by LRU eviction).
+    pub fn first_computed_at(&self, key: &Q::Key) -> Option<Revision>
+    where
+        Q::Storage: plumbing::DerivedQueryStorageOps<Q>,
+    {
+        DerivedQueryStorageOps::first_computed_at(self.storage, key)
+    }
+
+    /// Returns true if the value of this (derived) query for `key` is
+    /// overridden (see `QueryTableMut::set_override`).
+    pub fn is_overridden(&self, key: &Q::Key) -> bool
+    where
+        Q::Storage: plumbing::DerivedQueryStorageOps<Q>,
+    {
+        DerivedQueryStorageOps::is_overridden(self.storage, key)
+    }
+
+    /// Like `get`, for a derived query, but also tells whether the
+    /// value came from the memo (as is, or after validating it) or the
+    /// query had to be executed. This is what the generated
+    /// `foo_probed` methods call; the dependency recorded is the same
+    /// as for `get`.
+    pub fn get_probed(&self, key: Q::Key) -> (Q::Value, CacheOutcome)
+    where
+        Q::Storage: plumbing::DerivedQueryStorageOps<Q>,
+    {
+        DerivedQueryStorageOps::fetch_probed(self.storage, self.db, &key)
+            .unwrap_or_else(|err| panic!("{}", err.display(self.db)))
+    }
+
+    /// Registers `callback` to be invoked with each new value of this
+    /// (derived) query for `key`: once the query was re-executed for
+    /// `key` in a new revision, and its value turned out to change, the
+    /// callback is invoked with the new value. This only happens when the
+    /// query is brought up to date, by reading it (directly, or through
+    /// a query that depends on it) or with `Database::validate`, since
+    /// salsa does not re-execute queries otherwise; calling `validate`
+    /// for the subscribed keys after setting inputs is a way to be
+    /// notified of every change.
+    ///
+    /// If `key` has no memoized value yet, the callback is also invoked
+    /// with the first one. Callbacks are invoked on the thread that
+    /// brought the value up to date, and must not use the database.
+    /// Subscriptions last as long as the database (they are not kept by
+    /// `Storage::fork`).
+    pub fn subscribe(&self, key: Q::Key, callback: impl Fn(&Q::Value) + Send + Sync + 'static)
+    where
+        Q::Storage: plumbing::DerivedQueryStorageOps<Q>,
+    {
+        DerivedQueryStorageOps::subscribe(self.storage, &key, Box::new(callback))
+    }
+
     /// Remove all values for this query that have not been used in
     /// the most recent revision.
     pub fn sweep(&self, strategy: SweepStrategy)
     where
         Q::Storage: plumbing::QueryStorageMassOps,
     {
+        let strategy = strategy.resolve_roots(self.db);
         self.storage.sweep(self.db.salsa_runtime(), strategy);
     }
     /// Completely clears the storage for this query.
@@ -540,6 +1643,10 @@ where
     /// Assign a value to an "input query". Must be used outside of
     /// an active query computation.
     ///
+    /// This starts a new revision, unless the input was declared with
+    /// `#[salsa::input(eq)]` and already has an equal value (with the
+    /// same durability).
+    ///
     /// If you are using `snapshot`, see the notes on blocking
     /// and cancellation on [the `query_mut` method].
     ///
@@ -566,6 +1673,88 @@ where
         self.storage.set(self.db, &key, value, durability);
     }
 
+    /// Like `set`, but only sets the input if it does not already have
+    /// a value equal to `value` (by `PartialEq`, even if the input was
+    /// not declared with `#[salsa::input(eq)]`) and `Durability::LOW`.
+    /// Returns true if the value was set, and so started a new revision,
+    /// which tells whether any query may need to re-execute.
+    pub fn set_if_changed(&mut self, key: Q::Key, value: Q::Value) -> bool
+    where
+        Q::Storage: plumbing::InputQueryStorageOps<Q>,
+        Q::Value: PartialEq,
+    {
+        if self
+            .storage
+            .has_value(&key, Durability::LOW, &mut |old_value| *old_value == value)
+        {
+            return false;
+        }
+        self.set(key, value);
+        true
+    }
+
+    /// Interns `key` with the interned query `IQ`, which must belong to
+    /// the same query group, and sets this input for the resulting
+    /// id to `value`, returning the id. This is the same as interning
+    /// and then calling `set`, so it starts one new revision:
+    ///
+    /// ```rust,ignore
+    /// let file = FileTextQuery
+    ///     .in_db_mut(db)
+    ///     .set_interned::<InternPathQuery>(path, text);
+    /// ```
+    pub fn set_interned<IQ>(&mut self, key: IQ::Key, value: Q::Value) -> Q::Key
+    where
+        Q::Storage: plumbing::InputQueryStorageOps<Q>,
+        IQ: Query<Value = Q::Key>
+            + for<'d> QueryDb<
+                'd,
+                DynDb = <Q as QueryDb<'d>>::DynDb,
+                GroupStorage = <Q as QueryDb<'d>>::GroupStorage,
+            >,
+        IQ::Storage: plumbing::InternedQueryStorageOps<IQ>,
+    {
+        let db: &<Q as QueryDb<'me>>::DynDb = &*self.db;
+        let group_storage: &<Q as QueryDb<'me>>::GroupStorage = HasQueryGroup::group_storage(db);
+        let id = IQ::query_storage(group_storage).intern_borrowed(db, &key);
+        self.set(id.clone(), value);
+        id
+    }
+
+    /// Removes the value of an "input query" for `key`, returning the
+    /// value, if there was one. Afterwards, reading `key` behaves as if
+    /// it had never been set: it panics, or gives the query's default
+    /// value (see `#[salsa::default]`). Queries that read the removed
+    /// value are re-executed, as on any other write; if there was no
+    /// value, nothing happens.
+    ///
+    /// The storage for the removed value is freed, though a small
+    /// amount of bookkeeping for `key` is kept.
+    ///
+    /// Like `set`, this must be used outside of an active query
+    /// computation, and blocks in the same way.
+    pub fn remove(&mut self, key: Q::Key) -> Option<Q::Value>
+    where
+        Q::Storage: plumbing::InputQueryStorageOps<Q>,
+    {
+        self.storage.remove(self.db, &key)
+    }
+
+    /// Assign values to many keys of an "input query" at once. This
+    /// is equivalent to calling `set` for each pair, except that only
+    /// one new revision is created, rather than one per key (which
+    /// makes bulk loads cheaper, as dependent queries only need to be
+    /// re-validated once). If `values` is empty, nothing happens.
+    ///
+    /// Like `set`, this must be used outside of an active query
+    /// computation, and blocks in the same way.
+    pub fn set_many(&mut self, values: impl IntoIterator<Item = (Q::Key, Q::Value)>)
+    where
+        Q::Storage: plumbing::InputQueryStorageOps<Q>,
+    {
+        self.storage.set_many(self.db, values, Durability::LOW);
+    }
+
     /// Sets the size of LRU cache of values for this query table.
     ///
     /// That is, at most `cap` values will be preset in the table at the same
@@ -580,10 +1769,174 @@ where
         self.storage.set_lru_capacity(cap);
     }
 
+    /// Limits the number of values that this (interned) query holds at
+    /// once to `capacity`, or lifts the limit if it is `None` (the
+    /// default). Once the table is full, interning a new value panics,
+    /// except with `QueryTable::try_intern` (and the generated
+    /// `try_intern_foo` methods), which return an error instead; values
+    /// that are already interned can still be looked up and interned
+    /// again. Values freed by sweeping or `gc_interned` make room for new
+    /// ones. Lowering the capacity below the number of values does not
+    /// free any.
+    pub fn set_intern_capacity(&self, capacity: Option<usize>)
+    where
+        Q::Storage: plumbing::InternedQueryStorageOps<Q>,
+    {
+        self.storage.set_intern_capacity(capacity);
+    }
+
+    /// Keeps the value for `key` out of the LRU cache of this query, so
+    /// that it is never evicted to stay within the capacity set by
+    /// `set_lru_capacity`; it is kept until `unpin` is called, or until
+    /// it is discarded by sweeping.
+    ///
+    /// A language server could pin the queries of the files that are
+    /// open in the editor, for instance, so that looking them up never
+    /// stalls on recomputing a value that was evicted.
+    pub fn pin(&self, key: &Q::Key)
+    where
+        Q::Storage: plumbing::DerivedQueryStorageOps<Q>,
+    {
+        self.storage.set_pinned(key, true);
+    }
+
+    /// Undoes `pin`, letting the value for `key` be evicted again
+    /// (once it has been used again, it takes its place in the LRU
+    /// cache like any other value).
+    pub fn unpin(&self, key: &Q::Key)
+    where
+        Q::Storage: plumbing::DerivedQueryStorageOps<Q>,
+    {
+        self.storage.set_pinned(key, false);
+    }
+
+    /// Frees the values of this (interned) query for which `keep`
+    /// returns false, returning how many were freed. Their ids are
+    /// reused for values interned later, so -- as with sweeping (see
+    /// `InternKey`) -- any id for a freed value that is held outside the
+    /// database becomes invalid: looking it up may panic, or give a
+    /// different value.
+    ///
+    /// If any value is freed, this starts a new revision, and queries
+    /// that interned (or looked up) a freed value are re-executed when
+    /// they are next needed. Like `set`, it must be used outside of an
+    /// active query computation, and blocks in the same way.
+    pub fn gc_interned(&mut self, mut keep: impl FnMut(&Q::Key) -> bool) -> usize
+    where
+        Q::Storage: plumbing::InternedQueryStorageOps<Q>,
+    {
+        self.storage.retain(self.db, &mut keep)
+    }
+
+    /// Renumbers the values of this (interned) query in the order of
+    /// their keys, as if they had been interned in that order into an
+    /// empty table, and returns the new id of each old one. Ids are
+    /// otherwise handed out in the order values are first interned,
+    /// which depends on the order in which queries execute (and so can
+    /// differ between runs, or with parallel snapshots); sealing the
+    /// table once its values are interned makes the ids, and any output
+    /// that embeds them, reproducible.
+    ///
+    /// If any id changes, this starts a new revision, and queries that
+    /// interned (or looked up) a value whose id changed are re-executed
+    /// when they are next needed. Salsa cannot rewrite ids stored
+    /// elsewhere, though: inputs that hold ids must be set again with
+    /// the ids mapped through the returned table, and any id held
+    /// outside the database is invalid unless mapped. Like `set`, it
+    /// must be used outside of an active query computation, and blocks
+    /// in the same way.
+    pub fn seal_interner(&mut self) -> std::collections::HashMap<InternId, InternId>
+    where
+        Q::Key: Ord,
+        Q::Storage: plumbing::InternedQueryStorageOps<Q>,
+    {
+        self.storage.seal(self.db)
+    }
+
+    /// Memoizes `value` as the result of this (derived) query for `key`,
+    /// as though the query had just been executed and had read the
+    /// queries in `inputs`. This is the counterpart of
+    /// `QueryTable::verified_entries`, for loading saved results back
+    /// in. Priming starts a new revision (so that queries which already
+    /// read the old value see the primed one): the value is used as is
+    /// in that revision, and in later revisions it is validated against
+    /// `inputs` as usual, so the query is re-executed once any of them
+    /// changes.
+    ///
+    /// Salsa cannot check that the value and inputs are right, so it is
+    /// up to you to give the inputs that the query would actually read
+    /// (note that a `DatabaseKeyIndex` is only meaningful for the
+    /// database that it came from: the indices of the same keys may
+    /// differ between databases). The value is taken to have the lowest
+    /// durability, `Durability::LOW`. For `#[salsa::dependencies]`
+    /// queries, which do not memoize values, only the inputs are kept.
+    pub fn prime(&mut self, key: Q::Key, value: Q::Value, inputs: Vec<DatabaseKeyIndex>)
+    where
+        Q::Storage: plumbing::DerivedQueryStorageOps<Q>,
+    {
+        self.storage.prime(self.db, key, value, inputs)
+    }
+
+    /// Like `prime`, for each of the saved `entries` (each a key, its
+    /// value and its inputs), but only if `version` is the version of
+    /// this query's group (see `QueryTable::group_version`). Otherwise,
+    /// the entries were saved by a different version of the queries,
+    /// so they are discarded rather than trusted, and the queries are
+    /// executed as usual. Returns true if the entries were primed.
+    ///
+    /// To have saved entries discarded after changing how the queries
+    /// of a group compute their values, bump its version, as in
+    /// `#[salsa::query_group(MyStorage, version = 4)]`.
+    pub fn prime_versioned<I>(&mut self, version: u32, entries: I) -> bool
+    where
+        Q::Storage: plumbing::DerivedQueryStorageOps<Q>,
+        I: IntoIterator<Item = (Q::Key, Q::Value, Vec<DatabaseKeyIndex>)>,
+    {
+        if version != <<Q as QueryDb<'me>>::Group as plumbing::QueryGroup>::VERSION {
+            return false;
+        }
+        for (key, value, inputs) in entries {
+            self.storage.prime(self.db, key, value, inputs);
+        }
+        true
+    }
+
+    /// Overrides the value of this (derived) query for `key`, in a new
+    /// revision: until the override is cleared (with `clear_override`),
+    /// reading the query gives `value`, without executing it, as
+    /// though it were an input. This suits mocking derived results in
+    /// tests, or layering unsaved edits over computed values. Queries
+    /// that read the old value are re-executed as after setting an
+    /// input; the override has the lowest durability, `Durability::LOW`.
+    ///
+    /// Overridden values are kept by sweeping, LRU eviction,
+    /// `invalidate` and `clear`. Like `set`, this must be used outside
+    /// of an active query computation, and blocks in the same way.
+    pub fn set_override(&mut self, key: Q::Key, value: Q::Value)
+    where
+        Q::Storage: plumbing::DerivedQueryStorageOps<Q>,
+    {
+        self.storage.set_override(self.db, key, value)
+    }
+
+    /// Clears the override of `key` (see `set_override`), in a new
+    /// revision, so that the query is executed again when it is next
+    /// read. Does nothing if `key` is not overridden.
+    pub fn clear_override(&mut self, key: &Q::Key)
+    where
+        Q::Storage: plumbing::DerivedQueryStorageOps<Q>,
+    {
+        self.storage.clear_override(self.db, key)
+    }
+
     /// Marks the computed value as outdated.
     ///
     /// This causes salsa to re-execute the query function on the next access to
-    /// the query, even if all dependencies are up to date.
+    /// the query, even if all dependencies are up to date. Unlike
+    /// sweeping, the memoized value is kept, so queries further
+    /// downstream can still be validated without re-executing. (The
+    /// queries that read this one directly are conservatively
+    /// re-executed, however, as its inputs are no longer known.)
     ///
     /// This is most commonly used as part of the [on-demand input
     /// pattern](https://salsa-rs.github.io/salsa/common_patterns/on_demand_inputs.html).
@@ -593,6 +1946,89 @@ where
     {
         self.storage.invalidate(self.db, key)
     }
+
+    /// Discards the memoized values of this (derived) query for all
+    /// keys, in a new revision, so each is re-executed the next time it
+    /// is needed. The queries that read one of them are re-executed
+    /// then, too, while queries that do not depend on this one are
+    /// validated as usual. Unlike `QueryTable::purge`, this leaves the database
+    /// consistent, so it can be used in tests of incremental behavior,
+    /// or to recover from a query that is suspected to have computed a
+    /// wrong value.
+    pub fn clear(&mut self)
+    where
+        Q::Storage: plumbing::DerivedQueryStorageOps<Q>,
+    {
+        self.storage.clear(self.db)
+    }
+
+    /// Begins setting the value of an "input query" for `key`,
+    /// returning an [`InputSetter`] on which further options may be
+    /// chosen before the value is given with [`InputSetter::to`].
+    ///
+    /// This is what the generated `set_foo` methods of input queries
+    /// use.
+    ///
+    /// [`InputSetter`]: struct.InputSetter.html
+    /// [`InputSetter::to`]: struct.InputSetter.html#method.to
+    pub fn setter(self, key: Q::Key) -> InputSetter<'me, Q> {
+        InputSetter {
+            table: self,
+            key,
+            durability: Durability::LOW,
+        }
+    }
+}
+
+/// Return value from the generated `set_foo` methods of input
+/// queries (and from [`QueryTableMut::setter`]). Nothing is written
+/// until [the `to` method] is called with the new value:
+///
+/// ```rust,ignore
+/// db.set_file_text(id).to(text);
+/// db.set_file_text(id).with_durability(Durability::HIGH).to(text);
+/// ```
+///
+/// [`QueryTableMut::setter`]: struct.QueryTableMut.html#method.setter
+/// [the `to` method]: struct.InputSetter.html#method.to
+#[must_use = "the input is not set until `to` is called"]
+pub struct InputSetter<'me, Q>
+where
+    Q: Query,
+{
+    table: QueryTableMut<'me, Q>,
+    key: Q::Key,
+    durability: Durability,
+}
+
+impl<'me, Q> InputSetter<'me, Q>
+where
+    Q: Query,
+{
+    /// Sets the durability of the new value (the default is
+    /// `Durability::LOW`). Higher durabilities are a promise that the
+    /// value will rarely (or never) change again.
+    pub fn with_durability(mut self, durability: Durability) -> Self {
+        self.durability = durability;
+        self
+    }
+
+    /// Assigns `value` to the input. Must be used outside of an
+    /// active query computation.
+    ///
+    /// *Note:* Setting values will trigger cancellation of any
+    /// ongoing queries; this method blocks until those queries have
+    /// been cancelled. See the notes on blocking and cancellation on
+    /// [the `query_mut` method].
+    ///
+    /// [the `query_mut` method]: trait.Database.html#method.query_mut
+    pub fn to(mut self, value: Q::Value)
+    where
+        Q::Storage: plumbing::InputQueryStorageOps<Q>,
+    {
+        self.table
+            .set_with_durability(self.key, value, self.durability);
+    }
 }
 
 /// The error returned when a query could not be resolved due to a cycle
@@ -617,6 +2053,146 @@ where
     }
 }
 
+impl CycleError<DatabaseKeyIndex> {
+    /// Like the `Display` impl, but describes each participant as given
+    /// by `DatabaseKeyIndex::debug`.
+    fn display<'me, D>(&'me self, db: &'me D) -> impl fmt::Display + 'me
+    where
+        D: ?Sized + plumbing::DatabaseOps,
+    {
+        CycleErrorDisplay { error: self, db }
+    }
+}
+
+/// Helper type for `CycleError::display`
+struct CycleErrorDisplay<'me, D: ?Sized>
+where
+    D: plumbing::DatabaseOps,
+{
+    error: &'me CycleError<DatabaseKeyIndex>,
+    db: &'me D,
+}
+
+impl<D: ?Sized> fmt::Display for CycleErrorDisplay<'_, D>
+where
+    D: plumbing::DatabaseOps,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Internal error, cycle detected:\n")?;
+        for i in &self.error.cycle {
+            writeln!(f, "{:?}", i.debug(self.db))?;
+        }
+        Ok(())
+    }
+}
+
+/// Describes a cycle between queries. This is given to the recovery
+/// function of queries annotated with `#[salsa::cycle(recover_fn)]`,
+/// which is invoked (instead of panicking) when the query turns out to
+/// depend on itself.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Cycle {
+    participants: Vec<DatabaseKeyIndex>,
+}
+
+impl Cycle {
+    pub(crate) fn new(participants: Vec<DatabaseKeyIndex>) -> Self {
+        Cycle { participants }
+    }
+
+    /// Returns the queries that participate in the cycle, in the order
+    /// they were (recursively) invoked.
+    pub fn participant_keys(&self) -> &[DatabaseKeyIndex] {
+        &self.participants
+    }
+
+    /// Returns a human-readable description of each participant, as
+    /// given by `DatabaseKeyIndex::debug` (e.g. `parse_file(FileId(3))`).
+    pub fn all_participants<DB>(&self, db: &DB) -> Vec<String>
+    where
+        DB: ?Sized + plumbing::DatabaseOps,
+    {
+        self.participants
+            .iter()
+            .map(|key| format!("{:?}", key.debug(db)))
+            .collect()
+    }
+}
+
+/// Controls what happens when a query turns out to depend on itself;
+/// see `Runtime::set_cycle_handling`.
+#[derive(Copy, Clone, Debug)]
+pub enum CycleHandling {
+    /// Queries annotated with `#[salsa::cycle(recover_fn)]` recover
+    /// using `recover_fn`; other queries panic. This is the default.
+    Static,
+
+    /// Every query in a cycle panics, even those annotated with
+    /// `#[salsa::cycle]` (including fixpoint queries, which do not
+    /// iterate).
+    Panic,
+
+    /// Every query in a cycle recovers using the given function,
+    /// which is called with the cycle and the query to recover. It
+    /// returns the (boxed) value to use for that query, or `None` to
+    /// fall back to the `#[salsa::cycle]` function (or panic, if there
+    /// is none). A value of the wrong type is treated like `None`.
+    RecoverWith(fn(&Cycle, DatabaseKeyIndex) -> Option<Box<dyn std::any::Any>>),
+}
+
+impl Default for CycleHandling {
+    fn default() -> CycleHandling {
+        CycleHandling::Static
+    }
+}
+
+/// Controls what happens when a query would exceed the depth set with
+/// `Runtime::set_max_query_depth`; see `Runtime::set_overflow_behavior`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum OverflowBehavior {
+    /// Unwind with a `QueryStackOverflow` payload, without invoking the
+    /// panic hook. This is the default.
+    Unwind,
+
+    /// Panic, with a message that lists the queries on the stack. This
+    /// suits tests, where the panic hook prints the message.
+    Panic,
+
+    /// Treat the query like one that depends on itself, with the queries
+    /// on the stack (and the query itself) as the cycle: queries that
+    /// can recover from cycles (with `#[salsa::cycle]`, or as directed
+    /// by `CycleHandling`) use their recovered value, and fixpoint
+    /// queries use their initial value, instead of being executed. The
+    /// value is not memoized, and neither are the values of the queries
+    /// that (directly or indirectly) read it, so they are executed again
+    /// when next needed. Other queries unwind, as with `Unwind`.
+    Recover,
+}
+
+impl Default for OverflowBehavior {
+    fn default() -> OverflowBehavior {
+        OverflowBehavior::Unwind
+    }
+}
+
+/// How a derived query obtained the value it returned, as reported by
+/// the generated `foo_probed` methods (see `QueryTable::get_probed`).
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum CacheOutcome {
+    /// The memoized value was already verified in the current revision
+    /// (or another thread computed it while this one waited), so it was
+    /// returned as is.
+    Hit,
+
+    /// The memoized value was from an earlier revision, and checking
+    /// its inputs showed that it is still up to date.
+    Validated,
+
+    /// The query was executed (or recovered from a cycle or a stack
+    /// overflow).
+    Computed,
+}
+
 // Re-export the procedural macros.
 #[allow(unused_imports)]
 #[macro_use]
@@ -5,6 +5,37 @@ use std::sync::atomic::AtomicUsize;
 use std::sync::atomic::Ordering;
 use std::sync::Arc;
 
+/// The LRU capacities of several queries, by name, to be set all at
+/// once with `Database::apply_lru_config`. This keeps the capacities
+/// of all queries (say, read from a configuration file) in one place:
+///
+/// ```rust,ignore
+/// let config = LruConfig::default().cap("parse", 500).cap("typecheck", 200);
+/// db.apply_lru_config(&config);
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct LruConfig {
+    caps: Vec<(String, usize)>,
+}
+
+impl LruConfig {
+    /// Sets the LRU capacity of the query named `query` (e.g. `"parse"`)
+    /// to `capacity`. A capacity of 0 disables LRU eviction, as with
+    /// `set_lru_capacity`. If the same query is given twice, the last
+    /// capacity wins.
+    pub fn cap(mut self, query: impl Into<String>, capacity: usize) -> Self {
+        self.caps.push((query.into(), capacity));
+        self
+    }
+
+    /// Returns the queries and their capacities, in the order given.
+    pub fn caps(&self) -> impl Iterator<Item = (&str, usize)> + '_ {
+        self.caps
+            .iter()
+            .map(|(query, capacity)| (query.as_str(), *capacity))
+    }
+}
+
 /// A simple and approximate concurrent lru list.
 ///
 /// We assume but do not verify that each node is only used with one
@@ -26,6 +57,7 @@ where
 
 #[derive(Debug)]
 struct LruData<Node> {
+    capacity: usize,
     end_red_zone: usize,
     end_yellow_zone: usize,
     end_green_zone: usize,
@@ -78,6 +110,7 @@ where
     /// once.  If `len` is zero, this disables LRU caching completely.
     pub fn set_lru_capacity(&self, len: usize) {
         let mut data = self.data.lock();
+        data.capacity = len;
 
         // We require each zone to have at least 1 slot. Therefore,
         // the length cannot be just 1 or 2.
@@ -128,6 +161,12 @@ where
         self.data.lock().record_use(node)
     }
 
+    /// Returns the capacity last given to `set_lru_capacity` (zero if
+    /// LRU caching is disabled).
+    pub fn lru_capacity(&self) -> usize {
+        self.data.lock().capacity
+    }
+
     pub fn purge(&self) {
         self.green_zone.store(0, Ordering::SeqCst);
         *self.data.lock() = LruData::with_seed(LRU_SEED);
@@ -144,6 +183,7 @@ where
 
     fn with_rng(rng: Rand64) -> Self {
         LruData {
+            capacity: 0,
             end_yellow_zone: 0,
             end_green_zone: 0,
             end_red_zone: 0,
@@ -313,7 +353,7 @@ impl LruIndex {
         self.store(std::usize::MAX);
     }
 
-    fn is_in_lru(&self) -> bool {
+    pub(crate) fn is_in_lru(&self) -> bool {
         self.load() != std::usize::MAX
     }
 }
@@ -1,19 +1,33 @@
 #![allow(missing_docs)]
 
+use crate::debug::InternStats;
+use crate::debug::MemoEntry;
 use crate::debug::TableEntry;
 use crate::durability::Durability;
+use crate::CacheOutcome;
+use crate::Cycle;
 use crate::CycleError;
 use crate::Database;
+use crate::InternCapacityExceeded;
+use crate::InternId;
+use crate::InternWithIdError;
+use crate::InternedRef;
 use crate::Query;
 use crate::QueryTable;
 use crate::QueryTableMut;
+use crate::RecordedSet;
 use crate::RuntimeId;
+use crate::SetInputError;
 use crate::SweepStrategy;
+use std::any::Any;
 use std::borrow::Borrow;
+use std::collections::HashMap;
 use std::fmt::Debug;
 use std::hash::Hash;
 
+pub use crate::accumulator::{accumulate, accumulated, AccumulatedValues};
 pub use crate::derived::DependencyStorage;
+pub use crate::derived::MemoizedNoEqStorage;
 pub use crate::derived::MemoizedStorage;
 pub use crate::input::InputStorage;
 pub use crate::interned::InternedStorage;
@@ -34,6 +48,9 @@ pub trait DatabaseStorageTypes: Database {
     /// Defines the "storage type", where all the query data is kept.
     /// This type is defined by the `database_storage` macro.
     type DatabaseStorage: Default;
+
+    /// Deep-copies the given storage (see `Storage::fork`).
+    fn fork_database_storage(storage: &Self::DatabaseStorage) -> Self::DatabaseStorage;
 }
 
 /// Internal operations that the runtime uses to operate on the database.
@@ -57,8 +74,54 @@ pub trait DatabaseOps {
     /// True if the computed value for `input` may have changed since `revision`.
     fn maybe_changed_since(&self, input: DatabaseKeyIndex, revision: Revision) -> bool;
 
+    /// Returns the inputs recorded when `index` was last executed, if
+    /// they are known.
+    fn dependencies(&self, index: DatabaseKeyIndex) -> Option<Vec<DatabaseKeyIndex>>;
+
+    /// Returns the values pushed to accumulators when `index` was last
+    /// executed, if it has a memoized value.
+    fn accumulated(&self, index: DatabaseKeyIndex) -> Option<AccumulatedValues>;
+
+    /// Brings the value for `index` up to date (see `Database::validate`).
+    fn validate(&self, index: DatabaseKeyIndex);
+
+    /// Memoizes the value that `index` computed from provisional values,
+    /// once the fixpoint iteration it took part in has converged.
+    fn commit_provisional(
+        &self,
+        index: DatabaseKeyIndex,
+        inputs: Option<&[DatabaseKeyIndex]>,
+        durability: Durability,
+    );
+
+    /// Checks the memoized values of every query; see
+    /// `Database::debug_validate_dependencies`.
+    fn ops_validate_dependencies(&self);
+
     /// Executes the callback for each kind of query.
     fn for_each_query(&self, op: &mut dyn FnMut(&dyn QueryStorageMassOps));
+
+    /// Estimates the memory used by each query group, in bytes.
+    fn group_memory_usage(&self) -> Vec<(&'static str, usize)>;
+
+    /// Sets the input query named `name` with the given durability (see
+    /// `Database::set_input_by_name`).
+    fn set_input_by_name(
+        &mut self,
+        name: &str,
+        key: Box<dyn Any>,
+        value: Box<dyn Any>,
+        durability: Durability,
+    ) -> Result<(), SetInputError>;
+
+    /// Takes the inputs recorded by every input query, passing each to
+    /// `op` (see `Database::take_recording`).
+    fn take_recorded_sets(&self, op: &mut dyn FnMut(RecordedSet));
+
+    /// Sets the LRU capacity of the derived queries named `name`, in
+    /// every query group, returning false if there are none (see
+    /// `Database::apply_lru_config`).
+    fn set_lru_capacity_by_name(&self, name: &str, capacity: usize) -> bool;
 }
 
 /// Internal operations performed on the query storage as a whole
@@ -68,6 +131,23 @@ pub trait QueryStorageMassOps {
     /// Discards memoized values that are not up to date with the current revision.
     fn sweep(&self, runtime: &Runtime, strategy: SweepStrategy);
     fn purge(&self);
+
+    /// Invokes `op` with each memoized value (or, for inputs, each
+    /// value that has been set).
+    fn for_each_memo(&self, runtime: &Runtime, op: &mut dyn FnMut(MemoEntry<'_>));
+
+    /// Estimates the number of bytes used by the stored values (see
+    /// `Database::memory_usage`).
+    fn memory_usage(&self) -> usize;
+
+    /// Evicts memoized values until no more than the LRU capacity
+    /// remain, if one is set (see `Database::trigger_lru_eviction`).
+    fn enforce_lru_capacity(&self, db: &dyn Database);
+
+    /// Discards the memoized values and dependencies of a derived query,
+    /// keeping its keys; does nothing for other queries (see
+    /// `Database::reset_derived`).
+    fn clear_derived(&self);
 }
 
 pub trait DatabaseKey: Clone + Debug + Eq + Hash {}
@@ -77,12 +157,70 @@ pub trait QueryFunction: Query {
 
     fn recover(
         db: &<Self as QueryDb<'_>>::DynDb,
-        cycle: &[DatabaseKeyIndex],
+        cycle: &Cycle,
         key: &Self::Key,
     ) -> Option<Self::Value> {
         let _ = (db, cycle, key);
         None
     }
+
+    /// For a query declared with `#[salsa::cycle(fixpoint, initial =
+    /// expr)]`, returns `expr`: the value that the query provisionally
+    /// has when it turns out to depend on itself. The query is then
+    /// re-executed until its value stops changing.
+    fn cycle_initial(key: &Self::Key) -> Option<Self::Value> {
+        let _ = key;
+        None
+    }
+
+    /// Converts a value returned by a `CycleHandling::RecoverWith`
+    /// function to the value type of this query, or returns `None` if
+    /// it has some other type.
+    fn downcast_recovered(value: Box<dyn std::any::Any>) -> Option<Self::Value> {
+        let _ = value;
+        None
+    }
+
+    /// How many of the distinct values computed for each key are kept,
+    /// with the revisions they changed in, for `QueryTable::history`.
+    /// Set via `#[salsa::history(depth = N)]`; the default, 0, keeps
+    /// none.
+    const HISTORY_DEPTH: usize = 0;
+}
+
+/// Trait implemented for input queries.
+pub trait InputQuery: Query {
+    /// Returns the value to use when `key` is read before it has been
+    /// set, or `None` (the default) to panic. Set via the
+    /// `#[salsa::default = expr]` attribute.
+    fn default_value(key: &Self::Key) -> Option<Self::Value> {
+        let _ = key;
+        None
+    }
+
+    /// Returns true if setting the input to `new_value` would not
+    /// change it, in which case no new revision is started. The
+    /// default always returns false; `#[salsa::input(eq)]` compares
+    /// the values with `PartialEq`.
+    fn values_equal(old_value: &Self::Value, new_value: &Self::Value) -> bool {
+        let _ = (old_value, new_value);
+        false
+    }
+
+    /// If true, a value of this input can never be changed (or removed)
+    /// once it is set, so reading it records no dependency at all. Set
+    /// via `#[salsa::input(immortal)]`.
+    const IMMORTAL: bool = false;
+}
+
+/// Trait implemented for interned queries.
+pub trait InternedQuery: Query {
+    /// If true, the ids handed out by this query carry a generation
+    /// that is bumped whenever their index is reused, so that looking
+    /// up an id whose value was collected fails instead of giving the
+    /// value that now occupies the index. Set via
+    /// `#[salsa::interned(generational)]`.
+    const GENERATIONAL: bool = false;
 }
 
 /// Create a query table, which has access to the storage for the query
@@ -97,6 +235,54 @@ where
     QueryTable::new(db, query_storage)
 }
 
+/// Sets the input `Q` for a key and value given as `Any`, as
+/// `Database::set_input_by_name` does.
+pub fn set_input_from_any<'me, Q>(
+    db: &'me mut <Q as QueryDb<'me>>::DynDb,
+    key: Box<dyn Any>,
+    value: Box<dyn Any>,
+    durability: Durability,
+) -> Result<(), SetInputError>
+where
+    Q: Query + 'me,
+    Q::Storage: InputQueryStorageOps<Q>,
+    Q::Key: 'static,
+    Q::Value: 'static,
+{
+    let key = key
+        .downcast::<Q::Key>()
+        .map_err(|_| SetInputError::WrongKeyType {
+            input: Q::QUERY_NAME,
+        })?;
+    let value = value
+        .downcast::<Q::Value>()
+        .map_err(|_| SetInputError::WrongValueType {
+            input: Q::QUERY_NAME,
+        })?;
+    get_query_table_mut::<Q>(db).set_with_durability(*key, *value, durability);
+    Ok(())
+}
+
+/// Takes the inputs recorded by the input `Q`, passing each to `op`, as
+/// `Database::take_recording` does.
+pub fn take_recorded_sets<Q>(storage: &Q::Storage, op: &mut dyn FnMut(RecordedSet))
+where
+    Q: Query,
+    Q::Storage: InputQueryStorageOps<Q>,
+    Q::Key: 'static,
+    Q::Value: 'static,
+{
+    for (key, value, durability, revision) in storage.take_recorded() {
+        op(RecordedSet {
+            input: Q::QUERY_NAME,
+            key: Box::new(key),
+            value: Box::new(value),
+            durability,
+            revision,
+        });
+    }
+}
+
 /// Create a mutable query table, which has access to the storage
 /// for the query and offers methods like `set`.
 pub fn get_query_table_mut<'me, Q>(db: &'me mut <Q as QueryDb<'me>>::DynDb) -> QueryTableMut<'me, Q>
@@ -113,6 +299,14 @@ pub trait QueryGroup: Sized {
 
     /// Dyn version of the associated database trait.
     type DynDb: ?Sized + Database + HasQueryGroup<Self>;
+
+    /// Name of the query group trait (e.g., `MyDatabase`).
+    const GROUP_NAME: &'static str;
+
+    /// The version of the query group, as set with
+    /// `#[salsa::query_group(MyStorage, version = 3)]` (0 by default);
+    /// see `QueryTableMut::prime_versioned`.
+    const VERSION: u32 = 0;
 }
 
 /// Trait implemented by a database for each group that it supports.
@@ -132,6 +326,10 @@ where
 {
     fn new(group_index: u16) -> Self;
 
+    /// Returns an independent copy of this storage, with the same
+    /// memoized values (see `Storage::fork`).
+    fn fork(&self) -> Self;
+
     /// Format a database key index in a suitable way.
     fn fmt_index(
         &self,
@@ -149,6 +347,49 @@ where
         revision: Revision,
     ) -> bool;
 
+    /// Returns the inputs recorded when `index`, which must be from
+    /// this query, was last executed. Returns `None` if there is no
+    /// memoized value, if the inputs are not known (e.g. after an
+    /// untracked read), or if this is not a derived query.
+    fn dependencies(
+        &self,
+        db: &<Q as QueryDb<'_>>::DynDb,
+        index: DatabaseKeyIndex,
+    ) -> Option<Vec<DatabaseKeyIndex>>;
+
+    /// Returns the values pushed to accumulators by the last execution
+    /// of `index`. Returns `None` if there is no memoized value, or if
+    /// this is not a derived query.
+    fn accumulated(
+        &self,
+        db: &<Q as QueryDb<'_>>::DynDb,
+        index: DatabaseKeyIndex,
+    ) -> Option<AccumulatedValues>;
+
+    /// Re-validates (or re-executes) the value for `index`, if it is
+    /// derived and was computed before, without recording a dependency
+    /// on it.
+    fn validate(&self, db: &<Q as QueryDb<'_>>::DynDb, index: DatabaseKeyIndex);
+
+    /// Memoizes the value that `index`, which must be from this query,
+    /// computed from the provisional values of a fixpoint iteration that
+    /// has since converged. It is memoized with the given `inputs` (which
+    /// are untracked if `None`) and `durability`, rather than the ones
+    /// it read. Does nothing for queries that are not derived.
+    fn commit_provisional(
+        &self,
+        db: &<Q as QueryDb<'_>>::DynDb,
+        index: DatabaseKeyIndex,
+        inputs: Option<&[DatabaseKeyIndex]>,
+        durability: Durability,
+    );
+
+    /// Brings each memoized value of this query up to date, executes the
+    /// query again, and panics if that produces a different value or
+    /// reads different inputs (see `Database::debug_validate_dependencies`).
+    /// Does nothing for queries that are not derived.
+    fn debug_validate_dependencies(&self, db: &<Q as QueryDb<'_>>::DynDb);
+
     /// Execute the query, returning the result (often, the result
     /// will be memoized).  This is the "main method" for
     /// queries.
@@ -162,6 +403,17 @@ where
         key: &Q::Key,
     ) -> Result<Q::Value, CycleError<DatabaseKeyIndex>>;
 
+    /// Returns the value for `key` if it is already available and
+    /// known to be up-to-date in the current revision, without
+    /// executing or revalidating anything and without recording a
+    /// dependency.
+    fn peek(&self, db: &<Q as QueryDb<'_>>::DynDb, key: &Q::Key) -> Option<Q::Value>;
+
+    /// Returns the revision in which the value for `key` last changed,
+    /// if it is known to be up-to-date in the current revision (much
+    /// like `peek`).
+    fn changed_at(&self, db: &<Q as QueryDb<'_>>::DynDb, key: &Q::Key) -> Option<Revision>;
+
     /// Returns the durability associated with a given key.
     fn durability(&self, db: &<Q as QueryDb<'_>>::DynDb, key: &Q::Key) -> Durability;
 
@@ -185,6 +437,33 @@ where
         new_value: Q::Value,
         durability: Durability,
     );
+
+    /// Sets each of the given keys, all within a single new revision.
+    fn set_many<I>(&self, db: &mut <Q as QueryDb<'_>>::DynDb, values: I, durability: Durability)
+    where
+        I: IntoIterator<Item = (Q::Key, Q::Value)>;
+
+    /// Removes the value of `key`, returning it, so that `key` is
+    /// treated as if it had never been set.
+    fn remove(&self, db: &mut <Q as QueryDb<'_>>::DynDb, key: &Q::Key) -> Option<Q::Value>;
+
+    /// True if `key` has a value with the given durability for which
+    /// `matches` returns true.
+    fn has_value(
+        &self,
+        key: &Q::Key,
+        durability: Durability,
+        matches: &mut dyn FnMut(&Q::Value) -> bool,
+    ) -> bool;
+
+    /// Like `try_fetch`, but gives `None` (recording a dependency on
+    /// `key` all the same) if `key` has no value.
+    fn fetch_if_set(&self, db: &<Q as QueryDb<'_>>::DynDb, key: &Q::Key) -> Option<Q::Value>;
+
+    /// Returns (and forgets) the keys and values that were set while
+    /// the runtime was recording, with their durabilities and the
+    /// revisions they were set in.
+    fn take_recorded(&self) -> Vec<(Q::Key, Q::Value, Durability, Revision)>;
 }
 
 /// An optional trait that is implemented for "user mutable" storage:
@@ -192,6 +471,13 @@ where
 /// is set independently.
 pub trait LruQueryStorageOps {
     fn set_lru_capacity(&self, new_capacity: usize);
+
+    /// Returns the configured LRU capacity, or `None` if LRU caching
+    /// is disabled.
+    fn lru_capacity(&self) -> Option<usize>;
+
+    /// Returns the number of memoized values currently stored.
+    fn memoized_len(&self) -> usize;
 }
 
 pub trait DerivedQueryStorageOps<Q>
@@ -202,4 +488,165 @@ where
     where
         S: Eq + Hash,
         Q::Key: Borrow<S>;
+
+    /// Discards the memoized values of all keys, in a new revision.
+    fn clear(&self, db: &mut <Q as QueryDb<'_>>::DynDb);
+
+    /// Like `QueryStorageOps::try_fetch`, but also returns how the
+    /// value was obtained.
+    fn fetch_probed(
+        &self,
+        db: &<Q as QueryDb<'_>>::DynDb,
+        key: &Q::Key,
+    ) -> Result<(Q::Value, CacheOutcome), CycleError<DatabaseKeyIndex>>;
+
+    /// Returns the inputs recorded when `key` was last executed, in the
+    /// order they were read, or `None` if there is no memoized value
+    /// or its inputs are not known.
+    fn dependencies<S>(&self, key: &S) -> Option<Vec<DatabaseKeyIndex>>
+    where
+        S: Eq + Hash,
+        Q::Key: Borrow<S>;
+
+    /// Returns the values kept in the history of `key` (see
+    /// `QueryFunction::HISTORY_DEPTH`), oldest first.
+    fn history<S>(&self, key: &S) -> Vec<(Revision, Q::Value)>
+    where
+        S: Eq + Hash,
+        Q::Key: Borrow<S>;
+
+    /// Returns the revision in which `key` was first executed, or
+    /// `None` if there is no memoized value.
+    fn first_computed_at<S>(&self, key: &S) -> Option<Revision>
+    where
+        S: Eq + Hash,
+        Q::Key: Borrow<S>;
+
+    /// Returns the durability recorded when `key` was last executed,
+    /// or `None` if there is no memoized value.
+    fn memoized_durability<S>(&self, key: &S) -> Option<Durability>
+    where
+        S: Eq + Hash,
+        Q::Key: Borrow<S>;
+
+    /// Returns the index identifying `key`, or `None` if the query has
+    /// never been executed with `key`.
+    fn database_key_index<S>(&self, key: &S) -> Option<DatabaseKeyIndex>
+    where
+        S: Eq + Hash,
+        Q::Key: Borrow<S>;
+
+    /// Overrides the value of `key` with `value`, in a new revision.
+    fn set_override(&self, db: &mut <Q as QueryDb<'_>>::DynDb, key: Q::Key, value: Q::Value);
+
+    /// Clears the override of `key`, if any, in a new revision.
+    fn clear_override(&self, db: &mut <Q as QueryDb<'_>>::DynDb, key: &Q::Key);
+
+    /// Returns true if the value of `key` is currently overridden.
+    fn is_overridden(&self, key: &Q::Key) -> bool;
+
+    /// Sets whether the value for `key` is kept out of the LRU list,
+    /// so that it is never evicted.
+    fn set_pinned(&self, key: &Q::Key, pinned: bool);
+
+    /// Memoizes `value` for `key`, with the given inputs.
+    fn prime(
+        &self,
+        db: &mut <Q as QueryDb<'_>>::DynDb,
+        key: Q::Key,
+        value: Q::Value,
+        inputs: Vec<DatabaseKeyIndex>,
+    );
+
+    /// Registers `callback` to be invoked with the value of `key` each
+    /// time it is read or validated and has changed.
+    fn subscribe(&self, key: &Q::Key, callback: SubscriptionCallback<Q::Value>);
+
+    /// Returns the keys and memoized values that have been verified in
+    /// the current revision.
+    fn verified_entries(&self, runtime: &Runtime) -> Vec<(Q::Key, Q::Value)>;
+
+    /// Like `verified_entries`, but returns only the keys.
+    fn memoized_keys(&self, runtime: &Runtime) -> Vec<Q::Key>;
+}
+
+/// An optional trait that is implemented for interned storage.
+pub trait InternedQueryStorageOps<Q>
+where
+    Q: Query,
+{
+    /// Returns the ids of all values currently interned, in the order
+    /// in which they were first interned.
+    fn interned_ids(&self) -> Vec<InternId>;
+
+    /// Interns the key borrowed as `key`, like fetching the query does,
+    /// only converting it into an owned key if it is not interned yet.
+    fn intern_borrowed<B>(&self, db: &<Q as QueryDb<'_>>::DynDb, key: &B) -> Q::Value
+    where
+        Q::Key: Borrow<B>,
+        B: ?Sized + Hash + Eq + ToOwned,
+        B::Owned: Into<Q::Key>;
+
+    /// Interns `key` like fetching the query does, but returns an error
+    /// instead of panicking if it is new and the table is full.
+    fn try_intern(
+        &self,
+        db: &<Q as QueryDb<'_>>::DynDb,
+        key: &Q::Key,
+    ) -> Result<Q::Value, InternCapacityExceeded>;
+
+    /// Limits the number of values interned at once, or lifts the limit.
+    fn set_intern_capacity(&self, capacity: Option<usize>);
+
+    /// Interns each of `keys`, like fetching the query for each would,
+    /// but locking the interning table only once.
+    fn intern_many<I>(&self, db: &<Q as QueryDb<'_>>::DynDb, keys: I) -> Vec<Q::Value>
+    where
+        I: IntoIterator<Item = Q::Key>;
+
+    /// Describes the size of the interning table.
+    fn intern_stats(&self) -> InternStats;
+
+    /// Frees the interned values for which `keep` returns false, in a
+    /// new revision, returning how many were freed.
+    fn retain(
+        &self,
+        db: &mut <Q as QueryDb<'_>>::DynDb,
+        keep: &mut dyn FnMut(&Q::Key) -> bool,
+    ) -> usize;
+
+    /// Interns `key` with the id `id`, unless another value has that id
+    /// or `key` is already interned with another one.
+    fn intern_with_id(
+        &self,
+        db: &<Q as QueryDb<'_>>::DynDb,
+        key: Q::Key,
+        id: InternId,
+    ) -> Result<Q::Value, InternWithIdError>;
+
+    /// Renumbers the interned values in the order of their keys, in a
+    /// new revision if any id changed, returning the new id of each
+    /// old one.
+    fn seal(&self, db: &mut <Q as QueryDb<'_>>::DynDb) -> HashMap<InternId, InternId>
+    where
+        Q::Key: Ord;
+}
+
+/// A callback registered with `QueryTable::subscribe`.
+pub type SubscriptionCallback<V> = Box<dyn Fn(&V) + Send + Sync>;
+
+/// An optional trait that is implemented for the storage of the lookup
+/// queries of interned queries.
+pub trait LookupInternedQueryStorageOps<Q>
+where
+    Q: Query,
+{
+    /// Looks up the value interned as `key`, like fetching the query
+    /// does, but without cloning it.
+    fn lookup_ref(&self, db: &<Q as QueryDb<'_>>::DynDb, key: &Q::Key) -> InternedRef<Q::Value>;
+
+    /// Looks up the value interned as `key`, or returns `None` if no
+    /// value is interned as `key` (because it was freed, or comes from
+    /// another table).
+    fn try_lookup(&self, db: &<Q as QueryDb<'_>>::DynDb, key: &Q::Key) -> Option<Q::Value>;
 }
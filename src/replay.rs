@@ -0,0 +1,93 @@
+use crate::durability::Durability;
+use crate::plumbing::DatabaseOps;
+use crate::revision::Revision;
+use crate::Database;
+use crate::SetInputError;
+use std::any::Any;
+use std::collections::VecDeque;
+use std::fmt;
+
+/// An input that was set while the runtime was recording (see
+/// `Runtime::start_recording`).
+pub struct RecordedSet {
+    /// The name of the input query, as given to
+    /// `Database::set_input_by_name`.
+    pub input: &'static str,
+
+    /// The key that was set (for inputs with several keys, the tuple of
+    /// them).
+    pub key: Box<dyn Any>,
+
+    /// The value it was set to.
+    pub value: Box<dyn Any>,
+
+    /// The durability it was set with.
+    pub durability: Durability,
+
+    /// The revision of the recording database that the set started.
+    /// Values set together (with `set_many`) share a revision.
+    pub revision: Revision,
+}
+
+impl fmt::Debug for RecordedSet {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.debug_struct("RecordedSet")
+            .field("input", &self.input)
+            .field("durability", &self.durability)
+            .field("revision", &self.revision)
+            .finish()
+    }
+}
+
+/// The inputs that were set in a database while its runtime was
+/// recording, in the order they were set; returned by
+/// `Database::take_recording`.
+///
+/// Replaying the log into a fresh database (with `replay_until`)
+/// reproduces the inputs that the recording database had in each
+/// revision, so a bug report can carry the log and the revision in
+/// which the bug showed up. Only `set`s are recorded: inputs that were
+/// set before recording started, or removed, are not in the log.
+#[derive(Debug, Default)]
+pub struct InputLog {
+    sets: VecDeque<RecordedSet>,
+}
+
+impl InputLog {
+    pub(crate) fn new(mut sets: Vec<RecordedSet>) -> Self {
+        sets.sort_by_key(|set| set.revision);
+        InputLog { sets: sets.into() }
+    }
+
+    /// Returns the inputs that are still to be replayed, in order.
+    pub fn sets(&self) -> impl Iterator<Item = &RecordedSet> + '_ {
+        self.sets.iter()
+    }
+
+    /// Returns true if there is nothing left to replay.
+    pub fn is_empty(&self) -> bool {
+        self.sets.is_empty()
+    }
+
+    /// Sets the inputs that were set in `revision` (of the recording
+    /// database) or earlier in `db`, like `Database::set_input_by_name`
+    /// would, and removes them from the log. Calling this again with a
+    /// later revision continues where it left off, so a session can be
+    /// stepped through one recorded revision at a time.
+    ///
+    /// The revisions of `db` need not match the recorded ones (values
+    /// set together with `set_many` are replayed one by one, for
+    /// example); only the order of the sets is kept. Returns an error,
+    /// leaving the sets after the failing one in the log, if `db` has
+    /// no input of that name and type.
+    pub fn replay_until<DB>(&mut self, db: &mut DB, revision: Revision) -> Result<(), SetInputError>
+    where
+        DB: ?Sized + Database,
+    {
+        while matches!(self.sets.front(), Some(set) if set.revision <= revision) {
+            let set = self.sets.pop_front().unwrap();
+            DatabaseOps::set_input_by_name(db, set.input, set.key, set.value, set.durability)?;
+        }
+        Ok(())
+    }
+}
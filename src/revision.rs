@@ -8,18 +8,38 @@ const START: usize = 1;
 /// A unique identifier for the current version of the database; each
 /// time an input is changed, the revision number is incremented.
 /// `Revision` is used internally to track which values may need to be
-/// recomputed, but is not something you should have to interact with
-/// directly as a user of salsa.
+/// recomputed. Users can obtain the current revision with
+/// `Runtime::current_revision`, e.g. to detect that some data cached
+/// outside of salsa is out of date; revisions are ordered and display
+/// as `R1`, `R2`, and so on.
 #[derive(Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub struct Revision {
     generation: NonZeroUsize,
 }
 
 impl Revision {
-    pub(crate) fn start() -> Self {
+    /// The revision of a new database, before any input is set.
+    ///
+    /// ```
+    /// # use salsa::Revision;
+    /// assert_eq!(Revision::start().to_string(), "R1");
+    /// ```
+    pub fn start() -> Self {
         Self::from(START)
     }
 
+    /// Returns how many revisions were started after this one, up to
+    /// and including `later`, or `None` if `later` is before this one.
+    ///
+    /// ```
+    /// # use salsa::Revision;
+    /// let start = Revision::start();
+    /// assert_eq!(start.distance_to(start), Some(0));
+    /// ```
+    pub fn distance_to(self, later: Revision) -> Option<usize> {
+        later.as_usize().checked_sub(self.as_usize())
+    }
+
     pub(crate) fn from(g: usize) -> Self {
         Self {
             generation: NonZeroUsize::new(g).unwrap(),
@@ -41,6 +61,12 @@ impl std::fmt::Debug for Revision {
     }
 }
 
+impl std::fmt::Display for Revision {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(fmt, "R{}", self.generation)
+    }
+}
+
 #[derive(Debug)]
 pub(crate) struct AtomicRevision {
     data: AtomicUsize,
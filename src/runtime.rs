@@ -1,15 +1,21 @@
+use crate::accumulator::{AccumulatedValue, AccumulatedValues};
+use crate::debug::QueryStat;
 use crate::durability::Durability;
 use crate::plumbing::CycleDetected;
 use crate::revision::{AtomicRevision, Revision};
-use crate::{CycleError, Database, DatabaseKeyIndex, Event, EventKind};
+use crate::{
+    CycleError, CycleHandling, Database, DatabaseKeyIndex, Event, EventKind, OverflowBehavior,
+};
 use log::debug;
 use parking_lot::lock_api::{RawRwLock, RawRwLockRecursive};
 use parking_lot::{Mutex, RwLock};
 use rustc_hash::{FxHashMap, FxHasher};
 use smallvec::SmallVec;
+use std::collections::HashMap;
 use std::hash::{BuildHasherDefault, Hash};
-use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::Arc;
+use std::time::Duration;
 
 pub(crate) type FxIndexSet<K> = indexmap::IndexSet<K, BuildHasherDefault<FxHasher>>;
 pub(crate) type FxIndexMap<K, V> = indexmap::IndexMap<K, V, BuildHasherDefault<FxHasher>>;
@@ -38,6 +44,13 @@ pub struct Runtime {
 
     /// Shared state that is accessible via all runtimes.
     shared_state: Arc<SharedState>,
+
+    /// The number of `Database::batch` calls in progress.
+    batch_depth: usize,
+
+    /// The revision started by a set in the current batch, if any,
+    /// which later sets in the batch can reuse.
+    batch_revision: Option<Revision>,
 }
 
 impl Default for Runtime {
@@ -47,6 +60,8 @@ impl Default for Runtime {
             revision_guard: None,
             shared_state: Default::default(),
             local_state: Default::default(),
+            batch_depth: 0,
+            batch_revision: None,
         }
     }
 }
@@ -85,6 +100,35 @@ impl Runtime {
             revision_guard: Some(revision_guard),
             shared_state: self.shared_state.clone(),
             local_state: Default::default(),
+            batch_depth: 0,
+            batch_revision: None,
+        }
+    }
+
+    /// Returns a new runtime, with shared state of its own, that starts
+    /// in the same revision as this one (see `Storage::fork`).
+    pub(crate) fn fork(&self) -> Self {
+        if self.local_state.query_in_progress() {
+            panic!("it is not legal to `fork` during a query");
+        }
+
+        let revisions = &self.shared_state.revisions;
+        let shared_state = SharedState::with_durabilities(revisions.len());
+        for (forked, revision) in shared_state.revisions.iter().zip(revisions) {
+            forked.store(revision.load());
+        }
+        shared_state
+            .pending_revision
+            .store(self.shared_state.pending_revision.load());
+        shared_state.query_stats_enabled.store(
+            self.shared_state.query_stats_enabled.load(Ordering::SeqCst),
+            Ordering::SeqCst,
+        );
+        *shared_state.query_stats.lock() = self.shared_state.query_stats.lock().clone();
+
+        Runtime {
+            shared_state: Arc::new(shared_state),
+            ..Default::default()
         }
     }
 
@@ -111,6 +155,16 @@ impl Runtime {
     /// synthetic write at `Durability::HIGH`, (b) executing the query
     /// Q and then (c) doing a sweep.
     ///
+    /// Synthetic writes are also handy in tests, to check how queries
+    /// behave when "something" at a given durability changes. Note
+    /// that the invalidation is conservative: no input value actually
+    /// changes, so a derived query that depends on inputs of
+    /// durability D or lower will have its dependencies re-validated
+    /// the next time it is read, but will only be re-executed if one of
+    /// them really did change (or if it contains an untracked read).
+    /// This method is meant for diagnostics and testing; ordinary code
+    /// should set inputs instead.
+    ///
     /// **WARNING:** Just like an ordinary write, this method triggers
     /// cancellation. If you invoke it while a snapshot exists, it
     /// will block until that snapshot is dropped -- if that snapshot
@@ -126,15 +180,339 @@ impl Runtime {
         self.id
     }
 
+    /// Registers a callback to be invoked when this runtime observes
+    /// that its current revision has been canceled -- that is, when
+    /// another thread is waiting to set an input (see
+    /// [`is_current_revision_canceled`]). This is useful for
+    /// snapshots: the callback can, for example, set a flag that is
+    /// polled by expensive computations salsa does not know about.
+    ///
+    /// The callback fires at most once, on the thread that owns this
+    /// runtime, during its next interaction with salsa after the
+    /// cancellation (such as reading or executing a query). Registering
+    /// a new callback replaces any previous one that has not yet fired.
+    ///
+    /// [`is_current_revision_canceled`]: struct.Runtime.html#method.is_current_revision_canceled
+    pub fn on_cancellation(&self, hook: impl FnOnce() + Send + 'static) {
+        self.local_state.set_cancellation_hook(Box::new(hook));
+    }
+
+    /// Installs `handler` to receive the events of this runtime instead
+    /// of `Database::salsa_event` while `op` runs, then restores the
+    /// handler that was installed before (if any), even if `op` unwinds.
+    /// See `Database::with_event_handler`.
+    pub fn with_event_handler<R>(
+        &self,
+        handler: impl FnMut(&dyn Database, Event) + Send + 'static,
+        op: impl FnOnce() -> R,
+    ) -> R {
+        let previous = self
+            .local_state
+            .replace_event_handler(Some(Box::new(handler)));
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(op));
+        self.local_state.replace_event_handler(previous);
+        match result {
+            Ok(result) => result,
+            Err(payload) => std::panic::resume_unwind(payload),
+        }
+    }
+
+    /// Reports `event` to the handler installed with
+    /// `with_event_handler`, if there is one, and otherwise to
+    /// `db.salsa_event`. Events reported while the handler runs go to
+    /// `db.salsa_event`.
+    pub(crate) fn report_event<DB: ?Sized + Database>(&self, db: &DB, event: Event) {
+        if self.local_state.muted() {
+            return;
+        }
+        match self.local_state.replace_event_handler(None) {
+            Some(mut handler) => {
+                handler(db.ops_database(), event);
+                self.local_state.restore_event_handler(handler);
+            }
+            None => db.salsa_event(event),
+        }
+    }
+
+    /// Invokes the callback registered with `on_cancellation`, if
+    /// there is one and the current revision has been canceled.
+    fn check_cancellation_hook(&self) {
+        if self.local_state.has_cancellation_hook()
+            && self.pending_revision() > self.current_revision()
+        {
+            if let Some(hook) = self.local_state.take_cancellation_hook() {
+                hook();
+            }
+        }
+    }
+
+    /// Sets a wall-clock budget for each top-level query executed on
+    /// this runtime (that is, one that is not invoked by another
+    /// query), or removes it if `timeout` is `None`. Once the query has
+    /// been executing for longer than `timeout`, the current revision
+    /// counts as canceled for this runtime: `is_current_revision_canceled`
+    /// returns true (and `check_canceled` returns an error), so queries
+    /// stop through their ordinary cancellation path. This is a safety
+    /// valve against pathological inputs (e.g. a query that loops
+    /// forever), but only for queries that check for cancellation.
+    ///
+    /// Like any query that observes cancellation, a query that observes
+    /// the timeout is re-executed in the next revision. Within the
+    /// current revision, its (canceled) result is reused; to retry it
+    /// sooner, start a new revision, e.g. with `synthetic_write`.
+    pub fn set_query_timeout(&self, timeout: Option<Duration>) {
+        self.local_state.set_query_timeout(timeout);
+    }
+
+    /// Limits how many queries this runtime may execute inside of one
+    /// another, or removes the limit if `max_depth` is `None`. Deeply
+    /// recursive queries can otherwise overflow the native stack,
+    /// which aborts the process. When a query would exceed the limit,
+    /// salsa unwinds instead (like a panic, but without invoking the
+    /// panic hook), with a `QueryStackOverflow` payload that can be
+    /// recovered with `std::panic::catch_unwind` and `downcast` (see
+    /// `set_overflow_behavior` for alternatives). As with any
+    /// unwinding, the database remains usable afterwards.
+    ///
+    /// Only the queries that salsa executes count towards the depth:
+    /// those whose memoized values are reused, transparent queries and
+    /// inputs do not.
+    pub fn set_max_query_depth(&self, max_depth: Option<usize>) {
+        self.local_state.set_max_query_depth(max_depth);
+    }
+
+    /// Sets what happens when a query would exceed the depth set with
+    /// `set_max_query_depth`: by default, salsa unwinds with a
+    /// `QueryStackOverflow` payload, but it can also panic with the
+    /// stack of queries, or recover the way cycles are recovered from
+    /// (see `OverflowBehavior`). A server might unwind, and treat the
+    /// payload like cancellation, while tests panic.
+    pub fn set_overflow_behavior(&self, behavior: OverflowBehavior) {
+        self.local_state.set_overflow_behavior(behavior);
+    }
+
+    pub(crate) fn overflow_behavior(&self) -> OverflowBehavior {
+        self.local_state.overflow_behavior()
+    }
+
+    /// True if executing another query would exceed the depth set with
+    /// `set_max_query_depth`.
+    pub(crate) fn query_stack_is_full(&self) -> bool {
+        match self.local_state.max_query_depth() {
+            Some(max_depth) => self.local_state.query_depth() >= max_depth,
+            None => false,
+        }
+    }
+
+    /// Reports an `EventKind::DeepRecursion` event whenever this runtime
+    /// is about to execute a query while `depth - 1` executions of the
+    /// same query are on the stack already, or stops reporting them if
+    /// `depth` is `None`. Unlike cycles, recursion through other keys of
+    /// the same query is allowed, so this is only a diagnostic, meant to
+    /// surface accidental deep recursion before it overflows the stack
+    /// (or reaches `set_max_query_depth`).
+    pub fn set_recursion_warning_depth(&self, depth: Option<usize>) {
+        self.local_state.set_recursion_warning_depth(depth);
+    }
+
+    /// Enables or disables strict mode for this runtime, a debugging aid
+    /// meant for tests and CI. In strict mode, each derived query that
+    /// salsa executes is executed a second time, with the same inputs,
+    /// and salsa panics (naming the query) if the two values differ.
+    /// That usually means the query reads some state that salsa does not
+    /// track, without calling `report_untracked_read`, and so its value
+    /// will not be recomputed when that state changes.
+    ///
+    /// Queries that report untracked reads, whose values are not
+    /// memoized, or that take part in a cycle are not checked. Strict
+    /// mode makes executing queries (roughly) twice as slow. The second
+    /// execution is not visible to `salsa_event`, and does not count in
+    /// the query stats.
+    pub fn set_strict(&self, strict: bool) {
+        self.local_state.set_strict(strict);
+    }
+
+    pub(crate) fn is_strict(&self) -> bool {
+        self.local_state.strict()
+    }
+
+    /// Runs `op` without reporting events or recording query stats, for
+    /// the second execution of a query in strict mode and for the reads
+    /// and executions of `debug_validate_dependencies`.
+    pub(crate) fn muted<R>(&self, op: impl FnOnce() -> R) -> R {
+        struct Unmute<'a> {
+            local_state: &'a LocalState,
+            was_muted: bool,
+        }
+
+        impl Drop for Unmute<'_> {
+            fn drop(&mut self) {
+                self.local_state.replace_muted(self.was_muted);
+            }
+        }
+
+        let _unmute = Unmute {
+            local_state: &self.local_state,
+            was_muted: self.local_state.replace_muted(true),
+        };
+        op()
+    }
+
+    /// Returns the revision in which this runtime was snapshotted, or
+    /// `None` if it does not belong to a snapshot. The revision of a
+    /// snapshot cannot change, since setting an input waits until all
+    /// snapshots are dropped; in debug builds, salsa checks this each
+    /// time a snapshot reads a query, and panics (naming the query) if
+    /// the snapshot sees a value from a later revision.
+    pub fn snapshot_revision(&self) -> Option<Revision> {
+        self.revision_guard.as_ref().map(|guard| guard.revision)
+    }
+
+    /// In debug builds, panics if this runtime belongs to a snapshot
+    /// and the value of `database_key_index` it read (which changed in
+    /// `changed_at`) does not belong to the revision of the snapshot:
+    /// that would mean a value of a later revision was visible to it.
+    pub(crate) fn check_snapshot_read<DB>(
+        &self,
+        db: &DB,
+        database_key_index: DatabaseKeyIndex,
+        changed_at: Revision,
+    ) where
+        DB: ?Sized + Database,
+    {
+        if !cfg!(debug_assertions) {
+            return;
+        }
+        if let Some(guard) = &self.revision_guard {
+            let revision_now = self.current_revision();
+            if changed_at > guard.revision || revision_now != guard.revision {
+                panic!(
+                    "snapshot of {:?} read {:?} (changed in {:?}) in {:?}",
+                    guard.revision,
+                    database_key_index.debug(db),
+                    changed_at,
+                    revision_now,
+                );
+            }
+        }
+    }
+
     /// Returns the database-key for the query that this thread is
     /// actively executing (if any).
     pub fn active_query(&self) -> Option<DatabaseKeyIndex> {
         self.local_state.active_query()
     }
 
-    /// Read current value of the revision counter.
+    /// Returns the database-keys of the queries that this thread is
+    /// executing, from the outermost to the innermost (which is the
+    /// `active_query`). This is a breadcrumb trail for diagnostics: for
+    /// example, a panic hook can capture it (through a thread-local
+    /// handle to the database) to report which queries were executing
+    /// when a panic occurred.
+    pub fn active_query_stack(&self) -> Vec<DatabaseKeyIndex> {
+        self.local_state.active_query_stack()
+    }
+
+    /// True if this thread is executing a query, so that its reads are
+    /// being tracked. Inputs cannot be set while this is the case, so
+    /// this is useful for checking (e.g. in debug assertions) that
+    /// helper functions which mutate the database are not called from
+    /// within a query.
+    pub fn is_in_query(&self) -> bool {
+        self.local_state.query_in_progress()
+    }
+
+    /// Overrides how cycles are handled by queries executed on this
+    /// runtime, in place of their `#[salsa::cycle]` attributes (see
+    /// `CycleHandling`). This lets the same database recover from
+    /// cycles in some contexts and panic on them in others; a common
+    /// pattern is to set it on a snapshot. The setting is not shared
+    /// with (nor inherited by) snapshots of this runtime.
+    ///
+    /// Note that a value obtained through recovery is memoized like any
+    /// other, so it may be reused even after the handling is changed.
+    pub fn set_cycle_handling(&self, cycle_handling: CycleHandling) {
+        self.local_state.set_cycle_handling(cycle_handling);
+    }
+
+    /// Returns the cycle handling set with `set_cycle_handling`.
+    pub fn cycle_handling(&self) -> CycleHandling {
+        self.local_state.cycle_handling()
+    }
+
+    /// Enables or disables the collection of per-query execution
+    /// statistics (see `query_stats`). Collection is disabled by
+    /// default. The setting is shared with all snapshots of this
+    /// runtime.
+    pub fn set_query_stats_enabled(&self, enabled: bool) {
+        self.shared_state
+            .query_stats_enabled
+            .store(enabled, Ordering::SeqCst);
+    }
+
+    /// Starts recording the inputs that are set, with the revisions
+    /// they were set in, until `stop_recording` is called. The
+    /// recording is taken with `Database::take_recording`, and can be
+    /// replayed into another database with `InputLog::replay_until`.
+    /// The setting is shared with all snapshots of this runtime.
+    pub fn start_recording(&self) {
+        self.shared_state.recording.store(true, Ordering::SeqCst);
+    }
+
+    /// Stops recording the inputs that are set (see `start_recording`).
+    /// The inputs recorded so far are kept until they are taken.
+    pub fn stop_recording(&self) {
+        self.shared_state.recording.store(false, Ordering::SeqCst);
+    }
+
+    pub(crate) fn is_recording(&self) -> bool {
+        self.shared_state.recording.load(Ordering::Relaxed)
+    }
+
+    /// Returns the execution statistics collected so far for each
+    /// derived query key. Only queries that were accessed while
+    /// statistics were enabled (see `set_query_stats_enabled`) appear
+    /// in the result.
+    pub fn query_stats(&self) -> HashMap<DatabaseKeyIndex, QueryStat> {
+        self.shared_state
+            .query_stats
+            .lock()
+            .iter()
+            .map(|(&key, &stat)| (key, stat))
+            .collect()
+    }
+
+    /// Records that a memoized value for `database_key_index` was
+    /// returned without executing the query.
+    pub(crate) fn report_cache_hit(&self, database_key_index: DatabaseKeyIndex) {
+        self.record_query_stat(database_key_index, |stat| stat.cache_hits += 1);
+    }
+
+    fn record_query_stat(
+        &self,
+        database_key_index: DatabaseKeyIndex,
+        op: impl FnOnce(&mut QueryStat),
+    ) {
+        if !self.local_state.muted()
+            && self
+                .shared_state
+                .query_stats_enabled
+                .load(Ordering::Relaxed)
+        {
+            op(self
+                .shared_state
+                .query_stats
+                .lock()
+                .entry(database_key_index)
+                .or_default());
+        }
+    }
+
+    /// Read current value of the revision counter. This is incremented
+    /// each time an input is changed, so it can be used to tag data
+    /// computed outside of salsa with the revision it was derived from.
     #[inline]
-    pub(crate) fn current_revision(&self) -> Revision {
+    pub fn current_revision(&self) -> Revision {
         self.shared_state.revisions[0].load()
     }
 
@@ -156,6 +534,13 @@ impl Runtime {
         self.shared_state.pending_revision.load()
     }
 
+    /// Like `is_current_revision_canceled`, but without recording a
+    /// read (see `Database::yield_now`).
+    #[inline]
+    pub(crate) fn is_cancellation_pending(&self) -> bool {
+        self.pending_revision() > self.current_revision() || self.local_state.query_timed_out()
+    }
+
     /// Check if the current revision is canceled. If this method ever
     /// returns true, the currently executing query is also marked as
     /// having an *untracked read* -- this means that, in the next
@@ -168,7 +553,8 @@ impl Runtime {
     /// This method is useful for implementing cancellation of queries.
     /// You can do it in one of two ways, via `Result`s or via unwinding.
     ///
-    /// The `Result` approach looks like this:
+    /// The `Result` approach looks like this (`check_canceled` does the
+    /// first step):
     ///
     ///   * Some queries invoke `is_current_revision_canceled` and
     ///     return a special value, like `Err(Canceled)`, if it returns
@@ -193,6 +579,9 @@ impl Runtime {
     ///
     /// Note that salsa is explicitly designed to be panic-safe, so cancellation
     /// via unwinding is 100% valid approach to cancellation.
+    ///
+    /// This also returns true once the active query exceeds the timeout
+    /// set with `set_query_timeout`.
     #[inline]
     pub fn is_current_revision_canceled(&self) -> bool {
         let current_revision = self.current_revision();
@@ -201,7 +590,7 @@ impl Runtime {
             "is_current_revision_canceled: current_revision={:?}, pending_revision={:?}",
             current_revision, pending_revision
         );
-        if pending_revision > current_revision {
+        if pending_revision > current_revision || self.local_state.query_timed_out() {
             self.report_untracked_read();
             true
         } else {
@@ -241,6 +630,31 @@ impl Runtime {
         }
     }
 
+    /// Returns `Err(Canceled)` if the current revision is canceled (see
+    /// `is_current_revision_canceled`, which this calls). This suits the
+    /// `Result` approach to cancellation, without unwinding: queries
+    /// return `Result<T, Canceled>`, and propagate the error with `?`.
+    ///
+    /// ```rust,ignore
+    /// fn parse(db: &dyn ParseDatabase, file: FileId) -> Result<Ast, salsa::Canceled> {
+    ///     for item in db.items(file)? {
+    ///         db.salsa_runtime().check_canceled()?;
+    ///         ...
+    ///     }
+    /// }
+    /// ```
+    ///
+    /// As with `is_current_revision_canceled`, a query that sees the
+    /// error is re-executed in the next revision, so `Err(Canceled)` is
+    /// never reused from the memoized values.
+    pub fn check_canceled(&self) -> Result<(), Canceled> {
+        if self.is_current_revision_canceled() {
+            Err(Canceled)
+        } else {
+            Ok(())
+        }
+    }
+
     /// Acquires the **global query write lock** (ensuring that no queries are
     /// executing) and then increments the current revision counter; invokes
     /// `op` with the global query write lock still held.
@@ -269,8 +683,33 @@ impl Runtime {
     ) {
         log::debug!("increment_revision()");
 
+        if self.is_in_query() {
+            panic!(
+                "cannot set an input (or otherwise start a new revision) while a query is \
+                 executing (see `Runtime::is_in_query`)"
+            );
+        }
         if !self.permits_increment() {
-            panic!("increment_revision invoked during a query computation");
+            panic!("increment_revision invoked on a snapshot");
+        }
+
+        // In a batch, the revision started by an earlier set can be
+        // reused, as long as no memoized value was verified in it since
+        // (which would then be verified against outdated inputs). If a
+        // snapshot holds the lock, the revision is incremented as usual,
+        // so that the snapshot is canceled.
+        if let Some(batch_revision) = self.batch_revision {
+            let shared_state = self.shared_state.clone();
+            let lock = shared_state.query_lock.try_write();
+            if lock.is_some() && !shared_state.verified_since_write.load(Ordering::SeqCst) {
+                debug!("increment_revision: reusing {:?}", batch_revision);
+                if let Some(d) = op(batch_revision) {
+                    for rev in &shared_state.revisions[1..=d.index()] {
+                        rev.store(batch_revision);
+                    }
+                }
+                return;
+            }
         }
 
         // Set the `pending_revision` field so that people
@@ -293,6 +732,39 @@ impl Runtime {
                 rev.store(new_revision);
             }
         }
+
+        if self.batch_depth > 0 {
+            self.batch_revision = Some(new_revision);
+            self.shared_state
+                .verified_since_write
+                .store(false, Ordering::SeqCst);
+        }
+    }
+
+    /// Starts a batch (see `Database::batch`); batches can be nested.
+    pub(crate) fn start_batch(&mut self) {
+        if !self.permits_increment() {
+            panic!("cannot start a batch on a snapshot or while a query is executing");
+        }
+        self.batch_depth += 1;
+    }
+
+    /// Ends the batch started by the matching `start_batch`.
+    pub(crate) fn end_batch(&mut self) {
+        self.batch_depth -= 1;
+        if self.batch_depth == 0 {
+            self.batch_revision = None;
+        }
+    }
+
+    /// Records that a memoized value is being verified (or stored) in
+    /// the current revision, so that a batch does not reuse the revision
+    /// for its next set.
+    pub(crate) fn report_verified_memo(&self) {
+        let verified = &self.shared_state.verified_since_write;
+        if !verified.load(Ordering::Relaxed) {
+            verified.store(true, Ordering::SeqCst);
+        }
     }
 
     pub(crate) fn permits_increment(&self) -> bool {
@@ -313,12 +785,61 @@ impl Runtime {
             database_key_index
         );
 
-        db.salsa_event(Event {
-            runtime_id: self.id(),
-            kind: EventKind::WillExecute {
-                database_key: database_key_index,
+        self.check_cancellation_hook();
+
+        if let Some(max_depth) = self.local_state.max_query_depth() {
+            if self.local_state.query_depth() >= max_depth {
+                debug!(
+                    "{:?}: query stack would exceed depth {}",
+                    database_key_index, max_depth
+                );
+                if self.overflow_behavior() == OverflowBehavior::Panic {
+                    let stack: Vec<_> = self
+                        .active_query_stack()
+                        .into_iter()
+                        .map(|key| format!("{:?}", key.debug(db)))
+                        .collect();
+                    panic!(
+                        "executing {:?} would exceed the query depth of {}; active queries: {:?}",
+                        database_key_index.debug(db),
+                        max_depth,
+                        stack,
+                    );
+                }
+                std::panic::resume_unwind(Box::new(QueryStackOverflow {
+                    query: database_key_index,
+                    max_depth,
+                }));
+            }
+        }
+
+        if let Some(warning_depth) = self.local_state.recursion_warning_depth() {
+            let depth = self.local_state.query_depth_of(database_key_index) + 1;
+            if depth == warning_depth {
+                self.report_event(
+                    db,
+                    Event {
+                        runtime_id: self.id(),
+                        kind: EventKind::DeepRecursion {
+                            database_key: database_key_index,
+                            depth,
+                        },
+                    },
+                );
+            }
+        }
+
+        self.report_event(
+            db,
+            Event {
+                runtime_id: self.id(),
+                kind: EventKind::WillExecute {
+                    database_key: database_key_index,
+                },
             },
-        });
+        );
+
+        self.record_query_stat(database_key_index, |stat| stat.executions += 1);
 
         // Push the active query onto the stack.
         let max_durability = Durability::MAX;
@@ -335,8 +856,22 @@ impl Runtime {
             changed_at,
             durability,
             cycle,
+            accumulated,
+            provisional_heads,
+            provisional_members,
+            reported_durability,
+            durability_dependency,
             ..
         } = active_query.complete();
+        // An untracked read must still be seen in every new revision, and
+        // `depend_on_durability` must still see changes of its durability.
+        let durability = match (reported_durability, &dependencies) {
+            (Some(reported), Some(_)) => match durability_dependency {
+                Some(dependency) => durability.max(reported.min(dependency)),
+                None => durability.max(reported),
+            },
+            _ => durability,
+        };
 
         ComputedQueryResult {
             value,
@@ -344,6 +879,10 @@ impl Runtime {
             changed_at,
             dependencies,
             cycle,
+            accumulated: AccumulatedValues::new(accumulated),
+            provisional_heads,
+            provisional_members,
+            durability_dependency,
         }
     }
 
@@ -363,6 +902,8 @@ impl Runtime {
     ) {
         self.local_state
             .report_query_read(input, durability, changed_at);
+
+        self.check_cancellation_hook();
     }
 
     /// Reports that the query depends on some state unknown to salsa.
@@ -374,13 +915,85 @@ impl Runtime {
             .report_untracked_read(self.current_revision());
     }
 
+    /// Pushes a value to an accumulator on behalf of the active query
+    /// (see `plumbing::accumulate`).
+    pub(crate) fn accumulate(&self, value: AccumulatedValue) {
+        if !self.local_state.accumulate(value) {
+            panic!("values can only be pushed to an accumulator while executing a query");
+        }
+    }
+
     /// Acts as though the current query had read an input with the given durability; this will force the current query's durability to be at most `durability`.
     ///
     /// This is mostly useful to control the durability level for [on-demand inputs](https://salsa-rs.github.io/salsa/common_patterns/on_demand_inputs.html).
+    /// For example, a query that reads from a content-addressed external
+    /// store can report `Durability::HIGH`, so that its memoized value
+    /// (and those of the queries that read it) is not even re-validated
+    /// after changes to `LOW` durability inputs. Since no actual input
+    /// was read, such a value is only recomputed when it is explicitly
+    /// invalidated (see `QueryTableMut::invalidate`).
     pub fn report_synthetic_read(&self, durability: Durability) {
         self.local_state.report_synthetic_read(durability);
     }
 
+    /// Declares that the result of the active query is at least as
+    /// durable as `durability`, whatever the durabilities of the inputs
+    /// that it reads (before or after this call). This is the opposite
+    /// of `report_synthetic_read`, which can only lower the durability.
+    ///
+    /// **This is an expert escape hatch.** Salsa skips re-validating a
+    /// value while no input of its durability (or higher) has changed,
+    /// so if an input of lower durability that the query reads does
+    /// change, the query is *not* re-executed, and its stale result is
+    /// reused (by the queries that read it, too). Once it was reused in
+    /// a revision, it is not re-executed on account of that change
+    /// later on either. Only use this when the query's result cannot
+    /// change as a result of such changes, e.g. because it depends only
+    /// on parts of a volatile input that are fixed for as long as the
+    /// higher durability inputs are.
+    ///
+    /// This has no effect if the query also calls `report_untracked_read`,
+    /// as it is then re-executed in every new revision regardless. It also
+    /// does not raise the durability above what the query passes to
+    /// `depend_on_durability`, so that changes of that durability are
+    /// still seen.
+    pub fn report_durability(&self, durability: Durability) {
+        self.local_state.report_durability(durability);
+    }
+
+    /// Reports that the active query depends on all inputs of the
+    /// given durability (or higher), without naming them: its value is
+    /// re-validated (and the query re-executed) whenever any such input
+    /// changes, but not otherwise. This suits queries that read a set
+    /// of inputs which cannot be enumerated, such as state outside of
+    /// salsa that is loaded whenever inputs of that durability change.
+    /// It is coarser than reading the inputs themselves, but finer than
+    /// `report_untracked_read`, after which the query is re-executed in
+    /// every new revision.
+    ///
+    /// Like an input of that durability, this also lowers the durability
+    /// of the active query's result to at most `durability`.
+    pub fn depend_on_durability(&self, durability: Durability) {
+        self.local_state
+            .depend_on_durability(durability, self.last_changed_revision(durability));
+    }
+
+    /// Reports that the active query read a value computed from the
+    /// provisional values of the fixpoint queries `heads`, for which
+    /// fixed-point iteration is in progress (see
+    /// `QueryFunction::cycle_initial`). The active query's result must
+    /// then not be memoized until the iteration converges.
+    pub(crate) fn report_provisional_read(&self, heads: &[DatabaseKeyIndex]) {
+        self.local_state.report_provisional_read(heads);
+    }
+
+    /// Reports that the active query read the values of `members`,
+    /// which were computed from provisional values, and so are only
+    /// memoized once the iteration of their heads converges.
+    pub(crate) fn report_provisional_members(&self, members: Vec<ProvisionalMember>) {
+        self.local_state.report_provisional_members(members);
+    }
+
     /// An "anonymous" read is a read that doesn't come from executing
     /// a query, but from some other internal operation. It just
     /// modifies the "changed at" to be at least the given revision.
@@ -533,6 +1146,20 @@ struct SharedState {
     /// The dependency graph tracks which runtimes are blocked on one
     /// another, waiting for queries to terminate.
     dependency_graph: Mutex<DependencyGraph<DatabaseKeyIndex>>,
+
+    /// If true, execution statistics are recorded in `query_stats`.
+    query_stats_enabled: AtomicBool,
+
+    /// Execution statistics for each derived query key.
+    query_stats: Mutex<FxHashMap<DatabaseKeyIndex, QueryStat>>,
+
+    /// If true, the inputs that are set are recorded (see
+    /// `Runtime::start_recording`).
+    recording: AtomicBool,
+
+    /// Set when a memoized value is verified, and cleared when a batch
+    /// starts a new revision (see `Runtime::with_incremented_revision`).
+    verified_since_write: AtomicBool,
 }
 
 impl SharedState {
@@ -543,6 +1170,10 @@ impl SharedState {
             revisions: (0..durabilities).map(|_| AtomicRevision::start()).collect(),
             pending_revision: AtomicRevision::start(),
             dependency_graph: Default::default(),
+            query_stats_enabled: AtomicBool::new(false),
+            query_stats: Default::default(),
+            recording: AtomicBool::new(false),
+            verified_since_write: AtomicBool::new(false),
         }
     }
 }
@@ -589,6 +1220,32 @@ struct ActiveQuery {
 
     /// Stores the entire cycle, if one is found and this query is part of it.
     cycle: Vec<DatabaseKeyIndex>,
+
+    /// Values pushed to accumulators thus far.
+    accumulated: Vec<AccumulatedValue>,
+
+    /// The fixpoint queries whose provisional values were (possibly
+    /// indirectly) read thus far; see `report_provisional_read`.
+    provisional_heads: Vec<DatabaseKeyIndex>,
+
+    /// The queries computed from provisional values that were (possibly
+    /// indirectly) read thus far; see `report_provisional_members`.
+    provisional_members: Vec<ProvisionalMember>,
+
+    /// The highest durability given to `report_durability` thus far.
+    reported_durability: Option<Durability>,
+
+    /// The lowest durability given to `depend_on_durability` thus far.
+    durability_dependency: Option<Durability>,
+}
+
+/// A query whose value was computed from the provisional values of
+/// fixpoint queries (see `Runtime::report_provisional_members`).
+pub(crate) struct ProvisionalMember {
+    pub(crate) database_key_index: DatabaseKeyIndex,
+
+    /// The inputs it read, or `None` if it read untracked state.
+    pub(crate) inputs: Option<Vec<DatabaseKeyIndex>>,
 }
 
 pub(crate) struct ComputedQueryResult<V> {
@@ -608,6 +1265,21 @@ pub(crate) struct ComputedQueryResult<V> {
 
     /// The cycle if one occured while computing this value
     pub(crate) cycle: Vec<DatabaseKeyIndex>,
+
+    /// Values pushed to accumulators while computing this value
+    pub(crate) accumulated: AccumulatedValues,
+
+    /// The fixpoint queries whose provisional values this value was
+    /// computed from; if there are any, it must not be memoized.
+    pub(crate) provisional_heads: Vec<DatabaseKeyIndex>,
+
+    /// The queries computed from provisional values that this value
+    /// was computed from, to be memoized once their heads converge.
+    pub(crate) provisional_members: Vec<ProvisionalMember>,
+
+    /// If set, the value must be re-validated whenever an input of
+    /// this durability (or higher) changes.
+    pub(crate) durability_dependency: Option<Durability>,
 }
 
 impl ActiveQuery {
@@ -618,6 +1290,11 @@ impl ActiveQuery {
             changed_at: Revision::start(),
             dependencies: Some(FxIndexSet::default()),
             cycle: Vec::new(),
+            accumulated: Vec::new(),
+            provisional_heads: Vec::new(),
+            provisional_members: Vec::new(),
+            reported_durability: None,
+            durability_dependency: None,
         }
     }
 
@@ -640,11 +1317,94 @@ impl ActiveQuery {
         self.durability = self.durability.min(durability);
     }
 
+    fn add_durability_dependency(&mut self, durability: Durability, changed_at: Revision) {
+        self.durability_dependency = Some(match self.durability_dependency {
+            Some(previous) => previous.min(durability),
+            None => durability,
+        });
+        self.durability = self.durability.min(durability);
+        self.changed_at = self.changed_at.max(changed_at);
+    }
+
+    fn add_reported_durability(&mut self, durability: Durability) {
+        self.reported_durability = self.reported_durability.max(Some(durability));
+    }
+
+    fn add_provisional_read(&mut self, heads: &[DatabaseKeyIndex]) {
+        for &head in heads {
+            if !self.provisional_heads.contains(&head) {
+                self.provisional_heads.push(head);
+            }
+        }
+    }
+
+    fn add_provisional_members(&mut self, members: Vec<ProvisionalMember>) {
+        self.provisional_members.extend(members);
+    }
+
     fn add_anon_read(&mut self, changed_at: Revision) {
         self.changed_at = self.changed_at.max(changed_at);
     }
 }
 
+/// Returned by `Database::assert_no_cycles`; restores the previous
+/// cycle handling when dropped.
+#[must_use = "cycles are only forbidden while the guard is alive"]
+pub struct NoCyclesGuard<'me> {
+    runtime: &'me Runtime,
+    previous: CycleHandling,
+}
+
+impl<'me> NoCyclesGuard<'me> {
+    pub(crate) fn new(runtime: &'me Runtime) -> Self {
+        let previous = runtime.cycle_handling();
+        runtime.set_cycle_handling(CycleHandling::Panic);
+        NoCyclesGuard { runtime, previous }
+    }
+}
+
+impl Drop for NoCyclesGuard<'_> {
+    fn drop(&mut self) {
+        self.runtime.set_cycle_handling(self.previous);
+    }
+}
+
+/// The error returned by `Runtime::check_canceled` when the current
+/// revision is canceled.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Canceled;
+
+impl std::fmt::Display for Canceled {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        fmt.write_str("the current revision was canceled")
+    }
+}
+
+impl std::error::Error for Canceled {}
+
+/// The payload with which salsa unwinds when a query would exceed the
+/// depth set with `Runtime::set_max_query_depth`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct QueryStackOverflow {
+    /// The query that was not executed.
+    pub query: DatabaseKeyIndex,
+
+    /// The maximum depth of the query stack.
+    pub max_depth: usize,
+}
+
+impl std::fmt::Display for QueryStackOverflow {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            fmt,
+            "executing {:?} would exceed the maximum query depth of {}",
+            self.query, self.max_depth
+        )
+    }
+}
+
+impl std::error::Error for QueryStackOverflow {}
+
 /// A unique identifier for a particular runtime. Each time you create
 /// a snapshot, a fresh `RuntimeId` is generated. Once a snapshot is
 /// complete, its `RuntimeId` may potentially be re-used.
@@ -783,6 +1543,10 @@ where
 
 struct RevisionGuard {
     shared_state: Arc<SharedState>,
+
+    /// The current revision when the guard was acquired, which cannot
+    /// change while it is held.
+    revision: Revision,
 }
 
 impl RevisionGuard {
@@ -809,6 +1573,7 @@ impl RevisionGuard {
 
         Self {
             shared_state: shared_state.clone(),
+            revision: shared_state.revisions[0].load(),
         }
     }
 }
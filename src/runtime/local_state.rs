@@ -1,8 +1,15 @@
+use crate::accumulator::AccumulatedValue;
 use crate::durability::Durability;
 use crate::runtime::ActiveQuery;
+use crate::runtime::ProvisionalMember;
 use crate::runtime::Revision;
+use crate::CycleHandling;
+use crate::Database;
 use crate::DatabaseKeyIndex;
-use std::cell::{Ref, RefCell, RefMut};
+use crate::Event;
+use crate::OverflowBehavior;
+use std::cell::{Cell, Ref, RefCell, RefMut};
+use std::time::{Duration, Instant};
 
 /// State that is specific to a single execution thread.
 ///
@@ -16,12 +23,60 @@ pub(super) struct LocalState {
     /// Unwinding note: pushes onto this vector must be popped -- even
     /// during unwinding.
     query_stack: RefCell<Vec<ActiveQuery>>,
+
+    /// Callback registered with `Runtime::on_cancellation`, if it has
+    /// not yet fired.
+    cancellation_hook: RefCell<Option<CancellationHook>>,
+
+    /// Handler installed with `Database::with_event_handler`, if its
+    /// closure is running. Taken out while the handler itself runs.
+    event_handler: RefCell<Option<EventHandler>>,
+
+    /// Set with `Runtime::set_cycle_handling`.
+    cycle_handling: Cell<CycleHandling>,
+
+    /// Set with `Runtime::set_query_timeout`.
+    query_timeout: Cell<Option<Duration>>,
+
+    /// Set with `Runtime::set_max_query_depth`.
+    max_query_depth: Cell<Option<usize>>,
+
+    /// Set with `Runtime::set_recursion_warning_depth`.
+    recursion_warning_depth: Cell<Option<usize>>,
+
+    /// Set with `Runtime::set_overflow_behavior`.
+    overflow_behavior: Cell<OverflowBehavior>,
+
+    /// Set with `Runtime::set_strict`.
+    strict: Cell<bool>,
+
+    /// True while executing a query again in strict mode, during which
+    /// no events are reported and no query stats are recorded.
+    muted: Cell<bool>,
+
+    /// When the bottom-most query on the stack started executing, if
+    /// there is a `query_timeout`.
+    query_started_at: Cell<Option<Instant>>,
 }
 
+pub(super) type CancellationHook = Box<dyn FnOnce() + Send>;
+
+pub(super) type EventHandler = Box<dyn FnMut(&dyn Database, Event) + Send>;
+
 impl Default for LocalState {
     fn default() -> Self {
         LocalState {
             query_stack: Default::default(),
+            cancellation_hook: Default::default(),
+            event_handler: Default::default(),
+            cycle_handling: Default::default(),
+            query_timeout: Default::default(),
+            max_query_depth: Default::default(),
+            recursion_warning_depth: Default::default(),
+            overflow_behavior: Default::default(),
+            strict: Default::default(),
+            muted: Default::default(),
+            query_started_at: Default::default(),
         }
     }
 }
@@ -33,6 +88,10 @@ impl LocalState {
         max_durability: Durability,
     ) -> ActiveQueryGuard<'_> {
         let mut query_stack = self.query_stack.borrow_mut();
+        if query_stack.is_empty() {
+            self.query_started_at
+                .set(self.query_timeout.get().map(|_| Instant::now()));
+        }
         query_stack.push(ActiveQuery::new(database_key_index, max_durability));
         ActiveQueryGuard {
             local_state: self,
@@ -53,6 +112,92 @@ impl LocalState {
         self.query_stack.borrow_mut()
     }
 
+    pub(super) fn cycle_handling(&self) -> CycleHandling {
+        self.cycle_handling.get()
+    }
+
+    pub(super) fn set_cycle_handling(&self, cycle_handling: CycleHandling) {
+        self.cycle_handling.set(cycle_handling);
+    }
+
+    pub(super) fn set_query_timeout(&self, timeout: Option<Duration>) {
+        self.query_timeout.set(timeout);
+        if timeout.is_none() {
+            self.query_started_at.set(None);
+        }
+    }
+
+    /// True if the bottom-most query on the stack has been executing
+    /// for longer than the `query_timeout`.
+    pub(super) fn query_timed_out(&self) -> bool {
+        match (self.query_timeout.get(), self.query_started_at.get()) {
+            (Some(timeout), Some(started_at)) => {
+                self.query_in_progress() && started_at.elapsed() > timeout
+            }
+            _ => false,
+        }
+    }
+
+    pub(super) fn max_query_depth(&self) -> Option<usize> {
+        self.max_query_depth.get()
+    }
+
+    pub(super) fn set_max_query_depth(&self, max_depth: Option<usize>) {
+        self.max_query_depth.set(max_depth);
+    }
+
+    pub(super) fn recursion_warning_depth(&self) -> Option<usize> {
+        self.recursion_warning_depth.get()
+    }
+
+    pub(super) fn set_recursion_warning_depth(&self, depth: Option<usize>) {
+        self.recursion_warning_depth.set(depth);
+    }
+
+    pub(super) fn overflow_behavior(&self) -> OverflowBehavior {
+        self.overflow_behavior.get()
+    }
+
+    pub(super) fn set_overflow_behavior(&self, behavior: OverflowBehavior) {
+        self.overflow_behavior.set(behavior);
+    }
+
+    pub(super) fn strict(&self) -> bool {
+        self.strict.get()
+    }
+
+    pub(super) fn set_strict(&self, strict: bool) {
+        self.strict.set(strict);
+    }
+
+    pub(super) fn muted(&self) -> bool {
+        self.muted.get()
+    }
+
+    /// Sets whether events and query stats are muted, returning whether
+    /// they were before.
+    pub(super) fn replace_muted(&self, muted: bool) -> bool {
+        self.muted.replace(muted)
+    }
+
+    pub(super) fn query_depth(&self) -> usize {
+        self.query_stack.borrow().len()
+    }
+
+    /// Returns the number of executions of the query of
+    /// `database_key_index` (with any key) on the stack.
+    pub(super) fn query_depth_of(&self, database_key_index: DatabaseKeyIndex) -> usize {
+        self.query_stack
+            .borrow()
+            .iter()
+            .filter(|active_query| {
+                let key = active_query.database_key_index;
+                key.group_index() == database_key_index.group_index()
+                    && key.query_index() == database_key_index.query_index()
+            })
+            .count()
+    }
+
     pub(super) fn query_in_progress(&self) -> bool {
         !self.query_stack.borrow().is_empty()
     }
@@ -64,6 +209,14 @@ impl LocalState {
             .map(|active_query| active_query.database_key_index)
     }
 
+    pub(super) fn active_query_stack(&self) -> Vec<DatabaseKeyIndex> {
+        self.query_stack
+            .borrow()
+            .iter()
+            .map(|active_query| active_query.database_key_index)
+            .collect()
+    }
+
     pub(super) fn report_query_read(
         &self,
         input: DatabaseKeyIndex,
@@ -81,17 +234,81 @@ impl LocalState {
         }
     }
 
+    /// Records `value` for the active query, returning false if there
+    /// is none.
+    pub(super) fn accumulate(&self, value: AccumulatedValue) -> bool {
+        match self.query_stack.borrow_mut().last_mut() {
+            Some(top_query) => {
+                top_query.accumulated.push(value);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub(super) fn report_provisional_read(&self, heads: &[DatabaseKeyIndex]) {
+        if let Some(top_query) = self.query_stack.borrow_mut().last_mut() {
+            top_query.add_provisional_read(heads);
+        }
+    }
+
+    pub(super) fn report_provisional_members(&self, members: Vec<ProvisionalMember>) {
+        if let Some(top_query) = self.query_stack.borrow_mut().last_mut() {
+            top_query.add_provisional_members(members);
+        }
+    }
+
     pub(super) fn report_synthetic_read(&self, durability: Durability) {
         if let Some(top_query) = self.query_stack.borrow_mut().last_mut() {
             top_query.add_synthetic_read(durability);
         }
     }
 
+    pub(super) fn depend_on_durability(&self, durability: Durability, changed_at: Revision) {
+        if let Some(top_query) = self.query_stack.borrow_mut().last_mut() {
+            top_query.add_durability_dependency(durability, changed_at);
+        }
+    }
+
+    pub(super) fn report_durability(&self, durability: Durability) {
+        if let Some(top_query) = self.query_stack.borrow_mut().last_mut() {
+            top_query.add_reported_durability(durability);
+        }
+    }
+
     pub(super) fn report_anon_read(&self, revision: Revision) {
         if let Some(top_query) = self.query_stack.borrow_mut().last_mut() {
             top_query.add_anon_read(revision);
         }
     }
+
+    pub(super) fn set_cancellation_hook(&self, hook: CancellationHook) {
+        *self.cancellation_hook.borrow_mut() = Some(hook);
+    }
+
+    pub(super) fn has_cancellation_hook(&self) -> bool {
+        self.cancellation_hook.borrow().is_some()
+    }
+
+    pub(super) fn take_cancellation_hook(&self) -> Option<CancellationHook> {
+        self.cancellation_hook.borrow_mut().take()
+    }
+
+    pub(super) fn replace_event_handler(
+        &self,
+        handler: Option<EventHandler>,
+    ) -> Option<EventHandler> {
+        std::mem::replace(&mut *self.event_handler.borrow_mut(), handler)
+    }
+
+    /// Puts back a handler taken out with `replace_event_handler(None)`,
+    /// unless another one was installed in the meantime.
+    pub(super) fn restore_event_handler(&self, handler: EventHandler) {
+        let mut slot = self.event_handler.borrow_mut();
+        if slot.is_none() {
+            *slot = Some(handler);
+        }
+    }
 }
 
 impl std::panic::RefUnwindSafe for LocalState {}
@@ -50,4 +50,42 @@ impl<DB: DatabaseStorageTypes> Storage<DB> {
             runtime: self.runtime.snapshot(),
         }
     }
+
+    /// Returns an independent copy of this storage, suitable for use in
+    /// a copy of the database. Unlike a snapshot, the copy can be
+    /// mutated: it starts with the same inputs and memoized values (and
+    /// in the same revision), but from then on the two storages do not
+    /// affect each other. This copies every table, so it is expensive;
+    /// it is mostly useful in tests, to compare the results of changing
+    /// an input against those of the original database, and for
+    /// speculative ("what if") computations: set inputs on the copy,
+    /// read queries from it, and drop it, leaving the original database
+    /// untouched. (There is no cheaper copy-on-write mode; for a single
+    /// what-if at a time, setting the input and then restoring its old
+    /// value is cheaper still, though it creates two new revisions.)
+    ///
+    /// ```rust,ignore
+    /// impl MyDatabase {
+    ///     fn fork(&self) -> Self {
+    ///         MyDatabase {
+    ///             storage: self.storage.fork(),
+    ///         }
+    ///     }
+    /// }
+    /// ```
+    ///
+    /// Values being computed (on other threads) when the storage is
+    /// forked are absent from the copy, and will be computed again if
+    /// needed. Settings of the runtime (like the cycle handling) are not
+    /// copied.
+    ///
+    /// # Panics
+    ///
+    /// If invoked while a query is executing.
+    pub fn fork(&self) -> Self {
+        Storage {
+            runtime: self.runtime.fork(),
+            query_store: Arc::new(DB::fork_database_storage(&self.query_store)),
+        }
+    }
 }
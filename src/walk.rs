@@ -0,0 +1,68 @@
+use crate::plumbing::DatabaseOps;
+use crate::DatabaseKeyIndex;
+use rustc_hash::FxHashSet;
+
+/// Receives the queries visited by `Database::walk_dependencies`.
+///
+/// Each query is entered at most once, even if it is reachable along
+/// several paths (or through a cycle). Its dependencies are walked
+/// between the calls to `enter` and `exit`, in the order in which it
+/// read them, so a query is exited only after all of them are (apart
+/// from those entered earlier, which are skipped).
+pub trait DependencyVisitor {
+    /// Called when the walk reaches `node`. Returning false skips the
+    /// dependencies of `node` (which may still be reached along other
+    /// paths). The default returns true.
+    fn enter(&mut self, node: DatabaseKeyIndex) -> bool {
+        let _ = node;
+        true
+    }
+
+    /// Called once the dependencies of `node` have been walked (or
+    /// skipped). The default does nothing.
+    fn exit(&mut self, node: DatabaseKeyIndex) {
+        let _ = node;
+    }
+}
+
+/// Walks the dependencies recorded for `roots`, depth first, without
+/// recursing (so long chains of queries do not overflow the stack).
+pub(crate) fn walk_dependencies<DB>(
+    db: &DB,
+    roots: impl IntoIterator<Item = DatabaseKeyIndex>,
+    visitor: &mut dyn DependencyVisitor,
+) where
+    DB: ?Sized + DatabaseOps,
+{
+    let mut visited = FxHashSet::default();
+
+    // The queries that were entered but not yet exited, each with its
+    // dependencies that are still to be walked.
+    let mut stack: Vec<(DatabaseKeyIndex, std::vec::IntoIter<DatabaseKeyIndex>)> = vec![];
+    let mut enter =
+        |node: DatabaseKeyIndex, stack: &mut Vec<_>, visitor: &mut dyn DependencyVisitor| {
+            if !visited.insert(node) {
+                return;
+            }
+            let dependencies = if visitor.enter(node) {
+                db.dependencies(node).unwrap_or_default()
+            } else {
+                vec![]
+            };
+            stack.push((node, dependencies.into_iter()));
+        };
+
+    for root in roots {
+        enter(root, &mut stack, visitor);
+        while let Some((node, dependencies)) = stack.last_mut() {
+            match dependencies.next() {
+                Some(dependency) => enter(dependency, &mut stack, visitor),
+                None => {
+                    let node = *node;
+                    stack.pop();
+                    visitor.exit(node);
+                }
+            }
+        }
+    }
+}
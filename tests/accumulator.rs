@@ -0,0 +1,173 @@
+//! Test accumulators, which collect values pushed by queries as a side
+//! channel.
+
+use std::cell::Cell;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Diagnostic(String);
+
+#[salsa::accumulator(Diagnostic)]
+struct Diagnostics;
+
+#[salsa::accumulator(String)]
+struct Errors;
+
+#[salsa::accumulator(String)]
+struct Warnings;
+
+#[salsa::query_group(CheckStorage)]
+trait CheckDatabase: salsa::Database {
+    #[salsa::input]
+    fn file_text(&self, file: u32) -> String;
+
+    #[salsa::input]
+    fn files(&self) -> Vec<u32>;
+
+    fn check_file(&self, file: u32) -> usize;
+
+    fn check_all(&self) -> usize;
+
+    fn lint(&self) -> ();
+}
+
+fn check_file(db: &dyn CheckDatabase, file: u32) -> usize {
+    let text = db.file_text(file);
+    for word in text.split_whitespace() {
+        if word == "bad" {
+            Diagnostics::push(db, Diagnostic(format!("bad word in {}", file)));
+        }
+    }
+    text.len()
+}
+
+fn check_all(db: &dyn CheckDatabase) -> usize {
+    Diagnostics::push(db, Diagnostic("checking".to_string()));
+    let files = db.files();
+    files.iter().map(|&file| db.check_file(file)).sum()
+}
+
+fn lint(db: &dyn CheckDatabase) {
+    Errors::push(db, "error".to_string());
+    Warnings::push(db, "warning".to_string());
+}
+
+#[salsa::database(CheckStorage)]
+#[derive(Default)]
+struct Database {
+    storage: salsa::Storage<Self>,
+    executions: Cell<usize>,
+}
+
+impl salsa::Database for Database {
+    fn salsa_event(&self, event: salsa::Event) {
+        if let salsa::EventKind::WillExecute { .. } = event.kind {
+            self.executions.set(self.executions.get() + 1);
+        }
+    }
+}
+
+fn diagnostics(db: &Database) -> Vec<String> {
+    CheckAllQuery
+        .in_db(db)
+        .accumulated::<Diagnostics>(())
+        .into_iter()
+        .map(|Diagnostic(message)| message)
+        .collect()
+}
+
+#[test]
+fn transitive() {
+    let mut db = Database::default();
+    db.set_file_text(1).to("good bad".to_string());
+    db.set_file_text(2).to("good".to_string());
+    db.set_file_text(3).to("bad bad".to_string());
+    db.set_files().to(vec![1, 2, 3, 1]);
+
+    // The query's own values come first, and `check_file(1)` is only
+    // visited once even though it is read twice.
+    assert_eq!(
+        diagnostics(&db),
+        vec![
+            "checking".to_string(),
+            "bad word in 1".to_string(),
+            "bad word in 3".to_string(),
+            "bad word in 3".to_string(),
+        ]
+    );
+    assert_eq!(
+        CheckFileQuery.in_db(&db).accumulated::<Diagnostics>(2),
+        vec![]
+    );
+}
+
+#[test]
+fn replayed_on_revalidation() {
+    let mut db = Database::default();
+    db.set_file_text(1).to("bad".to_string());
+    db.set_file_text(2).to("good".to_string());
+    db.set_files().to(vec![1, 2]);
+
+    assert_eq!(
+        diagnostics(&db),
+        vec!["checking".to_string(), "bad word in 1".to_string()]
+    );
+
+    // Changing file 2 re-executes `check_file(2)` only; the values from
+    // `check_file(1)` come from its (revalidated) memoized value.
+    db.set_file_text(2).to("also bad".to_string());
+    db.executions.set(0);
+    assert_eq!(
+        diagnostics(&db),
+        vec![
+            "checking".to_string(),
+            "bad word in 1".to_string(),
+            "bad word in 2".to_string(),
+        ]
+    );
+    assert_eq!(db.executions.get(), 2);
+
+    // Once the problem is fixed, its value is no longer reported.
+    db.set_file_text(1).to("good".to_string());
+    assert_eq!(
+        diagnostics(&db),
+        vec!["checking".to_string(), "bad word in 2".to_string()]
+    );
+}
+
+#[test]
+fn get_by_database_key_index() {
+    let mut db = Database::default();
+    db.set_file_text(1).to("bad".to_string());
+    db.set_files().to(vec![1]);
+    db.check_all();
+
+    // `check_all` read `files()`, then `check_file(1)`.
+    let dependencies = CheckAllQuery.in_db(&db).dependencies(&()).unwrap();
+    assert_eq!(
+        Diagnostics::get(&db, dependencies[1]),
+        vec![Diagnostic("bad word in 1".to_string())]
+    );
+    assert_eq!(Diagnostics::get(&db, dependencies[0]), vec![]);
+}
+
+#[test]
+fn same_data_type() {
+    let db = Database::default();
+    db.lint();
+
+    // `Errors` and `Warnings` both collect `String`s, but each only
+    // gets the values pushed to it.
+    let lint = LintQuery.in_db(&db);
+    assert_eq!(lint.accumulated::<Errors>(()), vec!["error".to_string()]);
+    assert_eq!(
+        lint.accumulated::<Warnings>(()),
+        vec!["warning".to_string()]
+    );
+}
+
+#[test]
+#[should_panic(expected = "while executing a query")]
+fn push_outside_query() {
+    let db = Database::default();
+    Diagnostics::push(&db, Diagnostic("oops".to_string()));
+}
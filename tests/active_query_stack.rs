@@ -0,0 +1,39 @@
+//! Test `Runtime::active_query_stack`.
+
+use salsa::Database as _;
+
+#[salsa::query_group(QueryGroupStorage)]
+trait QueryGroup: salsa::Database {
+    fn outer(&self, x: u32) -> Vec<String>;
+
+    fn inner(&self, x: u32) -> Vec<String>;
+}
+
+fn outer(db: &dyn QueryGroup, x: u32) -> Vec<String> {
+    db.inner(x)
+}
+
+fn inner(db: &dyn QueryGroup, _x: u32) -> Vec<String> {
+    db.salsa_runtime()
+        .active_query_stack()
+        .into_iter()
+        .map(|index| format!("{:?}", index.debug(db)))
+        .collect()
+}
+
+#[salsa::database(QueryGroupStorage)]
+#[derive(Default)]
+struct Database {
+    storage: salsa::Storage<Self>,
+}
+
+impl salsa::Database for Database {}
+
+#[test]
+fn active_query_stack() {
+    let db = Database::default();
+    assert!(db.salsa_runtime().active_query_stack().is_empty());
+    assert_eq!(db.outer(1), vec!["outer(1)", "inner(1)"]);
+    assert_eq!(db.inner(2), vec!["inner(2)"]);
+    assert!(db.salsa_runtime().active_query_stack().is_empty());
+}
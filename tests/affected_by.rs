@@ -0,0 +1,82 @@
+//! Test `Database::affected_by`.
+
+use salsa::Database as _;
+
+#[salsa::query_group(QueryGroupStorage)]
+trait QueryGroup: salsa::Database {
+    #[salsa::input]
+    fn input(&self, x: u32) -> u32;
+
+    fn double(&self, x: u32) -> u32;
+
+    fn sum(&self) -> u32;
+
+    fn untracked(&self) -> u32;
+
+    fn after_untracked(&self) -> u32;
+}
+
+fn double(db: &dyn QueryGroup, x: u32) -> u32 {
+    db.input(x) * 2
+}
+
+fn sum(db: &dyn QueryGroup) -> u32 {
+    db.double(1) + db.double(2)
+}
+
+fn untracked(db: &dyn QueryGroup) -> u32 {
+    db.salsa_runtime().report_untracked_read();
+    0
+}
+
+fn after_untracked(db: &dyn QueryGroup) -> u32 {
+    db.untracked() + 1
+}
+
+#[salsa::database(QueryGroupStorage)]
+#[derive(Default)]
+struct Database {
+    storage: salsa::Storage<Self>,
+}
+
+impl salsa::Database for Database {}
+
+fn affected_by(db: &Database, key: salsa::DatabaseKeyIndex) -> Vec<String> {
+    db.affected_by(key)
+        .into_iter()
+        .map(|key| format!("{:?}", key.debug(db)))
+        .collect()
+}
+
+#[test]
+fn transitive_readers() {
+    let mut db = Database::default();
+    db.set_input(1).to(1);
+    db.set_input(2).to(2);
+    db.set_input(3).to(3);
+    assert_eq!(db.sum(), 6);
+    assert_eq!(db.double(3), 6);
+
+    let input_1 = DoubleQuery.in_db(&db).dependencies(&1).unwrap()[0];
+    assert_eq!(affected_by(&db, input_1), vec!["double(1)", "sum(())"]);
+
+    let double_2 = SumQuery.in_db(&db).dependencies(&()).unwrap()[1];
+    assert_eq!(affected_by(&db, double_2), vec!["sum(())"]);
+
+    let input_3 = DoubleQuery.in_db(&db).dependencies(&3).unwrap()[0];
+    assert_eq!(affected_by(&db, input_3), vec!["double(3)"]);
+}
+
+#[test]
+fn untracked_reads_are_always_affected() {
+    let mut db = Database::default();
+    db.set_input(1).to(1);
+    assert_eq!(db.double(1), 2);
+    assert_eq!(db.after_untracked(), 1);
+
+    let input_1 = DoubleQuery.in_db(&db).dependencies(&1).unwrap()[0];
+    assert_eq!(
+        affected_by(&db, input_1),
+        vec!["double(1)", "untracked(())", "after_untracked(())"]
+    );
+}
@@ -0,0 +1,42 @@
+//! Test that reading a query that returns an `Arc` hands out handles
+//! to the memoized allocation, rather than copies of it.
+
+use std::sync::Arc;
+
+#[salsa::query_group(QueryGroupStorage)]
+trait QueryGroup: salsa::Database {
+    #[salsa::input]
+    fn input(&self, x: u32) -> u32;
+
+    fn big(&self, x: u32) -> Arc<Vec<u32>>;
+}
+
+fn big(db: &dyn QueryGroup, x: u32) -> Arc<Vec<u32>> {
+    Arc::new(vec![db.input(x); 1024])
+}
+
+#[salsa::database(QueryGroupStorage)]
+#[derive(Default)]
+struct Database {
+    storage: salsa::Storage<Self>,
+}
+
+impl salsa::Database for Database {}
+
+#[test]
+fn reads_share_allocation() {
+    let mut db = Database::default();
+    db.set_input(1).to(1);
+    db.set_input(2).to(2);
+
+    let first = db.big(1);
+    assert!(Arc::ptr_eq(&first, &db.big(1)));
+
+    // `big(1)` is verified, not re-executed, in the new revision.
+    db.set_input(2).to(3);
+    assert!(Arc::ptr_eq(&first, &db.big(1)));
+
+    db.set_input(1).to(4);
+    assert!(!Arc::ptr_eq(&first, &db.big(1)));
+    assert_eq!(db.big(1)[0], 4);
+}
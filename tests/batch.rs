@@ -0,0 +1,97 @@
+//! Test `Database::batch`.
+
+use salsa::{Database as _, Durability};
+
+#[salsa::query_group(QueryGroupStorage)]
+trait QueryGroup: salsa::Database {
+    #[salsa::input]
+    fn input(&self, x: u32) -> u32;
+
+    #[salsa::input]
+    fn other_input(&self) -> u32;
+
+    fn sum(&self) -> u32;
+
+    fn second(&self) -> u32;
+}
+
+fn sum(db: &dyn QueryGroup) -> u32 {
+    db.input(1) + db.input(2) + db.other_input()
+}
+
+fn second(db: &dyn QueryGroup) -> u32 {
+    db.input(2)
+}
+
+#[salsa::database(QueryGroupStorage)]
+#[derive(Default)]
+struct Database {
+    storage: salsa::Storage<Self>,
+}
+
+impl salsa::Database for Database {}
+
+/// The number of revisions since the first.
+fn revision(db: &Database) -> usize {
+    salsa::Revision::start()
+        .distance_to(db.salsa_runtime().current_revision())
+        .unwrap()
+}
+
+#[test]
+fn sets_share_one_revision() {
+    let mut db = Database::default();
+    db.set_input(1).to(1);
+    db.set_input(2).with_durability(Durability::HIGH).to(2);
+    db.set_other_input().to(3);
+    assert_eq!(db.sum(), 6);
+    assert_eq!(db.second(), 2);
+    let before = revision(&db);
+
+    db.batch(|db| {
+        db.set_input(1).to(10);
+        db.set_input(2).with_durability(Durability::HIGH).to(20);
+        db.set_other_input().to(30);
+    });
+    assert_eq!(revision(&db), before + 1);
+    assert_eq!(db.sum(), 60);
+
+    // The change of the high durability input is seen, too.
+    assert_eq!(db.second(), 20);
+}
+
+#[test]
+fn reads_see_pending_sets() {
+    let mut db = Database::default();
+    db.set_input(1).to(1);
+    db.set_input(2).to(2);
+    db.set_other_input().to(3);
+    let before = revision(&db);
+
+    db.batch(|db| {
+        db.set_input(1).to(10);
+        db.set_input(2).to(20);
+        assert_eq!(db.sum(), 33);
+
+        // A set after a read starts a new revision, so the read value is
+        // not reused.
+        db.set_other_input().to(30);
+        assert_eq!(db.sum(), 60);
+    });
+    assert_eq!(revision(&db), before + 2);
+    assert_eq!(db.sum(), 60);
+}
+
+#[test]
+fn revisions_advance_again_after_batch() {
+    let mut db = Database::default();
+    db.batch(|db| {
+        db.set_input(1).to(1);
+        db.batch(|db| db.set_input(2).to(2));
+        db.set_other_input().to(3);
+    });
+    let after_batch = revision(&db);
+
+    db.set_input(1).to(10);
+    assert_eq!(revision(&db), after_batch + 1);
+}
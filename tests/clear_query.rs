@@ -0,0 +1,76 @@
+//! Test `QueryTableMut::clear`.
+
+use std::cell::RefCell;
+
+#[salsa::query_group(QueryGroupStorage)]
+trait QueryGroup: salsa::Database {
+    #[salsa::input]
+    fn input(&self, x: u32) -> u32;
+
+    fn double(&self, x: u32) -> u32;
+
+    fn double_plus_one(&self, x: u32) -> u32;
+
+    fn triple(&self, x: u32) -> u32;
+}
+
+fn double(db: &dyn QueryGroup, x: u32) -> u32 {
+    db.input(x) * 2
+}
+
+fn double_plus_one(db: &dyn QueryGroup, x: u32) -> u32 {
+    db.double(x) + 1
+}
+
+fn triple(db: &dyn QueryGroup, x: u32) -> u32 {
+    db.input(x) * 3
+}
+
+#[salsa::database(QueryGroupStorage)]
+#[derive(Default)]
+struct Database {
+    storage: salsa::Storage<Self>,
+    executed: RefCell<Vec<String>>,
+}
+
+impl salsa::Database for Database {
+    fn salsa_event(&self, event: salsa::Event) {
+        if let salsa::EventKind::WillExecute { database_key } = event.kind {
+            let query = format!("{:?}", database_key.debug(self));
+            self.executed.borrow_mut().push(query);
+        }
+    }
+}
+
+#[test]
+fn clear_re_executes_query_and_dependents() {
+    let mut db = Database::default();
+    db.set_input(1).to(10);
+    db.set_input(2).to(20);
+    assert_eq!(db.double_plus_one(1), 21);
+    assert_eq!(db.double_plus_one(2), 41);
+    assert_eq!(db.triple(1), 30);
+    db.executed.borrow_mut().clear();
+
+    DoubleQuery.in_db_mut(&mut db).clear();
+    assert_eq!(DoubleQuery.in_db(&db).dependencies(&1), None);
+
+    assert_eq!(db.double_plus_one(1), 21);
+    assert_eq!(db.triple(1), 30);
+    assert_eq!(
+        *db.executed.borrow(),
+        vec!["double_plus_one(1)", "double(1)"]
+    );
+}
+
+#[test]
+fn clear_unused_query() {
+    let mut db = Database::default();
+    db.set_input(1).to(10);
+    assert_eq!(db.triple(1), 30);
+    db.executed.borrow_mut().clear();
+
+    DoubleQuery.in_db_mut(&mut db).clear();
+    assert_eq!(db.triple(1), 30);
+    assert!(db.executed.borrow().is_empty());
+}
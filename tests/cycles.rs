@@ -1,4 +1,5 @@
-use salsa::{ParallelDatabase, Snapshot};
+use salsa::{CycleHandling, ParallelDatabase, Snapshot};
+use std::any::Any;
 
 #[derive(PartialEq, Eq, Hash, Clone, Debug)]
 struct Error {
@@ -39,15 +40,15 @@ trait Database: salsa::Database {
     fn cycle_c(&self) -> Result<(), Error>;
 }
 
-fn recover_a(_db: &dyn Database, cycle: &[String]) -> Result<(), Error> {
+fn recover_a(db: &dyn Database, cycle: &salsa::Cycle) -> Result<(), Error> {
     Err(Error {
-        cycle: cycle.to_owned(),
+        cycle: cycle.all_participants(db),
     })
 }
 
-fn recover_b(_db: &dyn Database, cycle: &[String]) -> Result<(), Error> {
+fn recover_b(db: &dyn Database, cycle: &salsa::Cycle) -> Result<(), Error> {
     Err(Error {
-        cycle: cycle.to_owned(),
+        cycle: cycle.all_participants(db),
     })
 }
 
@@ -160,3 +161,58 @@ fn parallel_cycle() {
     thread2.join().unwrap();
     eprintln!("OK");
 }
+
+fn recover_unit(_cycle: &salsa::Cycle, _key: salsa::DatabaseKeyIndex) -> Option<Box<dyn Any>> {
+    Some(Box::new(()))
+}
+
+#[test]
+#[should_panic(expected = "cycle detected")]
+fn cycle_handling_panic() {
+    let db = DatabaseImpl::default();
+    salsa::Database::salsa_runtime(&db).set_cycle_handling(CycleHandling::Panic);
+    let _ = db.cycle_a();
+}
+
+#[test]
+fn cycle_handling_recover_with() {
+    let db = DatabaseImpl::default();
+    let snapshot = db.snapshot();
+    salsa::Database::salsa_runtime(&*snapshot)
+        .set_cycle_handling(CycleHandling::RecoverWith(recover_unit));
+    snapshot.memoized_a();
+    snapshot.volatile_a();
+
+    // `recover_unit` gives a value of the wrong type for `cycle_a`
+    // and `cycle_b`, so they use their `#[salsa::cycle]` functions.
+    assert!(snapshot.cycle_c().is_err());
+}
+
+#[test]
+fn cycle_panic_lists_participants() {
+    let db = DatabaseImpl::default();
+    let err =
+        std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| db.memoized_a())).unwrap_err();
+    let message = err.downcast_ref::<String>().unwrap();
+    assert!(message.contains("cycle detected"), "{}", message);
+    assert!(message.contains("memoized_a(())"), "{}", message);
+    assert!(message.contains("memoized_b(())"), "{}", message);
+}
+
+#[test]
+#[should_panic(expected = "cycle_a(())")]
+fn assert_no_cycles_panics() {
+    let db = DatabaseImpl::default();
+    let _guard = salsa::Database::assert_no_cycles(&db);
+    let _ = db.cycle_a();
+}
+
+#[test]
+fn assert_no_cycles_is_scoped() {
+    let db = DatabaseImpl::default();
+    {
+        let _guard = salsa::Database::assert_no_cycles(&db);
+        db.cycle_leaf();
+    }
+    assert!(db.cycle_a().is_err());
+}
@@ -0,0 +1,61 @@
+//! Test the human-readable output of `DatabaseKeyIndex::debug`.
+
+use std::cell::RefCell;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+struct FileId(u32);
+
+#[salsa::query_group(SourceStorage)]
+trait SourceDatabase: salsa::Database {
+    #[salsa::input]
+    fn file_text(&self, file: FileId) -> String;
+}
+
+#[salsa::query_group(ParseStorage)]
+trait ParseDatabase: SourceDatabase {
+    fn parse_file(&self, file: FileId) -> usize;
+
+    fn parse_range(&self, file: FileId, start: usize, end: usize) -> usize;
+}
+
+fn parse_file(db: &dyn ParseDatabase, file: FileId) -> usize {
+    db.file_text(file).len()
+}
+
+fn parse_range(db: &dyn ParseDatabase, file: FileId, start: usize, end: usize) -> usize {
+    db.file_text(file)[start..end].len()
+}
+
+#[salsa::database(SourceStorage, ParseStorage)]
+#[derive(Default)]
+struct Database {
+    storage: salsa::Storage<Self>,
+    executed: RefCell<Vec<salsa::DatabaseKeyIndex>>,
+}
+
+impl salsa::Database for Database {
+    fn salsa_event(&self, event: salsa::Event) {
+        if let salsa::EventKind::WillExecute { database_key } = event.kind {
+            self.executed.borrow_mut().push(database_key);
+        }
+    }
+}
+
+#[test]
+fn debug_names_queries_and_keys() {
+    let mut db = Database::default();
+    db.set_file_text(FileId(3)).to("hello world".to_string());
+    db.parse_file(FileId(3));
+    db.parse_range(FileId(3), 0, 5);
+
+    let executed: Vec<String> = db
+        .executed
+        .borrow()
+        .iter()
+        .map(|key| format!("{:?}", key.debug(&db)))
+        .collect();
+    assert_eq!(
+        executed,
+        vec!["parse_file(FileId(3))", "parse_range((FileId(3), 0, 5))"]
+    );
+}
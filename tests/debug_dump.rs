@@ -0,0 +1,48 @@
+//! Test `Database::debug_all_memoized`.
+#![cfg(feature = "debug-dump")]
+
+use salsa::Database as _;
+
+#[salsa::query_group(QueryGroupStorage)]
+trait QueryGroup: salsa::Database {
+    #[salsa::input]
+    fn input(&self, x: u32) -> u32;
+
+    fn double(&self, x: u32) -> u32;
+}
+
+fn double(db: &dyn QueryGroup, x: u32) -> u32 {
+    db.input(x) * 2
+}
+
+#[salsa::database(QueryGroupStorage)]
+#[derive(Default)]
+struct Database {
+    storage: salsa::Storage<Self>,
+}
+
+impl salsa::Database for Database {}
+
+#[test]
+fn debug_all_memoized() {
+    let mut db = Database::default();
+    db.set_input(1).to(1);
+    db.set_input(2).to(2);
+    assert_eq!(db.double(1), 2);
+    assert_eq!(db.double(2), 4);
+
+    // `double(2)` is not verified in the new revision until it is
+    // used again.
+    db.set_input(1).to(10);
+    assert_eq!(db.double(1), 20);
+
+    assert_eq!(
+        format!("{:?}", db.debug_all_memoized()),
+        "[\
+         Memo { index: input(1), key: 1, verified: true, value: Some(10) }, \
+         Memo { index: input(2), key: 2, verified: true, value: Some(2) }, \
+         Memo { index: double(1), key: 1, verified: true, value: Some(20) }, \
+         Memo { index: double(2), key: 2, verified: false, value: Some(4) }\
+         ]"
+    );
+}
@@ -0,0 +1,127 @@
+//! Test `Database::debug_validate_dependencies`.
+#![cfg(feature = "debug-dump")]
+
+use salsa::Database as _;
+use std::cell::Cell;
+
+#[salsa::query_group(QueryGroupStorage)]
+trait QueryGroup: salsa::Database + AsRef<Cell<u32>> {
+    #[salsa::input]
+    fn input(&self, x: u32) -> u32;
+
+    fn double(&self, x: u32) -> u32;
+
+    fn add_untracked(&self, x: u32) -> u32;
+
+    fn input_or_other(&self) -> u32;
+
+    #[salsa::no_eq]
+    fn double_no_eq(&self, x: u32) -> u32;
+}
+
+fn double(db: &dyn QueryGroup, x: u32) -> u32 {
+    db.input(x) * 2
+}
+
+/// Reads state that salsa does not know about.
+fn add_untracked(db: &dyn QueryGroup, x: u32) -> u32 {
+    db.input(x) + db.as_ref().get()
+}
+
+fn double_no_eq(db: &dyn QueryGroup, x: u32) -> u32 {
+    db.input(x) * 2
+}
+
+fn input_or_other(db: &dyn QueryGroup) -> u32 {
+    if db.as_ref().get() == 0 {
+        db.input(1)
+    } else {
+        db.input(2)
+    }
+}
+
+#[salsa::database(QueryGroupStorage)]
+#[derive(Default)]
+struct Database {
+    storage: salsa::Storage<Self>,
+    untracked: Cell<u32>,
+    events: Cell<usize>,
+}
+
+impl salsa::Database for Database {
+    fn salsa_event(&self, _event: salsa::Event) {
+        self.events.set(self.events.get() + 1);
+    }
+}
+
+impl AsRef<Cell<u32>> for Database {
+    fn as_ref(&self) -> &Cell<u32> {
+        &self.untracked
+    }
+}
+
+#[test]
+fn consistent() {
+    let mut db = Database::default();
+    db.set_input(1).to(1);
+    db.set_input(2).to(2);
+    assert_eq!(db.double(1), 2);
+    assert_eq!(db.double(2), 4);
+
+    db.set_input(1).to(10);
+    db.debug_validate_dependencies();
+    assert_eq!(db.double(1), 20);
+}
+
+#[test]
+fn not_reported() {
+    let mut db = Database::default();
+    db.salsa_runtime().set_query_stats_enabled(true);
+    db.set_input(1).to(1);
+    db.set_input(2).to(2);
+    assert_eq!(db.double(1), 2);
+    assert_eq!(db.double(2), 4);
+
+    db.set_input(1).to(10);
+    assert_eq!(db.double(1), 20);
+    let events = db.events.get();
+    let stats = db.salsa_runtime().query_stats();
+
+    db.debug_validate_dependencies();
+    assert_eq!(db.events.get(), events);
+    assert_eq!(db.salsa_runtime().query_stats(), stats);
+}
+
+#[test]
+fn no_eq_values_are_not_compared() {
+    let mut db = Database::default();
+    db.set_input(1).to(1);
+    assert_eq!(db.double_no_eq(1), 2);
+    db.debug_validate_dependencies();
+}
+
+#[test]
+#[should_panic(expected = "memoized value differs from the value computed from scratch")]
+fn different_value() {
+    let mut db = Database::default();
+    db.set_input(1).to(1);
+    assert_eq!(db.add_untracked(1), 1);
+
+    db.untracked.set(5);
+    db.debug_validate_dependencies();
+}
+
+#[test]
+#[should_panic(
+    expected = "recorded inputs [\"input(1)\"] differ from the inputs read \
+                when computed from scratch [\"input(2)\"]"
+)]
+fn different_inputs() {
+    let mut db = Database::default();
+    db.set_input(1).to(1);
+    db.set_input(2).to(1);
+    assert_eq!(db.input_or_other(), 1);
+
+    db.untracked.set(1);
+    db.debug_validate_dependencies();
+}
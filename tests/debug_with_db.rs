@@ -0,0 +1,75 @@
+//! Test `salsa::DebugWithDb` and its derive.
+
+use salsa::DebugWithDb;
+use std::fmt;
+
+#[salsa::query_group(InternStorage)]
+trait Intern: salsa::Database {
+    #[salsa::interned]
+    fn intern_name(&self, name: String) -> Name;
+}
+
+salsa::intern_id!(pub struct Name);
+
+impl DebugWithDb<dyn Intern> for Name {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>, db: &dyn Intern) -> fmt::Result {
+        write!(f, "{}", db.lookup_intern_name(*self))
+    }
+}
+
+#[salsa::database(InternStorage)]
+#[derive(Default)]
+struct Database {
+    storage: salsa::Storage<Self>,
+}
+
+impl salsa::Database for Database {}
+
+#[derive(DebugWithDb)]
+#[debug_with_db(db = dyn Intern)]
+enum Expr {
+    Var(Name),
+    Call { callee: Box<Expr>, args: Vec<Expr> },
+    Unit,
+}
+
+#[derive(DebugWithDb)]
+#[debug_with_db(db = dyn Intern)]
+struct Labeled<T>(Option<Name>, T);
+
+#[derive(DebugWithDb)]
+struct Marker;
+
+#[test]
+fn derived() {
+    let db = Database::default();
+    let db: &dyn Intern = &db;
+    let f = db.intern_name("f".to_string());
+    let x = db.intern_name("x".to_string());
+
+    let expr = Expr::Call {
+        callee: Box::new(Expr::Var(f)),
+        args: vec![Expr::Var(x), Expr::Unit],
+    };
+    assert_eq!(
+        format!("{:?}", expr.debug(db)),
+        "Call { callee: Var(f), args: [Var(x), Unit] }"
+    );
+
+    let labeled = Labeled(Some(x), (f, 22_u32));
+    assert_eq!(
+        format!("{:?}", labeled.debug(db)),
+        "Labeled(Some(x), (f, 22))"
+    );
+    // `Marker` can be formatted with any database.
+    assert_eq!(format!("{:?}", Marker.debug(db)), "Marker");
+    assert_eq!(format!("{:?}", Marker.debug(&())), "Marker");
+}
+
+#[test]
+fn alternate() {
+    let db = Database::default();
+    let db: &dyn Intern = &db;
+    let x = db.intern_name("x".to_string());
+    assert_eq!(format!("{:#?}", vec![x].debug(db)), "[\n    x,\n]");
+}
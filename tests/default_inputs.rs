@@ -0,0 +1,85 @@
+//! Test `#[salsa::default = expr]` on input queries.
+
+use salsa::Database as _;
+
+#[salsa::query_group(QueryGroupStorage)]
+trait QueryGroup: salsa::Database {
+    #[salsa::input]
+    #[salsa::default = 22]
+    fn opt_level(&self) -> u32;
+
+    #[salsa::input]
+    #[salsa::default = String::from("none")]
+    fn name(&self, key: u32) -> String;
+
+    #[salsa::input]
+    fn required(&self) -> u32;
+
+    fn derived(&self) -> u32;
+}
+
+fn derived(db: &dyn QueryGroup) -> u32 {
+    db.opt_level() + 1
+}
+
+#[salsa::database(QueryGroupStorage)]
+#[derive(Default)]
+struct Database {
+    storage: salsa::Storage<Self>,
+}
+
+impl salsa::Database for Database {}
+
+fn derived_executions(db: &Database) -> usize {
+    db.salsa_runtime()
+        .query_stats()
+        .into_iter()
+        .filter(|(key, _)| format!("{:?}", key.debug(db)) == "derived(())")
+        .map(|(_, stat)| stat.executions)
+        .sum()
+}
+
+#[test]
+fn unset_input_uses_default() {
+    let db = Database::default();
+    assert_eq!(db.opt_level(), 22);
+    assert_eq!(db.name(1), "none");
+    assert_eq!(db.name(2), "none");
+}
+
+#[test]
+fn set_overrides_default() {
+    let mut db = Database::default();
+    db.salsa_runtime().set_query_stats_enabled(true);
+    assert_eq!(db.derived(), 23);
+    assert_eq!(derived_executions(&db), 1);
+
+    db.set_opt_level().to(3);
+    assert_eq!(db.opt_level(), 3);
+    assert_eq!(db.derived(), 4);
+    assert_eq!(derived_executions(&db), 2);
+
+    db.set_name(1).to("one".to_string());
+    assert_eq!(db.name(1), "one");
+    assert_eq!(db.name(2), "none");
+}
+
+#[test]
+fn default_is_not_recomputed() {
+    let mut db = Database::default();
+    db.salsa_runtime().set_query_stats_enabled(true);
+    assert_eq!(db.derived(), 23);
+
+    // An unrelated change does not re-execute `derived`, so the
+    // default value behaves like any other input.
+    db.set_name(1).to("one".to_string());
+    assert_eq!(db.derived(), 23);
+    assert_eq!(derived_executions(&db), 1);
+}
+
+#[test]
+#[should_panic(expected = "no value set for RequiredQuery(())")]
+fn no_default_panics() {
+    let db = Database::default();
+    db.required();
+}
@@ -0,0 +1,79 @@
+//! Test `Runtime::depend_on_durability`, which makes a query depend on
+//! all inputs of a given durability.
+
+use salsa::{Database as _, Durability};
+use std::cell::Cell;
+
+#[salsa::query_group(QueryGroupStorage)]
+trait QueryGroup: salsa::Database + AsRef<Cell<usize>> {
+    #[salsa::input]
+    fn input(&self, key: u32) -> u32;
+
+    /// Reads the (untracked) number of executions, and depends on all
+    /// `MEDIUM` durability inputs.
+    fn coarse(&self) -> usize;
+
+    fn reads_coarse(&self) -> usize;
+}
+
+fn coarse(db: &dyn QueryGroup) -> usize {
+    db.salsa_runtime().depend_on_durability(Durability::MEDIUM);
+    let executions = db.as_ref();
+    executions.set(executions.get() + 1);
+    executions.get()
+}
+
+fn reads_coarse(db: &dyn QueryGroup) -> usize {
+    db.coarse() * 10
+}
+
+#[salsa::database(QueryGroupStorage)]
+#[derive(Default)]
+struct Database {
+    storage: salsa::Storage<Self>,
+    executions: Cell<usize>,
+}
+
+impl salsa::Database for Database {}
+
+impl AsRef<Cell<usize>> for Database {
+    fn as_ref(&self) -> &Cell<usize> {
+        &self.executions
+    }
+}
+
+#[test]
+fn reexecuted_when_input_of_durability_changes() {
+    let mut db = Database::default();
+    db.set_input(1).with_durability(Durability::LOW).to(1);
+    db.set_input(2).with_durability(Durability::MEDIUM).to(2);
+    assert_eq!(db.coarse(), 1);
+
+    // A new revision that only changes a `LOW` durability input.
+    db.set_input(1).with_durability(Durability::LOW).to(10);
+    assert_eq!(db.coarse(), 1);
+
+    // `coarse` never read input 2, but it is `MEDIUM` durability.
+    db.set_input(2).with_durability(Durability::MEDIUM).to(20);
+    assert_eq!(db.coarse(), 2);
+
+    db.salsa_runtime_mut().synthetic_write(Durability::HIGH);
+    assert_eq!(db.coarse(), 3);
+}
+
+#[test]
+fn dependents_are_reexecuted() {
+    let mut db = Database::default();
+    db.set_input(2).with_durability(Durability::MEDIUM).to(2);
+    assert_eq!(db.reads_coarse(), 10);
+
+    db.salsa_runtime_mut().synthetic_write(Durability::LOW);
+    assert_eq!(db.reads_coarse(), 10);
+
+    db.set_input(2).with_durability(Durability::MEDIUM).to(20);
+    assert_eq!(db.reads_coarse(), 20);
+    assert_eq!(
+        CoarseQuery.in_db(&db).memoized_durability(&()),
+        Some(Durability::MEDIUM)
+    );
+}
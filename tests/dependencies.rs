@@ -0,0 +1,72 @@
+//! Test `QueryTable::dependencies`, which reports the inputs read by
+//! the last execution of a query.
+
+#[salsa::query_group(QueryGroupStorage)]
+trait QueryGroup: salsa::Database {
+    #[salsa::input]
+    fn input(&self, x: u32) -> u32;
+
+    fn sum(&self, x: u32, y: u32) -> u32;
+
+    fn untracked(&self) -> u32;
+}
+
+fn sum(db: &dyn QueryGroup, x: u32, y: u32) -> u32 {
+    db.input(y) + db.input(x)
+}
+
+fn untracked(db: &dyn QueryGroup) -> u32 {
+    db.salsa_runtime().report_untracked_read();
+    0
+}
+
+#[salsa::database(QueryGroupStorage)]
+#[derive(Default)]
+struct Database {
+    storage: salsa::Storage<Self>,
+}
+
+impl salsa::Database for Database {}
+
+fn dependencies(db: &Database, x: u32, y: u32) -> Option<Vec<String>> {
+    let deps = SumQuery.in_db(db).dependencies(&(x, y))?;
+    Some(
+        deps.into_iter()
+            .map(|index| format!("{:?}", index.debug(db)))
+            .collect(),
+    )
+}
+
+#[test]
+fn not_memoized() {
+    let db = Database::default();
+    assert_eq!(dependencies(&db, 1, 2), None);
+}
+
+#[test]
+fn read_order() {
+    let mut db = Database::default();
+    db.set_input(1).to(10);
+    db.set_input(2).to(20);
+
+    assert_eq!(db.sum(1, 2), 30);
+    assert_eq!(
+        dependencies(&db, 1, 2),
+        Some(vec!["input(2)".to_string(), "input(1)".to_string()])
+    );
+
+    // Unlike `peek`, the dependencies of a value from an older revision
+    // are still reported.
+    db.set_input(3).to(30);
+    assert_eq!(
+        dependencies(&db, 1, 2),
+        Some(vec!["input(2)".to_string(), "input(1)".to_string()])
+    );
+}
+
+#[test]
+fn untracked_read() {
+    let db = Database::default();
+    assert_eq!(db.untracked(), 0);
+    assert_eq!(UntrackedQuery.in_db(&db).dependencies(&()), None);
+}
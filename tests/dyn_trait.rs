@@ -23,6 +23,6 @@ fn output(db: &dyn DynTrait, x: u32) -> u32 {
 #[test]
 fn dyn_trait() {
     let mut query = DynTraitDatabase::default();
-    query.set_input(22, 23);
+    query.set_input(22).to(23);
     assert_eq!(query.output(22), 46);
 }
@@ -0,0 +1,96 @@
+//! Test that a memoized value which is recomputed to an equal value does
+//! not re-execute the queries that read it ("early cutoff"), unless its
+//! query is `#[salsa::no_eq]`.
+
+use std::cell::RefCell;
+use std::sync::Arc;
+
+#[salsa::query_group(EarlyCutoffStorage)]
+trait EarlyCutoffDatabase: salsa::Database + Logger {
+    #[salsa::input]
+    fn source(&self) -> Arc<String>;
+
+    fn parse(&self) -> Arc<Vec<String>>;
+
+    fn typecheck(&self) -> usize;
+
+    #[salsa::no_eq]
+    fn parse_no_eq(&self) -> Arc<Vec<String>>;
+
+    fn typecheck_no_eq(&self) -> usize;
+}
+
+trait Logger {
+    fn log(&self, event: &'static str);
+}
+
+fn parse(db: &dyn EarlyCutoffDatabase) -> Arc<Vec<String>> {
+    db.log("parse");
+    Arc::new(db.source().split_whitespace().map(String::from).collect())
+}
+
+fn typecheck(db: &dyn EarlyCutoffDatabase) -> usize {
+    db.log("typecheck");
+    db.parse().len()
+}
+
+fn parse_no_eq(db: &dyn EarlyCutoffDatabase) -> Arc<Vec<String>> {
+    db.log("parse_no_eq");
+    Arc::new(db.source().split_whitespace().map(String::from).collect())
+}
+
+fn typecheck_no_eq(db: &dyn EarlyCutoffDatabase) -> usize {
+    db.log("typecheck_no_eq");
+    db.parse_no_eq().len()
+}
+
+#[salsa::database(EarlyCutoffStorage)]
+#[derive(Default)]
+struct Database {
+    storage: salsa::Storage<Self>,
+    log: RefCell<Vec<&'static str>>,
+}
+
+impl salsa::Database for Database {}
+
+impl Logger for Database {
+    fn log(&self, event: &'static str) {
+        self.log.borrow_mut().push(event);
+    }
+}
+
+impl Database {
+    fn take_log(&self) -> Vec<&'static str> {
+        std::mem::take(&mut *self.log.borrow_mut())
+    }
+}
+
+#[test]
+fn whitespace_edit_does_not_rerun_typecheck() {
+    let mut db = Database::default();
+    db.set_source().to(Arc::new("fn main ( )".to_string()));
+    assert_eq!(db.typecheck(), 4);
+    assert_eq!(db.take_log(), vec!["typecheck", "parse"]);
+
+    // `parse` is re-executed, but produces an equal value, so `typecheck`
+    // is validated without re-executing it.
+    db.set_source().to(Arc::new("fn  main ( )\n".to_string()));
+    assert_eq!(db.typecheck(), 4);
+    assert_eq!(db.take_log(), vec!["parse"]);
+
+    db.set_source().to(Arc::new("fn main ( ) { }".to_string()));
+    assert_eq!(db.typecheck(), 6);
+    assert_eq!(db.take_log(), vec!["parse", "typecheck"]);
+}
+
+#[test]
+fn no_eq_reruns_dependents() {
+    let mut db = Database::default();
+    db.set_source().to(Arc::new("fn main ( )".to_string()));
+    assert_eq!(db.typecheck_no_eq(), 4);
+    assert_eq!(db.take_log(), vec!["typecheck_no_eq", "parse_no_eq"]);
+
+    db.set_source().to(Arc::new("fn  main ( )\n".to_string()));
+    assert_eq!(db.typecheck_no_eq(), 4);
+    assert_eq!(db.take_log(), vec!["parse_no_eq", "typecheck_no_eq"]);
+}
@@ -0,0 +1,56 @@
+//! Test `QueryTable::first_computed_at`, which reports the revision in
+//! which a memoized value was first computed.
+
+use salsa::Database as _;
+
+#[salsa::query_group(QueryGroupStorage)]
+trait QueryGroup: salsa::Database {
+    #[salsa::input]
+    fn input(&self, x: u32) -> u32;
+
+    fn double(&self, x: u32) -> u32;
+}
+
+fn double(db: &dyn QueryGroup, x: u32) -> u32 {
+    db.input(x) * 2
+}
+
+#[salsa::database(QueryGroupStorage)]
+#[derive(Default)]
+struct Database {
+    storage: salsa::Storage<Self>,
+}
+
+impl salsa::Database for Database {}
+
+#[test]
+fn kept_across_re_executions() {
+    let mut db = Database::default();
+    assert_eq!(DoubleQuery.in_db(&db).first_computed_at(&1), None);
+
+    db.set_input(1).to(1);
+    assert_eq!(db.double(1), 2);
+    let first = db.salsa_runtime().current_revision();
+    assert_eq!(DoubleQuery.in_db(&db).first_computed_at(&1), Some(first));
+
+    db.set_input(1).to(2);
+    db.set_input(2).to(3);
+    assert_eq!(db.double(1), 4);
+    assert_eq!(db.double(2), 6);
+    let now = db.salsa_runtime().current_revision();
+    assert_eq!(DoubleQuery.in_db(&db).first_computed_at(&1), Some(first));
+    assert_eq!(DoubleQuery.in_db(&db).first_computed_at(&2), Some(now));
+}
+
+#[test]
+fn starts_over_after_clear() {
+    let mut db = Database::default();
+    db.set_input(1).to(1);
+    assert_eq!(db.double(1), 2);
+
+    DoubleQuery.in_db_mut(&mut db).clear();
+    assert_eq!(DoubleQuery.in_db(&db).first_computed_at(&1), None);
+    assert_eq!(db.double(1), 2);
+    let now = db.salsa_runtime().current_revision();
+    assert_eq!(DoubleQuery.in_db(&db).first_computed_at(&1), Some(now));
+}
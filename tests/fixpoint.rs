@@ -0,0 +1,135 @@
+//! Test `#[salsa::cycle(fixpoint, initial = expr)]`.
+
+use std::cell::Cell;
+use std::collections::BTreeSet;
+use std::sync::Arc;
+
+#[salsa::query_group(GraphStorage)]
+trait Graph: salsa::Database {
+    #[salsa::input]
+    fn successors(&self, node: u32) -> Arc<Vec<u32>>;
+
+    /// The nodes reachable from `node` in one step or more.
+    #[salsa::cycle(fixpoint, initial = BTreeSet::new())]
+    fn reachable(&self, node: u32) -> BTreeSet<u32>;
+
+    #[salsa::cycle(fixpoint, initial = 0)]
+    fn diverges(&self) -> u32;
+}
+
+fn reachable(db: &dyn Graph, node: u32) -> BTreeSet<u32> {
+    let mut result = BTreeSet::new();
+    for &successor in db.successors(node).iter() {
+        result.insert(successor);
+        result.extend(db.reachable(successor));
+    }
+    result
+}
+
+fn diverges(db: &dyn Graph) -> u32 {
+    db.diverges() + 1
+}
+
+#[salsa::database(GraphStorage)]
+#[derive(Default)]
+struct Database {
+    storage: salsa::Storage<Self>,
+    executions: Cell<usize>,
+}
+
+impl salsa::Database for Database {
+    fn salsa_event(&self, event: salsa::Event) {
+        if let salsa::EventKind::WillExecute { .. } = event.kind {
+            self.executions.set(self.executions.get() + 1);
+        }
+    }
+}
+
+fn set(items: &[u32]) -> BTreeSet<u32> {
+    items.iter().copied().collect()
+}
+
+#[test]
+fn converges() {
+    let mut db = Database::default();
+    db.set_successors(0).to(Arc::new(vec![1]));
+    db.set_successors(1).to(Arc::new(vec![2, 3]));
+    db.set_successors(2).to(Arc::new(vec![0]));
+    db.set_successors(3).to(Arc::new(vec![]));
+
+    assert_eq!(db.reachable(0), set(&[0, 1, 2, 3]));
+    assert_eq!(db.reachable(1), set(&[0, 1, 2, 3]));
+    assert_eq!(db.reachable(3), set(&[]));
+
+    // Break the cycle.
+    db.set_successors(2).to(Arc::new(vec![]));
+    assert_eq!(db.reachable(0), set(&[1, 2, 3]));
+    assert_eq!(db.reachable(2), set(&[]));
+}
+
+#[test]
+fn memoizes_converged_cycle() {
+    let mut db = Database::default();
+    db.set_successors(0).to(Arc::new(vec![1]));
+    db.set_successors(1).to(Arc::new(vec![2, 3]));
+    db.set_successors(2).to(Arc::new(vec![0]));
+    db.set_successors(3).to(Arc::new(vec![]));
+    db.set_successors(4).to(Arc::new(vec![]));
+    assert_eq!(db.reachable(0), set(&[0, 1, 2, 3]));
+
+    // The other members of the cycle were memoized once it converged.
+    db.executions.set(0);
+    assert_eq!(db.reachable(1), set(&[0, 1, 2, 3]));
+    assert_eq!(db.reachable(2), set(&[0, 1, 2, 3]));
+    assert_eq!(db.executions.get(), 0);
+
+    // An input that the cycle does not read does not re-run it.
+    db.set_successors(4).to(Arc::new(vec![0]));
+    assert_eq!(db.reachable(0), set(&[0, 1, 2, 3]));
+    assert_eq!(db.reachable(2), set(&[0, 1, 2, 3]));
+    assert_eq!(db.executions.get(), 0);
+
+    // One that a member reads does.
+    db.set_successors(3).to(Arc::new(vec![4]));
+    assert_eq!(db.reachable(2), set(&[0, 1, 2, 3, 4]));
+    assert_eq!(db.reachable(0), set(&[0, 1, 2, 3, 4]));
+    assert_eq!(db.reachable(4), set(&[0, 1, 2, 3, 4]));
+}
+
+#[test]
+fn nested_cycles() {
+    let mut db = Database::default();
+    // 1 and 2 form a cycle inside the one of 0 and 1.
+    db.set_successors(0).to(Arc::new(vec![1]));
+    db.set_successors(1).to(Arc::new(vec![0, 2]));
+    db.set_successors(2).to(Arc::new(vec![1, 3]));
+    db.set_successors(3).to(Arc::new(vec![]));
+    db.set_successors(4).to(Arc::new(vec![]));
+    assert_eq!(db.reachable(0), set(&[0, 1, 2, 3]));
+
+    db.set_successors(4).to(Arc::new(vec![]));
+    db.executions.set(0);
+    assert_eq!(db.reachable(2), set(&[0, 1, 2, 3]));
+    assert_eq!(db.reachable(0), set(&[0, 1, 2, 3]));
+    assert_eq!(db.executions.get(), 0);
+
+    db.set_successors(2).to(Arc::new(vec![1]));
+    assert_eq!(db.reachable(0), set(&[0, 1, 2]));
+    assert_eq!(db.reachable(2), set(&[0, 1, 2]));
+}
+
+#[test]
+fn self_cycle() {
+    let mut db = Database::default();
+    db.set_successors(0).to(Arc::new(vec![0, 1]));
+    db.set_successors(1).to(Arc::new(vec![]));
+
+    assert_eq!(db.reachable(0), set(&[0, 1]));
+}
+
+#[test]
+#[should_panic(expected = "fixpoint iteration did not converge")]
+fn does_not_converge() {
+    let db = Database::default();
+    db.diverges();
+}
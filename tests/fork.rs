@@ -0,0 +1,81 @@
+//! Test `Storage::fork`, which gives an independent copy of a database.
+
+use std::cell::Cell;
+
+#[salsa::query_group(ForkStorage)]
+trait ForkDatabase: salsa::Database + Executions {
+    #[salsa::input]
+    fn input(&self, key: u32) -> u32;
+
+    #[salsa::interned]
+    fn intern_name(&self, name: String) -> salsa::InternId;
+
+    fn double(&self, key: u32) -> u32;
+}
+
+fn double(db: &dyn ForkDatabase, key: u32) -> u32 {
+    db.executions().set(db.executions().get() + 1);
+    db.input(key) * 2
+}
+
+trait Executions {
+    fn executions(&self) -> &Cell<usize>;
+}
+
+#[salsa::database(ForkStorage)]
+#[derive(Default)]
+struct Database {
+    storage: salsa::Storage<Self>,
+    executions: Cell<usize>,
+}
+
+impl salsa::Database for Database {}
+
+impl Database {
+    fn fork(&self) -> Self {
+        Database {
+            storage: self.storage.fork(),
+            executions: Cell::new(0),
+        }
+    }
+}
+
+impl Executions for Database {
+    fn executions(&self) -> &Cell<usize> {
+        &self.executions
+    }
+}
+
+#[test]
+fn fork_is_independent() {
+    let mut db = Database::default();
+    db.set_input(1).to(10);
+    db.set_input(2).to(20);
+    assert_eq!(db.double(1), 20);
+    assert_eq!(db.double(2), 40);
+
+    let mut fork = db.fork();
+    fork.set_input(1).to(100);
+    assert_eq!(fork.double(1), 200);
+    assert_eq!(db.double(1), 20);
+
+    // The memoized value of `double(2)` was copied into the fork.
+    assert_eq!(fork.double(2), 40);
+    assert_eq!(fork.executions.get(), 1);
+
+    db.set_input(2).to(30);
+    assert_eq!(db.double(2), 60);
+    assert_eq!(fork.double(2), 40);
+}
+
+#[test]
+fn fork_keeps_interned_ids() {
+    let db = Database::default();
+    let a = db.intern_name("a".to_string());
+
+    let fork = db.fork();
+    assert_eq!(fork.intern_name("a".to_string()), a);
+    let b = fork.intern_name("b".to_string());
+    assert_eq!(fork.lookup_intern_name(b), "b");
+    assert_eq!(db.intern_name("c".to_string()), b);
+}
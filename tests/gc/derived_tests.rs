@@ -8,7 +8,7 @@ fn compute_one_write_low() {
     let mut db = db::DatabaseImpl::default();
 
     // Will compute fibonacci(5)
-    db.set_use_triangular(5, false);
+    db.set_use_triangular(5).to(false);
     db.compute(5);
 
     db.salsa_runtime_mut().synthetic_write(Durability::LOW);
@@ -43,7 +43,7 @@ fn compute_one_write_high() {
     let mut db = db::DatabaseImpl::default();
 
     // Will compute fibonacci(5)
-    db.set_use_triangular(5, false);
+    db.set_use_triangular(5).to(false);
     db.compute(5);
 
     // Doing a synthetic write with durability *high* means that we
@@ -81,11 +81,11 @@ fn compute_switch() {
     let mut db = db::DatabaseImpl::default();
 
     // Will compute fibonacci(5)
-    db.set_use_triangular(5, false);
+    db.set_use_triangular(5).to(false);
     assert_eq!(db.compute(5), 5);
 
     // Change to triangular mode
-    db.set_use_triangular(5, true);
+    db.set_use_triangular(5).to(true);
 
     // Now computes triangular(5)
     assert_eq!(db.compute(5), 15);
@@ -138,11 +138,11 @@ fn compute_all() {
     let mut db = db::DatabaseImpl::default();
 
     for i in 0..6 {
-        db.set_use_triangular(i, (i % 2) != 0);
+        db.set_use_triangular(i).to((i % 2) != 0);
     }
 
-    db.set_min(0);
-    db.set_max(6);
+    db.set_min().to(0);
+    db.set_max().to(6);
 
     db.compute_all();
     db.salsa_runtime_mut().synthetic_write(Durability::LOW);
@@ -161,7 +161,7 @@ fn compute_all() {
     }
 
     // Reduce the range to exclude index 5.
-    db.set_max(5);
+    db.set_max().to(5);
     db.compute_all();
 
     assert_keys! {
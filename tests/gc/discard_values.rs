@@ -1,5 +1,5 @@
 use crate::db;
-use crate::group::{FibonacciQuery, GcDatabase};
+use crate::group::{FibonacciQuery, GcDatabase, TriangularQuery};
 use salsa::debug::DebugQueryTable;
 use salsa::{Database, Durability, SweepStrategy};
 
@@ -54,3 +54,33 @@ fn sweep_default() {
         "fibonacci(0)",
     ]);
 }
+
+#[test]
+fn discarded_values_keep_dependencies() {
+    let mut db = db::DatabaseImpl::default();
+    db.set_use_triangular(3).to(true);
+    db.set_use_triangular(4).to(true);
+    assert_eq!(db.compute(3), 6);
+
+    TriangularQuery.in_db(&db).sweep(
+        SweepStrategy::default()
+            .discard_values()
+            .sweep_all_revisions(),
+    );
+    db.set_use_triangular(4).to(false);
+
+    // The dependencies of `triangular` were kept, so `compute(3)` is
+    // still known to be up to date, without recomputing `triangular`.
+    db.clear_log();
+    assert_eq!(db.compute(3), 6);
+    db.assert_log(&[]);
+
+    // Reading `triangular` itself recomputes its value.
+    assert_eq!(db.triangular(3), 6);
+    db.assert_log(&[
+        "triangular(3)",
+        "triangular(2)",
+        "triangular(1)",
+        "triangular(0)",
+    ]);
+}
@@ -13,6 +13,10 @@ pub(crate) trait InternDatabase {
     #[salsa::interned]
     fn intern_str(&self, x: &'static str) -> InternId;
 
+    /// Like `intern_str`, but its ids carry a generation.
+    #[salsa::interned(generational)]
+    fn intern_str_gen(&self, x: &'static str) -> InternId;
+
     /// This just executes the intern query and returns the result.
     fn repeat_intern1(&self, x: &'static str) -> InternId;
 
@@ -192,3 +196,130 @@ fn discard_durability_after_synthetic_write_high() {
     assert_ne!(foo2, bar1);
     assert_eq!(foo1b, foo2);
 }
+
+/// Interned ids are reused once collected, so the order of the ids
+/// does not reflect the order in which the values were interned;
+/// `intern_keys_in_insertion_order` does.
+#[test]
+fn insertion_order_after_reuse() {
+    let mut db = db::DatabaseImpl::default();
+
+    let foo0 = db.intern_str("foo");
+    let bar = db.intern_str("bar");
+
+    db.salsa_runtime_mut().synthetic_write(Durability::HIGH);
+    db.intern_str("bar");
+
+    // This should collect "foo".
+    db.sweep_all(SweepStrategy::discard_outdated());
+
+    // "baz" re-uses the index of "foo".
+    let baz = db.intern_str("baz");
+    let foo1 = db.intern_str("foo");
+    assert_eq!(foo0, baz);
+
+    let ids: Vec<InternId> = InternStrQuery
+        .in_db(&db)
+        .intern_keys_in_insertion_order()
+        .collect();
+    assert_eq!(ids, vec![bar, baz, foo1]);
+}
+
+/// `gc_interned` frees only the values rejected by the predicate, even
+/// ones accessed in the current revision, and the queries that read
+/// them are re-executed.
+#[test]
+fn gc_interned_with_predicate() {
+    let mut db = db::DatabaseImpl::default();
+
+    let foo0 = db.repeat_intern1("foo");
+    let bar = db.repeat_intern1("bar");
+
+    let freed = InternStrQuery
+        .in_db_mut(&mut db)
+        .gc_interned(|key| !key.starts_with('f'));
+    assert_eq!(freed, 1);
+    assert_eq!(InternStrQuery.in_db(&db).intern_stats().free_ids, 1);
+    assert_eq!(db.try_lookup_intern_str(foo0), None);
+    assert_eq!(db.try_lookup_intern_str(bar), Some("bar"));
+
+    // "baz" re-uses the index of "foo", so `repeat_intern1("foo")`
+    // must not return its old result.
+    let baz = db.intern_str("baz");
+    assert_eq!(baz, foo0);
+    let foo1 = db.repeat_intern1("foo");
+    assert_ne!(foo1, foo0);
+    assert_eq!(db.lookup_intern_str(foo1), "foo");
+    assert_eq!(db.repeat_intern1("bar"), bar);
+
+    // Nothing is freed, so no new revision is needed.
+    let revision = db.salsa_runtime().current_revision();
+    let freed = InternStrQuery.in_db_mut(&mut db).gc_interned(|_| true);
+    assert_eq!(freed, 0);
+    assert_eq!(db.salsa_runtime().current_revision(), revision);
+}
+
+/// With `#[salsa::interned(generational)]`, an id whose index was
+/// reused after its value was freed no longer looks anything up.
+#[test]
+fn generational_stale_id() {
+    let mut db = db::DatabaseImpl::default();
+
+    let foo0 = db.intern_str_gen("foo");
+    let bar = db.intern_str_gen("bar");
+
+    let freed = InternStrGenQuery
+        .in_db_mut(&mut db)
+        .gc_interned(|key| !key.starts_with('f'));
+    assert_eq!(freed, 1);
+    assert_eq!(db.try_lookup_intern_str_gen(foo0), None);
+
+    // "baz" re-uses the index of "foo", but gets a different id.
+    let baz = db.intern_str_gen("baz");
+    assert_ne!(baz, foo0);
+    assert_eq!(InternStrGenQuery.in_db(&db).intern_stats().free_ids, 0);
+    assert_eq!(db.try_lookup_intern_str_gen(foo0), None);
+    assert_eq!(db.lookup_intern_str_gen(baz), "baz");
+    assert_eq!(db.lookup_intern_str_gen(bar), "bar");
+
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        db.lookup_intern_str_gen(foo0);
+    }));
+    assert!(result.is_err());
+}
+
+/// `seal_interner` renumbers the values in the order of their keys, so
+/// the ids no longer depend on the order of interning, and the queries
+/// that read a renumbered id are re-executed.
+#[test]
+fn seal_interner_renumbers_by_key() {
+    let mut db = db::DatabaseImpl::default();
+    let foo0 = db.repeat_intern1("foo");
+    let bar0 = db.repeat_intern1("bar");
+
+    let remap = InternStrQuery.in_db_mut(&mut db).seal_interner();
+    let foo1 = remap[&foo0];
+    let bar1 = remap[&bar0];
+    assert_eq!(bar1, InternId::from(0u32));
+    assert_eq!(foo1, InternId::from(1u32));
+    assert_eq!(db.lookup_intern_str(foo1), "foo");
+    assert_eq!(db.lookup_intern_str(bar1), "bar");
+    assert_eq!(db.repeat_intern1("foo"), foo1);
+    assert_eq!(db.repeat_intern2("bar"), bar1);
+
+    // Interning in the other order gives the same ids once sealed.
+    let mut other = db::DatabaseImpl::default();
+    other.intern_str("bar");
+    other.intern_str("foo");
+    let remap = InternStrQuery.in_db_mut(&mut other).seal_interner();
+    assert_eq!(remap[&bar1], bar1);
+    assert_eq!(remap[&foo1], foo1);
+    assert_eq!(other.intern_str("foo"), foo1);
+
+    // Sealing a sealed table changes nothing, so no new revision is needed.
+    let revision = db.salsa_runtime().current_revision();
+    let remap = InternStrQuery.in_db_mut(&mut db).seal_interner();
+    assert!(remap.iter().all(|(old, new)| old == new));
+    assert_eq!(db.salsa_runtime().current_revision(), revision);
+    assert_eq!(db.intern_str("baz"), InternId::from(2u32));
+}
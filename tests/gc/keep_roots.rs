@@ -0,0 +1,112 @@
+use salsa::debug::DebugQueryTable;
+use salsa::{Database, DatabaseKeyIndex, SweepStrategy};
+use std::cell::RefCell;
+
+#[salsa::query_group(KeepRoots)]
+trait KeepRootsDatabase: salsa::Database {
+    #[salsa::input]
+    fn file(&self, key: u32) -> u32;
+
+    fn parse(&self, key: u32) -> u32;
+
+    fn check(&self, key: u32) -> u32;
+}
+
+fn parse(db: &dyn KeepRootsDatabase, key: u32) -> u32 {
+    db.file(key) + 1
+}
+
+fn check(db: &dyn KeepRootsDatabase, key: u32) -> u32 {
+    db.parse(key) * 2
+}
+
+#[salsa::database(KeepRoots)]
+#[derive(Default)]
+struct DatabaseImpl {
+    storage: salsa::Storage<Self>,
+    executed: RefCell<Vec<DatabaseKeyIndex>>,
+}
+
+impl salsa::Database for DatabaseImpl {
+    fn salsa_event(&self, event: salsa::Event) {
+        if let salsa::EventKind::WillExecute { database_key } = event.kind {
+            self.executed.borrow_mut().push(database_key);
+        }
+    }
+}
+
+impl DatabaseImpl {
+    /// Finds the index of a query that was executed by its debug name.
+    fn executed_index(&self, name: &str) -> DatabaseKeyIndex {
+        *self
+            .executed
+            .borrow()
+            .iter()
+            .find(|key| format!("{:?}", key.debug(self)) == name)
+            .unwrap()
+    }
+}
+
+#[test]
+fn keep_roots_retains_reachable() {
+    let mut db = DatabaseImpl::default();
+    db.set_file(1).to(10);
+    db.set_file(2).to(20);
+    assert_eq!(db.check(1), 22);
+    assert_eq!(db.check(2), 42);
+
+    let root = db.executed_index("check(1)");
+    db.sweep_all(SweepStrategy::keep_roots(vec![root]));
+
+    assert_keys! {
+        db,
+        CheckQuery => (1),
+        ParseQuery => (1),
+    }
+}
+
+#[test]
+fn keep_roots_ignores_staleness() {
+    let mut db = DatabaseImpl::default();
+    db.set_file(1).to(10);
+    db.set_file(2).to(20);
+    assert_eq!(db.check(1), 22);
+    let root = db.executed_index("check(1)");
+
+    // A new revision in which only `check(2)` is used. `check(1)` is
+    // now outdated, but it is still kept because it is a root.
+    db.set_file(3).to(30);
+    assert_eq!(db.check(2), 42);
+    db.sweep_all(SweepStrategy::keep_roots(vec![root]));
+
+    assert_keys! {
+        db,
+        CheckQuery => (1),
+        ParseQuery => (1),
+    }
+
+    // The kept values can still be validated without re-executing.
+    db.executed.borrow_mut().clear();
+    assert_eq!(db.check(1), 22);
+    assert!(db.executed.borrow().is_empty());
+}
+
+#[test]
+fn keep_roots_on_single_query() {
+    let mut db = DatabaseImpl::default();
+    db.set_file(1).to(10);
+    db.set_file(2).to(20);
+    assert_eq!(db.check(1), 22);
+    assert_eq!(db.check(2), 42);
+
+    let root = db.executed_index("check(2)");
+    ParseQuery
+        .in_db(&db)
+        .sweep(SweepStrategy::keep_roots(vec![root]));
+
+    assert_keys! {
+        db,
+        CheckQuery => (1, 2),
+        ParseQuery => (2),
+    }
+}
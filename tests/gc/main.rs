@@ -14,6 +14,7 @@ mod derived_tests;
 mod discard_values;
 mod group;
 mod interned;
+mod keep_roots;
 mod log;
 mod shallow_constant_tests;
 mod volatile_tests;
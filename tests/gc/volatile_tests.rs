@@ -38,7 +38,7 @@ fn consistency_no_gc() {
 
     let cell = Arc::new(AtomicUsize::new(22));
 
-    db.set_atomic_cell(cell.clone());
+    db.set_atomic_cell().to(cell.clone());
 
     let v1 = db.repeat1();
 
@@ -55,7 +55,7 @@ fn consistency_with_gc() {
 
     let cell = Arc::new(AtomicUsize::new(22));
 
-    db.set_atomic_cell(cell.clone());
+    db.set_atomic_cell().to(cell.clone());
 
     let v1 = db.repeat1();
 
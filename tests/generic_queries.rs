@@ -0,0 +1,104 @@
+//! Test `#[salsa::instantiate]`, which expands a generic query into one
+//! query per listed type.
+
+use std::marker::PhantomData;
+
+trait Grammar: Clone + std::fmt::Debug + Eq + std::hash::Hash + 'static {
+    const NAME: &'static str;
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+struct Json;
+
+impl Grammar for Json {
+    const NAME: &'static str = "json";
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+struct Toml;
+
+impl Grammar for Toml {
+    const NAME: &'static str = "toml";
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Ast<T> {
+    description: String,
+    grammar: PhantomData<T>,
+}
+
+#[salsa::query_group(ParseStorage)]
+trait ParseDatabase: salsa::Database {
+    #[salsa::input]
+    fn file_text(&self, file: u32) -> String;
+
+    /// Parses `file` with the grammar `T`.
+    #[salsa::instantiate(parse_json = Json, parse_toml = Toml)]
+    fn parse<T: Grammar>(&self, file: u32) -> Ast<T>;
+
+    #[salsa::instantiate(describe_json = Json)]
+    #[salsa::invoke(describe_impl)]
+    fn describe<G: Grammar>(&self, files: Vec<u32>, grammar: G) -> Vec<Ast<G>>;
+}
+
+fn parse<T: Grammar>(db: &dyn ParseDatabase, file: u32) -> Ast<T> {
+    Ast {
+        description: format!("{} {}", T::NAME, db.file_text(file)),
+        grammar: PhantomData,
+    }
+}
+
+fn describe_impl<G: Grammar>(db: &dyn ParseDatabase, files: Vec<u32>, _grammar: G) -> Vec<Ast<G>> {
+    files
+        .into_iter()
+        .map(|file| Ast {
+            description: format!(
+                "{} file {} ({} bytes)",
+                G::NAME,
+                file,
+                db.file_text(file).len()
+            ),
+            grammar: PhantomData,
+        })
+        .collect()
+}
+
+#[salsa::database(ParseStorage)]
+#[derive(Default)]
+struct Database {
+    storage: salsa::Storage<Self>,
+}
+
+impl salsa::Database for Database {}
+
+#[test]
+fn one_query_per_instance() {
+    let mut db = Database::default();
+    db.set_file_text(1).to("{}".to_string());
+
+    assert_eq!(db.parse_json(1).description, "json {}");
+    assert_eq!(db.parse_toml(1).description, "toml {}");
+    assert_eq!(ParseJsonQuery.in_db(&db).peek(&1), Some(db.parse_json(1)));
+    assert_eq!(ParseTomlQuery.in_db(&db).peek(&1), Some(db.parse_toml(1)));
+
+    db.set_file_text(1).to("[]".to_string());
+    assert_eq!(db.parse_json(1).description, "json []");
+    assert_eq!(db.parse_toml(1).description, "toml []");
+}
+
+#[test]
+fn instance_with_invoke() {
+    let mut db = Database::default();
+    db.set_file_text(1).to("{}".to_string());
+    db.set_file_text(2).to("{ }".to_string());
+
+    let descriptions: Vec<_> = db
+        .describe_json(vec![1, 2], Json)
+        .into_iter()
+        .map(|ast| ast.description)
+        .collect();
+    assert_eq!(
+        descriptions,
+        vec!["json file 1 (2 bytes)", "json file 2 (3 bytes)"]
+    );
+}
@@ -0,0 +1,67 @@
+//! Test `#[salsa::query_group(.., version = N)]` and
+//! `QueryTableMut::prime_versioned`.
+
+#[salsa::query_group(QueryGroupStorage, version = 3)]
+trait QueryGroup: salsa::Database {
+    #[salsa::input]
+    fn input(&self, x: u32) -> u32;
+
+    fn double(&self, x: u32) -> u32;
+}
+
+fn double(db: &dyn QueryGroup, x: u32) -> u32 {
+    db.input(x) * 2
+}
+
+#[salsa::query_group(UnversionedStorage)]
+trait Unversioned: salsa::Database {
+    fn constant(&self) -> u32;
+}
+
+fn constant(_db: &dyn Unversioned) -> u32 {
+    22
+}
+
+#[salsa::database(QueryGroupStorage, UnversionedStorage)]
+#[derive(Default)]
+struct Database {
+    storage: salsa::Storage<Self>,
+}
+
+impl salsa::Database for Database {}
+
+#[test]
+fn group_version() {
+    let db = Database::default();
+    assert_eq!(DoubleQuery.in_db(&db).group_version(), 3);
+    assert_eq!(ConstantQuery.in_db(&db).group_version(), 0);
+}
+
+#[test]
+fn prime_matching_version() {
+    let mut db = Database::default();
+    db.set_input(1).to(1);
+    db.double(1);
+    let inputs = DoubleQuery.in_db(&db).dependencies(&1).unwrap();
+
+    // A wrong value, to show that the primed value is used as is.
+    let primed = DoubleQuery
+        .in_db_mut(&mut db)
+        .prime_versioned(3, vec![(1, 100, inputs)]);
+    assert!(primed);
+    assert_eq!(db.double(1), 100);
+}
+
+#[test]
+fn prime_stale_version() {
+    let mut db = Database::default();
+    db.set_input(1).to(1);
+    db.double(1);
+    let inputs = DoubleQuery.in_db(&db).dependencies(&1).unwrap();
+
+    let primed = DoubleQuery
+        .in_db_mut(&mut db)
+        .prime_versioned(2, vec![(1, 100, inputs)]);
+    assert!(!primed);
+    assert_eq!(db.double(1), 2);
+}
@@ -0,0 +1,79 @@
+//! Test `#[salsa::history]`, which keeps the last few values of a query.
+
+use salsa::{Database as _, Revision, SweepStrategy};
+
+#[salsa::query_group(HistoryStorage)]
+trait HistoryDatabase: salsa::Database {
+    #[salsa::input]
+    fn input(&self, key: u32) -> u32;
+
+    #[salsa::history(depth = 2)]
+    fn is_big(&self, key: u32) -> bool;
+
+    fn no_history(&self, key: u32) -> bool;
+}
+
+fn is_big(db: &dyn HistoryDatabase, key: u32) -> bool {
+    db.input(key) >= 5
+}
+
+fn no_history(db: &dyn HistoryDatabase, key: u32) -> bool {
+    db.is_big(key)
+}
+
+#[salsa::database(HistoryStorage)]
+#[derive(Default)]
+struct Database {
+    storage: salsa::Storage<Self>,
+}
+
+impl salsa::Database for Database {}
+
+#[test]
+fn keeps_the_last_distinct_values() {
+    let mut db = Database::default();
+    assert!(db.is_big_at_history(1).is_empty());
+
+    db.set_input(1).to(2);
+    let r2 = db.salsa_runtime().current_revision();
+    assert!(!db.is_big(1));
+    assert_eq!(db.is_big_at_history(1), vec![(r2, false)]);
+
+    // An equal value (which is backdated) is not recorded again.
+    db.set_input(1).to(4);
+    assert!(!db.is_big(1));
+    assert_eq!(db.is_big_at_history(1), vec![(r2, false)]);
+
+    db.set_input(1).to(5);
+    let r4 = db.salsa_runtime().current_revision();
+    assert!(db.is_big(1));
+    assert_eq!(db.is_big_at_history(1), vec![(r2, false), (r4, true)]);
+
+    // Only `depth` values are kept.
+    db.set_input(1).to(3);
+    let r5 = db.salsa_runtime().current_revision();
+    assert!(!db.is_big(1));
+    assert_eq!(db.is_big_at_history(1), vec![(r4, true), (r5, false)]);
+    assert!(IsBigQuery.in_db(&db).history(&2).is_empty());
+
+    // Discarding the memoized value keeps the history, and computing
+    // the same value again does not add to it.
+    IsBigQuery.in_db(&db).sweep(
+        SweepStrategy::default()
+            .discard_everything()
+            .sweep_all_revisions(),
+    );
+    assert!(!db.is_big(1));
+    assert_eq!(db.is_big_at_history(1), vec![(r4, true), (r5, false)]);
+}
+
+#[test]
+fn queries_without_history_keep_none() {
+    let mut db = Database::default();
+    db.set_input(1).to(1);
+    db.no_history(1);
+    db.set_input(1).to(2);
+    db.no_history(1);
+    let history: Vec<(Revision, bool)> = NoHistoryQuery.in_db(&db).history(&1);
+    assert!(history.is_empty());
+}
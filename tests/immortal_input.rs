@@ -0,0 +1,87 @@
+//! Test `#[salsa::input(immortal)]`, whose values can only be set once
+//! and are read without recording a dependency.
+
+#[salsa::query_group(QueryGroupStorage)]
+trait QueryGroup: salsa::Database {
+    #[salsa::input(immortal)]
+    fn builtin(&self, id: u32) -> u32;
+
+    #[salsa::input(eq, immortal)]
+    fn version(&self) -> u32;
+
+    #[salsa::input]
+    fn user(&self, id: u32) -> u32;
+
+    fn builtin_sum(&self) -> u32;
+
+    fn user_plus_builtin(&self, id: u32) -> u32;
+
+    fn builtin_or_zero(&self, id: u32) -> u32;
+}
+
+fn builtin_sum(db: &dyn QueryGroup) -> u32 {
+    db.builtin(1) + db.builtin(2)
+}
+
+fn user_plus_builtin(db: &dyn QueryGroup, id: u32) -> u32 {
+    db.user(id) + db.builtin(1)
+}
+
+fn builtin_or_zero(db: &dyn QueryGroup, id: u32) -> u32 {
+    db.try_builtin(id).unwrap_or(0)
+}
+
+#[salsa::database(QueryGroupStorage)]
+#[derive(Default)]
+struct Database {
+    storage: salsa::Storage<Self>,
+}
+
+impl salsa::Database for Database {}
+
+#[test]
+fn reads_record_no_dependency() {
+    let mut db = Database::default();
+    db.set_builtin(1).to(1);
+    db.set_builtin(2).to(2);
+    db.set_user(10).to(10);
+    assert_eq!(db.builtin_sum(), 3);
+    assert_eq!(db.user_plus_builtin(10), 11);
+
+    assert_eq!(BuiltinSumQuery.in_db(&db).dependencies(&()), Some(vec![]));
+    let dependencies = UserPlusBuiltinQuery.in_db(&db).dependencies(&10).unwrap();
+    assert_eq!(dependencies.len(), 1);
+}
+
+#[test]
+fn unset_key_is_tracked() {
+    let mut db = Database::default();
+    assert_eq!(db.builtin_or_zero(1), 0);
+
+    db.set_builtin(1).to(1);
+    assert_eq!(db.builtin_or_zero(1), 1);
+}
+
+#[test]
+fn setting_an_equal_value_is_allowed() {
+    let mut db = Database::default();
+    db.set_version().to(1);
+    db.set_version().to(1);
+    assert_eq!(db.version(), 1);
+}
+
+#[test]
+#[should_panic(expected = "cannot change BuiltinQuery(1): the input is immortal")]
+fn setting_twice_panics() {
+    let mut db = Database::default();
+    db.set_builtin(1).to(1);
+    db.set_builtin(1).to(2);
+}
+
+#[test]
+#[should_panic(expected = "cannot change BuiltinQuery(1): the input is immortal")]
+fn removing_panics() {
+    let mut db = Database::default();
+    db.set_builtin(1).to(1);
+    BuiltinQuery.in_db_mut(&mut db).remove(1);
+}
@@ -27,11 +27,11 @@ fn add3(db: &dyn ConstantsDatabase, key1: char, key2: char, key3: char) -> usize
 #[test]
 fn invalidate_constant() {
     let db = &mut TestContextImpl::default();
-    db.set_input_with_durability('a', 44, Durability::HIGH);
-    db.set_input_with_durability('b', 22, Durability::HIGH);
+    db.set_input('a').with_durability(Durability::HIGH).to(44);
+    db.set_input('b').with_durability(Durability::HIGH).to(22);
     assert_eq!(db.add('a', 'b'), 66);
 
-    db.set_input_with_durability('a', 66, Durability::HIGH);
+    db.set_input('a').with_durability(Durability::HIGH).to(66);
     assert_eq!(db.add('a', 'b'), 88);
 }
 
@@ -40,15 +40,15 @@ fn invalidate_constant_1() {
     let db = &mut TestContextImpl::default();
 
     // Not constant:
-    db.set_input('a', 44);
+    db.set_input('a').to(44);
     assert_eq!(db.add('a', 'a'), 88);
 
     // Becomes constant:
-    db.set_input_with_durability('a', 44, Durability::HIGH);
+    db.set_input('a').with_durability(Durability::HIGH).to(44);
     assert_eq!(db.add('a', 'a'), 88);
 
     // Invalidates:
-    db.set_input_with_durability('a', 33, Durability::HIGH);
+    db.set_input('a').with_durability(Durability::HIGH).to(33);
     assert_eq!(db.add('a', 'a'), 66);
 }
 
@@ -57,17 +57,17 @@ fn invalidate_constant_1() {
 #[test]
 fn set_after_constant_same_value() {
     let db = &mut TestContextImpl::default();
-    db.set_input_with_durability('a', 44, Durability::HIGH);
-    db.set_input_with_durability('a', 44, Durability::HIGH);
-    db.set_input('a', 44);
+    db.set_input('a').with_durability(Durability::HIGH).to(44);
+    db.set_input('a').with_durability(Durability::HIGH).to(44);
+    db.set_input('a').to(44);
 }
 
 #[test]
 fn not_constant() {
     let mut db = TestContextImpl::default();
 
-    db.set_input('a', 22);
-    db.set_input('b', 44);
+    db.set_input('a').to(22);
+    db.set_input('b').to(44);
     assert_eq!(db.add('a', 'b'), 66);
     assert_eq!(Durability::LOW, AddQuery.in_db(&db).durability(('a', 'b')));
 }
@@ -76,8 +76,8 @@ fn not_constant() {
 fn durability() {
     let mut db = TestContextImpl::default();
 
-    db.set_input_with_durability('a', 22, Durability::HIGH);
-    db.set_input_with_durability('b', 44, Durability::HIGH);
+    db.set_input('a').with_durability(Durability::HIGH).to(22);
+    db.set_input('b').with_durability(Durability::HIGH).to(44);
     assert_eq!(db.add('a', 'b'), 66);
     assert_eq!(Durability::HIGH, AddQuery.in_db(&db).durability(('a', 'b')));
 }
@@ -86,8 +86,8 @@ fn durability() {
 fn mixed_constant() {
     let mut db = TestContextImpl::default();
 
-    db.set_input_with_durability('a', 22, Durability::HIGH);
-    db.set_input('b', 44);
+    db.set_input('a').with_durability(Durability::HIGH).to(22);
+    db.set_input('b').to(44);
     assert_eq!(db.add('a', 'b'), 66);
     assert_eq!(Durability::LOW, AddQuery.in_db(&db).durability(('a', 'b')));
 }
@@ -96,20 +96,20 @@ fn mixed_constant() {
 fn becomes_constant_with_change() {
     let mut db = TestContextImpl::default();
 
-    db.set_input('a', 22);
-    db.set_input('b', 44);
+    db.set_input('a').to(22);
+    db.set_input('b').to(44);
     assert_eq!(db.add('a', 'b'), 66);
     assert_eq!(Durability::LOW, AddQuery.in_db(&db).durability(('a', 'b')));
 
-    db.set_input_with_durability('a', 23, Durability::HIGH);
+    db.set_input('a').with_durability(Durability::HIGH).to(23);
     assert_eq!(db.add('a', 'b'), 67);
     assert_eq!(Durability::LOW, AddQuery.in_db(&db).durability(('a', 'b')));
 
-    db.set_input_with_durability('b', 45, Durability::HIGH);
+    db.set_input('b').with_durability(Durability::HIGH).to(45);
     assert_eq!(db.add('a', 'b'), 68);
     assert_eq!(Durability::HIGH, AddQuery.in_db(&db).durability(('a', 'b')));
 
-    db.set_input_with_durability('b', 45, Durability::MEDIUM);
+    db.set_input('b').with_durability(Durability::MEDIUM).to(45);
     assert_eq!(db.add('a', 'b'), 68);
     assert_eq!(
         Durability::MEDIUM,
@@ -125,15 +125,15 @@ fn becomes_constant_with_change() {
 fn constant_to_non_constant() {
     let mut db = TestContextImpl::default();
 
-    db.set_input_with_durability('a', 11, Durability::HIGH);
-    db.set_input_with_durability('b', 22, Durability::HIGH);
-    db.set_input_with_durability('c', 33, Durability::HIGH);
+    db.set_input('a').with_durability(Durability::HIGH).to(11);
+    db.set_input('b').with_durability(Durability::HIGH).to(22);
+    db.set_input('c').with_durability(Durability::HIGH).to(33);
 
     // Here, `add3` invokes `add`, which yields 33. Both calls are
     // constant.
     assert_eq!(db.add3('a', 'b', 'c'), 66);
 
-    db.set_input('a', 11);
+    db.set_input('a').to(11);
 
     // Here, `add3` invokes `add`, which *still* yields 33, but which
     // is no longer constant. Since value didn't change, we might
@@ -143,6 +143,53 @@ fn constant_to_non_constant() {
 
     // In that case, we would not get the correct result here, when
     // 'a' changes *again*.
-    db.set_input('a', 22);
+    db.set_input('a').to(22);
     assert_eq!(db.add3('a', 'b', 'c'), 77);
 }
+
+#[test]
+fn custom_durability_levels() {
+    let mut db = TestContextImpl::default();
+    let vendored = Durability::new(3);
+
+    db.set_input('a').with_durability(vendored).to(22);
+    db.set_input('b').with_durability(Durability::HIGH).to(44);
+    assert_eq!(db.add('a', 'b'), 66);
+    assert_eq!(vendored, AddQuery.in_db(&db).durability(('a', 'b')));
+
+    // Changing an input of lower durability does not affect the
+    // durability of `add`.
+    db.set_input('c').with_durability(Durability::new(2)).to(1);
+    assert_eq!(db.add('a', 'b'), 66);
+    assert_eq!(vendored, AddQuery.in_db(&db).durability(('a', 'b')));
+
+    db.set_input('a').with_durability(vendored).to(23);
+    assert_eq!(db.add('a', 'b'), 67);
+}
+
+#[test]
+#[should_panic(expected = "durability level 8 is out of range")]
+fn durability_level_out_of_range() {
+    Durability::new(Durability::LEN as u8);
+}
+
+#[test]
+fn set_many() {
+    let db = &mut TestContextImpl::default();
+    db.set_input('a').with_durability(Durability::HIGH).to(44);
+    db.set_input('b').with_durability(Durability::HIGH).to(22);
+    assert_eq!(db.add('a', 'b'), 66);
+    db.assert_log(&["add(a, b)"]);
+
+    // Replacing a high-durability value must invalidate its readers,
+    // even though the new values are set with low durability.
+    InputQuery.in_db_mut(db).set_many(vec![('a', 1), ('c', 3)]);
+    assert_eq!(db.input('c'), 3);
+    assert_eq!(db.add('a', 'b'), 23);
+    db.assert_log(&["add(a, b)"]);
+
+    // An empty batch changes nothing.
+    InputQuery.in_db_mut(db).set_many(vec![]);
+    assert_eq!(db.add('a', 'b'), 23);
+    db.assert_log(&[]);
+}
@@ -31,7 +31,7 @@ fn dep_derived1(db: &dyn MemoizedDepInputsContext) -> usize {
 fn revalidate() {
     let db = &mut TestContextImpl::default();
 
-    db.set_dep_input1(0);
+    db.set_dep_input1().to(0);
 
     // Initial run starts from Memoized2:
     let v = db.dep_memoized2();
@@ -41,19 +41,19 @@ fn revalidate() {
     // After that, we first try to validate Memoized1 but wind up
     // running Memoized2. Note that we don't try to validate
     // Derived1, so it is invoked by Memoized1.
-    db.set_dep_input1(44);
+    db.set_dep_input1().to(44);
     let v = db.dep_memoized2();
     assert_eq!(v, 44);
     db.assert_log(&["Memoized1 invoked", "Derived1 invoked", "Memoized2 invoked"]);
 
     // Here validation of Memoized1 succeeds so Memoized2 never runs.
-    db.set_dep_input1(45);
+    db.set_dep_input1().to(45);
     let v = db.dep_memoized2();
     assert_eq!(v, 44);
     db.assert_log(&["Memoized1 invoked", "Derived1 invoked"]);
 
     // Here, a change to input2 doesn't affect us, so nothing runs.
-    db.set_dep_input2(45);
+    db.set_dep_input2().to(45);
     let v = db.dep_memoized2();
     assert_eq!(v, 44);
     db.assert_log(&[]);
@@ -1,4 +1,5 @@
 use crate::implementation::{TestContext, TestContextImpl};
+use salsa::Database as _;
 
 #[salsa::query_group(MemoizedInputs)]
 pub(crate) trait MemoizedInputsContext: TestContext {
@@ -7,6 +8,10 @@ pub(crate) trait MemoizedInputsContext: TestContext {
     fn input1(&self) -> usize;
     #[salsa::input]
     fn input2(&self) -> usize;
+
+    fn double_eq_input(&self) -> usize;
+    #[salsa::input(eq)]
+    fn eq_input(&self) -> usize;
 }
 
 fn max(db: &dyn MemoizedInputsContext) -> usize {
@@ -14,12 +19,17 @@ fn max(db: &dyn MemoizedInputsContext) -> usize {
     std::cmp::max(db.input1(), db.input2())
 }
 
+fn double_eq_input(db: &dyn MemoizedInputsContext) -> usize {
+    db.log().add("Double invoked");
+    db.eq_input() * 2
+}
+
 #[test]
 fn revalidate() {
     let db = &mut TestContextImpl::default();
 
-    db.set_input1(0);
-    db.set_input2(0);
+    db.set_input1().to(0);
+    db.set_input2().to(0);
 
     let v = db.max();
     assert_eq!(v, 0);
@@ -29,7 +39,7 @@ fn revalidate() {
     assert_eq!(v, 0);
     db.assert_log(&[]);
 
-    db.set_input1(44);
+    db.set_input1().to(44);
     db.assert_log(&[]);
 
     let v = db.max();
@@ -40,11 +50,11 @@ fn revalidate() {
     assert_eq!(v, 44);
     db.assert_log(&[]);
 
-    db.set_input1(44);
+    db.set_input1().to(44);
     db.assert_log(&[]);
-    db.set_input2(66);
+    db.set_input2().to(66);
     db.assert_log(&[]);
-    db.set_input1(64);
+    db.set_input1().to(64);
     db.assert_log(&[]);
 
     let v = db.max();
@@ -62,15 +72,63 @@ fn revalidate() {
 fn set_after_no_change() {
     let db = &mut TestContextImpl::default();
 
-    db.set_input2(0);
+    db.set_input2().to(0);
 
-    db.set_input1(44);
+    db.set_input1().to(44);
     let v = db.max();
     assert_eq!(v, 44);
     db.assert_log(&["Max invoked"]);
 
-    db.set_input1(44);
+    db.set_input1().to(44);
     let v = db.max();
     assert_eq!(v, 44);
     db.assert_log(&["Max invoked"]);
 }
+
+/// Test that invoking `set` on an `#[salsa::input(eq)]` input with
+/// the same value does *not* trigger a new revision.
+#[test]
+fn set_eq_input_after_no_change() {
+    let db = &mut TestContextImpl::default();
+
+    db.set_eq_input().to(22);
+    assert_eq!(db.double_eq_input(), 44);
+    db.assert_log(&["Double invoked"]);
+
+    db.set_eq_input().to(22);
+    assert_eq!(db.double_eq_input(), 44);
+    db.assert_log(&[]);
+
+    db.set_eq_input().to(23);
+    assert_eq!(db.double_eq_input(), 46);
+    db.assert_log(&["Double invoked"]);
+
+    // Changing only the durability still counts as a change.
+    db.set_eq_input()
+        .with_durability(salsa::Durability::HIGH)
+        .to(23);
+    assert_eq!(db.double_eq_input(), 46);
+    db.assert_log(&["Double invoked"]);
+}
+
+/// Test that `set_if_changed` only sets (and starts a new revision)
+/// when the value differs, even for an input without `eq`.
+#[test]
+fn set_if_changed() {
+    let db = &mut TestContextImpl::default();
+
+    db.set_input2().to(0);
+    assert!(Input1Query.in_db_mut(db).set_if_changed((), 44));
+    assert_eq!(db.max(), 44);
+    db.assert_log(&["Max invoked"]);
+
+    let revision = db.salsa_runtime().current_revision();
+    assert!(!Input1Query.in_db_mut(db).set_if_changed((), 44));
+    assert_eq!(db.salsa_runtime().current_revision(), revision);
+    assert_eq!(db.max(), 44);
+    db.assert_log(&[]);
+
+    assert!(Input1Query.in_db_mut(db).set_if_changed((), 45));
+    assert_eq!(db.max(), 45);
+    db.assert_log(&["Max invoked"]);
+}
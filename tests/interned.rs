@@ -28,6 +28,18 @@ trait Intern {
 
     #[salsa::interned]
     fn intern_key(&self, x: String) -> InternKey;
+
+    #[salsa::interned]
+    fn intern_name(&self, x: String) -> NameId;
+
+    #[salsa::interned]
+    fn intern_bad_key(&self, x: String) -> BadKey;
+
+    #[salsa::interned]
+    fn intern_bytes(&self, x: Box<[u8]>) -> InternId;
+
+    #[salsa::interned]
+    fn intern_path(&self, module: NameId, name: String) -> PathId;
 }
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
@@ -43,6 +55,24 @@ impl salsa::InternKey for InternKey {
     }
 }
 
+salsa::intern_id!(pub struct NameId, debug = "Name");
+
+salsa::intern_id!(pub struct PathId);
+
+/// An `InternKey` whose methods are not inverses.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct BadKey(InternId);
+
+impl salsa::InternKey for BadKey {
+    fn from_intern_id(v: InternId) -> Self {
+        BadKey(v)
+    }
+
+    fn as_intern_id(&self) -> InternId {
+        self.0.next().unwrap()
+    }
+}
+
 #[test]
 fn test_intern1() {
     let db = Database::default();
@@ -75,6 +105,22 @@ fn test_intern2() {
     assert_eq!((format!("x"), format!("bar")), db.lookup_intern2(bar0));
 }
 
+#[test]
+fn test_intern_composite_key() {
+    let db = Database::default();
+    let std = db.intern_name(format!("std"));
+    let core = db.intern_name(format!("core"));
+    let std_vec0 = db.intern_path(std, format!("Vec"));
+    let core_vec = db.intern_path(core, format!("Vec"));
+    let std_vec1 = db.intern_path(std, format!("Vec"));
+
+    assert_eq!(std_vec0, std_vec1);
+    assert_ne!(std_vec0, core_vec);
+
+    assert_eq!((std, format!("Vec")), db.lookup_intern_path(std_vec0));
+    assert_eq!((core, format!("Vec")), db.lookup_intern_path(core_vec));
+}
+
 #[test]
 fn test_intern_key() {
     let db = Database::default();
@@ -90,3 +136,202 @@ fn test_intern_key() {
     assert_eq!(format!("foo"), db.lookup_intern_key(foo0));
     assert_eq!(format!("bar"), db.lookup_intern_key(bar0));
 }
+
+#[test]
+fn test_intern_id_macro() {
+    let db = Database::default();
+    let foo0 = db.intern_name(format!("foo"));
+    let bar0 = db.intern_name(format!("bar"));
+    let foo1 = db.intern_name(format!("foo"));
+
+    assert_eq!(foo0, foo1);
+    assert_ne!(foo0, bar0);
+
+    assert_eq!(format!("foo"), db.lookup_intern_name(foo0));
+    assert_eq!(format!("bar"), db.lookup_intern_name(bar0));
+
+    assert_eq!(InternId::from(foo0), salsa::InternKey::as_intern_id(&foo0));
+    assert_eq!(format!("{:?}", foo0), format!("Name({})", foo0));
+}
+
+#[test]
+fn test_intern_keys_in_insertion_order() {
+    let db = Database::default();
+    let bar = db.intern1(format!("bar"));
+    let foo = db.intern1(format!("foo"));
+    db.intern1(format!("bar"));
+    let baz = db.intern1(format!("baz"));
+
+    let ids: Vec<InternId> = Intern1Query
+        .in_db(&db)
+        .intern_keys_in_insertion_order()
+        .collect();
+    assert_eq!(ids, vec![bar, foo, baz]);
+}
+
+#[test]
+fn test_intern_borrowed() {
+    let db = Database::default();
+    let bar = db.intern1(format!("bar"));
+    assert_eq!(Intern1Query.in_db(&db).intern_borrowed("bar"), bar);
+
+    let foo = Intern1Query.in_db(&db).intern_borrowed("foo");
+    assert_ne!(foo, bar);
+    assert_eq!(db.intern1(format!("foo")), foo);
+    assert_eq!(db.lookup_intern1(foo), "foo");
+}
+
+#[test]
+fn test_intern_slice() {
+    let db = Database::default();
+    let foo = db.intern_bytes(Box::from(&b"foo"[..]));
+    assert_eq!(
+        InternBytesQuery.in_db(&db).intern_borrowed(&b"foo"[..]),
+        foo
+    );
+
+    let bar = InternBytesQuery.in_db(&db).intern_borrowed(&b"bar"[..]);
+    assert_ne!(foo, bar);
+    assert_eq!(&*db.lookup_intern_bytes(bar), b"bar");
+}
+
+#[test]
+fn test_intern_many() {
+    let db = Database::default();
+    let foo = db.intern1(format!("foo"));
+    let ids = Intern1Query.in_db(&db).intern_many(vec![
+        format!("bar"),
+        format!("foo"),
+        format!("baz"),
+        format!("bar"),
+    ]);
+    assert_eq!(ids[1], foo);
+    assert_eq!(ids[0], ids[3]);
+    assert_ne!(ids[0], ids[2]);
+    assert_eq!(db.intern1(format!("baz")), ids[2]);
+    assert_eq!(db.lookup_intern1(ids[0]), "bar");
+}
+
+#[test]
+fn test_lookup_ref() {
+    let db = Database::default();
+    let foo = db.intern2(format!("foo"), format!("bar"));
+    let value = db.lookup_intern2_ref(foo);
+    assert_eq!(*value, (format!("foo"), format!("bar")));
+    assert_eq!(value.0.as_str(), "foo");
+    assert_eq!(format!("{:?}", value), r#"("foo", "bar")"#);
+
+    // The value is borrowed from the interning table, so looking it up
+    // again gives the same allocation.
+    let again = db.lookup_intern2_ref(foo);
+    assert!(std::ptr::eq(&*value, &*again));
+}
+
+#[test]
+fn test_try_lookup() {
+    let db = Database::default();
+    let foo = db.intern1(format!("foo"));
+    assert_eq!(db.try_lookup_intern1(foo), Some(format!("foo")));
+
+    // An id that was never handed out (e.g. a deserialized one).
+    let unknown = InternId::from(100u32);
+    assert_eq!(db.try_lookup_intern1(unknown), None);
+    assert_eq!(db.try_lookup_intern_name(NameId::from(foo)), None);
+}
+
+#[test]
+fn test_intern_stats() {
+    let db = Database::default();
+    let stats = Intern1Query.in_db(&db).intern_stats();
+    assert_eq!(stats.entries, 0);
+    assert_eq!(stats.load_factor(), 0.0);
+
+    db.intern1(format!("bar"));
+    db.intern1(format!("foo"));
+    db.intern1(format!("bar"));
+    let stats = Intern1Query.in_db(&db).intern_stats();
+    assert_eq!(stats.entries, 2);
+    assert_eq!(stats.free_ids, 0);
+    assert!(stats.capacity >= 2);
+    assert!(stats.load_factor() > 0.0 && stats.load_factor() <= 1.0);
+}
+
+#[test]
+#[cfg(debug_assertions)]
+#[should_panic(expected = "inconsistent `InternKey` impl")]
+fn test_inconsistent_intern_key() {
+    let db = Database::default();
+    db.intern_bad_key(format!("foo"));
+}
+
+#[test]
+fn test_intern_with_id() {
+    let db = Database::default();
+    let table = Intern1Query.in_db(&db);
+    let foo = table.intern_with_id(format!("foo"), InternId::from(2u32));
+    assert_eq!(foo, Ok(InternId::from(2u32)));
+    assert_eq!(db.intern1(format!("foo")), InternId::from(2u32));
+    assert_eq!(db.lookup_intern1(InternId::from(2u32)), "foo");
+
+    // Giving a value the id it already has is fine.
+    assert_eq!(
+        table.intern_with_id(format!("foo"), InternId::from(2u32)),
+        Ok(InternId::from(2u32))
+    );
+    assert_eq!(
+        table.intern_with_id(format!("foo"), InternId::from(3u32)),
+        Err(salsa::InternWithIdError::AlreadyInterned {
+            id: InternId::from(2u32)
+        })
+    );
+    assert_eq!(
+        table.intern_with_id(format!("bar"), InternId::from(2u32)),
+        Err(salsa::InternWithIdError::IdTaken {
+            id: InternId::from(2u32)
+        })
+    );
+
+    // Other values get the ids that are still free.
+    let mut ids = vec![
+        db.intern1(format!("a")),
+        db.intern1(format!("b")),
+        db.intern1(format!("c")),
+    ];
+    ids.sort();
+    assert_eq!(
+        ids,
+        vec![
+            InternId::from(0u32),
+            InternId::from(1u32),
+            InternId::from(3u32)
+        ]
+    );
+}
+
+#[test]
+fn test_intern_capacity() {
+    let mut db = Database::default();
+    let foo = db.intern1(format!("foo"));
+    Intern1Query.in_db_mut(&mut db).set_intern_capacity(Some(2));
+
+    let bar = db.try_intern1(format!("bar")).unwrap();
+    assert_eq!(
+        db.try_intern1(format!("baz")),
+        Err(salsa::InternCapacityExceeded {
+            query: "intern1",
+            capacity: 2
+        })
+    );
+
+    // Values that are already interned are still found.
+    assert_eq!(db.try_intern1(format!("foo")), Ok(foo));
+    assert_eq!(db.intern1(format!("bar")), bar);
+
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        db.intern1(format!("baz"));
+    }));
+    assert!(result.is_err());
+
+    Intern1Query.in_db_mut(&mut db).set_intern_capacity(None);
+    assert!(db.try_intern1(format!("baz")).is_ok());
+}
@@ -0,0 +1,28 @@
+//! Test `Runtime::is_in_query`.
+
+use salsa::Database as _;
+
+#[salsa::query_group(QueryGroupStorage)]
+trait QueryGroup: salsa::Database {
+    fn in_query(&self) -> bool;
+}
+
+fn in_query(db: &dyn QueryGroup) -> bool {
+    db.salsa_runtime().is_in_query()
+}
+
+#[salsa::database(QueryGroupStorage)]
+#[derive(Default)]
+struct Database {
+    storage: salsa::Storage<Self>,
+}
+
+impl salsa::Database for Database {}
+
+#[test]
+fn is_in_query() {
+    let db = Database::default();
+    assert!(!db.salsa_runtime().is_in_query());
+    assert!(db.in_query());
+    assert!(!db.salsa_runtime().is_in_query());
+}
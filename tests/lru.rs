@@ -1,4 +1,5 @@
 //! Test setting LRU actually limits the number of things in the database;
+use salsa::Database as _;
 use std::sync::{
     atomic::{AtomicUsize, Ordering},
     Arc,
@@ -26,6 +27,7 @@ impl Drop for HotPotato {
 trait QueryGroup: salsa::Database {
     fn get(&self, x: u32) -> Arc<HotPotato>;
     fn get_volatile(&self, x: u32) -> usize;
+    fn get_square(&self, x: u32) -> u32;
 }
 
 fn get(_db: &dyn QueryGroup, x: u32) -> Arc<HotPotato> {
@@ -38,13 +40,24 @@ fn get_volatile(db: &dyn QueryGroup, _x: u32) -> usize {
     COUNTER.fetch_add(1, Ordering::SeqCst)
 }
 
+fn get_square(_db: &dyn QueryGroup, x: u32) -> u32 {
+    x * x
+}
+
 #[salsa::database(QueryGroupStorage)]
 #[derive(Default)]
 struct Database {
     storage: salsa::Storage<Self>,
+    discarded: AtomicUsize,
 }
 
-impl salsa::Database for Database {}
+impl salsa::Database for Database {
+    fn salsa_event(&self, event: salsa::Event) {
+        if let salsa::EventKind::WillDiscardStaleValue { .. } = event.kind {
+            self.discarded.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+}
 
 #[test]
 fn lru_works() {
@@ -100,3 +113,96 @@ fn lru_doesnt_break_volatile_queries() {
         assert_eq!(x, i)
     }
 }
+
+#[test]
+fn lru_introspection() {
+    let mut db = Database::default();
+    assert_eq!(GetSquareQuery.in_db(&db).lru_capacity(), None);
+    assert!(GetSquareQuery.in_db(&db).is_empty());
+
+    GetSquareQuery.in_db_mut(&mut db).set_lru_capacity(32);
+    assert_eq!(GetSquareQuery.in_db(&db).lru_capacity(), Some(32));
+
+    for i in 0..16u32 {
+        assert_eq!(db.get_square(i), i * i);
+    }
+    assert_eq!(GetSquareQuery.in_db(&db).len(), 16);
+    assert_eq!(db.discarded.load(Ordering::SeqCst), 0);
+
+    for i in 16..128u32 {
+        assert_eq!(db.get_square(i), i * i);
+    }
+    assert_eq!(GetSquareQuery.in_db(&db).len(), 32);
+    assert_eq!(db.discarded.load(Ordering::SeqCst), 128 - 32);
+
+    GetSquareQuery.in_db_mut(&mut db).set_lru_capacity(0);
+    assert_eq!(GetSquareQuery.in_db(&db).lru_capacity(), None);
+}
+
+#[test]
+fn lru_trigger_eviction() {
+    let mut db = Database::default();
+    for i in 0..64u32 {
+        assert_eq!(db.get_square(i), i * i);
+    }
+
+    // Lowering the capacity does not evict anything by itself.
+    GetSquareQuery.in_db_mut(&mut db).set_lru_capacity(16);
+    assert_eq!(GetSquareQuery.in_db(&db).len(), 64);
+    for i in 0..8u32 {
+        assert_eq!(db.get_square(i), i * i);
+    }
+    assert_eq!(db.discarded.load(Ordering::SeqCst), 0);
+
+    db.trigger_lru_eviction();
+    assert_eq!(GetSquareQuery.in_db(&db).len(), 16);
+    assert_eq!(db.discarded.load(Ordering::SeqCst), 64 - 16);
+
+    // The values used since the capacity was set were kept.
+    db.discarded.store(0, Ordering::SeqCst);
+    for i in 0..8u32 {
+        assert_eq!(db.get_square(i), i * i);
+    }
+    assert_eq!(db.discarded.load(Ordering::SeqCst), 0);
+
+    // Nothing is left to evict.
+    db.trigger_lru_eviction();
+    assert_eq!(GetSquareQuery.in_db(&db).len(), 16);
+}
+
+#[test]
+fn lru_skips_pinned() {
+    let mut db = Database::default();
+    GetSquareQuery.in_db_mut(&mut db).set_lru_capacity(32);
+    GetSquareQuery.in_db_mut(&mut db).pin(&3);
+
+    for i in 0..128u32 {
+        assert_eq!(db.get_square(i), i * i);
+    }
+    assert_eq!(GetSquareQuery.in_db(&db).len(), 33);
+    assert_eq!(GetSquareQuery.in_db(&db).peek(&3), Some(9));
+
+    // Once unpinned, the value can be evicted again.
+    GetSquareQuery.in_db_mut(&mut db).unpin(&3);
+    for i in 0..128u32 {
+        assert_eq!(db.get_square(i), i * i);
+    }
+    assert_eq!(GetSquareQuery.in_db(&db).len(), 32);
+}
+
+#[test]
+fn lru_config() {
+    let db = Database::default();
+    let config = salsa::LruConfig::default()
+        .cap("get_square", 32)
+        .cap("get", 16)
+        .cap("get_cube", 8);
+    assert_eq!(db.apply_lru_config(&config), vec!["get_cube"]);
+    assert_eq!(GetSquareQuery.in_db(&db).lru_capacity(), Some(32));
+    assert_eq!(GetQuery.in_db(&db).lru_capacity(), Some(16));
+    assert_eq!(GetVolatileQuery.in_db(&db).lru_capacity(), None);
+
+    let config = salsa::LruConfig::default().cap("get_square", 0);
+    assert!(db.apply_lru_config(&config).is_empty());
+    assert_eq!(GetSquareQuery.in_db(&db).lru_capacity(), None);
+}
@@ -2,10 +2,50 @@
 trait MyDatabase: salsa::Database {
     #[salsa::invoke(another_module::another_name)]
     fn my_query(&self, key: ()) -> ();
+
+    #[salsa::invoke(Analyzer::associated)]
+    fn associated(&self, key: u32) -> u32;
+
+    #[salsa::invoke(AnalyzerExt::method)]
+    fn method(&self, key: u32) -> u32;
 }
 
 mod another_module {
     pub(crate) fn another_name(_: &dyn crate::MyDatabase, (): ()) -> () {}
 }
 
+struct Analyzer;
+
+impl Analyzer {
+    fn associated(_: &dyn MyDatabase, key: u32) -> u32 {
+        key + 1
+    }
+}
+
+trait AnalyzerExt {
+    fn method(&self, key: u32) -> u32;
+}
+
+impl AnalyzerExt for dyn MyDatabase + '_ {
+    fn method(&self, key: u32) -> u32 {
+        self.associated(key) * 2
+    }
+}
+
+#[salsa::database(MyStruct)]
+#[derive(Default)]
+struct DatabaseStruct {
+    storage: salsa::Storage<Self>,
+}
+
+impl salsa::Database for DatabaseStruct {}
+
 fn main() {}
+
+#[test]
+fn invoke_paths() {
+    let db = DatabaseStruct::default();
+    db.my_query(());
+    assert_eq!(db.associated(1), 2);
+    assert_eq!(db.method(1), 4);
+}
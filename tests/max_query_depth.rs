@@ -0,0 +1,95 @@
+//! Test `Runtime::set_max_query_depth`.
+
+use salsa::{Database as _, OverflowBehavior, QueryStackOverflow};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+
+#[salsa::query_group(QueryGroupStorage)]
+trait QueryGroup: salsa::Database {
+    fn depth(&self, n: u32) -> u32;
+
+    #[salsa::cycle(recover_capped)]
+    fn capped(&self, n: u32) -> u32;
+}
+
+fn depth(db: &dyn QueryGroup, n: u32) -> u32 {
+    if n == 0 {
+        0
+    } else {
+        db.depth(n - 1) + 1
+    }
+}
+
+fn capped(db: &dyn QueryGroup, n: u32) -> u32 {
+    if n == 0 {
+        0
+    } else {
+        db.capped(n - 1) + 1
+    }
+}
+
+fn recover_capped(_db: &dyn QueryGroup, cycle: &salsa::Cycle, _n: &u32) -> u32 {
+    100 + cycle.participant_keys().len() as u32
+}
+
+#[salsa::database(QueryGroupStorage)]
+#[derive(Default)]
+struct Database {
+    storage: salsa::Storage<Self>,
+}
+
+impl salsa::Database for Database {}
+
+#[test]
+fn max_query_depth() {
+    let db = Database::default();
+    db.salsa_runtime().set_max_query_depth(Some(10));
+    assert_eq!(db.depth(9), 9);
+
+    let payload = catch_unwind(AssertUnwindSafe(|| db.depth(20))).unwrap_err();
+    let overflow = payload.downcast::<QueryStackOverflow>().unwrap();
+    assert_eq!(overflow.max_depth, 10);
+    assert_eq!(format!("{:?}", overflow.query.debug(&db)), "depth(10)");
+
+    // The database is still usable, and the values computed before the
+    // overflow are reused.
+    assert_eq!(db.depth(9), 9);
+    db.salsa_runtime().set_max_query_depth(None);
+    assert_eq!(db.depth(20), 20);
+}
+
+#[test]
+fn overflow_behavior_panic() {
+    let db = Database::default();
+    db.salsa_runtime().set_max_query_depth(Some(3));
+    db.salsa_runtime()
+        .set_overflow_behavior(OverflowBehavior::Panic);
+
+    let payload = catch_unwind(AssertUnwindSafe(|| db.depth(5))).unwrap_err();
+    let message = payload.downcast::<String>().unwrap();
+    assert_eq!(
+        *message,
+        "executing depth(2) would exceed the query depth of 3; \
+         active queries: [\"depth(5)\", \"depth(4)\", \"depth(3)\"]"
+    );
+}
+
+#[test]
+fn overflow_behavior_recover() {
+    let db = Database::default();
+    db.salsa_runtime().set_max_query_depth(Some(10));
+    db.salsa_runtime()
+        .set_overflow_behavior(OverflowBehavior::Recover);
+
+    // `capped(10)` recovers, as if it were in a cycle with the 10
+    // queries on the stack.
+    assert_eq!(db.capped(20), 121);
+
+    // Queries that cannot recover still unwind.
+    let payload = catch_unwind(AssertUnwindSafe(|| db.depth(20))).unwrap_err();
+    assert!(payload.downcast::<QueryStackOverflow>().is_ok());
+
+    // Neither the recovered value nor those computed from it are
+    // memoized, so they are computed anew without the limit.
+    db.salsa_runtime().set_max_query_depth(None);
+    assert_eq!(db.capped(20), 20);
+}
@@ -0,0 +1,57 @@
+//! Test `QueryTable::memoized_durability`, which reports the durability
+//! recorded for a memoized value.
+
+use salsa::Durability;
+
+#[salsa::query_group(QueryGroupStorage)]
+trait QueryGroup: salsa::Database {
+    #[salsa::input]
+    fn input(&self, x: u32) -> u32;
+
+    fn sum(&self, x: u32, y: u32) -> u32;
+}
+
+fn sum(db: &dyn QueryGroup, x: u32, y: u32) -> u32 {
+    db.input(x) + db.input(y)
+}
+
+#[salsa::database(QueryGroupStorage)]
+#[derive(Default)]
+struct Database {
+    storage: salsa::Storage<Self>,
+}
+
+impl salsa::Database for Database {}
+
+#[test]
+fn not_memoized() {
+    let db = Database::default();
+    assert_eq!(SumQuery.in_db(&db).memoized_durability(&(1, 2)), None);
+}
+
+#[test]
+fn lowest_input_durability() {
+    let mut db = Database::default();
+    db.set_input(1).with_durability(Durability::HIGH).to(10);
+    db.set_input(2).with_durability(Durability::HIGH).to(20);
+    db.set_input(3).with_durability(Durability::LOW).to(30);
+
+    assert_eq!(db.sum(1, 2), 30);
+    assert_eq!(db.sum(1, 3), 40);
+    assert_eq!(
+        SumQuery.in_db(&db).memoized_durability(&(1, 2)),
+        Some(Durability::HIGH)
+    );
+    assert_eq!(
+        SumQuery.in_db(&db).memoized_durability(&(1, 3)),
+        Some(Durability::LOW)
+    );
+
+    // The recorded durability is still reported after a low durability
+    // input changes, before the value is verified again.
+    db.set_input(3).to(31);
+    assert_eq!(
+        SumQuery.in_db(&db).memoized_durability(&(1, 2)),
+        Some(Durability::HIGH)
+    );
+}
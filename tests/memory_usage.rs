@@ -0,0 +1,45 @@
+//! Test `Database::memory_usage`.
+
+use salsa::Database as _;
+
+#[salsa::query_group(InputsStorage)]
+trait Inputs: salsa::Database {
+    #[salsa::input]
+    fn input(&self, key: u32) -> u64;
+}
+
+#[salsa::query_group(DerivedStorage)]
+trait Derived: Inputs {
+    fn double(&self, key: u32) -> u64;
+}
+
+fn double(db: &dyn Derived, key: u32) -> u64 {
+    db.input(key) * 2
+}
+
+#[salsa::database(InputsStorage, DerivedStorage)]
+#[derive(Default)]
+struct Database {
+    storage: salsa::Storage<Self>,
+}
+
+impl salsa::Database for Database {}
+
+#[test]
+fn memory_usage_per_group() {
+    let mut db = Database::default();
+    assert_eq!(db.memory_usage(), vec![("Inputs", 0), ("Derived", 0)]);
+
+    for key in 0..3 {
+        db.set_input(key).to(u64::from(key));
+    }
+    db.double(0);
+    db.double(1);
+
+    // Each entry is estimated as the size of a `u32` key plus a `u64`
+    // value.
+    assert_eq!(
+        db.memory_usage(),
+        vec![("Inputs", 3 * 12), ("Derived", 2 * 12)]
+    );
+}
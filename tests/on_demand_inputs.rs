@@ -4,7 +4,11 @@
 //! via a b query with zero inputs, which uses `add_synthetic_read` to
 //! tweak durability and `invalidate` to clear the input.
 
-use std::{cell::Cell, collections::HashMap, rc::Rc};
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashMap,
+    rc::Rc,
+};
 
 use salsa::{Database as _, Durability};
 
@@ -84,10 +88,15 @@ fn on_demand_input_durability() {
     assert_eq!(db.b(2), 20);
 
     let validated = Rc::new(Cell::new(0));
+    let revisions = Rc::new(RefCell::new(Vec::new()));
     db.on_event = Some(Box::new({
         let validated = Rc::clone(&validated);
+        let revisions = Rc::clone(&revisions);
         move |event| match event.kind {
-            salsa::EventKind::DidValidateMemoizedValue { .. } => validated.set(validated.get() + 1),
+            salsa::EventKind::DidValidateMemoizedValue { revision, .. } => {
+                validated.set(validated.get() + 1);
+                revisions.borrow_mut().push(revision);
+            }
             _ => (),
         }
     }));
@@ -103,4 +112,41 @@ fn on_demand_input_durability() {
     assert_eq!(db.c(1), 10);
     assert_eq!(db.c(2), 20);
     assert_eq!(validated.get(), 4);
+
+    // All validations within a revision report the same revision, and
+    // the second batch happened in a later revision.
+    let revisions = revisions.borrow();
+    assert_eq!(revisions.len(), 6);
+    assert!(revisions[..2].iter().all(|&r| r == revisions[0]));
+    assert!(revisions[2..].iter().all(|&r| r == revisions[2]));
+    assert!(revisions[0] < revisions[2]);
+}
+
+#[test]
+fn invalidate_keeps_unchanged_dependents() {
+    let mut db = Database::default();
+    db.external_state.insert(1, 10);
+    assert_eq!(db.c(1), 10);
+
+    let executed = Rc::new(RefCell::new(Vec::new()));
+    db.on_event = Some(Box::new({
+        let executed = Rc::clone(&executed);
+        move |event| {
+            if let salsa::EventKind::WillExecute { database_key } = event.kind {
+                executed.borrow_mut().push(database_key);
+            }
+        }
+    }));
+
+    // `b` reads `a`, whose inputs are now unknown, so it is re-executed
+    // (and re-executes `a`). But `b` gives the same value as before, so
+    // `c` is validated without being re-executed.
+    AQuery.in_db_mut(&mut db).invalidate(&1);
+    assert_eq!(db.c(1), 10);
+    let executed: Vec<String> = executed
+        .borrow()
+        .iter()
+        .map(|key| format!("{:?}", key.debug(&db)))
+        .collect();
+    assert_eq!(executed, vec!["b(1)", "a(1)"]);
 }
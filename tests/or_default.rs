@@ -0,0 +1,63 @@
+//! Test the `foo_or_default` methods generated for
+//! `#[salsa::or_default]` queries, and `QueryTable::get_or_default`.
+
+#[salsa::query_group(QueryGroupStorage)]
+trait QueryGroup: salsa::Database {
+    #[salsa::input]
+    fn names(&self) -> Vec<String>;
+
+    #[salsa::or_default]
+    fn position(&self, name: String) -> Option<usize>;
+
+    #[salsa::or_default]
+    #[salsa::transparent]
+    fn first(&self) -> Option<String>;
+}
+
+fn position(db: &dyn QueryGroup, name: String) -> Option<usize> {
+    db.names().iter().position(|n| *n == name)
+}
+
+fn first(db: &dyn QueryGroup) -> Option<String> {
+    db.names().first().cloned()
+}
+
+#[salsa::query_group(DependentStorage)]
+trait Dependent: QueryGroup {
+    fn position_plus_one(&self, name: String) -> usize;
+}
+
+fn position_plus_one(db: &dyn Dependent, name: String) -> usize {
+    db.position_or_default(name) + 1
+}
+
+#[salsa::database(QueryGroupStorage, DependentStorage)]
+#[derive(Default)]
+struct Database {
+    storage: salsa::Storage<Self>,
+}
+
+impl salsa::Database for Database {}
+
+#[test]
+fn records_dependency() {
+    let mut db = Database::default();
+    db.set_names().to(vec!["a".to_string()]);
+    assert_eq!(db.position_plus_one("b".to_string()), 1);
+
+    db.set_names().to(vec!["a".to_string(), "b".to_string()]);
+    assert_eq!(db.position_plus_one("b".to_string()), 2);
+}
+
+#[test]
+fn or_default() {
+    let mut db = Database::default();
+    db.set_names().to(vec!["a".to_string(), "b".to_string()]);
+    assert_eq!(db.position_or_default("b".to_string()), 1);
+    assert_eq!(db.position_or_default("c".to_string()), 0);
+    assert_eq!(PositionQuery.in_db(&db).get_or_default("a".to_string()), 0);
+    assert_eq!(db.first_or_default(), "a");
+
+    db.set_names().to(vec![]);
+    assert_eq!(db.first_or_default(), "");
+}
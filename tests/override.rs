@@ -0,0 +1,95 @@
+//! Test `QueryTableMut::set_override`, which sets the value of a
+//! derived query as though it were an input.
+
+use salsa::{Database as _, SweepStrategy};
+use std::cell::Cell;
+
+#[salsa::query_group(QueryGroupStorage)]
+trait QueryGroup: salsa::Database + AsRef<Cell<usize>> {
+    #[salsa::input]
+    fn input(&self, key: u32) -> u32;
+
+    fn config(&self, key: u32) -> u32;
+
+    fn doubled_config(&self, key: u32) -> u32;
+}
+
+fn config(db: &dyn QueryGroup, key: u32) -> u32 {
+    let executions = db.as_ref();
+    executions.set(executions.get() + 1);
+    db.input(key) + 1
+}
+
+fn doubled_config(db: &dyn QueryGroup, key: u32) -> u32 {
+    db.config(key) * 2
+}
+
+#[salsa::database(QueryGroupStorage)]
+#[derive(Default)]
+struct Database {
+    storage: salsa::Storage<Self>,
+    executions: Cell<usize>,
+}
+
+impl salsa::Database for Database {}
+
+impl AsRef<Cell<usize>> for Database {
+    fn as_ref(&self) -> &Cell<usize> {
+        &self.executions
+    }
+}
+
+#[test]
+fn override_takes_precedence() {
+    let mut db = Database::default();
+    db.set_input(1).to(10);
+    assert_eq!(db.doubled_config(1), 22);
+    assert_eq!(db.executions.get(), 1);
+
+    ConfigQuery.in_db_mut(&mut db).set_override(1, 100);
+    assert!(ConfigQuery.in_db(&db).is_overridden(&1));
+    assert_eq!(db.config(1), 100);
+    assert_eq!(db.doubled_config(1), 200);
+
+    // The override does not depend on the input.
+    db.set_input(1).to(20);
+    assert_eq!(db.doubled_config(1), 200);
+    assert_eq!(db.executions.get(), 1);
+}
+
+#[test]
+fn clear_override_falls_back() {
+    let mut db = Database::default();
+    db.set_input(1).to(10);
+    ConfigQuery.in_db_mut(&mut db).set_override(1, 100);
+    assert_eq!(db.doubled_config(1), 200);
+    assert_eq!(db.executions.get(), 0);
+
+    ConfigQuery.in_db_mut(&mut db).clear_override(&1);
+    assert!(!ConfigQuery.in_db(&db).is_overridden(&1));
+    assert_eq!(db.doubled_config(1), 22);
+    assert_eq!(db.executions.get(), 1);
+
+    // Clearing again does nothing.
+    let revision = db.salsa_runtime().current_revision();
+    ConfigQuery.in_db_mut(&mut db).clear_override(&1);
+    assert_eq!(db.salsa_runtime().current_revision(), revision);
+}
+
+#[test]
+fn override_survives_sweep_and_clear() {
+    let mut db = Database::default();
+    db.set_input(1).to(10);
+    ConfigQuery.in_db_mut(&mut db).set_override(1, 100);
+    db.set_input(1).to(20);
+
+    db.sweep_all(
+        SweepStrategy::default()
+            .discard_everything()
+            .sweep_all_revisions(),
+    );
+    ConfigQuery.in_db_mut(&mut db).invalidate(&1);
+    ConfigQuery.in_db_mut(&mut db).clear();
+    assert_eq!(db.config(1), 100);
+    assert_eq!(db.executions.get(), 0);
+}
@@ -1,6 +1,6 @@
 use salsa::{Database, ParallelDatabase, Snapshot};
 use std::panic::{self, AssertUnwindSafe};
-use std::sync::atomic::{AtomicU32, Ordering::SeqCst};
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering::SeqCst};
 
 #[salsa::query_group(PanicSafelyStruct)]
 trait PanicSafelyDatabase: salsa::Database {
@@ -10,6 +10,8 @@ trait PanicSafelyDatabase: salsa::Database {
     fn panic_safely(&self) -> ();
 
     fn outer(&self) -> ();
+
+    fn flaky(&self) -> usize;
 }
 
 fn panic_safely(db: &dyn PanicSafelyDatabase) -> () {
@@ -23,6 +25,17 @@ fn outer(db: &dyn PanicSafelyDatabase) -> () {
     db.panic_safely();
 }
 
+static FLAKY_PANICS: AtomicBool = AtomicBool::new(true);
+static FLAKY_CALLS: AtomicU32 = AtomicU32::new(0);
+
+fn flaky(db: &dyn PanicSafelyDatabase) -> usize {
+    FLAKY_CALLS.fetch_add(1, SeqCst);
+    if FLAKY_PANICS.load(SeqCst) {
+        panic!("flaky");
+    }
+    db.one()
+}
+
 #[salsa::database(PanicSafelyStruct)]
 #[derive(Default)]
 struct DatabaseStruct {
@@ -42,7 +55,7 @@ impl salsa::ParallelDatabase for DatabaseStruct {
 #[test]
 fn should_panic_safely() {
     let mut db = DatabaseStruct::default();
-    db.set_one(0);
+    db.set_one().to(0);
 
     // Invoke `db.panic_safely() without having set `db.one`. `db.one` will
     // return 0 and we should catch the panic.
@@ -53,7 +66,7 @@ fn should_panic_safely() {
     assert!(result.is_err());
 
     // Set `db.one` to 1 and assert ok
-    db.set_one(1);
+    db.set_one().to(1);
     let result = panic::catch_unwind(AssertUnwindSafe(|| db.panic_safely()));
     assert!(result.is_ok());
 
@@ -63,12 +76,12 @@ fn should_panic_safely() {
         db.outer();
         assert_eq!(OUTER_CALLS.load(SeqCst), 1);
 
-        db.set_one(0);
+        db.set_one().to(0);
         let result = panic::catch_unwind(AssertUnwindSafe(|| db.outer()));
         assert!(result.is_err());
         assert_eq!(OUTER_CALLS.load(SeqCst), 1);
 
-        db.set_one(1);
+        db.set_one().to(1);
         db.outer();
         assert_eq!(OUTER_CALLS.load(SeqCst), 1);
     }
@@ -93,3 +106,22 @@ fn panics_clear_query_stack() {
     // revision should panic.
     assert_eq!(db.salsa_runtime().active_query(), None);
 }
+
+#[test]
+fn panicked_query_is_reexecuted() {
+    let mut db = DatabaseStruct::default();
+    db.set_one().to(1);
+
+    let result = panic::catch_unwind(AssertUnwindSafe(|| db.flaky()));
+    assert!(result.is_err());
+    assert_eq!(FLAKY_CALLS.load(SeqCst), 1);
+
+    // Other queries still work, and nothing was memoized for `flaky`, so
+    // reading it again (in the same revision) executes it again.
+    db.panic_safely();
+    FLAKY_PANICS.store(false, SeqCst);
+    assert_eq!(db.flaky(), 1);
+    assert_eq!(FLAKY_CALLS.load(SeqCst), 2);
+    assert_eq!(db.flaky(), 1);
+    assert_eq!(FLAKY_CALLS.load(SeqCst), 2);
+}
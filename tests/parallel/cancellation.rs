@@ -1,5 +1,7 @@
 use crate::setup::{CancelationFlag, Canceled, Knobs, ParDatabase, ParDatabaseImpl, WithValue};
-use salsa::ParallelDatabase;
+use salsa::{Database, ParallelDatabase};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 
 macro_rules! assert_canceled {
     ($flag:expr, $thread:expr) => {
@@ -32,10 +34,10 @@ fn in_par_get_set_cancellation_immediate() {
     check_cancelation(|flag| {
         let mut db = ParDatabaseImpl::default();
 
-        db.set_input('a', 100);
-        db.set_input('b', 010);
-        db.set_input('c', 001);
-        db.set_input('d', 0);
+        db.set_input('a').to(100);
+        db.set_input('b').to(010);
+        db.set_input('c').to(001);
+        db.set_input('d').to(0);
 
         let thread1 = std::thread::spawn({
             let db = db.snapshot();
@@ -54,7 +56,7 @@ fn in_par_get_set_cancellation_immediate() {
         db.wait_for(1);
 
         // Try to set the input. This will signal cancellation.
-        db.set_input('d', 1000);
+        db.set_input('d').to(1000);
 
         // This should re-compute the value (even though no input has changed).
         let thread2 = std::thread::spawn({
@@ -75,10 +77,10 @@ fn in_par_get_set_cancellation_transitive() {
     check_cancelation(|flag| {
         let mut db = ParDatabaseImpl::default();
 
-        db.set_input('a', 100);
-        db.set_input('b', 010);
-        db.set_input('c', 001);
-        db.set_input('d', 0);
+        db.set_input('a').to(100);
+        db.set_input('b').to(010);
+        db.set_input('c').to(001);
+        db.set_input('d').to(0);
 
         let thread1 = std::thread::spawn({
             let db = db.snapshot();
@@ -97,7 +99,7 @@ fn in_par_get_set_cancellation_transitive() {
         db.wait_for(1);
 
         // Try to set the input. This will signal cancellation.
-        db.set_input('d', 1000);
+        db.set_input('d').to(1000);
 
         // This should re-compute the value (even though no input has changed).
         let thread2 = std::thread::spawn({
@@ -117,7 +119,7 @@ fn no_back_dating_in_cancellation() {
     check_cancelation(|flag| {
         let mut db = ParDatabaseImpl::default();
 
-        db.set_input('a', 1);
+        db.set_input('a').to(1);
         let thread1 = std::thread::spawn({
             let db = db.snapshot();
             move || {
@@ -134,7 +136,7 @@ fn no_back_dating_in_cancellation() {
         db.wait_for(1);
 
         // Set unrelated input to bump revision
-        db.set_input('b', 2);
+        db.set_input('b').to(2);
 
         // Here we should recompuet the whole chain again, clearing the cancellation
         // state. If we get `usize::max()` here, it is a bug!
@@ -142,8 +144,8 @@ fn no_back_dating_in_cancellation() {
 
         assert_canceled!(flag, thread1);
 
-        db.set_input('a', 3);
-        db.set_input('a', 4);
+        db.set_input('a').to(3);
+        db.set_input('a').to(4);
         assert_eq!(db.sum3("ab"), 6);
     })
 }
@@ -158,7 +160,7 @@ fn no_back_dating_in_cancellation() {
 fn transitive_cancellation() {
     let mut db = ParDatabaseImpl::default();
 
-    db.set_input('a', 1);
+    db.set_input('a').to(1);
     let thread1 = std::thread::spawn({
         let db = db.snapshot();
         move || {
@@ -174,7 +176,7 @@ fn transitive_cancellation() {
 
     db.wait_for(1);
 
-    db.set_input('b', 2);
+    db.set_input('b').to(2);
 
     // Check that when we call `sum3_drop_sum` we don't wind up having
     // to actually re-execute it, because the result of `sum2` winds
@@ -185,3 +187,69 @@ fn transitive_cancellation() {
 
     assert_eq!(thread1.join().unwrap(), 22);
 }
+
+/// Check that a hook registered with `on_cancellation` fires on the
+/// snapshot's next salsa interaction after a write is requested.
+#[test]
+fn cancellation_hook() {
+    let mut db = ParDatabaseImpl::default();
+    db.set_input('a').to(1);
+
+    let fired = Arc::new(AtomicBool::new(false));
+    let thread1 = std::thread::spawn({
+        let db = db.snapshot();
+        let fired = fired.clone();
+        move || {
+            db.salsa_runtime().on_cancellation({
+                let fired = fired.clone();
+                move || fired.store(true, Ordering::SeqCst)
+            });
+
+            // Not yet canceled.
+            assert_eq!(db.input('a'), 1);
+            assert!(!fired.load(Ordering::SeqCst));
+
+            db.signal(1);
+            while !db.salsa_runtime().is_current_revision_canceled() {
+                std::thread::yield_now();
+            }
+
+            // The hook runs on our next read.
+            assert!(!fired.load(Ordering::SeqCst));
+            assert_eq!(db.input('a'), 1);
+            assert!(fired.load(Ordering::SeqCst));
+        }
+    });
+
+    db.wait_for(1);
+    db.set_input('a').to(2);
+
+    thread1.join().unwrap();
+    assert!(fired.load(Ordering::SeqCst));
+}
+
+/// Check that `check_canceled` reports cancellation as an error
+/// once another thread starts to set an input.
+#[test]
+fn check_canceled_result() {
+    let mut db = ParDatabaseImpl::default();
+    db.set_input('a').to(1);
+
+    let thread = std::thread::spawn({
+        let db = db.snapshot();
+        move || {
+            assert_eq!(db.salsa_runtime().check_canceled(), Ok(()));
+            db.signal(1);
+            loop {
+                if let Err(canceled) = db.salsa_runtime().check_canceled() {
+                    return canceled;
+                }
+                std::thread::yield_now();
+            }
+        }
+    });
+
+    db.wait_for(1);
+    db.set_input('a').to(2);
+    assert_eq!(thread.join().unwrap(), salsa::Canceled);
+}
@@ -0,0 +1,113 @@
+use crate::signal::Signal;
+use salsa::ParallelDatabase;
+use salsa::Snapshot;
+use std::cell::Cell;
+use std::sync::Arc;
+
+#[salsa::query_group(FixpointStorage)]
+trait FixpointDatabase: salsa::Database + AsRef<Knobs> {
+    #[salsa::input]
+    fn limit(&self) -> u32;
+
+    /// Counts up to `limit`, one iteration at a time.
+    #[salsa::cycle(fixpoint, initial = 0)]
+    fn head(&self) -> u32;
+
+    fn member(&self) -> u32;
+
+    fn observer(&self) -> u32;
+}
+
+#[derive(Clone, Default)]
+struct Knobs {
+    signal: Arc<Signal>,
+
+    /// If true, the next execution of `member` signals 1 and waits
+    /// for 2.
+    wait_in_member: Cell<bool>,
+
+    /// If true, signal 2 when this database is about to block.
+    signal_on_will_block: Cell<bool>,
+}
+
+fn head(db: &dyn FixpointDatabase) -> u32 {
+    db.limit();
+    db.member()
+}
+
+fn member(db: &dyn FixpointDatabase) -> u32 {
+    let knobs: &Knobs = db.as_ref();
+    if knobs.wait_in_member.replace(false) {
+        knobs.signal.signal(1);
+        knobs.signal.wait_for(2);
+    }
+    (db.head() + 1).min(db.limit())
+}
+
+fn observer(db: &dyn FixpointDatabase) -> u32 {
+    db.member()
+}
+
+#[salsa::database(FixpointStorage)]
+#[derive(Default)]
+struct Database {
+    storage: salsa::Storage<Self>,
+    knobs: Knobs,
+}
+
+impl salsa::Database for Database {
+    fn salsa_event(&self, event: salsa::Event) {
+        if let salsa::EventKind::WillBlockOn { .. } = event.kind {
+            if self.knobs.signal_on_will_block.get() {
+                self.knobs.signal.signal(2);
+            }
+        }
+    }
+}
+
+impl ParallelDatabase for Database {
+    fn snapshot(&self) -> Snapshot<Self> {
+        Snapshot::new(Database {
+            storage: self.storage.snapshot(),
+            knobs: self.knobs.clone(),
+        })
+    }
+}
+
+impl AsRef<Knobs> for Database {
+    fn as_ref(&self) -> &Knobs {
+        &self.knobs
+    }
+}
+
+/// One thread checks whether `member` changed while another executes
+/// it from the provisional value of `head`, which happens to equal the
+/// old value of `member`. The first thread must not take that value as
+/// final, as the iteration converges to a different one.
+#[test]
+fn in_par_provisional_member() {
+    let mut db = Database::default();
+    db.set_limit().to(1);
+    assert_eq!(db.head(), 1);
+    assert_eq!(db.observer(), 1);
+
+    db.set_limit().to(3);
+
+    let thread1 = std::thread::spawn({
+        let db = db.snapshot();
+        db.knobs.wait_in_member.set(true);
+        move || db.head()
+    });
+
+    let thread2 = std::thread::spawn({
+        let db = db.snapshot();
+        db.knobs.signal_on_will_block.set(true);
+        move || {
+            db.knobs.signal.wait_for(1);
+            db.observer()
+        }
+    });
+
+    assert_eq!(thread1.join().unwrap(), 3);
+    assert_eq!(thread2.join().unwrap(), 3);
+}
@@ -10,7 +10,7 @@ use std::sync::Arc;
 fn in_par_get_set_cancellation() {
     let mut db = ParDatabaseImpl::default();
 
-    db.set_input('a', 1);
+    db.set_input('a').to(1);
 
     let signal = Arc::new(Signal::default());
 
@@ -50,7 +50,7 @@ fn in_par_get_set_cancellation() {
             signal.wait_for(1);
 
             // This will block until thread1 drops the revision lock.
-            db.set_input('a', 2);
+            db.set_input('a').to(2);
 
             db.input('a')
         }
@@ -63,3 +63,36 @@ fn in_par_get_set_cancellation() {
     let c = thread2.join().unwrap();
     assert_eq!(c, 2);
 }
+
+/// A snapshot keeps the revision it was created in, even while the
+/// master waits to set an input.
+#[test]
+fn snapshot_revision() {
+    let mut db = ParDatabaseImpl::default();
+    db.set_input('a').to(1);
+    assert_eq!(db.salsa_runtime().snapshot_revision(), None);
+
+    let revision = db.salsa_runtime().current_revision();
+    let snapshot = db.snapshot();
+    assert_eq!(snapshot.salsa_runtime().snapshot_revision(), Some(revision));
+
+    let signal = Arc::new(Signal::default());
+    let thread = std::thread::spawn({
+        let signal = signal.clone();
+        move || {
+            signal.signal(1);
+            db.set_input('a').to(2);
+            db.salsa_runtime().current_revision()
+        }
+    });
+
+    signal.wait_for(1);
+    while !snapshot.salsa_runtime().is_current_revision_canceled() {
+        std::thread::yield_now();
+    }
+    assert_eq!(snapshot.input('a'), 1);
+    assert_eq!(snapshot.salsa_runtime().snapshot_revision(), Some(revision));
+    drop(snapshot);
+
+    assert!(thread.join().unwrap() > revision);
+}
@@ -7,12 +7,12 @@ use salsa::ParallelDatabase;
 fn in_par_two_independent_queries() {
     let mut db = ParDatabaseImpl::default();
 
-    db.set_input('a', 100);
-    db.set_input('b', 010);
-    db.set_input('c', 001);
-    db.set_input('d', 200);
-    db.set_input('e', 020);
-    db.set_input('f', 002);
+    db.set_input('a').to(100);
+    db.set_input('b').to(010);
+    db.set_input('c').to(001);
+    db.set_input('d').to(200);
+    db.set_input('e').to(020);
+    db.set_input('f').to(002);
 
     let thread1 = std::thread::spawn({
         let db = db.snapshot();
@@ -0,0 +1,32 @@
+use crate::setup::{ParDatabase, ParDatabaseImpl};
+use salsa::ParallelDatabase;
+
+/// Intern overlapping sets of names from several threads at once, and
+/// check that they all agree on the resulting ids.
+#[test]
+fn in_par_intern() {
+    let db = ParDatabaseImpl::default();
+
+    let threads: Vec<_> = (0..4)
+        .map(|thread| {
+            let db = db.snapshot();
+            std::thread::spawn(move || {
+                (0..100)
+                    .map(|i| {
+                        let name = format!("name{}", (i + thread * 50) % 200);
+                        (name.clone(), db.intern_name(name))
+                    })
+                    .collect::<Vec<_>>()
+            })
+        })
+        .collect();
+
+    let mut ids = std::collections::HashMap::new();
+    for thread in threads {
+        for (name, id) in thread.join().unwrap() {
+            assert_eq!(*ids.entry(name.clone()).or_insert(id), id);
+            assert_eq!(db.lookup_intern_name(id), name);
+        }
+    }
+    assert_eq!(ids.len(), 200);
+}
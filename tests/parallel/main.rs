@@ -1,8 +1,10 @@
 mod setup;
 
 mod cancellation;
+mod fixpoint;
 mod frozen;
 mod independent;
+mod interned;
 mod race;
 mod signal;
 mod stress;
@@ -7,9 +7,9 @@ use salsa::ParallelDatabase;
 fn in_par_get_set_race() {
     let mut db = ParDatabaseImpl::default();
 
-    db.set_input('a', 100);
-    db.set_input('b', 010);
-    db.set_input('c', 001);
+    db.set_input('a').to(100);
+    db.set_input('b').to(010);
+    db.set_input('c').to(001);
 
     let thread1 = std::thread::spawn({
         let db = db.snapshot();
@@ -20,7 +20,7 @@ fn in_par_get_set_race() {
     });
 
     let thread2 = std::thread::spawn(move || {
-        db.set_input('a', 1000);
+        db.set_input('a').to(1000);
         db.sum("a")
     });
 
@@ -23,6 +23,9 @@ pub(crate) trait ParDatabase: Knobs {
 
     /// Invokes `sum2_drop_sum`
     fn sum3_drop_sum(&self, key: &'static str) -> usize;
+
+    #[salsa::interned]
+    fn intern_name(&self, name: String) -> salsa::InternId;
 }
 
 #[derive(PartialEq, Eq)]
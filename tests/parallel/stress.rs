@@ -139,7 +139,7 @@ impl WriteOp {
     fn execute(self, db: &mut StressDatabaseImpl) {
         match self {
             WriteOp::SetA(key, value) => {
-                db.set_a(key, value);
+                db.set_a(key).to(value);
             }
         }
     }
@@ -181,7 +181,7 @@ impl ReadOp {
 fn stress_test() {
     let mut db = StressDatabaseImpl::default();
     for i in 0..10 {
-        db.set_a(i, i);
+        db.set_a(i).to(i);
     }
 
     let mut rng = rand::thread_rng();
@@ -0,0 +1,193 @@
+//! Test `QueryTable::peek`, which returns values that are already
+//! computed without executing anything, `QueryTable::changed_at`,
+//! `QueryTable::verified_entries`, `QueryTable::memoized_keys` and
+//! `QueryTableMut::prime`.
+
+use salsa::Database as _;
+
+#[salsa::query_group(QueryGroupStorage)]
+trait QueryGroup: salsa::Database {
+    #[salsa::input]
+    fn input(&self, x: u32) -> u32;
+
+    fn double(&self, x: u32) -> u32;
+
+    fn peek_double(&self, x: u32) -> Option<u32>;
+
+    fn quadruple(&self, x: u32) -> u32;
+
+    #[salsa::interned]
+    fn intern(&self, x: String) -> salsa::InternId;
+}
+
+fn double(db: &dyn QueryGroup, x: u32) -> u32 {
+    db.input(x) * 2
+}
+
+fn peek_double(db: &dyn QueryGroup, x: u32) -> Option<u32> {
+    DoubleQuery.in_db(db).peek(&x)
+}
+
+fn quadruple(db: &dyn QueryGroup, x: u32) -> u32 {
+    db.double(x) * 2
+}
+
+#[salsa::database(QueryGroupStorage)]
+#[derive(Default)]
+struct Database {
+    storage: salsa::Storage<Self>,
+}
+
+impl salsa::Database for Database {}
+
+#[test]
+fn peek_derived() {
+    let mut db = Database::default();
+    db.set_input(1).to(1);
+
+    assert_eq!(DoubleQuery.in_db(&db).peek(&1), None);
+    assert_eq!(db.double(1), 2);
+    assert_eq!(DoubleQuery.in_db(&db).peek(&1), Some(2));
+
+    // In a new revision the old value is not returned, even though it
+    // would still be valid, until the query is called again.
+    db.set_input(2).to(2);
+    assert_eq!(DoubleQuery.in_db(&db).peek(&1), None);
+    assert_eq!(db.double(1), 2);
+    assert_eq!(DoubleQuery.in_db(&db).peek(&1), Some(2));
+}
+
+#[test]
+fn peek_does_not_record_dependency() {
+    let mut db = Database::default();
+    db.set_input(1).to(1);
+    assert_eq!(db.double(1), 2);
+    assert_eq!(db.peek_double(1), Some(2));
+
+    // Had `peek_double` recorded a read of `double(1)`, this change
+    // would force it to re-execute (and return `None`, as `double(1)`
+    // is not yet recomputed in the new revision).
+    db.set_input(1).to(5);
+    assert_eq!(db.peek_double(1), Some(2));
+}
+
+#[test]
+fn peek_input() {
+    let mut db = Database::default();
+    assert_eq!(InputQuery.in_db(&db).peek(&1), None);
+    db.set_input(1).to(1);
+    assert_eq!(InputQuery.in_db(&db).peek(&1), Some(1));
+}
+
+#[test]
+fn peek_interned() {
+    let db = Database::default();
+    assert_eq!(InternQuery.in_db(&db).peek(&"a".to_string()), None);
+    let id = db.intern("a".to_string());
+    assert_eq!(InternQuery.in_db(&db).peek(&"a".to_string()), Some(id));
+    assert_eq!(
+        InternLookupQuery.in_db(&db).peek(&id),
+        Some("a".to_string())
+    );
+}
+
+#[test]
+fn changed_at() {
+    let mut db = Database::default();
+    db.set_input(1).to(1);
+    let r1 = db.salsa_runtime().current_revision();
+    assert_eq!(DoubleQuery.in_db(&db).changed_at(&1), None);
+    assert_eq!(db.double(1), 2);
+    assert_eq!(DoubleQuery.in_db(&db).changed_at(&1), Some(r1));
+
+    // Not verified in the new revision yet, so it is unknown.
+    db.set_input(1).to(1);
+    let r2 = db.salsa_runtime().current_revision();
+    assert_eq!(InputQuery.in_db(&db).changed_at(&1), Some(r2));
+    assert_eq!(DoubleQuery.in_db(&db).changed_at(&1), None);
+    assert!(DoubleQuery.in_db(&db).changed_since(&1, r1));
+
+    // Re-executing `double(1)` gives the same value, so it is backdated.
+    assert_eq!(db.double(1), 2);
+    assert_eq!(DoubleQuery.in_db(&db).changed_at(&1), Some(r1));
+    assert!(!DoubleQuery.in_db(&db).changed_since(&1, r1));
+
+    db.intern("a".to_string());
+    assert_eq!(
+        InternQuery.in_db(&db).changed_at(&"a".to_string()),
+        Some(r2)
+    );
+}
+
+#[test]
+fn verified_entries() {
+    let mut db = Database::default();
+    db.set_input(1).to(1);
+    db.set_input(2).to(2);
+    assert_eq!(db.double(1), 2);
+    assert_eq!(db.double(2), 4);
+
+    let mut entries: Vec<_> = DoubleQuery.in_db(&db).verified_entries().collect();
+    entries.sort();
+    assert_eq!(entries, vec![(1, 2), (2, 4)]);
+
+    // In a new revision, only the values verified again are included.
+    db.set_input(2).to(3);
+    assert_eq!(DoubleQuery.in_db(&db).verified_entries().count(), 0);
+    assert_eq!(db.double(2), 6);
+    let entries: Vec<_> = DoubleQuery.in_db(&db).verified_entries().collect();
+    assert_eq!(entries, vec![(2, 6)]);
+}
+
+#[test]
+fn memoized_keys() {
+    let mut db = Database::default();
+    db.set_input(1).to(1);
+    db.set_input(2).to(2);
+    assert_eq!(db.double(1), 2);
+    assert_eq!(db.double(2), 4);
+
+    let mut keys: Vec<_> = DoubleQuery.in_db(&db).memoized_keys().collect();
+    keys.sort();
+    assert_eq!(keys, vec![1, 2]);
+
+    // Stale keys are left out until they are verified again.
+    db.set_input(2).to(3);
+    assert_eq!(DoubleQuery.in_db(&db).memoized_keys().count(), 0);
+    assert_eq!(db.double(1), 2);
+    let keys: Vec<_> = DoubleQuery.in_db(&db).memoized_keys().collect();
+    assert_eq!(keys, vec![1]);
+}
+
+#[test]
+fn prime_derived() {
+    let mut db = Database::default();
+    db.set_input(1).to(1);
+    db.set_input(2).to(2);
+    db.double(2);
+    let inputs = DoubleQuery.in_db(&db).dependencies(&2).unwrap();
+
+    // A wrong value, to show that the primed value is used as is.
+    DoubleQuery.in_db_mut(&mut db).prime(1, 100, inputs);
+    assert_eq!(DoubleQuery.in_db(&db).peek(&1), Some(100));
+    assert_eq!(db.double(1), 100);
+
+    // `double(1)` was primed as reading `input(2)`, so it is only
+    // re-executed once that changes.
+    db.set_input(1).to(5);
+    assert_eq!(db.double(1), 100);
+    db.set_input(2).to(3);
+    assert_eq!(db.double(1), 10);
+}
+
+#[test]
+fn prime_invalidates_dependents() {
+    let mut db = Database::default();
+    db.set_input(1).to(1);
+    assert_eq!(db.quadruple(1), 4);
+
+    // `quadruple(1)` was verified in the current revision, but priming
+    // `double(1)` starts a new one, so it is re-executed.
+    DoubleQuery.in_db_mut(&mut db).prime(1, 100, vec![]);
+    assert_eq!(db.quadruple(1), 200);
+}
@@ -0,0 +1,58 @@
+//! Test the generated `foo_fn` methods, which return a query as a
+//! closure.
+
+#[salsa::query_group(QueryGroupStorage)]
+trait QueryGroup: salsa::Database {
+    #[salsa::input]
+    fn input(&self, key: u32) -> u32;
+
+    fn double(&self, key: u32) -> u32;
+
+    fn sum(&self, a: u32, b: u32) -> u32;
+
+    fn total(&self, keys: Vec<u32>) -> u32;
+}
+
+fn double(db: &dyn QueryGroup, key: u32) -> u32 {
+    db.input(key) * 2
+}
+
+fn sum(db: &dyn QueryGroup, a: u32, b: u32) -> u32 {
+    db.input(a) + db.input(b)
+}
+
+fn total(db: &dyn QueryGroup, keys: Vec<u32>) -> u32 {
+    keys.into_iter().map(db.double_fn()).sum()
+}
+
+#[salsa::database(QueryGroupStorage)]
+#[derive(Default)]
+struct Database {
+    storage: salsa::Storage<Self>,
+}
+
+impl salsa::Database for Database {}
+
+#[test]
+fn query_as_closure() {
+    let mut db = Database::default();
+    db.set_input(1).to(10);
+    db.set_input(2).to(20);
+
+    let doubled: Vec<u32> = vec![1, 2].into_iter().map(db.double_fn()).collect();
+    assert_eq!(doubled, vec![20, 40]);
+
+    let sum = db.sum_fn();
+    assert_eq!(sum(1, 2), 30);
+}
+
+#[test]
+fn closure_records_dependencies() {
+    let mut db = Database::default();
+    db.set_input(1).to(10);
+    db.set_input(2).to(20);
+    assert_eq!(db.total(vec![1, 2]), 60);
+
+    db.set_input(2).to(25);
+    assert_eq!(db.total(vec![1, 2]), 70);
+}
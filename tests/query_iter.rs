@@ -0,0 +1,58 @@
+//! Test `#[salsa::iter]`, which generates `foo_iter` methods.
+
+use std::sync::Arc;
+
+#[salsa::query_group(QueryGroupStorage)]
+trait QueryGroup: salsa::Database {
+    #[salsa::input]
+    fn len(&self) -> u32;
+
+    #[salsa::iter]
+    fn squares(&self) -> Arc<Vec<u32>>;
+
+    fn sum_of_squares(&self) -> u32;
+}
+
+fn squares(db: &dyn QueryGroup) -> Arc<Vec<u32>> {
+    Arc::new((0..db.len()).map(|i| i * i).collect())
+}
+
+fn sum_of_squares(db: &dyn QueryGroup) -> u32 {
+    db.squares_iter().sum()
+}
+
+#[salsa::database(QueryGroupStorage)]
+#[derive(Default)]
+struct Database {
+    storage: salsa::Storage<Self>,
+}
+
+impl salsa::Database for Database {}
+
+#[test]
+fn iterates_without_cloning() {
+    let mut db = Database::default();
+    db.set_len().to(4);
+
+    let mut iter = db.squares_iter();
+    assert_eq!(iter.len(), 4);
+    assert_eq!(iter.next(), Some(0));
+    assert_eq!(iter.next_back(), Some(9));
+    assert_eq!(iter.as_slice(), &[1, 4]);
+    assert_eq!(iter.collect::<Vec<_>>(), vec![1, 4]);
+
+    // Both iterators share the memoized `Vec`.
+    let first = db.squares_iter();
+    let second = db.squares_iter();
+    assert!(std::ptr::eq(first.as_slice(), second.as_slice()));
+}
+
+#[test]
+fn records_dependency() {
+    let mut db = Database::default();
+    db.set_len().to(3);
+    assert_eq!(db.sum_of_squares(), 5);
+
+    db.set_len().to(4);
+    assert_eq!(db.sum_of_squares(), 14);
+}
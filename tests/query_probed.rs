@@ -0,0 +1,57 @@
+//! Test the generated `foo_probed` methods, which tell how the value of
+//! a query was obtained.
+
+use salsa::{CacheOutcome, Database as _, Durability};
+
+#[salsa::query_group(QueryGroupStorage)]
+trait QueryGroup: salsa::Database {
+    #[salsa::input]
+    fn input(&self, key: u32) -> u32;
+
+    fn double(&self, key: u32) -> u32;
+
+    #[salsa::dependencies]
+    fn triple(&self, key: u32) -> u32;
+}
+
+fn double(db: &dyn QueryGroup, key: u32) -> u32 {
+    db.input(key) * 2
+}
+
+fn triple(db: &dyn QueryGroup, key: u32) -> u32 {
+    db.input(key) * 3
+}
+
+#[salsa::database(QueryGroupStorage)]
+#[derive(Default)]
+struct Database {
+    storage: salsa::Storage<Self>,
+}
+
+impl salsa::Database for Database {}
+
+#[test]
+fn probed_outcomes() {
+    let mut db = Database::default();
+    db.set_input(1).to(10);
+
+    assert_eq!(db.double_probed(1), (20, CacheOutcome::Computed));
+    assert_eq!(db.double_probed(1), (20, CacheOutcome::Hit));
+
+    db.salsa_runtime_mut().synthetic_write(Durability::LOW);
+    assert_eq!(db.double_probed(1), (20, CacheOutcome::Validated));
+    assert_eq!(db.double_probed(1), (20, CacheOutcome::Hit));
+
+    db.set_input(1).to(11);
+    assert_eq!(db.double_probed(1), (22, CacheOutcome::Computed));
+}
+
+#[test]
+fn probed_dependencies_query() {
+    let mut db = Database::default();
+    db.set_input(1).to(10);
+
+    // Values of `#[salsa::dependencies]` queries are not memoized.
+    assert_eq!(db.triple_probed(1), (30, CacheOutcome::Computed));
+    assert_eq!(db.triple_probed(1), (30, CacheOutcome::Computed));
+}
@@ -0,0 +1,105 @@
+//! Test the opt-in per-query execution statistics.
+
+use salsa::{Database as _, ParallelDatabase, Snapshot};
+use std::collections::HashMap;
+
+#[salsa::query_group(QueryGroupStorage)]
+trait QueryGroup: salsa::Database {
+    #[salsa::input]
+    fn input(&self, x: u32) -> u32;
+
+    fn double(&self, x: u32) -> u32;
+
+    fn plus_one(&self, x: u32) -> u32;
+}
+
+fn double(db: &dyn QueryGroup, x: u32) -> u32 {
+    db.input(x) * 2
+}
+
+fn plus_one(db: &dyn QueryGroup, x: u32) -> u32 {
+    db.input(x) + 1
+}
+
+#[salsa::database(QueryGroupStorage)]
+#[derive(Default)]
+struct Database {
+    storage: salsa::Storage<Self>,
+}
+
+impl salsa::Database for Database {}
+
+impl ParallelDatabase for Database {
+    fn snapshot(&self) -> Snapshot<Self> {
+        Snapshot::new(Database {
+            storage: self.storage.snapshot(),
+        })
+    }
+}
+
+/// Returns the collected stats as `(executions, cache_hits)`, keyed by
+/// the debug output of the query key.
+fn stats(db: &Database) -> HashMap<String, (usize, usize)> {
+    db.salsa_runtime()
+        .query_stats()
+        .into_iter()
+        .map(|(key, stat)| {
+            (
+                format!("{:?}", key.debug(db)),
+                (stat.executions, stat.cache_hits),
+            )
+        })
+        .collect()
+}
+
+#[test]
+fn disabled_by_default() {
+    let mut db = Database::default();
+    db.set_input(1).to(1);
+    db.double(1);
+    db.double(1);
+    assert!(db.salsa_runtime().query_stats().is_empty());
+}
+
+#[test]
+fn counts_executions_and_hits() {
+    let mut db = Database::default();
+    db.salsa_runtime().set_query_stats_enabled(true);
+
+    db.set_input(1).to(1);
+    db.set_input(2).to(2);
+    assert_eq!(db.double(1), 2);
+    assert_eq!(db.double(1), 2);
+    assert_eq!(db.plus_one(2), 3);
+    assert_eq!(
+        stats(&db),
+        vec![
+            ("double(1)".to_string(), (1, 1)),
+            ("plus_one(2)".to_string(), (1, 0)),
+        ]
+        .into_iter()
+        .collect()
+    );
+
+    // Changing an input that `double(1)` does not depend on lets it be
+    // validated without re-executing.
+    db.set_input(2).to(4);
+    assert_eq!(db.double(1), 2);
+    assert_eq!(stats(&db)["double(1)"], (1, 2));
+
+    // Changing its input forces re-execution.
+    db.set_input(1).to(3);
+    assert_eq!(db.double(1), 6);
+    assert_eq!(stats(&db)["double(1)"], (2, 2));
+}
+
+#[test]
+fn shared_with_snapshots() {
+    let mut db = Database::default();
+    db.salsa_runtime().set_query_stats_enabled(true);
+    db.set_input(1).to(1);
+
+    let snapshot = db.snapshot();
+    snapshot.double(1);
+    assert_eq!(stats(&db)["double(1)"], (1, 0));
+}
@@ -0,0 +1,72 @@
+//! Test `Runtime::set_query_timeout`.
+
+use salsa::Database as _;
+use std::time::{Duration, Instant};
+
+#[salsa::query_group(QueryGroupStorage)]
+trait QueryGroup: salsa::Database {
+    fn spin(&self) -> Result<(), salsa::Canceled>;
+
+    fn outer(&self) -> Result<(), salsa::Canceled>;
+
+    fn quick(&self) -> Result<(), salsa::Canceled>;
+}
+
+/// Loops until canceled.
+fn spin(db: &dyn QueryGroup) -> Result<(), salsa::Canceled> {
+    loop {
+        db.salsa_runtime().check_canceled()?;
+        std::thread::sleep(Duration::from_millis(1));
+    }
+}
+
+fn outer(db: &dyn QueryGroup) -> Result<(), salsa::Canceled> {
+    db.spin()
+}
+
+fn quick(db: &dyn QueryGroup) -> Result<(), salsa::Canceled> {
+    db.salsa_runtime().check_canceled()
+}
+
+#[salsa::database(QueryGroupStorage)]
+#[derive(Default)]
+struct Database {
+    storage: salsa::Storage<Self>,
+}
+
+impl salsa::Database for Database {}
+
+#[test]
+fn timeout_cancels_query() {
+    let db = Database::default();
+    db.salsa_runtime()
+        .set_query_timeout(Some(Duration::from_millis(20)));
+    assert_eq!(db.outer(), Err(salsa::Canceled));
+
+    // The budget starts anew with each top-level query.
+    std::thread::sleep(Duration::from_millis(30));
+    assert_eq!(db.quick(), Ok(()));
+
+    // Outside of a query, nothing is canceled.
+    assert!(!db.salsa_runtime().is_current_revision_canceled());
+}
+
+#[test]
+fn timed_out_query_reruns_in_new_revision() {
+    let timeout = Duration::from_millis(20);
+    let mut db = Database::default();
+    db.salsa_runtime().set_query_timeout(Some(timeout));
+    assert_eq!(db.spin(), Err(salsa::Canceled));
+
+    // The canceled result is reused within the revision...
+    let start = Instant::now();
+    assert_eq!(db.spin(), Err(salsa::Canceled));
+    assert!(start.elapsed() < timeout);
+
+    // ...but `spin` is executed again in the next one.
+    db.salsa_runtime_mut()
+        .synthetic_write(salsa::Durability::LOW);
+    let start = Instant::now();
+    assert_eq!(db.spin(), Err(salsa::Canceled));
+    assert!(start.elapsed() >= timeout);
+}
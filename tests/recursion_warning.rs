@@ -0,0 +1,78 @@
+//! Test `Runtime::set_recursion_warning_depth`.
+
+use salsa::Database as _;
+use std::cell::RefCell;
+
+#[salsa::query_group(QueryGroupStorage)]
+trait QueryGroup: salsa::Database {
+    fn depth(&self, n: u32) -> u32;
+
+    fn outer(&self, n: u32) -> u32;
+}
+
+fn depth(db: &dyn QueryGroup, n: u32) -> u32 {
+    if n == 0 {
+        0
+    } else {
+        db.depth(n - 1) + 1
+    }
+}
+
+fn outer(db: &dyn QueryGroup, n: u32) -> u32 {
+    if n == 0 {
+        db.depth(2)
+    } else {
+        db.outer(n - 1) + 1
+    }
+}
+
+#[salsa::database(QueryGroupStorage)]
+#[derive(Default)]
+struct Database {
+    storage: salsa::Storage<Self>,
+    warnings: RefCell<Vec<String>>,
+}
+
+impl salsa::Database for Database {
+    fn salsa_event(&self, event: salsa::Event) {
+        if let salsa::EventKind::DeepRecursion {
+            database_key,
+            depth,
+        } = event.kind
+        {
+            let warning = format!("{:?} {}", database_key.debug(self), depth);
+            self.warnings.borrow_mut().push(warning);
+        }
+    }
+}
+
+#[test]
+fn warns_once_at_threshold() {
+    let db = Database::default();
+    db.salsa_runtime().set_recursion_warning_depth(Some(5));
+    assert_eq!(db.depth(3), 3);
+    assert!(db.warnings.borrow().is_empty());
+
+    assert_eq!(db.depth(20), 20);
+    assert_eq!(*db.warnings.borrow(), vec!["depth(16) 5"]);
+}
+
+#[test]
+fn counts_the_same_query_only() {
+    let db = Database::default();
+    db.salsa_runtime().set_recursion_warning_depth(Some(4));
+
+    // Six queries on the stack, but at most three of each.
+    assert_eq!(db.outer(2), 4);
+    assert!(db.warnings.borrow().is_empty());
+
+    assert_eq!(db.depth(10), 10);
+    assert_eq!(*db.warnings.borrow(), vec!["depth(7) 4"]);
+}
+
+#[test]
+fn disabled_by_default() {
+    let db = Database::default();
+    assert_eq!(db.depth(100), 100);
+    assert!(db.warnings.borrow().is_empty());
+}
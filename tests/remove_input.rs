@@ -0,0 +1,71 @@
+//! Test `QueryTableMut::remove` on input queries.
+
+#[salsa::query_group(QueryGroupStorage)]
+trait QueryGroup: salsa::Database {
+    #[salsa::input]
+    fn text(&self, key: u32) -> String;
+
+    #[salsa::input]
+    #[salsa::default = 22]
+    fn opt_level(&self) -> u32;
+
+    fn text_len(&self, key: u32) -> usize;
+
+    fn derived(&self) -> u32;
+}
+
+fn text_len(db: &dyn QueryGroup, key: u32) -> usize {
+    db.text(key).len()
+}
+
+fn derived(db: &dyn QueryGroup) -> u32 {
+    db.opt_level() + 1
+}
+
+#[salsa::database(QueryGroupStorage)]
+#[derive(Default)]
+struct Database {
+    storage: salsa::Storage<Self>,
+}
+
+impl salsa::Database for Database {}
+
+#[test]
+fn remove_returns_value() {
+    let mut db = Database::default();
+    db.set_text(1).to("one".to_string());
+    db.set_text(2).to("two".to_string());
+
+    assert_eq!(
+        TextQuery.in_db_mut(&mut db).remove(1),
+        Some("one".to_string())
+    );
+    assert_eq!(TextQuery.in_db_mut(&mut db).remove(1), None);
+    assert_eq!(TextQuery.in_db_mut(&mut db).remove(3), None);
+    assert_eq!(db.text(2), "two");
+
+    // The key can be set again.
+    db.set_text(1).to("uno".to_string());
+    assert_eq!(db.text_len(1), 3);
+}
+
+#[test]
+#[should_panic(expected = "no value set for TextQuery(1)")]
+fn read_after_remove_panics() {
+    let mut db = Database::default();
+    db.set_text(1).to("one".to_string());
+    assert_eq!(db.text_len(1), 3);
+
+    TextQuery.in_db_mut(&mut db).remove(1);
+    db.text_len(1);
+}
+
+#[test]
+fn remove_restores_default() {
+    let mut db = Database::default();
+    db.set_opt_level().to(3);
+    assert_eq!(db.derived(), 4);
+
+    assert_eq!(OptLevelQuery.in_db_mut(&mut db).remove(()), Some(3));
+    assert_eq!(db.derived(), 23);
+}
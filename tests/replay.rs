@@ -0,0 +1,82 @@
+//! Test recording the inputs set in a database with
+//! `Runtime::start_recording`, and replaying them with
+//! `InputLog::replay_until`.
+
+use salsa::debug::DebugQueryTable;
+use salsa::{Database as _, Durability, Revision};
+
+#[salsa::query_group(FilesStorage)]
+trait Files: salsa::Database {
+    #[salsa::input]
+    fn file_text(&self, file: u32) -> String;
+
+    fn file_len(&self, file: u32) -> usize;
+}
+
+fn file_len(db: &dyn Files, file: u32) -> usize {
+    db.file_text(file).len()
+}
+
+#[salsa::database(FilesStorage)]
+#[derive(Default)]
+struct Database {
+    storage: salsa::Storage<Self>,
+}
+
+impl salsa::Database for Database {}
+
+#[test]
+fn replay_steps_through_revisions() {
+    let mut db = Database::default();
+    db.set_file_text(0).to("not recorded".to_string());
+
+    db.salsa_runtime().start_recording();
+    db.set_file_text(1)
+        .with_durability(Durability::HIGH)
+        .to("a".to_string());
+    FileTextQuery
+        .in_db_mut(&mut db)
+        .set_many(vec![(2, "bb".to_string()), (3, "ccc".to_string())]);
+    let revision = db.salsa_runtime().current_revision();
+    db.set_file_text(1).to("aaaa".to_string());
+    db.salsa_runtime().stop_recording();
+    db.set_file_text(2).to("not recorded".to_string());
+
+    let mut log = db.take_recording();
+    assert!(db.take_recording().is_empty());
+    let recorded: Vec<_> = log
+        .sets()
+        .map(|set| {
+            (
+                set.input,
+                set.durability,
+                Revision::start().distance_to(set.revision),
+            )
+        })
+        .collect();
+    assert_eq!(
+        recorded,
+        vec![
+            ("file_text", Durability::HIGH, Some(2)),
+            ("file_text", Durability::LOW, Some(3)),
+            ("file_text", Durability::LOW, Some(3)),
+            ("file_text", Durability::LOW, Some(4)),
+        ]
+    );
+
+    let mut replayed = Database::default();
+    log.replay_until(&mut replayed, revision).unwrap();
+    assert_eq!(replayed.file_len(1), 1);
+    assert_eq!(replayed.file_len(2), 2);
+    assert_eq!(replayed.file_len(3), 3);
+    assert_eq!(
+        FileTextQuery.in_db(&replayed).durability(1),
+        Durability::HIGH
+    );
+
+    log.replay_until(&mut replayed, db.salsa_runtime().current_revision())
+        .unwrap();
+    assert!(log.is_empty());
+    assert_eq!(replayed.file_len(1), 4);
+    assert_eq!(replayed.file_len(2), 2);
+}
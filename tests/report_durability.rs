@@ -0,0 +1,118 @@
+//! Test `Runtime::report_durability`, which raises the durability of
+//! the active query.
+
+use salsa::debug::DebugQueryTable;
+use salsa::{Database as _, Durability};
+use std::cell::Cell;
+
+#[salsa::query_group(QueryGroupStorage)]
+trait QueryGroup: salsa::Database + AsRef<Cell<usize>> {
+    #[salsa::input]
+    fn text(&self) -> String;
+
+    fn text_len(&self) -> usize;
+
+    fn text_len_untracked(&self) -> usize;
+
+    fn text_len_depends(&self) -> usize;
+}
+
+fn text_len(db: &dyn QueryGroup) -> usize {
+    let executions = db.as_ref();
+    executions.set(executions.get() + 1);
+    db.salsa_runtime().report_durability(Durability::HIGH);
+    db.text().len()
+}
+
+fn text_len_untracked(db: &dyn QueryGroup) -> usize {
+    let executions = db.as_ref();
+    executions.set(executions.get() + 1);
+    db.salsa_runtime().report_durability(Durability::HIGH);
+    db.salsa_runtime().report_untracked_read();
+    db.text().len()
+}
+
+fn text_len_depends(db: &dyn QueryGroup) -> usize {
+    let executions = db.as_ref();
+    executions.set(executions.get() + 1);
+    db.salsa_runtime().report_durability(Durability::HIGH);
+    db.salsa_runtime().depend_on_durability(Durability::MEDIUM);
+    db.text().len()
+}
+
+#[salsa::database(QueryGroupStorage)]
+#[derive(Default)]
+struct Database {
+    storage: salsa::Storage<Self>,
+    executions: Cell<usize>,
+}
+
+impl salsa::Database for Database {}
+
+impl AsRef<Cell<usize>> for Database {
+    fn as_ref(&self) -> &Cell<usize> {
+        &self.executions
+    }
+}
+
+#[test]
+fn reported_durability_skips_revalidation() {
+    let mut db = Database::default();
+    db.set_text().to("hello".to_string());
+    assert_eq!(db.text_len(), 5);
+    assert_eq!(db.executions.get(), 1);
+    assert_eq!(TextLenQuery.in_db(&db).durability(()), Durability::HIGH);
+
+    // Only a `LOW` durability input changed, so `text_len` is reused,
+    // even though the text (and here, its length) changed.
+    db.set_text().to("hello world".to_string());
+    assert_eq!(db.text_len(), 5);
+    assert_eq!(db.executions.get(), 1);
+
+    // The stale result was verified in the current revision, so a
+    // later `HIGH` change does not revive the `LOW` change either.
+    db.salsa_runtime_mut().synthetic_write(Durability::HIGH);
+    assert_eq!(db.text_len(), 5);
+    assert_eq!(db.executions.get(), 1);
+
+    // Here `text_len` is re-validated on the first read after a `HIGH`
+    // change, and it sees that the text changed.
+    db.set_text().to("hi".to_string());
+    db.salsa_runtime_mut().synthetic_write(Durability::HIGH);
+    assert_eq!(db.text_len(), 2);
+    assert_eq!(db.executions.get(), 2);
+}
+
+#[test]
+fn untracked_read_is_not_raised() {
+    let mut db = Database::default();
+    db.set_text().to("hello".to_string());
+    assert_eq!(db.text_len_untracked(), 5);
+    assert_eq!(db.executions.get(), 1);
+    assert_eq!(
+        TextLenUntrackedQuery.in_db(&db).durability(()),
+        Durability::LOW
+    );
+
+    db.set_text().to("hello world".to_string());
+    assert_eq!(db.text_len_untracked(), 11);
+    assert_eq!(db.executions.get(), 2);
+}
+
+#[test]
+fn durability_dependency_is_not_exceeded() {
+    let mut db = Database::default();
+    db.set_text()
+        .with_durability(Durability::HIGH)
+        .to("hello".to_string());
+    assert_eq!(db.text_len_depends(), 5);
+    assert_eq!(db.executions.get(), 1);
+    assert_eq!(
+        TextLenDependsQuery.in_db(&db).durability(()),
+        Durability::MEDIUM
+    );
+
+    db.salsa_runtime_mut().synthetic_write(Durability::MEDIUM);
+    assert_eq!(db.text_len_depends(), 5);
+    assert_eq!(db.executions.get(), 2);
+}
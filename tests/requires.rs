@@ -0,0 +1,95 @@
+//! Test `#[salsa::requires(expr)]`, which checks a precondition of a
+//! query before it is executed.
+
+use std::panic::{catch_unwind, AssertUnwindSafe};
+
+#[salsa::query_group(QueryGroupStorage)]
+trait QueryGroup: salsa::Database {
+    #[salsa::input]
+    fn loaded(&self) -> bool;
+
+    #[salsa::input]
+    fn text(&self, file: u32) -> String;
+
+    #[salsa::requires(db.loaded())]
+    fn length(&self, file: u32) -> usize;
+
+    #[salsa::requires(db.loaded())]
+    #[salsa::requires(db.text(1).len() < db.text(2).len())]
+    fn growth(&self) -> usize;
+}
+
+fn length(db: &dyn QueryGroup, file: u32) -> usize {
+    db.text(file).len()
+}
+
+fn growth(db: &dyn QueryGroup) -> usize {
+    db.length(2) - db.length(1)
+}
+
+#[salsa::database(QueryGroupStorage)]
+#[derive(Default)]
+struct Database {
+    storage: salsa::Storage<Self>,
+}
+
+impl salsa::Database for Database {}
+
+fn panic_message(op: impl FnOnce()) -> String {
+    let payload = catch_unwind(AssertUnwindSafe(op)).unwrap_err();
+    match payload.downcast::<String>() {
+        Ok(message) => *message,
+        Err(payload) => payload.downcast_ref::<&str>().unwrap().to_string(),
+    }
+}
+
+#[test]
+fn precondition_holds() {
+    let mut db = Database::default();
+    db.set_loaded().to(true);
+    db.set_text(1).to("a".to_string());
+    db.set_text(2).to("abc".to_string());
+    assert_eq!(db.length(1), 1);
+    assert_eq!(db.growth(), 2);
+}
+
+#[test]
+#[cfg(debug_assertions)]
+fn precondition_violated() {
+    let mut db = Database::default();
+    db.set_loaded().to(false);
+    db.set_text(1).to("abc".to_string());
+    db.set_text(2).to("a".to_string());
+    let message = panic_message(|| {
+        db.length(1);
+    });
+    assert!(
+        message.starts_with("precondition of length(1) does not hold: `db"),
+        "{}",
+        message
+    );
+
+    db.set_loaded().to(true);
+    assert_eq!(db.length(1), 3);
+    let message = panic_message(|| {
+        db.growth();
+    });
+    assert!(
+        message.starts_with("precondition of growth(()) does not hold: `db"),
+        "{}",
+        message
+    );
+}
+
+#[test]
+#[cfg(debug_assertions)]
+fn precondition_is_a_dependency() {
+    let mut db = Database::default();
+    db.set_loaded().to(true);
+    db.set_text(1).to("abc".to_string());
+    assert_eq!(db.length(1), 3);
+
+    // The memoized value is not reused once the precondition fails.
+    db.set_loaded().to(false);
+    assert!(catch_unwind(AssertUnwindSafe(|| db.length(1))).is_err());
+}
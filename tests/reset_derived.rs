@@ -0,0 +1,63 @@
+//! Test `Database::reset_derived`.
+
+use salsa::{Database as _, InternId};
+use std::cell::Cell;
+
+#[salsa::query_group(QueryGroupStorage)]
+trait QueryGroup: salsa::Database + AsRef<Cell<usize>> {
+    #[salsa::input]
+    fn input(&self, key: u32) -> u32;
+
+    #[salsa::interned]
+    fn intern_name(&self, name: String) -> InternId;
+
+    fn double(&self, key: u32) -> u32;
+}
+
+fn double(db: &dyn QueryGroup, key: u32) -> u32 {
+    let executions = db.as_ref();
+    executions.set(executions.get() + 1);
+    db.input(key) * 2
+}
+
+#[salsa::database(QueryGroupStorage)]
+#[derive(Default)]
+struct Database {
+    storage: salsa::Storage<Self>,
+    executions: Cell<usize>,
+}
+
+impl salsa::Database for Database {}
+
+impl AsRef<Cell<usize>> for Database {
+    fn as_ref(&self) -> &Cell<usize> {
+        &self.executions
+    }
+}
+
+#[test]
+fn reset_derived() {
+    let mut db = Database::default();
+    db.set_input(1).to(10);
+    db.set_input(2).to(20);
+    let name = db.intern_name("a".to_string());
+    assert_eq!(db.double(1), 20);
+    assert_eq!(db.double(2), 40);
+    assert_eq!(db.executions.get(), 2);
+
+    let revision = db.salsa_runtime().current_revision();
+    db.reset_derived();
+    assert!(db.salsa_runtime().current_revision() > revision);
+
+    // The derived queries are executed again, but the inputs and
+    // interned values are kept.
+    assert_eq!(db.double(1), 20);
+    assert_eq!(db.double(2), 40);
+    assert_eq!(db.executions.get(), 4);
+    assert_eq!(db.input(1), 10);
+    assert_eq!(db.intern_name("a".to_string()), name);
+
+    // Once recomputed, the values are memoized as usual.
+    assert_eq!(db.double(1), 20);
+    assert_eq!(db.executions.get(), 4);
+}
@@ -0,0 +1,85 @@
+//! Test `Event::resolve`, which renders the keys in an event.
+
+use std::cell::RefCell;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+struct FileId(u32);
+
+#[salsa::query_group(ParseStorage)]
+trait ParseDatabase: salsa::Database {
+    #[salsa::input]
+    fn file_text(&self, file: FileId) -> String;
+
+    fn parse_range(&self, file: FileId, start: usize, end: usize) -> usize;
+}
+
+fn parse_range(db: &dyn ParseDatabase, file: FileId, start: usize, end: usize) -> usize {
+    db.file_text(file)[start..end].len()
+}
+
+#[salsa::database(ParseStorage)]
+#[derive(Default)]
+struct Database {
+    storage: salsa::Storage<Self>,
+    events: RefCell<Vec<salsa::ResolvedEvent>>,
+}
+
+impl salsa::Database for Database {
+    fn salsa_event(&self, event: salsa::Event) {
+        self.events.borrow_mut().push(event.resolve(self));
+    }
+}
+
+#[test]
+fn resolved_events_name_queries_and_keys() {
+    let mut db = Database::default();
+    db.set_file_text(FileId(3)).to("hello world".to_string());
+    db.events.borrow_mut().clear();
+    db.parse_range(FileId(3), 0, 5);
+
+    let events = db.events.borrow();
+    let key = match &events[0].kind {
+        salsa::ResolvedEventKind::WillExecute { database_key } => database_key,
+        kind => panic!("unexpected event {:?}", kind),
+    };
+    assert_eq!(key.query_name, "parse_range");
+    assert_eq!(key.key, "(FileId(3), 0, 5)");
+    assert_eq!(key.to_string(), "parse_range((FileId(3), 0, 5))");
+    assert_eq!(events[0].kind.database_key(), Some(key));
+}
+
+#[test]
+fn set_input_events() {
+    let mut db = Database::default();
+    db.set_file_text(FileId(1)).to("a".to_string());
+    FileTextQuery.in_db_mut(&mut db).set_many(vec![
+        (FileId(1), "c".to_string()),
+        (FileId(2), "b".to_string()),
+    ]);
+    db.set_file_text(FileId(3))
+        .with_durability(salsa::Durability::HIGH)
+        .to("d".to_string());
+
+    let events: Vec<String> = db
+        .events
+        .borrow()
+        .iter()
+        .map(|event| match &event.kind {
+            salsa::ResolvedEventKind::DidSetInput {
+                database_key,
+                durability,
+                revision,
+            } => format!("{} {:?} {:?}", database_key, durability, revision),
+            kind => panic!("unexpected event {:?}", kind),
+        })
+        .collect();
+    assert_eq!(
+        events,
+        vec![
+            "file_text(FileId(1)) Durability(0) R2",
+            "file_text(FileId(1)) Durability(0) R3",
+            "file_text(FileId(2)) Durability(0) R3",
+            "file_text(FileId(3)) Durability(7) R4",
+        ]
+    );
+}
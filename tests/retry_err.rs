@@ -0,0 +1,69 @@
+//! Test `#[salsa::retry_err]`, which re-executes queries whose memoized
+//! result is an `Err` in every new revision.
+
+use salsa::{Database as _, Durability};
+use std::cell::Cell;
+
+#[salsa::query_group(QueryGroupStorage)]
+trait QueryGroup: salsa::Database + AsRef<Cell<usize>> {
+    #[salsa::input]
+    fn input(&self, key: u32) -> u32;
+
+    #[salsa::retry_err]
+    fn checked(&self, key: u32) -> Result<u32, String>;
+}
+
+fn checked(db: &dyn QueryGroup, key: u32) -> Result<u32, String> {
+    let executions = db.as_ref();
+    executions.set(executions.get() + 1);
+    let value = db.input(key);
+    if value == 0 {
+        Err(format!("input {} is zero", key))
+    } else {
+        Ok(value)
+    }
+}
+
+#[salsa::database(QueryGroupStorage)]
+#[derive(Default)]
+struct Database {
+    storage: salsa::Storage<Self>,
+    executions: Cell<usize>,
+}
+
+impl salsa::Database for Database {}
+
+impl AsRef<Cell<usize>> for Database {
+    fn as_ref(&self) -> &Cell<usize> {
+        &self.executions
+    }
+}
+
+#[test]
+fn err_is_retried_in_new_revisions() {
+    let mut db = Database::default();
+    db.set_input(1).to(0);
+
+    assert!(db.checked(1).is_err());
+    assert!(db.checked(1).is_err());
+    assert_eq!(db.executions.get(), 1);
+
+    db.salsa_runtime_mut().synthetic_write(Durability::LOW);
+    assert!(db.checked(1).is_err());
+    assert_eq!(db.executions.get(), 2);
+}
+
+#[test]
+fn ok_is_memoized() {
+    let mut db = Database::default();
+    db.set_input(1).to(0);
+    assert!(db.checked(1).is_err());
+
+    db.set_input(1).to(5);
+    assert_eq!(db.checked(1), Ok(5));
+    assert_eq!(db.executions.get(), 2);
+
+    db.salsa_runtime_mut().synthetic_write(Durability::LOW);
+    assert_eq!(db.checked(1), Ok(5));
+    assert_eq!(db.executions.get(), 2);
+}
@@ -0,0 +1,56 @@
+//! Test `Runtime::current_revision`.
+
+use salsa::Database as _;
+
+#[salsa::query_group(QueryGroupStorage)]
+trait QueryGroup: salsa::Database {
+    #[salsa::input]
+    fn input(&self, x: u32) -> u32;
+
+    fn double(&self, x: u32) -> u32;
+}
+
+fn double(db: &dyn QueryGroup, x: u32) -> u32 {
+    db.input(x) * 2
+}
+
+#[salsa::database(QueryGroupStorage)]
+#[derive(Default)]
+struct Database {
+    storage: salsa::Storage<Self>,
+}
+
+impl salsa::Database for Database {}
+
+#[test]
+fn current_revision() {
+    let mut db = Database::default();
+    let start = db.salsa_runtime().current_revision();
+    assert_eq!(start.to_string(), "R1");
+
+    // Executing queries does not change the revision...
+    db.set_input(1).to(1);
+    let revision = db.salsa_runtime().current_revision();
+    assert!(revision > start);
+    assert_eq!(db.double(1), 2);
+    assert_eq!(db.salsa_runtime().current_revision(), revision);
+
+    // ...but setting inputs does.
+    db.set_input(1).to(2);
+    assert!(db.salsa_runtime().current_revision() > revision);
+    assert_eq!(db.salsa_runtime().current_revision().to_string(), "R3");
+}
+
+#[test]
+fn distance_to() {
+    let mut db = Database::default();
+    let start = db.salsa_runtime().current_revision();
+    assert_eq!(start, salsa::Revision::start());
+
+    db.set_input(1).to(1);
+    db.set_input(1).to(2);
+    let now = db.salsa_runtime().current_revision();
+    assert_eq!(start.distance_to(now), Some(2));
+    assert_eq!(now.distance_to(start), None);
+    assert_eq!(now.distance_to(now), Some(0));
+}
@@ -0,0 +1,25 @@
+//! Test the `serde` impls for `InternId`.
+#![cfg(feature = "serde")]
+
+use salsa::InternId;
+
+#[test]
+fn serialize_as_u32() {
+    let intern_id = InternId::from(22_u32);
+    assert_eq!(serde_json::to_string(&intern_id).unwrap(), "22");
+}
+
+#[test]
+fn round_trip() {
+    let intern_id = InternId::from(22_u32);
+    let text = serde_json::to_string(&intern_id).unwrap();
+    let intern_id2: InternId = serde_json::from_str(&text).unwrap();
+    assert_eq!(intern_id, intern_id2);
+}
+
+#[test]
+fn deserialize_out_of_range() {
+    let text = InternId::MAX.to_string();
+    let result: Result<InternId, _> = serde_json::from_str(&text);
+    assert!(result.is_err());
+}
@@ -0,0 +1,70 @@
+//! Test `Database::set_input_by_name`, which sets inputs given their
+//! names and type-erased keys and values.
+
+use salsa::{Database as _, SetInputError};
+
+#[salsa::query_group(FilesStorage)]
+trait Files: salsa::Database {
+    #[salsa::input]
+    fn file_text(&self, file: u32) -> String;
+
+    fn file_len(&self, file: u32) -> usize;
+}
+
+fn file_len(db: &dyn Files, file: u32) -> usize {
+    db.file_text(file).len()
+}
+
+#[salsa::query_group(OptionsStorage)]
+trait Options: salsa::Database {
+    #[salsa::input]
+    fn option(&self, section: String, name: String) -> bool;
+}
+
+#[salsa::database(FilesStorage, OptionsStorage)]
+#[derive(Default)]
+struct Database {
+    storage: salsa::Storage<Self>,
+}
+
+impl salsa::Database for Database {}
+
+#[test]
+fn sets_inputs_of_each_group() {
+    let mut db = Database::default();
+    db.set_input_by_name("file_text", Box::new(1_u32), Box::new("abc".to_string()))
+        .unwrap();
+    db.set_input_by_name(
+        "option",
+        Box::new(("lints".to_string(), "strict".to_string())),
+        Box::new(true),
+    )
+    .unwrap();
+    assert_eq!(db.file_len(1), 3);
+    assert!(db.option("lints".to_string(), "strict".to_string()));
+
+    db.set_input_by_name("file_text", Box::new(1_u32), Box::new("abcd".to_string()))
+        .unwrap();
+    assert_eq!(db.file_len(1), 4);
+}
+
+#[test]
+fn errors() {
+    let mut db = Database::default();
+    assert_eq!(
+        db.set_input_by_name("file_len", Box::new(1_u32), Box::new(3_usize)),
+        Err(SetInputError::NoSuchInput {
+            name: "file_len".to_string()
+        })
+    );
+    assert_eq!(
+        db.set_input_by_name("file_text", Box::new(1_u64), Box::new("abc".to_string())),
+        Err(SetInputError::WrongKeyType { input: "file_text" })
+    );
+    let error = db
+        .set_input_by_name("file_text", Box::new(1_u32), Box::new("abc"))
+        .unwrap_err();
+    assert_eq!(error, SetInputError::WrongValueType { input: "file_text" });
+    assert_eq!(error.to_string(), "wrong value type for input `file_text`");
+    assert_eq!(db.try_file_text(1), None);
+}
@@ -0,0 +1,43 @@
+//! Test `QueryTableMut::set_interned`, which interns a key and sets an
+//! input for it in one call.
+
+use salsa::Database as _;
+
+salsa::intern_id!(pub struct FileId);
+
+#[salsa::query_group(FilesStorage)]
+trait Files: salsa::Database {
+    #[salsa::interned]
+    fn intern_path(&self, path: String) -> FileId;
+
+    #[salsa::input]
+    fn file_text(&self, file: FileId) -> String;
+}
+
+#[salsa::database(FilesStorage)]
+#[derive(Default)]
+struct Database {
+    storage: salsa::Storage<Self>,
+}
+
+impl salsa::Database for Database {}
+
+#[test]
+fn interns_and_sets() {
+    let mut db = Database::default();
+    assert_eq!(db.salsa_runtime().current_revision().to_string(), "R1");
+
+    let file = FileTextQuery
+        .in_db_mut(&mut db)
+        .set_interned::<InternPathQuery>("main.rs".to_string(), "fn main() {}".to_string());
+    assert_eq!(db.intern_path("main.rs".to_string()), file);
+    assert_eq!(db.file_text(file), "fn main() {}");
+    assert_eq!(db.salsa_runtime().current_revision().to_string(), "R2");
+
+    // Setting a path that is interned already reuses its id.
+    let again = FileTextQuery
+        .in_db_mut(&mut db)
+        .set_interned::<InternPathQuery>("main.rs".to_string(), "".to_string());
+    assert_eq!(again, file);
+    assert_eq!(db.file_text(file), "");
+}
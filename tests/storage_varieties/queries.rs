@@ -6,6 +6,9 @@ pub(crate) trait Counter: salsa::Database {
 pub(crate) trait Database: Counter {
     fn memoized(&self) -> usize;
     fn volatile(&self) -> usize;
+    #[salsa::volatile]
+    fn volatile_attr(&self) -> usize;
+    fn memoized_attr(&self) -> usize;
 }
 
 /// Because this query is memoized, we only increment the counter
@@ -20,3 +23,13 @@ fn volatile(db: &dyn Database) -> usize {
     db.salsa_runtime().report_untracked_read();
     db.increment()
 }
+
+/// Like `volatile`, but using the `#[salsa::volatile]` attribute
+/// rather than reporting the untracked read manually.
+fn volatile_attr(db: &dyn Database) -> usize {
+    db.increment()
+}
+
+fn memoized_attr(db: &dyn Database) -> usize {
+    db.volatile_attr()
+}
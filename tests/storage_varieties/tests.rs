@@ -47,3 +47,18 @@ fn intermingled() {
     assert_eq!(v4 + 1, v5);
     assert_eq!(v5, v6);
 }
+
+#[test]
+fn volatile_attr() {
+    let mut db = DatabaseImpl::default();
+    let v1 = db.volatile_attr();
+    let v2 = db.memoized_attr(); // cached
+    assert_eq!(v1, v2);
+
+    db.salsa_runtime_mut().synthetic_write(Durability::LOW); // clears volatile caches
+
+    let v3 = db.memoized_attr(); // re-executes volatile_attr
+    let v4 = db.volatile_attr(); // cached
+    assert_eq!(v1 + 1, v3);
+    assert_eq!(v3, v4);
+}
@@ -0,0 +1,117 @@
+//! Test `Runtime::set_strict`, which executes queries twice to detect
+//! reads of state that salsa does not track.
+
+use salsa::Database as _;
+use std::cell::Cell;
+
+#[salsa::query_group(QueryGroupStorage)]
+trait QueryGroup: salsa::Database + AsRef<Cell<u32>> {
+    #[salsa::input]
+    fn input(&self) -> u32;
+
+    fn tracked(&self) -> u32;
+
+    fn sneaky(&self) -> u32;
+
+    fn reported(&self) -> u32;
+
+    #[salsa::no_eq]
+    fn tracked_no_eq(&self) -> u32;
+}
+
+fn tracked(db: &dyn QueryGroup) -> u32 {
+    db.input() * 2
+}
+
+fn tracked_no_eq(db: &dyn QueryGroup) -> u32 {
+    db.input() * 2
+}
+
+/// Reads (and bumps) a counter that salsa does not know about.
+fn sneaky(db: &dyn QueryGroup) -> u32 {
+    let counter = db.as_ref();
+    counter.set(counter.get() + 1);
+    db.input() + counter.get()
+}
+
+fn reported(db: &dyn QueryGroup) -> u32 {
+    db.salsa_runtime().report_untracked_read();
+    sneaky(db)
+}
+
+#[salsa::database(QueryGroupStorage)]
+#[derive(Default)]
+struct Database {
+    storage: salsa::Storage<Self>,
+    counter: Cell<u32>,
+    executions: Cell<usize>,
+}
+
+impl salsa::Database for Database {
+    fn salsa_event(&self, event: salsa::Event) {
+        if let salsa::EventKind::WillExecute { .. } = event.kind {
+            self.executions.set(self.executions.get() + 1);
+        }
+    }
+}
+
+impl AsRef<Cell<u32>> for Database {
+    fn as_ref(&self) -> &Cell<u32> {
+        &self.counter
+    }
+}
+
+fn strict_db() -> Database {
+    let mut db = Database::default();
+    db.set_input().to(10);
+    db.salsa_runtime().set_strict(true);
+    db
+}
+
+#[test]
+fn deterministic_query() {
+    let db = strict_db();
+    assert_eq!(db.tracked(), 20);
+}
+
+#[test]
+fn no_eq_query() {
+    // Values of `no_eq` queries cannot be compared, so they are not
+    // checked.
+    let db = strict_db();
+    assert_eq!(db.tracked_no_eq(), 20);
+}
+
+#[test]
+fn second_execution_is_not_reported() {
+    let db = strict_db();
+    db.salsa_runtime().set_query_stats_enabled(true);
+    assert_eq!(db.tracked(), 20);
+
+    // `tracked` is executed twice, to compare the values, but only
+    // its first execution is visible.
+    assert_eq!(db.executions.get(), 1);
+    let stats = db.salsa_runtime().query_stats();
+    let stat = stats.values().next().unwrap();
+    assert_eq!((stats.len(), stat.executions, stat.cache_hits), (1, 1, 0));
+}
+
+#[test]
+#[should_panic(expected = "SneakyQuery(()): query produced a different value")]
+fn untracked_read() {
+    let db = strict_db();
+    db.sneaky();
+}
+
+#[test]
+fn reported_untracked_read() {
+    let db = strict_db();
+    assert_eq!(db.reported(), 11);
+}
+
+#[test]
+fn not_strict() {
+    let mut db = Database::default();
+    db.set_input().to(10);
+    assert_eq!(db.sneaky(), 11);
+}
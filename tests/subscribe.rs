@@ -0,0 +1,79 @@
+//! Test `QueryTable::subscribe`, which registers callbacks invoked with
+//! the new values of a query.
+
+use salsa::Database as _;
+use std::cell::RefCell;
+use std::sync::{Arc, Mutex};
+
+#[salsa::query_group(QueryGroupStorage)]
+trait QueryGroup: salsa::Database {
+    #[salsa::input]
+    fn input(&self, x: u32) -> u32;
+
+    fn is_small(&self, x: u32) -> bool;
+}
+
+fn is_small(db: &dyn QueryGroup, x: u32) -> bool {
+    db.input(x) < 10
+}
+
+#[salsa::database(QueryGroupStorage)]
+#[derive(Default)]
+struct Database {
+    storage: salsa::Storage<Self>,
+    executed: RefCell<Vec<salsa::DatabaseKeyIndex>>,
+}
+
+impl salsa::Database for Database {
+    fn salsa_event(&self, event: salsa::Event) {
+        if let salsa::EventKind::WillExecute { database_key } = event.kind {
+            self.executed.borrow_mut().push(database_key);
+        }
+    }
+}
+
+fn subscribe(db: &Database, x: u32) -> Arc<Mutex<Vec<bool>>> {
+    let seen = Arc::new(Mutex::new(vec![]));
+    let log = seen.clone();
+    IsSmallQuery
+        .in_db(db)
+        .subscribe(x, move |&value| log.lock().unwrap().push(value));
+    seen
+}
+
+#[test]
+fn notified_of_changes() {
+    let mut db = Database::default();
+    db.set_input(1).to(2);
+    let seen = subscribe(&db, 1);
+
+    assert!(db.is_small(1));
+    assert!(db.is_small(1));
+    assert_eq!(*seen.lock().unwrap(), vec![true]);
+
+    // The value is re-computed, but has not changed.
+    db.set_input(1).to(3);
+    assert!(db.is_small(1));
+    assert_eq!(*seen.lock().unwrap(), vec![true]);
+
+    db.set_input(1).to(30);
+    assert!(!db.is_small(1));
+    assert_eq!(*seen.lock().unwrap(), vec![true, false]);
+}
+
+#[test]
+fn notified_on_validate() {
+    let mut db = Database::default();
+    db.set_input(1).to(2);
+    assert!(db.is_small(1));
+    let seen = subscribe(&db, 1);
+    let key = db.executed.borrow()[0];
+
+    // The memoized value was there before subscribing.
+    db.validate(&[key]);
+    assert_eq!(*seen.lock().unwrap(), vec![]);
+
+    db.set_input(1).to(30);
+    db.validate(&[key]);
+    assert_eq!(*seen.lock().unwrap(), vec![false]);
+}
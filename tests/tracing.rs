@@ -0,0 +1,111 @@
+//! Test the spans emitted with the `tracing` feature.
+#![cfg(feature = "tracing")]
+
+use std::fmt::Debug;
+use std::sync::{Arc, Mutex};
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Event, Metadata};
+
+#[salsa::query_group(QueryGroupStorage)]
+trait QueryGroup: salsa::Database {
+    #[salsa::input]
+    fn input(&self, key: u32) -> u32;
+
+    fn double(&self, key: u32) -> u32;
+
+    fn quadruple(&self, key: u32) -> u32;
+}
+
+fn double(db: &dyn QueryGroup, key: u32) -> u32 {
+    db.input(key) * 2
+}
+
+fn quadruple(db: &dyn QueryGroup, key: u32) -> u32 {
+    db.double(key) * 2
+}
+
+#[salsa::database(QueryGroupStorage)]
+#[derive(Default)]
+struct Database {
+    storage: salsa::Storage<Self>,
+}
+
+impl salsa::Database for Database {}
+
+/// Records each `query` span as it is entered, as its fields and its
+/// depth in the stack of entered spans.
+#[derive(Default)]
+struct Recorder {
+    spans: Mutex<Vec<String>>,
+    entered: Mutex<Vec<(String, usize)>>,
+    depth: Mutex<usize>,
+}
+
+struct Fields(Vec<String>);
+
+impl Visit for Fields {
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        self.0.push(format!("{}={:?}", field.name(), value));
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.push(format!("{}={}", field.name(), value));
+    }
+}
+
+struct Subscriber(Arc<Recorder>);
+
+impl tracing::Subscriber for Subscriber {
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        metadata.name() == "query"
+    }
+
+    fn new_span(&self, span: &Attributes<'_>) -> Id {
+        let mut fields = Fields(vec![]);
+        span.record(&mut fields);
+        let mut spans = self.0.spans.lock().unwrap();
+        spans.push(fields.0.join(" "));
+        Id::from_u64(spans.len() as u64)
+    }
+
+    fn record(&self, _span: &Id, _values: &Record<'_>) {}
+
+    fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+
+    fn event(&self, _event: &Event<'_>) {}
+
+    fn enter(&self, span: &Id) {
+        let fields = self.0.spans.lock().unwrap()[span.into_u64() as usize - 1].clone();
+        let mut depth = self.0.depth.lock().unwrap();
+        self.0.entered.lock().unwrap().push((fields, *depth));
+        *depth += 1;
+    }
+
+    fn exit(&self, _span: &Id) {
+        *self.0.depth.lock().unwrap() -= 1;
+    }
+}
+
+#[test]
+fn nested_query_spans() {
+    let mut db = Database::default();
+    db.set_input(1).to(3);
+
+    let recorder = Arc::new(Recorder::default());
+    tracing::subscriber::with_default(Subscriber(recorder.clone()), || {
+        assert_eq!(db.quadruple(1), 12);
+        // Memoized, so no new span.
+        assert_eq!(db.quadruple(1), 12);
+    });
+
+    let entered = recorder.entered.lock().unwrap().clone();
+    assert_eq!(
+        entered,
+        vec![
+            (format!("name=quadruple key=1 revision=R2"), 0),
+            (format!("name=double key=1 revision=R2"), 1),
+        ]
+    );
+    assert_eq!(*recorder.depth.lock().unwrap(), 0);
+}
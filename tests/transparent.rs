@@ -1,5 +1,7 @@
 //! Test that transparent (uncached) queries work
 
+use std::cell::RefCell;
+
 #[salsa::query_group(QueryGroupStorage)]
 trait QueryGroup {
     #[salsa::input]
@@ -21,19 +23,42 @@ fn get(db: &dyn QueryGroup, x: u32) -> u32 {
 #[derive(Default)]
 struct Database {
     storage: salsa::Storage<Self>,
+    executed: RefCell<Vec<String>>,
 }
 
-impl salsa::Database for Database {}
+impl salsa::Database for Database {
+    fn salsa_event(&self, event: salsa::Event) {
+        if let salsa::EventKind::WillExecute { database_key } = event.kind {
+            let executed = format!("{:?}", database_key.debug(self));
+            self.executed.borrow_mut().push(executed);
+        }
+    }
+}
 
 #[test]
 fn transparent_queries_work() {
     let mut db = Database::default();
 
-    db.set_input(1, 10);
+    db.set_input(1).to(10);
     assert_eq!(db.get(1), 10);
     assert_eq!(db.get(1), 10);
 
-    db.set_input(1, 92);
+    db.set_input(1).to(92);
     assert_eq!(db.get(1), 92);
     assert_eq!(db.get(1), 92);
 }
+
+#[test]
+fn transparent_reads_are_recorded_on_caller() {
+    let mut db = Database::default();
+
+    db.set_input(1).to(10);
+    assert_eq!(db.get(1), 10);
+
+    // Only `get` executes; the read of `input(1)` made by `wrap` is
+    // recorded as a dependency of `get`.
+    assert_eq!(*db.executed.borrow(), vec!["get(1)".to_string()]);
+    let dependencies = GetQuery.in_db(&db).dependencies(&1).unwrap();
+    assert_eq!(dependencies.len(), 1);
+    assert_eq!(format!("{:?}", dependencies[0].debug(&db)), "input(1)");
+}
@@ -0,0 +1,45 @@
+//! Test the `try_foo` methods generated for inputs.
+
+#[salsa::query_group(QueryGroupStorage)]
+trait QueryGroup: salsa::Database {
+    #[salsa::input]
+    fn file_text(&self, file: u32) -> String;
+
+    fn file_len(&self, file: u32) -> Option<usize>;
+}
+
+fn file_len(db: &dyn QueryGroup, file: u32) -> Option<usize> {
+    db.try_file_text(file).map(|text| text.len())
+}
+
+#[salsa::database(QueryGroupStorage)]
+#[derive(Default)]
+struct Database {
+    storage: salsa::Storage<Self>,
+}
+
+impl salsa::Database for Database {}
+
+#[test]
+fn unset_input_is_none() {
+    let mut db = Database::default();
+    assert_eq!(db.try_file_text(1), None);
+    assert_eq!(db.file_len(1), None);
+
+    db.set_file_text(1).to("hello".to_string());
+    assert_eq!(db.try_file_text(1), Some("hello".to_string()));
+    assert_eq!(db.file_len(1), Some(5));
+}
+
+#[test]
+fn removed_input_is_none() {
+    let mut db = Database::default();
+    db.set_file_text(1).to("hello".to_string());
+    assert_eq!(db.file_len(1), Some(5));
+
+    FileTextQuery.in_db_mut(&mut db).remove(1);
+    assert_eq!(db.file_len(1), None);
+
+    db.set_file_text(1).to("hi".to_string());
+    assert_eq!(db.file_len(1), Some(2));
+}
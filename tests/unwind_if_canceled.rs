@@ -0,0 +1,119 @@
+//! Test `Database::unwind_if_canceled` (and `Database::yield_now`) and
+//! the events they report.
+
+use salsa::Database as _;
+use salsa::{ResolvedEvent, ResolvedEventKind};
+use std::cell::RefCell;
+use std::panic::AssertUnwindSafe;
+use std::time::Duration;
+
+#[salsa::query_group(QueryGroupStorage)]
+trait QueryGroup: salsa::Database {
+    fn spin(&self) -> ();
+
+    fn spin_yielding(&self) -> ();
+}
+
+/// Loops until canceled.
+fn spin(db: &dyn QueryGroup) {
+    loop {
+        db.unwind_if_canceled();
+        std::thread::sleep(Duration::from_millis(1));
+    }
+}
+
+/// Loops until canceled, checking with `yield_now`.
+fn spin_yielding(db: &dyn QueryGroup) {
+    loop {
+        db.yield_now();
+        std::thread::sleep(Duration::from_millis(1));
+    }
+}
+
+#[salsa::database(QueryGroupStorage)]
+#[derive(Default)]
+struct Database {
+    storage: salsa::Storage<Self>,
+    events: RefCell<Vec<ResolvedEvent>>,
+}
+
+impl salsa::Database for Database {
+    fn salsa_event(&self, event: salsa::Event) {
+        let event = event.resolve(self);
+        match event.kind {
+            ResolvedEventKind::WillCheckCancellation { .. }
+            | ResolvedEventKind::WasCanceled { .. } => self.events.borrow_mut().push(event),
+            _ => {}
+        }
+    }
+}
+
+#[test]
+fn unwinds_with_canceled() {
+    let db = Database::default();
+    db.salsa_runtime()
+        .set_query_timeout(Some(Duration::from_millis(20)));
+    let payload = std::panic::catch_unwind(AssertUnwindSafe(|| db.spin())).unwrap_err();
+    assert_eq!(
+        payload.downcast_ref::<salsa::Canceled>(),
+        Some(&salsa::Canceled)
+    );
+
+    let events = db.events.borrow();
+    assert!(events.len() > 2);
+    let (last, checks) = events.split_last().unwrap();
+    for event in checks {
+        assert!(
+            matches!(&event.kind, ResolvedEventKind::WillCheckCancellation { .. }),
+            "unexpected event {:?}",
+            event
+        );
+        assert_eq!(event.kind.database_key().unwrap().to_string(), "spin(())");
+    }
+    assert!(
+        matches!(&last.kind, ResolvedEventKind::WasCanceled { .. }),
+        "unexpected event {:?}",
+        last
+    );
+    assert_eq!(last.kind.database_key().unwrap().to_string(), "spin(())");
+}
+
+#[test]
+fn check_outside_of_query() {
+    let db = Database::default();
+    db.unwind_if_canceled();
+
+    let events = db.events.borrow();
+    assert_eq!(events.len(), 1);
+    assert_eq!(
+        events[0].kind,
+        ResolvedEventKind::WillCheckCancellation { database_key: None }
+    );
+}
+
+#[test]
+fn yield_now_only_reports_cancellation() {
+    let db = Database::default();
+    db.yield_now();
+    assert!(db.events.borrow().is_empty());
+
+    db.salsa_runtime()
+        .set_query_timeout(Some(Duration::from_millis(20)));
+    let payload = std::panic::catch_unwind(AssertUnwindSafe(|| db.spin_yielding())).unwrap_err();
+    assert_eq!(
+        payload.downcast_ref::<salsa::Canceled>(),
+        Some(&salsa::Canceled)
+    );
+
+    // Only the check that found the cancellation reports events.
+    let events = db.events.borrow();
+    assert_eq!(events.len(), 2);
+    assert!(matches!(
+        &events[0].kind,
+        ResolvedEventKind::WillCheckCancellation { .. }
+    ));
+    assert!(matches!(
+        &events[1].kind,
+        ResolvedEventKind::WasCanceled { .. }
+    ));
+}
@@ -0,0 +1,55 @@
+//! Test `Database::validate`.
+
+use salsa::Database as _;
+use std::cell::RefCell;
+
+#[salsa::query_group(QueryGroupStorage)]
+trait QueryGroup: salsa::Database {
+    #[salsa::input]
+    fn input(&self, x: u32) -> u32;
+
+    fn double(&self, x: u32) -> u32;
+}
+
+fn double(db: &dyn QueryGroup, x: u32) -> u32 {
+    db.input(x) * 2
+}
+
+#[salsa::database(QueryGroupStorage)]
+#[derive(Default)]
+struct Database {
+    storage: salsa::Storage<Self>,
+    executed: RefCell<Vec<salsa::DatabaseKeyIndex>>,
+}
+
+impl salsa::Database for Database {
+    fn salsa_event(&self, event: salsa::Event) {
+        if let salsa::EventKind::WillExecute { database_key } = event.kind {
+            self.executed.borrow_mut().push(database_key);
+        }
+    }
+}
+
+#[test]
+fn validate_executes_stale_queries() {
+    let mut db = Database::default();
+    db.set_input(1).to(1);
+    db.set_input(2).to(2);
+    db.double(1);
+    db.double(2);
+    let keys = db.executed.take();
+    assert_eq!(keys.len(), 2);
+
+    db.set_input(1).to(10);
+    db.validate(&keys);
+    assert_eq!(db.executed.take(), vec![keys[0]]);
+
+    // The values are up to date now, so reading them executes nothing.
+    assert_eq!(db.double(1), 20);
+    assert_eq!(db.double(2), 4);
+    assert!(db.executed.borrow().is_empty());
+
+    // Validating again does nothing either.
+    db.validate(&keys);
+    assert!(db.executed.borrow().is_empty());
+}
@@ -41,7 +41,7 @@ fn execute() {
     let mut db = DatabaseStruct::default();
 
     // test what happens with inputs:
-    db.set_input(1, 2, 3);
+    db.set_input(1, 2).to(3);
     assert_eq!(db.input(1, 2), 3);
 
     assert_eq!(db.none(), 22);
@@ -49,3 +49,18 @@ fn execute() {
     assert_eq!(db.two(11, 2), 22);
     assert_eq!(db.trailing(24, 2), 22);
 }
+
+#[test]
+fn input_entries_are_independent() {
+    let mut db = DatabaseStruct::default();
+    db.set_input(1, 2).to(3);
+    db.set_input(2, 1)
+        .with_durability(salsa::Durability::HIGH)
+        .to(4);
+    assert_eq!(db.input(1, 2), 3);
+    assert_eq!(db.input(2, 1), 4);
+
+    db.set_input(2, 1).to(5);
+    assert_eq!(db.input(1, 2), 3);
+    assert_eq!(db.input(2, 1), 5);
+}
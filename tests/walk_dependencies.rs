@@ -0,0 +1,121 @@
+//! Test `Database::walk_dependencies`.
+
+use salsa::{Database as _, DatabaseKeyIndex, DependencyVisitor};
+use std::cell::RefCell;
+
+#[salsa::query_group(GraphStorage)]
+trait GraphDatabase: salsa::Database {
+    #[salsa::input]
+    fn input(&self, key: u32) -> u32;
+
+    fn top(&self) -> u32;
+
+    fn middle(&self, key: u32) -> u32;
+}
+
+fn top(db: &dyn GraphDatabase) -> u32 {
+    db.middle(1) + db.middle(2)
+}
+
+fn middle(db: &dyn GraphDatabase, key: u32) -> u32 {
+    db.input(0) + db.input(key)
+}
+
+#[salsa::database(GraphStorage)]
+#[derive(Default)]
+struct Database {
+    storage: salsa::Storage<Self>,
+    executed: RefCell<Vec<DatabaseKeyIndex>>,
+}
+
+impl salsa::Database for Database {
+    fn salsa_event(&self, event: salsa::Event) {
+        if let salsa::EventKind::WillExecute { database_key } = event.kind {
+            self.executed.borrow_mut().push(database_key);
+        }
+    }
+}
+
+/// Records the walk as a list of `enter`/`exit` lines.
+struct Trace<'db> {
+    db: &'db Database,
+    lines: Vec<String>,
+    skip: Option<&'static str>,
+}
+
+impl DependencyVisitor for Trace<'_> {
+    fn enter(&mut self, node: DatabaseKeyIndex) -> bool {
+        let name = format!("{:?}", node.debug(self.db));
+        let walk = self.skip != Some(name.as_str());
+        self.lines.push(format!("enter {}", name));
+        walk
+    }
+
+    fn exit(&mut self, node: DatabaseKeyIndex) {
+        self.lines.push(format!("exit {:?}", node.debug(self.db)));
+    }
+}
+
+fn setup() -> (Database, DatabaseKeyIndex) {
+    let mut db = Database::default();
+    for key in 0..3 {
+        db.set_input(key).to(key);
+    }
+    assert_eq!(db.top(), 3);
+    let root = db.executed.borrow()[0];
+    (db, root)
+}
+
+#[test]
+fn walks_depth_first_visiting_each_query_once() {
+    let (db, root) = setup();
+    let mut trace = Trace {
+        db: &db,
+        lines: vec![],
+        skip: None,
+    };
+    db.walk_dependencies(root, &mut trace);
+    assert_eq!(
+        trace.lines,
+        vec![
+            "enter top(())",
+            "enter middle(1)",
+            "enter input(0)",
+            "exit input(0)",
+            "enter input(1)",
+            "exit input(1)",
+            "exit middle(1)",
+            "enter middle(2)",
+            "enter input(2)",
+            "exit input(2)",
+            "exit middle(2)",
+            "exit top(())",
+        ]
+    );
+}
+
+#[test]
+fn enter_can_skip_dependencies() {
+    let (db, root) = setup();
+    let mut trace = Trace {
+        db: &db,
+        lines: vec![],
+        skip: Some("middle(1)"),
+    };
+    db.walk_dependencies(root, &mut trace);
+    assert_eq!(
+        trace.lines,
+        vec![
+            "enter top(())",
+            "enter middle(1)",
+            "exit middle(1)",
+            "enter middle(2)",
+            "enter input(0)",
+            "exit input(0)",
+            "enter input(2)",
+            "exit input(2)",
+            "exit middle(2)",
+            "exit top(())",
+        ]
+    );
+}
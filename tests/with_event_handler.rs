@@ -0,0 +1,107 @@
+//! Test `Database::with_event_handler`, which routes the events of a
+//! closure to a handler of its own.
+
+use salsa::Database as _;
+use std::cell::RefCell;
+use std::sync::{Arc, Mutex};
+
+#[salsa::query_group(LengthStorage)]
+trait LengthDatabase: salsa::Database {
+    #[salsa::input]
+    fn text(&self, key: u32) -> String;
+
+    fn length(&self, key: u32) -> usize;
+}
+
+fn length(db: &dyn LengthDatabase, key: u32) -> usize {
+    db.text(key).len()
+}
+
+#[salsa::database(LengthStorage)]
+#[derive(Default)]
+struct Database {
+    storage: salsa::Storage<Self>,
+    events: RefCell<Vec<String>>,
+}
+
+impl salsa::Database for Database {
+    fn salsa_event(&self, event: salsa::Event) {
+        self.events.borrow_mut().push(describe(event.resolve(self)));
+    }
+}
+
+fn describe(event: salsa::ResolvedEvent) -> String {
+    match event.kind {
+        salsa::ResolvedEventKind::WillExecute { database_key } => {
+            format!("execute {}", database_key)
+        }
+        kind => format!("{:?}", kind),
+    }
+}
+
+type Captured = Arc<Mutex<Vec<String>>>;
+
+fn capture() -> (
+    Captured,
+    impl FnMut(&dyn salsa::Database, salsa::Event) + Send,
+) {
+    let events = Captured::default();
+    let sink = events.clone();
+    let handler = move |db: &dyn salsa::Database, event: salsa::Event| {
+        sink.lock().unwrap().push(describe(event.resolve(db)));
+    };
+    (events, handler)
+}
+
+#[test]
+fn events_go_to_the_handler_while_it_is_installed() {
+    let mut db = Database::default();
+    db.set_text(1).to("hello".to_string());
+    db.events.borrow_mut().clear();
+
+    let (captured, handler) = capture();
+    let len = db.with_event_handler(handler, || db.length(1));
+    assert_eq!(len, 5);
+    assert_eq!(*captured.lock().unwrap(), vec!["execute length(1)"]);
+    assert!(db.events.borrow().is_empty());
+
+    // Afterwards, events go to `salsa_event` again.
+    db.set_text(1).to("hi".to_string());
+    db.events.borrow_mut().clear();
+    db.length(1);
+    assert_eq!(*db.events.borrow(), vec!["execute length(1)"]);
+    assert_eq!(captured.lock().unwrap().len(), 1);
+}
+
+#[test]
+fn nested_handlers_restore_the_outer_one() {
+    let mut db = Database::default();
+    db.set_text(1).to("a".to_string());
+    db.set_text(2).to("b".to_string());
+
+    let (outer, outer_handler) = capture();
+    let (inner, inner_handler) = capture();
+    db.with_event_handler(outer_handler, || {
+        db.with_event_handler(inner_handler, || db.length(1));
+        db.length(2);
+    });
+    assert_eq!(*inner.lock().unwrap(), vec!["execute length(1)"]);
+    assert_eq!(*outer.lock().unwrap(), vec!["execute length(2)"]);
+}
+
+#[test]
+fn handler_is_removed_when_the_closure_unwinds() {
+    let mut db = Database::default();
+    db.set_text(1).to("a".to_string());
+
+    let (captured, handler) = capture();
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        db.with_event_handler(handler, || panic!("boom"))
+    }));
+    assert!(result.is_err());
+
+    db.events.borrow_mut().clear();
+    db.length(1);
+    assert_eq!(*db.events.borrow(), vec!["execute length(1)"]);
+    assert!(captured.lock().unwrap().is_empty());
+}